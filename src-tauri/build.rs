@@ -1,15 +1,27 @@
 fn main() {
-    cc::Build::new()
-        .file("native/ocr_bridge.m")
-        .file("native/service_bridge.m")
-        .file("native/autostart_bridge.m")
-        .flag("-fobjc-arc")
-        .compile("native_bridge");
+    // sidecar 文件名带 target triple（与 Tauri externalBin 约定一致）
+    println!(
+        "cargo:rustc-env=FYLA_TARGET_TRIPLE={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
 
-    println!("cargo:rustc-link-lib=framework=Vision");
-    println!("cargo:rustc-link-lib=framework=AppKit");
-    println!("cargo:rustc-link-lib=framework=Quartz");
-    println!("cargo:rustc-link-lib=framework=ServiceManagement");
+    // Vision/PDFKit/ImageIO/Finder Services/SMAppService/Keychain 桥接只在 macOS 上编译
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        cc::Build::new()
+            .file("native/ocr_bridge.m")
+            .file("native/service_bridge.m")
+            .file("native/autostart_bridge.m")
+            .file("native/keychain_bridge.m")
+            .flag("-fobjc-arc")
+            .compile("native_bridge");
+
+        println!("cargo:rustc-link-lib=framework=Vision");
+        println!("cargo:rustc-link-lib=framework=AppKit");
+        println!("cargo:rustc-link-lib=framework=Quartz");
+        println!("cargo:rustc-link-lib=framework=ImageIO");
+        println!("cargo:rustc-link-lib=framework=ServiceManagement");
+        println!("cargo:rustc-link-lib=framework=Security");
+    }
 
     tauri_build::build();
 }
@@ -0,0 +1,52 @@
+#import <Foundation/Foundation.h>
+#import <Security/Security.h>
+
+// API Key 存在登录钥匙串的 generic password 条目里，service + account 唯一确定一条
+
+static NSMutableDictionary *keychain_query(const char* service, const char* account) {
+    return [@{
+        (__bridge id)kSecClass: (__bridge id)kSecClassGenericPassword,
+        (__bridge id)kSecAttrService: [NSString stringWithUTF8String:service],
+        (__bridge id)kSecAttrAccount: [NSString stringWithUTF8String:account],
+    } mutableCopy];
+}
+
+int keychain_set(const char* service, const char* account, const char* secret) {
+    @autoreleasepool {
+        NSData *data = [NSData dataWithBytes:secret length:strlen(secret)];
+        NSMutableDictionary *query = keychain_query(service, account);
+        OSStatus status = SecItemUpdate((__bridge CFDictionaryRef)query,
+                                        (__bridge CFDictionaryRef)@{(__bridge id)kSecValueData: data});
+        if (status == errSecItemNotFound) {
+            query[(__bridge id)kSecValueData] = data;
+            query[(__bridge id)kSecAttrAccessible] = (__bridge id)kSecAttrAccessibleAfterFirstUnlock;
+            status = SecItemAdd((__bridge CFDictionaryRef)query, NULL);
+        }
+        return (int)status;
+    }
+}
+
+char* keychain_get(const char* service, const char* account) {
+    @autoreleasepool {
+        NSMutableDictionary *query = keychain_query(service, account);
+        query[(__bridge id)kSecReturnData] = @YES;
+        query[(__bridge id)kSecMatchLimit] = (__bridge id)kSecMatchLimitOne;
+        CFTypeRef result = NULL;
+        OSStatus status = SecItemCopyMatching((__bridge CFDictionaryRef)query, &result);
+        if (status != errSecSuccess || !result) return NULL;
+
+        NSData *data = (__bridge_transfer NSData *)result;
+        char *secret = malloc(data.length + 1);
+        if (!secret) return NULL;
+        memcpy(secret, data.bytes, data.length);
+        secret[data.length] = '\0';
+        return secret;
+    }
+}
+
+int keychain_delete(const char* service, const char* account) {
+    @autoreleasepool {
+        OSStatus status = SecItemDelete((__bridge CFDictionaryRef)keychain_query(service, account));
+        return (status == errSecItemNotFound) ? 0 : (int)status;
+    }
+}
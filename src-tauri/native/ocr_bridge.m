@@ -2,12 +2,57 @@
 #import <AppKit/AppKit.h>
 #import <Vision/Vision.h>
 #import <Quartz/Quartz.h>
+#import <ImageIO/ImageIO.h>
 #import <ServiceManagement/ServiceManagement.h>
 
 // --- OCR ---
 
+// 逗号分隔的语言列表（如 "zh-Hans,en"），为空时使用默认中英文
+static NSArray<NSString *> *recognition_languages(const char* languages) {
+    NSArray<NSString *> *fallback = @[@"zh-Hans", @"zh-Hant", @"en"];
+    if (!languages || strlen(languages) == 0) return fallback;
+
+    NSMutableArray<NSString *> *result = [NSMutableArray array];
+    NSString *joined = [NSString stringWithUTF8String:languages];
+    for (NSString *part in [joined componentsSeparatedByString:@","]) {
+        NSString *trimmed = [part stringByTrimmingCharactersInSet:
+            [NSCharacterSet whitespaceCharacterSet]];
+        if (trimmed.length > 0) [result addObject:trimmed];
+    }
+    return result.count > 0 ? result : fallback;
+}
+
+// 对 CGImage 执行 Vision 文字识别
+static NSString *recognize_cgimage(CGImageRef cgImage, const char* languages) {
+    __block NSString *resultText = @"";
+    dispatch_semaphore_t sem = dispatch_semaphore_create(0);
+
+    VNRecognizeTextRequest *request = [[VNRecognizeTextRequest alloc]
+        initWithCompletionHandler:^(VNRequest *req, __unused NSError *error) {
+            NSArray<VNRecognizedTextObservation *> *observations = req.results;
+            NSMutableArray *lines = [NSMutableArray array];
+            for (VNRecognizedTextObservation *obs in observations) {
+                VNRecognizedText *candidate = [[obs topCandidates:1] firstObject];
+                if (candidate) [lines addObject:candidate.string];
+            }
+            resultText = [lines componentsJoinedByString:@"\n"];
+            dispatch_semaphore_signal(sem);
+        }];
+
+    request.recognitionLevel = VNRequestTextRecognitionLevelAccurate;
+    request.recognitionLanguages = recognition_languages(languages);
+    request.usesLanguageCorrection = YES;
+
+    VNImageRequestHandler *handler = [[VNImageRequestHandler alloc]
+        initWithCGImage:cgImage options:@{}];
+    [handler performRequests:@[request] error:nil];
+    dispatch_semaphore_wait(sem, DISPATCH_TIME_FOREVER);
+
+    return resultText;
+}
+
 // OCR 识别图片文件中的文本
-char* recognize_text_from_path(const char* path) {
+char* recognize_text_from_path(const char* path, const char* languages) {
     @autoreleasepool {
         NSString *filePath = [NSString stringWithUTF8String:path];
         NSImage *image = [[NSImage alloc] initWithContentsOfFile:filePath];
@@ -16,44 +61,51 @@ char* recognize_text_from_path(const char* path) {
         CGImageRef cgImage = [image CGImageForProposedRect:nil context:nil hints:nil];
         if (!cgImage) return strdup("");
 
-        __block NSString *resultText = @"";
-        dispatch_semaphore_t sem = dispatch_semaphore_create(0);
-
-        VNRecognizeTextRequest *request = [[VNRecognizeTextRequest alloc]
-            initWithCompletionHandler:^(VNRequest *req, __unused NSError *error) {
-                NSArray<VNRecognizedTextObservation *> *observations = req.results;
-                NSMutableArray *lines = [NSMutableArray array];
-                for (VNRecognizedTextObservation *obs in observations) {
-                    VNRecognizedText *candidate = [[obs topCandidates:1] firstObject];
-                    if (candidate) [lines addObject:candidate.string];
-                }
-                resultText = [lines componentsJoinedByString:@"\n"];
-                dispatch_semaphore_signal(sem);
-            }];
-
-        request.recognitionLevel = VNRequestTextRecognitionLevelAccurate;
-        request.recognitionLanguages = @[@"zh-Hans", @"zh-Hant", @"en"];
-        request.usesLanguageCorrection = YES;
-
-        VNImageRequestHandler *handler = [[VNImageRequestHandler alloc]
-            initWithCGImage:cgImage options:@{}];
-        [handler performRequests:@[request] error:nil];
-        dispatch_semaphore_wait(sem, DISPATCH_TIME_FOREVER);
-
+        NSString *resultText = recognize_cgimage(cgImage, languages);
         return strdup([resultText UTF8String]);
     }
 }
 
-// 将 PDF 首页渲染为 CGImage 并 OCR
-char* recognize_text_from_pdf(const char* path) {
+// 打开 PDF，加密文件用 password 解锁（可为 NULL），失败返回 NULL
+static CGPDFDocumentRef open_pdf_document(const char* path, const char* password) {
+    NSString *filePath = [NSString stringWithUTF8String:path];
+    NSURL *fileURL = [NSURL fileURLWithPath:filePath];
+    CGPDFDocumentRef pdfDoc = CGPDFDocumentCreateWithURL((__bridge CFURLRef)fileURL);
+    if (!pdfDoc) return NULL;
+
+    if (!CGPDFDocumentIsUnlocked(pdfDoc)) {
+        if (!password || !CGPDFDocumentUnlockWithPassword(pdfDoc, password)) {
+            CGPDFDocumentRelease(pdfDoc);
+            return NULL;
+        }
+    }
+    return pdfDoc;
+}
+
+// 返回 PDF 页数，无法打开时返回 -1
+int pdf_page_count(const char* path, const char* password) {
     @autoreleasepool {
-        NSString *filePath = [NSString stringWithUTF8String:path];
-        NSURL *fileURL = [NSURL fileURLWithPath:filePath];
-        PDFDocument *doc = [[PDFDocument alloc] initWithURL:fileURL];
-        if (!doc || doc.pageCount == 0) return strdup("");
+        CGPDFDocumentRef pdfDoc = open_pdf_document(path, password);
+        if (!pdfDoc) return -1;
 
-        PDFPage *page = [doc pageAtIndex:0];
-        NSRect bounds = [page boundsForBox:kPDFDisplayBoxMediaBox];
+        int count = (int)CGPDFDocumentGetNumberOfPages(pdfDoc);
+        CGPDFDocumentRelease(pdfDoc);
+        return count;
+    }
+}
+
+// 将 PDF 指定页（从 1 开始）渲染为 CGImage 并 OCR
+char* recognize_text_from_pdf_page(const char* path, int page_number, const char* password, const char* languages) {
+    @autoreleasepool {
+        CGPDFDocumentRef pdfDoc = open_pdf_document(path, password);
+        if (!pdfDoc) return strdup("");
+
+        CGPDFPageRef pdfPage = CGPDFDocumentGetPage(pdfDoc, (size_t)page_number);
+        if (!pdfPage) {
+            CGPDFDocumentRelease(pdfDoc);
+            return strdup("");
+        }
+        CGRect bounds = CGPDFPageGetBoxRect(pdfPage, kCGPDFMediaBox);
 
         CGFloat scale = 2.0;
         NSInteger width = (NSInteger)(bounds.size.width * scale);
@@ -64,58 +116,90 @@ char* recognize_text_from_pdf(const char* path) {
         CGContextRef ctx = CGBitmapContextCreate(NULL, width, height, 8, width * 4,
             colorSpace, (CGBitmapInfo)kCGImageAlphaPremultipliedLast);
         CGColorSpaceRelease(colorSpace);
-        if (!ctx) return strdup("");
-
-        CGContextScaleCTM(ctx, scale, scale);
-        CGPDFDocumentRef pdfDoc = CGPDFDocumentCreateWithURL((__bridge CFURLRef)fileURL);
-        if (pdfDoc) {
-            CGPDFPageRef pdfPage = CGPDFDocumentGetPage(pdfDoc, 1);
-            if (pdfPage) {
-                CGContextDrawPDFPage(ctx, pdfPage);
-            }
+        if (!ctx) {
             CGPDFDocumentRelease(pdfDoc);
+            return strdup("");
         }
 
+        CGContextScaleCTM(ctx, scale, scale);
+        CGContextDrawPDFPage(ctx, pdfPage);
+        CGPDFDocumentRelease(pdfDoc);
+
         CGImageRef cgImage = CGBitmapContextCreateImage(ctx);
         CGContextRelease(ctx);
         if (!cgImage) return strdup("");
 
-        __block NSString *resultText = @"";
-        dispatch_semaphore_t sem = dispatch_semaphore_create(0);
-
-        VNRecognizeTextRequest *request = [[VNRecognizeTextRequest alloc]
-            initWithCompletionHandler:^(VNRequest *req, __unused NSError *error) {
-                NSArray<VNRecognizedTextObservation *> *observations = req.results;
-                NSMutableArray *lines = [NSMutableArray array];
-                for (VNRecognizedTextObservation *obs in observations) {
-                    VNRecognizedText *candidate = [[obs topCandidates:1] firstObject];
-                    if (candidate) [lines addObject:candidate.string];
-                }
-                resultText = [lines componentsJoinedByString:@"\n"];
-                dispatch_semaphore_signal(sem);
-            }];
-
-        request.recognitionLevel = VNRequestTextRecognitionLevelAccurate;
-        request.recognitionLanguages = @[@"zh-Hans", @"zh-Hant", @"en"];
-        request.usesLanguageCorrection = YES;
-
-        VNImageRequestHandler *handler = [[VNImageRequestHandler alloc]
-            initWithCGImage:cgImage options:@{}];
-        [handler performRequests:@[request] error:nil];
-        dispatch_semaphore_wait(sem, DISPATCH_TIME_FOREVER);
-
+        NSString *resultText = recognize_cgimage(cgImage, languages);
         CGImageRelease(cgImage);
         return strdup([resultText UTF8String]);
     }
 }
 
+// 将 PDF 指定页（从 1 开始）渲染为白底 PNG，最长边缩放到 max_edge 像素，成功返回 1
+int render_pdf_page_png(const char* path, int page_number, const char* password, int max_edge, const char* output_path) {
+    @autoreleasepool {
+        CGPDFDocumentRef pdfDoc = open_pdf_document(path, password);
+        if (!pdfDoc) return 0;
+
+        CGPDFPageRef pdfPage = CGPDFDocumentGetPage(pdfDoc, (size_t)page_number);
+        if (!pdfPage) {
+            CGPDFDocumentRelease(pdfDoc);
+            return 0;
+        }
+        CGRect bounds = CGPDFPageGetBoxRect(pdfPage, kCGPDFCropBox);
+        CGFloat longest = MAX(bounds.size.width, bounds.size.height);
+        if (longest <= 0 || max_edge <= 0) {
+            CGPDFDocumentRelease(pdfDoc);
+            return 0;
+        }
+
+        CGFloat scale = (CGFloat)max_edge / longest;
+        size_t width = (size_t)MAX(1, lround(bounds.size.width * scale));
+        size_t height = (size_t)MAX(1, lround(bounds.size.height * scale));
+
+        CGColorSpaceRef colorSpace = CGColorSpaceCreateDeviceRGB();
+        CGContextRef ctx = CGBitmapContextCreate(NULL, width, height, 8, width * 4,
+            colorSpace, (CGBitmapInfo)kCGImageAlphaPremultipliedLast);
+        CGColorSpaceRelease(colorSpace);
+        if (!ctx) {
+            CGPDFDocumentRelease(pdfDoc);
+            return 0;
+        }
+
+        // 扫描件和透明背景的页面都按纸面白底输出，避免 VLM 看到黑底
+        CGContextSetRGBFillColor(ctx, 1.0, 1.0, 1.0, 1.0);
+        CGContextFillRect(ctx, CGRectMake(0, 0, width, height));
+        CGContextScaleCTM(ctx, scale, scale);
+        CGContextTranslateCTM(ctx, -bounds.origin.x, -bounds.origin.y);
+        CGContextDrawPDFPage(ctx, pdfPage);
+        CGPDFDocumentRelease(pdfDoc);
+
+        CGImageRef cgImage = CGBitmapContextCreateImage(ctx);
+        CGContextRelease(ctx);
+        if (!cgImage) return 0;
+
+        NSBitmapImageRep *rep = [[NSBitmapImageRep alloc] initWithCGImage:cgImage];
+        CGImageRelease(cgImage);
+        NSData *png = [rep representationUsingType:NSBitmapImageFileTypePNG properties:@{}];
+        if (!png) return 0;
+
+        NSString *outPath = [NSString stringWithUTF8String:output_path];
+        return [png writeToFile:outPath atomically:YES] ? 1 : 0;
+    }
+}
+
 // 使用 PDFKit 直接提取 PDF 文本层
-char* extract_text_from_pdf(const char* path) {
+char* extract_text_from_pdf(const char* path, const char* password) {
     @autoreleasepool {
         NSString *filePath = [NSString stringWithUTF8String:path];
         NSURL *fileURL = [NSURL fileURLWithPath:filePath];
         PDFDocument *doc = [[PDFDocument alloc] initWithURL:fileURL];
         if (!doc) return strdup("");
+        if ([doc isLocked]) {
+            if (!password || ![doc unlockWithPassword:[NSString stringWithUTF8String:password]]) {
+                return strdup("");
+            }
+        }
 
         NSString *text = [doc string];
         if (!text) return strdup("");
@@ -123,6 +207,35 @@ char* extract_text_from_pdf(const char* path) {
     }
 }
 
+// --- VLM 图片预处理 ---
+
+// 用 ImageIO 解码（含 HEIC），按 EXIF 方向摆正并缩放到最长边 max_edge，重新编码为不带元数据的 JPEG，成功返回 1
+int prepare_image_for_vlm(const char* path, int max_edge, const char* output_path) {
+    @autoreleasepool {
+        NSURL *url = [NSURL fileURLWithPath:[NSString stringWithUTF8String:path]];
+        CGImageSourceRef source = CGImageSourceCreateWithURL((__bridge CFURLRef)url, NULL);
+        if (!source) return 0;
+
+        NSDictionary *options = @{
+            (__bridge id)kCGImageSourceCreateThumbnailFromImageAlways: @YES,
+            (__bridge id)kCGImageSourceCreateThumbnailWithTransform: @YES,
+            (__bridge id)kCGImageSourceThumbnailMaxPixelSize: @(max_edge),
+        };
+        CGImageRef cgImage = CGImageSourceCreateThumbnailAtIndex(source, 0, (__bridge CFDictionaryRef)options);
+        CFRelease(source);
+        if (!cgImage) return 0;
+
+        NSBitmapImageRep *rep = [[NSBitmapImageRep alloc] initWithCGImage:cgImage];
+        CGImageRelease(cgImage);
+        NSData *jpeg = [rep representationUsingType:NSBitmapImageFileTypeJPEG
+                                         properties:@{NSImageCompressionFactor: @0.85}];
+        if (!jpeg) return 0;
+
+        NSString *outPath = [NSString stringWithUTF8String:output_path];
+        return [jpeg writeToFile:outPath atomically:YES] ? 1 : 0;
+    }
+}
+
 // --- Autostart (SMAppService, macOS 13+) ---
 
 int autostart_enable(void) {
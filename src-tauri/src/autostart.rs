@@ -1,3 +1,4 @@
+#[cfg(target_os = "macos")]
 unsafe extern "C" {
     fn autostart_enable() -> i32;
     fn autostart_disable() -> i32;
@@ -5,6 +6,7 @@ unsafe extern "C" {
 }
 
 /// Enables or disables launch-at-login via the native macOS API.
+#[cfg(target_os = "macos")]
 pub fn set_autostart(enabled: bool) -> Result<(), String> {
     let result = unsafe {
         if enabled {
@@ -21,6 +23,17 @@ pub fn set_autostart(enabled: bool) -> Result<(), String> {
 }
 
 /// Returns whether launch-at-login is currently enabled.
+#[cfg(target_os = "macos")]
 pub fn is_enabled() -> bool {
     unsafe { autostart_is_enabled() == 1 }
 }
+
+#[cfg(not(target_os = "macos"))]
+pub fn set_autostart(_enabled: bool) -> Result<(), String> {
+    Err("当前系统暂不支持开机自启".into())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn is_enabled() -> bool {
+    false
+}
@@ -1,24 +1,70 @@
-use anyhow::Result;
+use crate::{http, secrets};
+use anyhow::{Result, anyhow};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
+/// Merged config layers and the file state they were read from; see [`ConfigLayers::current`].
+static LAYERS: Mutex<Option<(LayerStamp, Arc<ConfigLayers>)>> = Mutex::new(None);
 static HISTORY_LOCK: Mutex<()> = Mutex::new(());
 static PAPER_HISTORY_LOCK: Mutex<()> = Mutex::new(());
 static PAPER_CHAT_SESSION_LOCK: Mutex<()> = Mutex::new(());
+static PDF_EXTRACTOR_PIN_LOCK: Mutex<()> = Mutex::new(());
 const DEFAULT_PAPER_REVIEW_PROMPT_TEMPLATE: &str =
     include_str!("../../src/lib/paper-review-prompt-template.txt");
 
+/// Current config.json schema version, written on every save.
+pub const CONFIG_VERSION: u32 = 2;
+/// Ordered migrations: entry `i` upgrades a config from version `i` to `i + 1`.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] =
+    &[migrate_to_model_profiles, migrate_missing_archive_root];
+/// Environment variable naming the team config file; takes precedence over the
+/// `teamConfigPath` setting.
+const TEAM_CONFIG_ENV: &str = "FYLA_TEAM_CONFIG";
+/// Per-folder override file, looked up from a file's folder upwards.
+const FOLDER_CONFIG_FILE: &str = ".fyla.json";
+/// Fields a folder override may set. They only shape file names, so a folder copied from
+/// elsewhere cannot change endpoints, keys or redaction.
+const FOLDER_FIELDS: &[&str] = &["customRules", "namingStyle", "includeDate", "nameTemplate"];
+/// Fields that only make sense in the user's own config.json.
+const USER_ONLY_FIELDS: &[&str] = &["configVersion", "teamConfigPath"];
+/// Archive root that early builds used as the default; it only existed on one machine.
+const LEGACY_PAPER_ARCHIVE_ROOT: &str = "/Users/chenghaoyang/Local/papers";
+const NAMING_STYLES: &[&str] = &[
+    "kebab-case",
+    "Train-Case",
+    "snake_case",
+    "camelCase",
+    "PascalCase",
+    "chinese",
+];
+const NAME_TEMPLATE_VARS: &[&str] = &[
+    "type", "title", "date", "author", "number", "taken", "camera", "place",
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct AppConfig {
-    pub provider: String,
-    pub ollama_url: String,
-    pub ollama_model: String,
-    pub openai_key: String,
-    pub openai_model: String,
-    pub openai_base_url: String,
+    // 配置格式版本；旧文件没有该字段，按 0 处理后依次迁移
+    #[serde(default)]
+    pub config_version: u32,
+    // 模型配置列表，各任务按 id 引用
+    pub profiles: Vec<ModelProfile>,
+    pub rename_profile: String,
+    // 为空时沿用命名模型
+    pub vlm_profile: String,
+    pub paper_review_profile: String,
+    // 为空时沿用论文解读模型
+    pub paper_chat_profile: String,
+    pub embedding_profile: String,
+    // 主 embedding 模型不可用时的备选，可为空
+    pub embedding_fallback_profile: String,
     pub custom_rules: String,
     pub naming_style: String,
     pub include_date: bool,
@@ -28,27 +74,230 @@ pub struct AppConfig {
     pub auto_categorize: bool,
     // VLM 设置
     pub vlm_enabled: bool,
-    pub vlm_same_as_llm: bool,
-    pub vlm_base_url: String,
-    pub vlm_key: String,
-    pub vlm_model: String,
-    pub paper_provider: String,
-    pub paper_ollama_url: String,
-    pub paper_ollama_model: String,
-    pub paper_openai_key: String,
-    pub paper_openai_model: String,
-    pub paper_openai_base_url: String,
-    pub paper_embedding_provider: String,
-    pub paper_embedding_ollama_url: String,
-    pub paper_embedding_ollama_model: String,
-    pub paper_embedding_openai_key: String,
-    pub paper_embedding_openai_model: String,
-    pub paper_embedding_openai_base_url: String,
+    // 发给 VLM 前图片最长边的像素上限
+    pub vlm_max_image_edge: u32,
+    // OCR 设置
+    pub ocr_engine: String,
+    pub ocr_languages: String,
+    pub ocr_tessdata_dir: String,
+    pub ocr_page_range: String,
+    pub ocr_parallelism: u32,
+    // 加密 PDF 的常用密码，每行一个；保存时移入钥匙串，这里只留引用
+    pub pdf_passwords: String,
+    // 发给模型前的脱敏策略：off / remote / all
+    pub redaction_mode: String,
+    // 启用的内置检测：email,phone,iban,card,nationalId
+    pub redaction_kinds: String,
+    // 用户自定义正则，每行一个
+    pub redaction_patterns: String,
+    // 按文件夹覆盖策略，每行 "/path/to/folder = off|remote|all"
+    pub redaction_folders: String,
     pub paper_fulltext_token_limit: u32,
     pub paper_archive_root: String,
     pub paper_review_prompt_template: String,
+    // 团队共享配置文件路径；环境变量 FYLA_TEAM_CONFIG 优先
+    pub team_config_path: String,
+    // 访问模型服务的网络设置；代理为空时沿用系统代理
+    pub http_proxy: String,
+    // 不走代理的主机，逗号分隔；本机地址始终直连
+    pub http_no_proxy: String,
+    // 额外信任的 CA 证书（PEM）路径
+    pub http_ca_bundle: String,
+    // 附加请求头，每行 "Name: value"
+    pub http_headers: String,
+    pub http_timeouts: HttpTimeouts,
+    // 每百万 token 的价格，用于记账；未列出的模型按 0 计
+    pub model_prices: Vec<ModelPrice>,
+    // 云端模型的月度预算，达到后暂停云端调用
+    pub monthly_budget: Option<f64>,
+}
+
+/// Price of a model per million tokens, in the user's currency.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ModelPrice {
+    pub model: String,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.input_per_million
+            + completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Per-task HTTP timeouts in seconds; unset values use the built-in defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HttpTimeouts {
+    pub test: Option<u32>,
+    pub rename: Option<u32>,
+    pub vlm: Option<u32>,
+    pub embedding: Option<u32>,
+    /// Connection setup, for every task.
+    pub connect: Option<u32>,
+    /// Longest pause between chunks of a streamed reply.
+    pub stream_idle: Option<u32>,
+}
+
+/// A named model endpoint. Renaming, VLM, paper review, paper chat and embeddings each
+/// pick one by `id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ModelProfile {
+    pub id: String,
+    pub name: String,
+    /// `ollama`, `openai` for any OpenAI-compatible API, or `mock` for scripted offline
+    /// replies.
+    pub provider: String,
+    pub base_url: String,
+    /// Reference to the stored key (see `secrets`); unused by Ollama.
+    pub api_key: String,
+    pub model: String,
+    pub capabilities: ModelCapabilities,
+    pub sampling: SamplingDefaults,
+    /// Behaviour of a `mock` profile; unused by other providers.
+    pub mock: Option<MockSettings>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ModelCapabilities {
+    pub chat: bool,
+    pub vision: bool,
+    pub embedding: bool,
 }
 
+/// Sampling parameters sent with every request to the profile; unset values are left to
+/// the provider, except where a task needs its own output limit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SamplingDefaults {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+}
+
+/// Scripted replies of a `mock` profile, so pipelines can be exercised without a model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MockSettings {
+    /// Checked in order; the first match wins, otherwise a built-in reply for the task is used.
+    pub rules: Vec<MockRule>,
+    /// Characters per streamed chunk; 0 streams the whole reply as one chunk.
+    pub chunk_chars: u32,
+    /// Pause before each chunk, in milliseconds.
+    pub chunk_delay_ms: u32,
+    /// Fails after this many chunks; 0 fails before any output.
+    pub fail_after_chunks: Option<u32>,
+    pub fail_message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MockRule {
+    /// Text the prompt must contain; empty matches every prompt.
+    pub contains: String,
+    /// Task key (see [`ModelTask::key`]) the rule is limited to; empty means any task.
+    pub task: String,
+    pub reply: String,
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            chat: true,
+            vision: false,
+            embedding: false,
+        }
+    }
+}
+
+impl Default for ModelProfile {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            provider: "ollama".into(),
+            base_url: default_ollama_url(),
+            api_key: String::new(),
+            model: String::new(),
+            capabilities: ModelCapabilities::default(),
+            sampling: SamplingDefaults::default(),
+            mock: None,
+        }
+    }
+}
+
+impl ModelProfile {
+    pub fn is_openai(&self) -> bool {
+        self.provider == "openai"
+    }
+
+    pub fn is_mock(&self) -> bool {
+        self.provider == "mock"
+    }
+
+    /// Base URL without the trailing slash, falling back to the provider default.
+    pub fn endpoint(&self) -> &str {
+        let base = self.base_url.trim().trim_end_matches('/');
+        match (base.is_empty(), self.is_openai()) {
+            (false, _) => base,
+            (true, true) => "https://api.openai.com/v1",
+            (true, false) => "http://localhost:11434",
+        }
+    }
+}
+
+/// A task that sends requests to a model profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelTask {
+    Rename,
+    Vlm,
+    PaperReview,
+    PaperChat,
+    Embedding,
+    EmbeddingFallback,
+}
+
+impl ModelTask {
+    /// Name used in the usage ledger; both embedding profiles count as `embedding`.
+    pub fn key(self) -> &'static str {
+        match self {
+            ModelTask::Rename => "rename",
+            ModelTask::Vlm => "vlm",
+            ModelTask::PaperReview => "paperReview",
+            ModelTask::PaperChat => "paperChat",
+            ModelTask::Embedding | ModelTask::EmbeddingFallback => "embedding",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ModelTask::Rename => "文件命名",
+            ModelTask::Vlm => "图片识别（VLM）",
+            ModelTask::PaperReview => "论文解读",
+            ModelTask::PaperChat => "论文聊天",
+            ModelTask::Embedding => "论文 Embedding",
+            ModelTask::EmbeddingFallback => "备选 Embedding",
+        }
+    }
+
+    fn supported_by(self, capabilities: &ModelCapabilities) -> bool {
+        match self {
+            ModelTask::Vlm => capabilities.vision,
+            ModelTask::Embedding | ModelTask::EmbeddingFallback => capabilities.embedding,
+            _ => capabilities.chat,
+        }
+    }
+}
+
+fn default_ollama_url() -> String {
+    "http://localhost:11434".into()
+}
 fn default_naming_style() -> String {
     "kebab-case".into()
 }
@@ -58,23 +307,32 @@ fn default_watch_extensions() -> String {
 fn default_openai_base_url() -> String {
     "https://api.openai.com/v1".into()
 }
-fn default_paper_provider() -> String {
-    "openai".into()
-}
-fn default_paper_embedding_provider() -> String {
+fn default_ocr_engine() -> String {
     "auto".into()
 }
-fn default_paper_embedding_ollama_model() -> String {
-    "nomic-embed-text".into()
+fn default_ocr_languages() -> String {
+    "chi_sim+chi_tra+eng".into()
 }
-fn default_paper_embedding_openai_model() -> String {
-    "text-embedding-3-small".into()
+fn default_ocr_parallelism() -> u32 {
+    2
+}
+fn default_redaction_mode() -> String {
+    "off".into()
+}
+fn default_redaction_kinds() -> String {
+    "email,phone,iban,card,nationalId".into()
 }
 fn default_paper_fulltext_token_limit() -> u32 {
     60_000
 }
-fn default_paper_archive_root() -> String {
-    "/Users/chenghaoyang/Local/papers".into()
+/// Default paper archive: `papers` under the app data dir.
+pub fn default_paper_archive_root() -> String {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("fyla")
+        .join("papers")
+        .to_string_lossy()
+        .into_owned()
 }
 pub fn default_paper_review_prompt_template() -> String {
     DEFAULT_PAPER_REVIEW_PROMPT_TEMPLATE.trim().to_string()
@@ -94,17 +352,71 @@ impl AppConfig {
             normalize_paper_review_prompt_template_value(&self.paper_review_prompt_template);
         self
     }
+
+    /// Directory that paper reviews are saved under; blank means the default.
+    pub fn paper_archive_dir(&self) -> PathBuf {
+        match self.paper_archive_root.trim() {
+            "" => PathBuf::from(default_paper_archive_root()),
+            value => PathBuf::from(value),
+        }
+    }
+
+    pub fn profile(&self, id: &str) -> Option<&ModelProfile> {
+        let id = id.trim();
+        self.profiles.iter().find(|profile| profile.id == id)
+    }
+
+    /// Id of the profile assigned to `task`, following "same as" fallbacks.
+    fn profile_id_for(&self, task: ModelTask) -> &str {
+        let id = match task {
+            ModelTask::Rename => &self.rename_profile,
+            ModelTask::Vlm => &self.vlm_profile,
+            ModelTask::PaperReview => &self.paper_review_profile,
+            ModelTask::PaperChat => &self.paper_chat_profile,
+            ModelTask::Embedding => &self.embedding_profile,
+            ModelTask::EmbeddingFallback => &self.embedding_fallback_profile,
+        };
+        match task {
+            ModelTask::Vlm if id.trim().is_empty() => &self.rename_profile,
+            ModelTask::PaperChat if id.trim().is_empty() => &self.paper_review_profile,
+            _ => id,
+        }
+    }
+
+    /// Profile assigned to `task`, checked against the profile's capabilities.
+    pub fn model_for(&self, task: ModelTask) -> anyhow::Result<&ModelProfile> {
+        let id = self.profile_id_for(task);
+        let profile = self.profile(id).ok_or_else(|| {
+            anyhow::anyhow!("未找到{}使用的模型配置，请在设置中选择", task.label())
+        })?;
+        if !task.supported_by(&profile.capabilities) {
+            return Err(anyhow::anyhow!(
+                "模型配置「{}」未标记支持{}，请在设置中调整",
+                profile.name,
+                task.label()
+            ));
+        }
+        Ok(profile)
+    }
+
+    /// Fallback embedding profile, if one is assigned.
+    pub fn embedding_fallback(&self) -> Option<anyhow::Result<&ModelProfile>> {
+        (!self.embedding_fallback_profile.trim().is_empty())
+            .then(|| self.model_for(ModelTask::EmbeddingFallback))
+    }
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
-        Self {
-            provider: "ollama".into(),
-            ollama_url: "http://localhost:11434".into(),
-            ollama_model: "llama3.2".into(),
-            openai_key: String::new(),
-            openai_model: "gpt-4o-mini".into(),
-            openai_base_url: default_openai_base_url(),
+        let mut config = Self {
+            config_version: CONFIG_VERSION,
+            profiles: Vec::new(),
+            rename_profile: String::new(),
+            vlm_profile: String::new(),
+            paper_review_profile: String::new(),
+            paper_chat_profile: String::new(),
+            embedding_profile: String::new(),
+            embedding_fallback_profile: String::new(),
             custom_rules: String::new(),
             naming_style: default_naming_style(),
             include_date: false,
@@ -113,57 +425,1215 @@ impl Default for AppConfig {
             name_template: String::new(),
             auto_categorize: false,
             vlm_enabled: false,
+            vlm_max_image_edge: 2048,
+            ocr_engine: default_ocr_engine(),
+            ocr_languages: default_ocr_languages(),
+            ocr_tessdata_dir: String::new(),
+            ocr_page_range: String::new(),
+            ocr_parallelism: default_ocr_parallelism(),
+            pdf_passwords: String::new(),
+            redaction_mode: default_redaction_mode(),
+            redaction_kinds: default_redaction_kinds(),
+            redaction_patterns: String::new(),
+            redaction_folders: String::new(),
+            paper_fulltext_token_limit: default_paper_fulltext_token_limit(),
+            paper_archive_root: default_paper_archive_root(),
+            paper_review_prompt_template: default_paper_review_prompt_template(),
+            team_config_path: String::new(),
+            http_proxy: String::new(),
+            http_no_proxy: String::new(),
+            http_ca_bundle: String::new(),
+            http_headers: String::new(),
+            http_timeouts: HttpTimeouts::default(),
+            model_prices: Vec::new(),
+            monthly_budget: None,
+        };
+        // 新安装的默认模型与旧版扁平字段的默认值一致
+        let legacy = LegacyModelFields::default().into_profiles();
+        config.profiles = serde_json::from_value(Value::Array(legacy.profiles)).unwrap_or_default();
+        config.rename_profile = legacy.rename;
+        config.vlm_profile = legacy.vlm;
+        config.paper_review_profile = legacy.paper_review;
+        config.embedding_profile = legacy.embedding;
+        config.embedding_fallback_profile = legacy.embedding_fallback;
+        config
+    }
+}
+
+/// Model settings of configs saved before profiles existed: one flat set of fields each
+/// for renaming, VLM, paper review and embeddings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct LegacyModelFields {
+    provider: String,
+    ollama_url: String,
+    ollama_model: String,
+    openai_key: String,
+    openai_model: String,
+    openai_base_url: String,
+    vlm_same_as_llm: bool,
+    vlm_base_url: String,
+    vlm_key: String,
+    vlm_model: String,
+    paper_provider: String,
+    paper_ollama_url: String,
+    paper_ollama_model: String,
+    paper_openai_key: String,
+    paper_openai_model: String,
+    paper_openai_base_url: String,
+    paper_embedding_provider: String,
+    paper_embedding_ollama_url: String,
+    paper_embedding_ollama_model: String,
+    paper_embedding_openai_key: String,
+    paper_embedding_openai_model: String,
+    paper_embedding_openai_base_url: String,
+}
+
+impl Default for LegacyModelFields {
+    fn default() -> Self {
+        Self {
+            provider: "ollama".into(),
+            ollama_url: default_ollama_url(),
+            ollama_model: "llama3.2".into(),
+            openai_key: String::new(),
+            openai_model: "gpt-4o-mini".into(),
+            openai_base_url: default_openai_base_url(),
             vlm_same_as_llm: true,
             vlm_base_url: String::new(),
             vlm_key: String::new(),
             vlm_model: String::new(),
-            paper_provider: default_paper_provider(),
-            paper_ollama_url: "http://localhost:11434".into(),
+            paper_provider: "openai".into(),
+            paper_ollama_url: default_ollama_url(),
             paper_ollama_model: "llama3.2".into(),
             paper_openai_key: String::new(),
             paper_openai_model: "gpt-4.1".into(),
             paper_openai_base_url: default_openai_base_url(),
-            paper_embedding_provider: default_paper_embedding_provider(),
-            paper_embedding_ollama_url: "http://localhost:11434".into(),
-            paper_embedding_ollama_model: default_paper_embedding_ollama_model(),
+            paper_embedding_provider: "auto".into(),
+            paper_embedding_ollama_url: default_ollama_url(),
+            paper_embedding_ollama_model: "nomic-embed-text".into(),
             paper_embedding_openai_key: String::new(),
-            paper_embedding_openai_model: default_paper_embedding_openai_model(),
+            paper_embedding_openai_model: "text-embedding-3-small".into(),
             paper_embedding_openai_base_url: default_openai_base_url(),
-            paper_fulltext_token_limit: default_paper_fulltext_token_limit(),
-            paper_archive_root: default_paper_archive_root(),
-            paper_review_prompt_template: default_paper_review_prompt_template(),
         }
     }
 }
 
+impl LegacyModelFields {
+    /// Turns the flat fields into profiles and task assignments. Both providers of each
+    /// group become profiles so switching later keeps the old values; identical
+    /// endpoints share one profile.
+    fn into_profiles(self) -> LegacyProfiles {
+        let mut profiles = Vec::new();
+        let ollama = add_legacy_profile(
+            &mut profiles,
+            "ollama",
+            "Ollama",
+            "ollama",
+            &self.ollama_url,
+            "",
+            &self.ollama_model,
+            json!({ "chat": true, "vision": self.vlm_same_as_llm, "embedding": false }),
+        );
+        let openai = add_legacy_profile(
+            &mut profiles,
+            "openai",
+            "OpenAI",
+            "openai",
+            &self.openai_base_url,
+            &self.openai_key,
+            &self.openai_model,
+            json!({ "chat": true, "vision": self.vlm_same_as_llm, "embedding": false }),
+        );
+        let rename = if self.provider == "openai" {
+            openai
+        } else {
+            ollama
+        };
+
+        let vlm = if self.vlm_same_as_llm {
+            String::new()
+        } else {
+            add_legacy_profile(
+                &mut profiles,
+                "vlm",
+                "VLM",
+                "openai",
+                &self.vlm_base_url,
+                &self.vlm_key,
+                &self.vlm_model,
+                json!({ "chat": false, "vision": true, "embedding": false }),
+            )
+        };
+
+        let paper_ollama = add_legacy_profile(
+            &mut profiles,
+            "paper-ollama",
+            "论文 Ollama",
+            "ollama",
+            &self.paper_ollama_url,
+            "",
+            &self.paper_ollama_model,
+            json!({ "chat": true, "vision": false, "embedding": false }),
+        );
+        let paper_openai = add_legacy_profile(
+            &mut profiles,
+            "paper-openai",
+            "论文 OpenAI",
+            "openai",
+            &self.paper_openai_base_url,
+            &self.paper_openai_key,
+            &self.paper_openai_model,
+            json!({ "chat": true, "vision": false, "embedding": false }),
+        );
+        let paper_review = if self.paper_provider == "ollama" {
+            paper_ollama
+        } else {
+            paper_openai
+        };
+
+        let embedding_ollama = add_legacy_profile(
+            &mut profiles,
+            "embedding-ollama",
+            "Embedding Ollama",
+            "ollama",
+            &self.paper_embedding_ollama_url,
+            "",
+            &self.paper_embedding_ollama_model,
+            json!({ "chat": false, "vision": false, "embedding": true }),
+        );
+        let embedding_openai = add_legacy_profile(
+            &mut profiles,
+            "embedding-openai",
+            "Embedding OpenAI",
+            "openai",
+            &self.paper_embedding_openai_base_url,
+            &self.paper_embedding_openai_key,
+            &self.paper_embedding_openai_model,
+            json!({ "chat": false, "vision": false, "embedding": true }),
+        );
+        let (embedding, embedding_fallback) = match self.paper_embedding_provider.as_str() {
+            "ollama" => (embedding_ollama, String::new()),
+            "openai" => (embedding_openai, String::new()),
+            // auto：本地优先，失败时用云端
+            _ => (embedding_ollama, embedding_openai),
+        };
+
+        LegacyProfiles {
+            profiles,
+            rename,
+            vlm,
+            paper_review,
+            embedding,
+            embedding_fallback,
+        }
+    }
+}
+
+/// Version 1 profiles built from [`LegacyModelFields`] as raw JSON, so the migration
+/// keeps producing the same file when the profile structs change later.
+struct LegacyProfiles {
+    profiles: Vec<Value>,
+    rename: String,
+    vlm: String,
+    paper_review: String,
+    embedding: String,
+    embedding_fallback: String,
+}
+
+impl LegacyProfiles {
+    /// camelCase task field → profile id; paper chat follows paper review.
+    fn assignments(self) -> [(&'static str, String); 6] {
+        [
+            ("renameProfile", self.rename),
+            ("vlmProfile", self.vlm),
+            ("paperReviewProfile", self.paper_review),
+            ("paperChatProfile", String::new()),
+            ("embeddingProfile", self.embedding),
+            ("embeddingFallbackProfile", self.embedding_fallback),
+        ]
+    }
+}
+
+/// Adds a migrated profile and returns its id, reusing an existing profile with the same
+/// endpoint, key and model.
+#[allow(clippy::too_many_arguments)]
+fn add_legacy_profile(
+    profiles: &mut Vec<Value>,
+    id: &str,
+    name: &str,
+    provider: &str,
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+    capabilities: Value,
+) -> String {
+    let base_url = base_url.trim().trim_end_matches('/');
+    let api_key = api_key.trim();
+    let model = model.trim();
+    if let Some(existing) = profiles.iter_mut().find(|profile| {
+        profile["provider"] == provider
+            && profile["baseUrl"] == base_url
+            && profile["model"] == model
+            && profile["apiKey"] == api_key
+    }) {
+        for key in ["chat", "vision", "embedding"] {
+            let enabled = existing["capabilities"][key].as_bool().unwrap_or(false)
+                || capabilities[key].as_bool().unwrap_or(false);
+            existing["capabilities"][key] = enabled.into();
+        }
+        return existing["id"].as_str().unwrap_or(id).to_string();
+    }
+    let label = if model.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} · {}", name, model)
+    };
+    profiles.push(json!({
+        "id": id,
+        "name": label,
+        "provider": provider,
+        "baseUrl": base_url,
+        "apiKey": api_key,
+        "model": model,
+        "capabilities": capabilities,
+    }));
+    id.into()
+}
+
 fn config_path() -> PathBuf {
     let base = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
     base.join("fyla").join("config.json")
 }
 
-/// Loads config from disk, returning defaults if the file doesn't exist.
+/// Loads the effective config: built-in defaults, then the team file, then the user's
+/// config.json (see [`ConfigLayers`]). A missing or invalid user file counts as empty.
+/// Older schema versions are migrated in memory and fields that fail to parse fall back
+/// to the layer below; [`upgrade_stored_config`] reports and persists that.
 pub fn load_config() -> AppConfig {
-    let path = config_path();
-    if let Ok(data) = fs::read_to_string(&path) {
-        serde_json::from_str::<AppConfig>(&data)
-            .unwrap_or_default()
-            .normalized()
-    } else {
-        AppConfig::default()
+    ConfigLayers::current().effective(None).normalized()
+}
+
+/// Applies the nearest per-folder override above `path` to `config`. Only naming fields
+/// can be overridden, and not those the team config locks.
+pub fn for_path(config: &AppConfig, path: &Path) -> AppConfig {
+    let Some(folder) = path.parent().and_then(FolderLayer::find) else {
+        return config.clone();
+    };
+    let layers = ConfigLayers::current();
+    let Ok(Value::Object(mut map)) = serde_json::to_value(config) else {
+        return config.clone();
+    };
+    for (key, value) in folder.values {
+        if !layers.is_locked(&key) {
+            map.insert(key, value);
+        }
+    }
+    serde_json::from_value(Value::Object(map)).unwrap_or_else(|_| config.clone())
+}
+
+/// A config.json parsed by [`parse_config`].
+struct ParsedConfig {
+    config: AppConfig,
+    /// Whether any migration ran.
+    migrated: bool,
+    /// Fields dropped because their values did not parse.
+    dropped: Vec<String>,
+}
+
+fn parse_config(data: &str) -> Result<ParsedConfig> {
+    let (map, migrated) = migrate_user_file(data)?;
+    let (config, dropped) = deserialize_lenient(map);
+    Ok(ParsedConfig {
+        config,
+        migrated,
+        dropped,
+    })
+}
+
+/// Parses config.json and runs the pending migrations; returns whether any ran.
+fn migrate_user_file(data: &str) -> Result<(Map<String, Value>, bool)> {
+    let value = serde_json::from_str::<Value>(data)
+        .map_err(|e| anyhow!("config.json 不是有效的 JSON: {}", e))?;
+    let Value::Object(mut map) = value else {
+        return Err(anyhow!("config.json 的内容不是对象"));
+    };
+
+    let version = stored_version(&map) as usize;
+    // 更高版本写入的文件不做迁移，认识的字段照常读取
+    let pending = MIGRATIONS.get(version..).unwrap_or_default();
+    for migrate in pending {
+        migrate(&mut map);
+    }
+    if !pending.is_empty() {
+        map.insert("configVersion".into(), CONFIG_VERSION.into());
+    }
+    Ok((map, !pending.is_empty()))
+}
+
+/// Deserializes `map`, dropping only the fields whose values do not parse instead of
+/// resetting the whole config.
+fn deserialize_lenient(mut map: Map<String, Value>) -> (AppConfig, Vec<String>) {
+    if let Ok(config) = serde_json::from_value::<AppConfig>(Value::Object(map.clone())) {
+        return (config, Vec::new());
+    }
+    let dropped = drop_unparsable(&mut map);
+    let config = serde_json::from_value(Value::Object(map)).unwrap_or_default();
+    (config, dropped)
+}
+
+/// Removes the fields whose values do not parse and returns their names.
+fn drop_unparsable(map: &mut Map<String, Value>) -> Vec<String> {
+    let mut dropped = Vec::new();
+    map.retain(|key, value| {
+        let parses = field_parses(key, value);
+        if !parses {
+            dropped.push(key.clone());
+        }
+        parses
+    });
+    dropped
+}
+
+fn field_parses(key: &str, value: &Value) -> bool {
+    let single = Map::from_iter([(key.to_string(), value.clone())]);
+    serde_json::from_value::<AppConfig>(Value::Object(single)).is_ok()
+}
+
+/// The schema version of a raw config.json; files without one count as version 0.
+fn stored_version(map: &Map<String, Value>) -> u64 {
+    map.get("configVersion")
+        .and_then(Value::as_u64)
+        .unwrap_or(0)
+}
+
+/// Keeps what a newer app version wrote to config.json when this version saves over it:
+/// fields it does not know, values it cannot parse and were not changed here, and the
+/// higher `configVersion`, so going back to the newer version loses nothing.
+fn keep_newer_fields(user: &mut Map<String, Value>, stored: Map<String, Value>) {
+    let version = stored_version(&stored);
+    if version <= u64::from(CONFIG_VERSION) {
+        return;
+    }
+    log::warn!(
+        target: "config",
+        "config.json has version {} (this app writes {}); keeping the fields it does not know",
+        version,
+        CONFIG_VERSION
+    );
+    let known = default_map();
+    for (key, value) in stored {
+        let unknown = !known.contains_key(&key);
+        if unknown || (!user.contains_key(&key) && !field_parses(&key, &value)) {
+            user.insert(key, value);
+        }
+    }
+    user.insert("configVersion".into(), version.into());
+}
+
+/// Version 0 → 1: moves the flat per-task model fields into model profiles.
+fn migrate_to_model_profiles(map: &mut Map<String, Value>) {
+    if map.contains_key("profiles") {
+        return;
+    }
+    let mut legacy = serde_json::from_value::<LegacyModelFields>(Value::Object(map.clone()))
+        .unwrap_or_default()
+        .into_profiles();
+    map.insert(
+        "profiles".into(),
+        Value::Array(std::mem::take(&mut legacy.profiles)),
+    );
+    for (key, id) in legacy.assignments() {
+        map.insert(key.into(), id.into());
+    }
+}
+
+/// Version 1 → 2: drops the old hard-coded archive root when it does not exist on this
+/// machine, so the data-dir default applies.
+fn migrate_missing_archive_root(map: &mut Map<String, Value>) {
+    let is_legacy_default = map
+        .get("paperArchiveRoot")
+        .and_then(Value::as_str)
+        .is_some_and(|root| root == LEGACY_PAPER_ARCHIVE_ROOT);
+    if is_legacy_default && !Path::new(LEGACY_PAPER_ARCHIVE_ROOT).is_dir() {
+        map.insert(
+            "paperArchiveRoot".into(),
+            default_paper_archive_root().into(),
+        );
+    }
+}
+
+/// Persists config to disk as JSON, creating parent directories if needed. The config is
+/// validated first and rejected with the offending fields listed, including changes to
+/// team-locked fields. API keys are moved into secure storage so the file only holds
+/// references to them, and only fields that differ from the team and built-in defaults
+/// are written. Returns [`AppConfig::warnings`] for the saved config.
+pub fn save_config(config: &AppConfig) -> Result<Vec<ConfigIssue>> {
+    let mut issues = config.validate();
+    if let Some(team) = &ConfigLayers::current().team {
+        issues.extend(team.locked_changes(config));
+    }
+    if !issues.is_empty() {
+        let details = issues
+            .iter()
+            .map(ConfigIssue::to_string)
+            .collect::<Vec<_>>()
+            .join("；");
+        return Err(anyhow!("配置未保存：{}", details));
     }
+    write_config(config)?;
+    Ok(config.warnings())
 }
 
-/// Persists config to disk as JSON, creating parent directories if needed.
-pub fn save_config(config: &AppConfig) -> Result<()> {
+fn write_config(config: &AppConfig) -> Result<()> {
     let path = config_path();
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
-    let data = serde_json::to_string_pretty(&config.clone().normalized())?;
-    fs::write(path, data)?;
+    let mut config = config.clone().normalized();
+    config.config_version = CONFIG_VERSION;
+    secrets::store_config_secrets(&mut config, &load_config())?;
+    // 只保存与当前生效的团队/内置默认值不同的字段；换了团队配置时，
+    // 未改动的字段随新的团队默认值变化
+    let Value::Object(full) = serde_json::to_value(&config)? else {
+        return Err(anyhow!("配置无法序列化"));
+    };
+    let mut user = ConfigLayers::current().user_layer(full);
+    // 更高版本写入的文件：保留本版本不认识的字段和版本号
+    if let Ok(data) = fs::read_to_string(&path)
+        && let Ok(Value::Object(stored)) = serde_json::from_str::<Value>(&data)
+    {
+        keep_newer_fields(&mut user, stored);
+    }
+    fs::write(path, serde_json::to_string_pretty(&user)?)?;
+    ConfigLayers::invalidate();
     Ok(())
 }
 
+/// Checks config.json once at startup. Files that do not parse, or have fields that do
+/// not, are backed up next to it and the problems returned for the user. Older schema
+/// versions and plaintext API keys are rewritten in place, after a backup.
+pub fn upgrade_stored_config() -> Result<Vec<String>> {
+    let Ok(data) = fs::read_to_string(config_path()) else {
+        return Ok(ConfigLayers::current()
+            .team_error
+            .clone()
+            .into_iter()
+            .collect());
+    };
+    let parsed = match parse_config(&data) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            // 原文件保持不动，用户下次保存设置时才会被覆盖
+            let backup = backup_config(&data)?;
+            return Ok(vec![format!(
+                "{}，已改用默认设置，原文件已备份到 {}",
+                err,
+                backup.display()
+            )]);
+        }
+    };
+
+    let mut problems = Vec::new();
+    if let Err(err) = TeamLayer::load(&parsed.config.team_config_path) {
+        problems.push(format!("{:#}", err));
+    }
+    let version = match serde_json::from_str::<Value>(&data) {
+        Ok(Value::Object(map)) => stored_version(&map),
+        _ => 0,
+    };
+    if version > u64::from(CONFIG_VERSION) {
+        // 不改写更高版本的文件；保存设置时保留本版本不认识的字段
+        problems.push(format!(
+            "config.json 由更新版本的 Fyla 写入（配置版本 {}，当前支持 {}），\
+             本版本不认识的设置不会生效，但保存时会原样保留",
+            version, CONFIG_VERSION
+        ));
+        return Ok(problems);
+    }
+    let has_plaintext = secrets::has_plaintext(&parsed.config);
+    if !parsed.migrated && parsed.dropped.is_empty() && !has_plaintext {
+        return Ok(problems);
+    }
+    let backup = backup_config(&data)?;
+    if !parsed.dropped.is_empty() {
+        problems.push(format!(
+            "config.json 中以下字段无法读取，已恢复默认值：{}（原文件已备份到 {}）",
+            parsed.dropped.join("、"),
+            backup.display()
+        ));
+    }
+    // 写回的是叠加团队配置后的结果，write_config 只保留用户自己的改动
+    write_config(&ConfigLayers::current().effective(None))?;
+    log::info!(
+        target: "config",
+        "rewrote config.json (version {}, backup {})",
+        CONFIG_VERSION,
+        backup.display()
+    );
+    Ok(problems)
+}
+
+/// Copies `data` to `config.{hash}.bak.json`; the same content is only backed up once.
+fn backup_config(data: &str) -> Result<PathBuf> {
+    let hash = Sha256::digest(data.as_bytes())
+        .iter()
+        .take(6)
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let path = config_path().with_file_name(format!("config.{}.bak.json", hash));
+    if !path.exists() {
+        fs::write(&path, data)?;
+    }
+    Ok(path)
+}
+
+/// Where a field's effective value comes from, lowest priority first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConfigLayer {
+    Default,
+    Team,
+    User,
+    Folder,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldSource {
+    pub layer: ConfigLayer,
+    /// Locked by the team config; user and folder values are ignored.
+    pub locked: bool,
+}
+
+/// The layers behind the effective config and the source of each field.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigSources {
+    pub team_path: Option<String>,
+    pub team_error: Option<String>,
+    pub folder_path: Option<String>,
+    /// camelCase field name → source.
+    pub fields: BTreeMap<String, FieldSource>,
+}
+
+/// Reports which layer every field comes from, including the override for `folder`.
+pub fn config_sources(folder: Option<&Path>) -> ConfigSources {
+    let folder = folder.and_then(FolderLayer::find);
+    ConfigLayers::current().sources(folder.as_ref())
+}
+
+/// Config layers from lowest to highest priority: built-in defaults, the team file, the
+/// user's config.json and, for files in a folder, its `.fyla.json`. Fields the team file
+/// lists as `locked` skip the user and folder layers. Model profiles from the team file
+/// and the user are merged by `id` rather than replaced as a whole list.
+struct ConfigLayers {
+    team_path: Option<PathBuf>,
+    team: Option<TeamLayer>,
+    team_error: Option<String>,
+    user: Map<String, Value>,
+}
+
+/// Modification time and size of the files behind the cached layers, and the team path
+/// (which the environment can change without touching any file).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LayerStamp {
+    user: Option<(SystemTime, u64)>,
+    team_path: Option<PathBuf>,
+    team: Option<(SystemTime, u64)>,
+}
+
+impl LayerStamp {
+    fn of(layers: &ConfigLayers) -> Self {
+        let setting = layers
+            .user
+            .get("teamConfigPath")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let team_path = TeamLayer::path(setting);
+        Self {
+            user: file_stamp(&config_path()),
+            team: team_path.as_deref().and_then(file_stamp),
+            team_path,
+        }
+    }
+}
+
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl ConfigLayers {
+    /// The layers as currently on disk. They are read and merged once and reused until
+    /// config.json or the team file changes (or the app saves), so a broken or missing
+    /// team file is reported once instead of on every config read.
+    fn current() -> Arc<Self> {
+        let mut cached = LAYERS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((stamp, layers)) = cached.as_ref()
+            && *stamp == LayerStamp::of(layers)
+        {
+            return layers.clone();
+        }
+        let layers = Arc::new(Self::load());
+        *cached = Some((LayerStamp::of(&layers), layers.clone()));
+        layers
+    }
+
+    /// Drops the cached layers; the next read goes back to disk.
+    fn invalidate() {
+        *LAYERS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+
+    fn load() -> Self {
+        let mut user = match fs::read_to_string(config_path()) {
+            Ok(data) => migrate_user_file(&data)
+                .map(|(map, _)| map)
+                .unwrap_or_else(|err| {
+                    log::warn!(target: "config", "{:#}", err);
+                    Map::new()
+                }),
+            Err(_) => Map::new(),
+        };
+        drop_unparsable(&mut user);
+        let setting = user
+            .get("teamConfigPath")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let team_path = TeamLayer::path(setting);
+        let (team, team_error) = match TeamLayer::load(setting) {
+            Ok(team) => (team, None),
+            Err(err) => {
+                log::warn!(target: "config", "{:#}", err);
+                (None, Some(format!("{:#}", err)))
+            }
+        };
+        Self {
+            team_path,
+            team,
+            team_error,
+            user,
+        }
+    }
+
+    /// Built-in defaults overlaid with the team values.
+    fn base(team: Option<&TeamLayer>) -> Map<String, Value> {
+        let mut map = default_map();
+        if let Some(team) = team {
+            map.extend(team.values.clone());
+        }
+        map
+    }
+
+    fn is_locked(&self, key: &str) -> bool {
+        self.team
+            .as_ref()
+            .is_some_and(|team| team.locked.contains(key))
+    }
+
+    /// Profiles the team file provides, if any.
+    fn team_profiles(&self) -> Option<&Vec<Value>> {
+        self.team
+            .as_ref()
+            .and_then(|team| team.values.get("profiles"))
+            .and_then(Value::as_array)
+    }
+
+    fn effective(&self, folder: Option<&FolderLayer>) -> AppConfig {
+        let mut map = Self::base(self.team.as_ref());
+        let upper = self
+            .user
+            .iter()
+            .chain(folder.into_iter().flat_map(|folder| &folder.values));
+        for (key, value) in upper {
+            if self.is_locked(key) {
+                continue;
+            }
+            let value = match (key.as_str(), value) {
+                ("profiles", Value::Array(user)) => Value::Array(merge_profiles(
+                    self.team_profiles().map_or(&[], Vec::as_slice),
+                    user,
+                )),
+                _ => value.clone(),
+            };
+            map.insert(key.clone(), value);
+        }
+        serde_json::from_value(Value::Object(map)).unwrap_or_default()
+    }
+
+    /// The part of the full config `full` that config.json keeps: fields config.json
+    /// already set, which stay the user's choice even when they equal a default, and
+    /// fields that differ from the team and built-in defaults. With team profiles, only
+    /// the profiles that are new or differ from the team's profile with the same id are
+    /// kept, so untouched team profiles keep following the team file; team profiles the
+    /// user deleted are kept as `removed` markers.
+    fn user_layer(&self, full: Map<String, Value>) -> Map<String, Value> {
+        let base = Self::base(self.team.as_ref());
+        full.into_iter()
+            .filter_map(|(key, value)| {
+                if USER_ONLY_FIELDS.contains(&key.as_str()) {
+                    return Some((key, value));
+                }
+                if self.is_locked(&key) {
+                    return None;
+                }
+                if let ("profiles", Some(team), Value::Array(profiles)) =
+                    (key.as_str(), self.team_profiles(), &value)
+                {
+                    // 团队文件里的配置可能只写了部分字段，补齐默认值后再比较
+                    let team = team
+                        .iter()
+                        .filter_map(|profile| {
+                            serde_json::from_value::<ModelProfile>(profile.clone()).ok()
+                        })
+                        .filter_map(|profile| serde_json::to_value(profile).ok())
+                        .collect::<Vec<_>>();
+                    let mut changed = profiles
+                        .iter()
+                        .filter(|profile| !team.contains(profile))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    changed.extend(
+                        team.iter()
+                            .map(|profile| &profile["id"])
+                            .filter(|id| !profiles.iter().any(|profile| &profile["id"] == *id))
+                            .map(|id| json!({ "id": id, "removed": true })),
+                    );
+                    return (!changed.is_empty()).then_some((key, Value::Array(changed)));
+                }
+                (self.user.contains_key(&key) || base.get(&key) != Some(&value))
+                    .then_some((key, value))
+            })
+            .collect()
+    }
+
+    fn sources(&self, folder: Option<&FolderLayer>) -> ConfigSources {
+        let fields = default_map()
+            .into_iter()
+            .filter(|(key, _)| key != "configVersion")
+            .map(|(key, _)| {
+                let locked = self.is_locked(&key);
+                let layer = if !locked && folder.is_some_and(|f| f.values.contains_key(&key)) {
+                    ConfigLayer::Folder
+                } else if !locked && self.user.contains_key(&key) {
+                    ConfigLayer::User
+                } else if self
+                    .team
+                    .as_ref()
+                    .is_some_and(|team| team.values.contains_key(&key))
+                {
+                    ConfigLayer::Team
+                } else {
+                    ConfigLayer::Default
+                };
+                (key, FieldSource { layer, locked })
+            })
+            .collect();
+        ConfigSources {
+            team_path: self
+                .team_path
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            team_error: self.team_error.clone(),
+            folder_path: folder.map(|folder| folder.path.display().to_string()),
+            fields,
+        }
+    }
+}
+
+/// Team profiles with the user's profiles laid over them: a user profile replaces the
+/// team profile with the same id, one marked `removed` hides it, other user profiles
+/// are added after the team's.
+fn merge_profiles(team: &[Value], user: &[Value]) -> Vec<Value> {
+    let id = |profile: &Value| {
+        profile
+            .get("id")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let mut merged = team.to_vec();
+    for profile in user {
+        let removed = profile["removed"].as_bool().unwrap_or(false);
+        let existing = merged
+            .iter()
+            .position(|existing| id(existing).is_some() && id(existing) == id(profile));
+        match (existing, removed) {
+            (Some(index), true) => {
+                merged.remove(index);
+            }
+            (Some(index), false) => merged[index] = profile.clone(),
+            (None, true) => {}
+            (None, false) => merged.push(profile.clone()),
+        }
+    }
+    merged
+}
+
+/// Shared defaults from a team config file: AppConfig fields plus a `locked` list of
+/// field names users cannot override. The file is not migrated, so it must use the
+/// current field names.
+#[derive(Debug)]
+struct TeamLayer {
+    values: Map<String, Value>,
+    locked: HashSet<String>,
+}
+
+impl TeamLayer {
+    /// Team file path: the environment variable, else the user's setting.
+    fn path(setting: &str) -> Option<PathBuf> {
+        std::env::var(TEAM_CONFIG_ENV)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .or_else(|| (!setting.trim().is_empty()).then(|| setting.to_string()))
+            .map(|value| PathBuf::from(value.trim()))
+    }
+
+    /// Reads the team file, if one is configured.
+    fn load(setting: &str) -> Result<Option<Self>> {
+        let Some(path) = Self::path(setting) else {
+            return Ok(None);
+        };
+        let data = fs::read_to_string(&path)
+            .map_err(|e| anyhow!("无法读取团队配置 {}: {}", path.display(), e))?;
+        Self::parse(&data)
+            .map(Some)
+            .map_err(|e| anyhow!("团队配置 {} {}", path.display(), e))
+    }
+
+    fn parse(data: &str) -> Result<Self> {
+        let Ok(Value::Object(mut values)) = serde_json::from_str::<Value>(data) else {
+            return Err(anyhow!("不是有效的 JSON 对象"));
+        };
+        let locked = match values.remove("locked") {
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(String::from)
+                .collect(),
+            _ => HashSet::new(),
+        };
+        for key in USER_ONLY_FIELDS {
+            values.remove(*key);
+        }
+        let dropped = drop_unparsable(&mut values);
+        if !dropped.is_empty() {
+            log::warn!(
+                target: "config",
+                "team config: ignored fields {}",
+                dropped.join(", ")
+            );
+        }
+        Ok(Self { values, locked })
+    }
+
+    /// Locked fields whose value in `config` differs from the team's.
+    fn locked_changes(&self, config: &AppConfig) -> Vec<ConfigIssue> {
+        let base = ConfigLayers::base(Some(self));
+        let Ok(Value::Object(map)) = serde_json::to_value(config) else {
+            return Vec::new();
+        };
+        let mut fields = self
+            .locked
+            .iter()
+            .filter(|key| {
+                map.get(*key)
+                    .is_some_and(|value| base.get(*key) != Some(value))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        fields.sort();
+        fields
+            .into_iter()
+            .map(|field| ConfigIssue {
+                field,
+                message: "由团队配置锁定，不能修改".into(),
+            })
+            .collect()
+    }
+}
+
+/// A `.fyla.json` folder override, limited to [`FOLDER_FIELDS`].
+struct FolderLayer {
+    path: PathBuf,
+    values: Map<String, Value>,
+}
+
+impl FolderLayer {
+    /// Nearest override file in `dir` or one of its ancestors.
+    fn find(dir: &Path) -> Option<Self> {
+        let path = dir
+            .ancestors()
+            .map(|dir| dir.join(FOLDER_CONFIG_FILE))
+            .find(|path| path.is_file())?;
+        let parsed = fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str::<Value>(&data).ok());
+        let values = match parsed {
+            Some(Value::Object(mut values)) => {
+                values.retain(|key, _| FOLDER_FIELDS.contains(&key.as_str()));
+                drop_unparsable(&mut values);
+                values
+            }
+            _ => {
+                log::warn!(target: "config", "ignored invalid folder config {}", path.display());
+                Map::new()
+            }
+        };
+        Some(Self { path, values })
+    }
+}
+
+fn default_map() -> Map<String, Value> {
+    match serde_json::to_value(AppConfig::default()) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    }
+}
+
+/// A config value that failed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// camelCase field name as stored in config.json, e.g. `profiles[0].baseUrl`.
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.field, self.message)
+    }
+}
+
+impl AppConfig {
+    /// Field-level checks run before saving: URLs, numeric ranges, enumerations,
+    /// templates and patterns.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |field: String, message: String| {
+            issues.push(ConfigIssue { field, message });
+        };
+
+        let mut ids = HashSet::new();
+        for (index, profile) in self.profiles.iter().enumerate() {
+            let field = |name: &str| format!("profiles[{}].{}", index, name);
+            if profile.id.trim().is_empty() {
+                issue(field("id"), "不能为空".into());
+            } else if !ids.insert(profile.id.trim()) {
+                issue(field("id"), format!("与其他模型配置重复: {}", profile.id));
+            }
+            if !matches!(profile.provider.as_str(), "ollama" | "openai" | "mock") {
+                issue(field("provider"), "只能是 ollama、openai 或 mock".into());
+            }
+            if let Some(message) = invalid_url(&profile.base_url) {
+                issue(field("baseUrl"), message);
+            }
+            let sampling = &profile.sampling;
+            if sampling
+                .temperature
+                .is_some_and(|value| !(0.0..=2.0).contains(&value))
+            {
+                issue(field("sampling.temperature"), "应在 0 到 2 之间".into());
+            }
+            if sampling
+                .top_p
+                .is_some_and(|value| !(0.0..=1.0).contains(&value))
+            {
+                issue(field("sampling.topP"), "应在 0 到 1 之间".into());
+            }
+            if sampling.max_tokens == Some(0) {
+                issue(field("sampling.maxTokens"), "应大于 0".into());
+            }
+        }
+        for (field, id) in [
+            ("renameProfile", &self.rename_profile),
+            ("vlmProfile", &self.vlm_profile),
+            ("paperReviewProfile", &self.paper_review_profile),
+            ("paperChatProfile", &self.paper_chat_profile),
+            ("embeddingProfile", &self.embedding_profile),
+            ("embeddingFallbackProfile", &self.embedding_fallback_profile),
+        ] {
+            if !id.trim().is_empty() && self.profile(id).is_none() {
+                issue(field.into(), format!("引用了不存在的模型配置: {}", id));
+            }
+        }
+
+        if !NAMING_STYLES.contains(&self.naming_style.as_str()) {
+            issue(
+                "namingStyle".into(),
+                format!("未知的命名风格: {}", self.naming_style),
+            );
+        }
+        if self.name_template.contains(['/', '\\']) {
+            issue("nameTemplate".into(), "不能包含路径分隔符".into());
+        }
+        if !(256..=8192).contains(&self.vlm_max_image_edge) {
+            issue("vlmMaxImageEdge".into(), "应在 256 到 8192 之间".into());
+        }
+        if !matches!(self.ocr_engine.as_str(), "auto" | "vision" | "tesseract") {
+            issue(
+                "ocrEngine".into(),
+                "只能是 auto、vision 或 tesseract".into(),
+            );
+        }
+        if !(1..=8).contains(&self.ocr_parallelism) {
+            issue("ocrParallelism".into(), "应在 1 到 8 之间".into());
+        }
+        if let Some(message) = invalid_page_range(&self.ocr_page_range) {
+            issue("ocrPageRange".into(), message);
+        }
+        if !matches!(self.redaction_mode.as_str(), "off" | "remote" | "all") {
+            issue("redactionMode".into(), "只能是 off、remote 或 all".into());
+        }
+        for (line_number, line) in self.redaction_patterns.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Err(err) = Regex::new(line) {
+                issue(
+                    "redactionPatterns".into(),
+                    format!("第 {} 行不是有效的正则表达式: {}", line_number + 1, err),
+                );
+            }
+        }
+        for (line_number, line) in self.redaction_folders.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let valid = line.rsplit_once('=').is_some_and(|(folder, mode)| {
+                !folder.trim().is_empty() && matches!(mode.trim(), "off" | "remote" | "all")
+            });
+            if !valid {
+                issue(
+                    "redactionFolders".into(),
+                    format!("第 {} 行应为「文件夹 = off|remote|all」", line_number + 1),
+                );
+            }
+        }
+        if self.paper_fulltext_token_limit != 0 && self.paper_fulltext_token_limit < 4_000 {
+            issue("paperFulltextTokenLimit".into(), "应不少于 4000".into());
+        }
+
+        if let Some(message) = invalid_url(&self.http_proxy) {
+            issue("httpProxy".into(), message);
+        }
+        let ca_bundle = self.http_ca_bundle.trim();
+        if !ca_bundle.is_empty() && !Path::new(ca_bundle).is_file() {
+            issue("httpCaBundle".into(), "找不到该证书文件".into());
+        }
+        if let Err(err) = http::parse_headers(&self.http_headers) {
+            issue("httpHeaders".into(), err.to_string());
+        }
+        let timeouts = &self.http_timeouts;
+        for (name, value) in [
+            ("test", timeouts.test),
+            ("rename", timeouts.rename),
+            ("vlm", timeouts.vlm),
+            ("embedding", timeouts.embedding),
+            ("connect", timeouts.connect),
+            ("streamIdle", timeouts.stream_idle),
+        ] {
+            if value.is_some_and(|secs| !(1..=3600).contains(&secs)) {
+                issue(
+                    format!("httpTimeouts.{}", name),
+                    "应在 1 到 3600 秒之间".into(),
+                );
+            }
+        }
+        for (index, price) in self.model_prices.iter().enumerate() {
+            let field = |name: &str| format!("modelPrices[{}].{}", index, name);
+            if price.model.trim().is_empty() {
+                issue(field("model"), "请填写模型名称".into());
+            }
+            for (name, value) in [
+                ("inputPerMillion", price.input_per_million),
+                ("outputPerMillion", price.output_per_million),
+            ] {
+                if value.is_nan() || value < 0.0 {
+                    issue(field(name), "价格不能为负数".into());
+                }
+            }
+        }
+        if self
+            .monthly_budget
+            .is_some_and(|budget| !(budget > 0.0 && budget.is_finite()))
+        {
+            issue("monthlyBudget".into(), "预算应大于 0".into());
+        }
+        issues
+    }
+
+    /// Checks that do not block saving: a naming template is passed to the model as
+    /// text, so unknown variables and stray braces may be meant literally.
+    pub fn warnings(&self) -> Vec<ConfigIssue> {
+        name_template_warning(&self.name_template)
+            .map(|message| ConfigIssue {
+                field: "nameTemplate".into(),
+                message: format!("{}，将按原文交给模型", message),
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+fn invalid_url(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    match reqwest::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => None,
+        Ok(_) => Some("必须是 http:// 或 https:// 地址".into()),
+        Err(err) => Some(format!("不是有效的 URL: {}", err)),
+    }
+}
+
+fn name_template_warning(template: &str) -> Option<String> {
+    let mut rest = template;
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            return Some("存在多余的 }".into());
+        }
+        let Some(close) = rest[open..].find('}') else {
+            return Some("存在未闭合的 {".into());
+        };
+        let name = &rest[open + 1..open + close];
+        if !NAME_TEMPLATE_VARS.contains(&name) {
+            return Some(format!(
+                "未知变量 {{{}}}，可用：{}",
+                name,
+                NAME_TEMPLATE_VARS
+                    .iter()
+                    .map(|var| format!("{{{}}}", var))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ));
+        }
+        rest = &rest[open + close + 1..];
+    }
+    None
+}
+
+fn invalid_page_range(spec: &str) -> Option<String> {
+    let spec = spec.trim();
+    if spec.is_empty() || spec.eq_ignore_ascii_case("all") {
+        return None;
+    }
+    let page = |value: &str| value.is_empty() || value.parse::<u32>().is_ok_and(|n| n > 0);
+    let valid = spec
+        .split([',', ';'])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .all(|part| match part.split_once('-') {
+            Some((start, end)) => page(start.trim()) && page(end.trim()),
+            None => part.parse::<u32>().is_ok_and(|n| n > 0),
+        });
+    (!valid).then(|| "格式应为 1-5,8,10- 这样的页码列表".into())
+}
+
 // --- 历史记录 ---
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -422,6 +1892,63 @@ pub fn upsert_paper_chat_session(entry: PaperChatSessionEntry) -> Result<PaperCh
     Ok(entry)
 }
 
+/// Rewrites paper history and chat sessions in one step while holding both locks, e.g.
+/// after the archive moved. The closures return whether they changed an entry. Each file
+/// is replaced atomically, and history is restored if writing the sessions fails.
+pub fn rewrite_paper_records(
+    mut rewrite_history: impl FnMut(&mut PaperHistoryEntry) -> bool,
+    mut rewrite_session: impl FnMut(&mut PaperChatSessionEntry) -> bool,
+) -> Result<(usize, usize)> {
+    let _history_lock = PAPER_HISTORY_LOCK.lock().unwrap();
+    let _session_lock = PAPER_CHAT_SESSION_LOCK.lock().unwrap();
+    let previous_history = load_paper_history();
+    let mut history = previous_history.clone();
+    let mut sessions = load_paper_chat_sessions();
+
+    let mut history_changed = 0;
+    for entry in &mut history {
+        if rewrite_history(entry) {
+            history_changed += 1;
+        }
+    }
+    let mut sessions_changed = 0;
+    for entry in &mut sessions {
+        if rewrite_session(entry) {
+            sessions_changed += 1;
+        }
+    }
+    if history_changed == 0 && sessions_changed == 0 {
+        return Ok((0, 0));
+    }
+
+    write_json_atomic(&paper_history_path(), &history)?;
+    if let Err(err) = write_json_atomic(&paper_chat_sessions_path(), &sessions) {
+        if let Err(restore_err) = write_json_atomic(&paper_history_path(), &previous_history) {
+            log::warn!(
+                target: "config",
+                "failed to restore paper history: {:#}",
+                restore_err
+            );
+        }
+        return Err(err);
+    }
+    Ok((history_changed, sessions_changed))
+}
+
+/// Writes `value` as JSON next to `path` and renames it into place, so readers see
+/// either the old or the new file.
+fn write_json_atomic<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    fs::write(&temp, serde_json::to_string_pretty(value)?)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })?;
+    Ok(())
+}
+
 fn normalize_paper_chat_session_entry(mut entry: PaperChatSessionEntry) -> PaperChatSessionEntry {
     if entry.paper_key.trim().is_empty() {
         entry.paper_key = entry.session_id.clone();
@@ -439,6 +1966,72 @@ fn normalize_paper_chat_session_entry(mut entry: PaperChatSessionEntry) -> Paper
     entry
 }
 
+// --- PDF 提取器固定 ---
+
+fn pdf_extractor_pins_path() -> PathBuf {
+    let base = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    base.join("fyla").join("pdf-extractor-pins.json")
+}
+
+/// Extractor labels pinned per PDF for paper review, keyed by the SHA-256 of the file
+/// so a pin follows the file through renames, moves and archive relocation. Older
+/// versions keyed pins by path; those move to the hash key when first read.
+pub fn load_pdf_extractor_pins() -> HashMap<String, String> {
+    let path = pdf_extractor_pins_path();
+    if let Ok(data) = fs::read_to_string(&path) {
+        serde_json::from_str(&data).unwrap_or_default()
+    } else {
+        HashMap::new()
+    }
+}
+
+/// The extractor pinned for the PDF at `path`, whose contents hash to `file_hash`.
+pub fn get_pdf_extractor_pin(path: &str, file_hash: &str) -> Option<String> {
+    let mut pins = load_pdf_extractor_pins();
+    if let Some(pin) = pins.remove(file_hash) {
+        return Some(pin);
+    }
+    let pin = pins.remove(path)?;
+    if let Err(err) = update_pdf_extractor_pins(|pins| {
+        rekey_path_pin(pins, path, file_hash);
+    }) {
+        log::warn!(target: "config", "failed to migrate extractor pin for {}: {:#}", path, err);
+    }
+    Some(pin)
+}
+
+/// Pins `extractor` for the PDF at `path`, or clears the pin when `None`.
+pub fn set_pdf_extractor_pin(path: &str, file_hash: &str, extractor: Option<&str>) -> Result<()> {
+    update_pdf_extractor_pins(|pins| {
+        pins.remove(path);
+        match extractor {
+            Some(extractor) => pins.insert(file_hash.to_string(), extractor.to_string()),
+            None => pins.remove(file_hash),
+        };
+    })
+}
+
+fn update_pdf_extractor_pins(update: impl FnOnce(&mut HashMap<String, String>)) -> Result<()> {
+    let _lock = PDF_EXTRACTOR_PIN_LOCK.lock().unwrap();
+    let mut pins = load_pdf_extractor_pins();
+    update(&mut pins);
+
+    let pins_path = pdf_extractor_pins_path();
+    if let Some(parent) = pins_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let data = serde_json::to_string_pretty(&pins)?;
+    fs::write(pins_path, data)?;
+    Ok(())
+}
+
+/// Moves a pin stored under `path` to `file_hash`, unless the hash already has one.
+fn rekey_path_pin(pins: &mut HashMap<String, String>, path: &str, file_hash: &str) {
+    if let Some(pin) = pins.remove(path) {
+        pins.entry(file_hash.to_string()).or_insert(pin);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -463,4 +2056,395 @@ mod tests {
             default_paper_review_prompt_template()
         );
     }
+
+    #[test]
+    fn legacy_model_fields_migrate_to_profiles() {
+        let parsed = parse_config(
+            r#"{
+                "provider": "openai",
+                "openaiKey": "env:OPENAI_API_KEY",
+                "openaiModel": "gpt-4o-mini",
+                "openaiBaseUrl": "https://api.example.com/v1/",
+                "vlmSameAsLlm": false,
+                "vlmBaseUrl": "https://vision.example.com/v1",
+                "vlmModel": "vision-1",
+                "paperProvider": "openai",
+                "paperOpenaiKey": "env:OPENAI_API_KEY",
+                "paperOpenaiModel": "gpt-4o-mini",
+                "paperOpenaiBaseUrl": "https://api.example.com/v1",
+                "paperEmbeddingProvider": "auto"
+            }"#,
+        )
+        .unwrap();
+        assert!(parsed.migrated);
+        let config = parsed.config;
+        assert_eq!(config.config_version, CONFIG_VERSION);
+        // 迁移写出的是版本 1 的字段，不随之后的结构体变化
+        let (map, _) = migrate_user_file(r#"{ "provider": "ollama" }"#).unwrap();
+        let keys = map["profiles"][0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                "apiKey",
+                "baseUrl",
+                "capabilities",
+                "id",
+                "model",
+                "name",
+                "provider"
+            ]
+        );
+
+        // 论文与改名的端点、Key、模型相同，合并为同一个配置
+        assert_eq!(config.rename_profile, "openai");
+        assert_eq!(
+            config.model_for(ModelTask::PaperReview).unwrap().id,
+            "openai"
+        );
+        assert_eq!(config.model_for(ModelTask::PaperChat).unwrap().id, "openai");
+        let openai = config.profile("openai").unwrap();
+        assert_eq!(openai.base_url, "https://api.example.com/v1");
+        assert_eq!(openai.api_key, "env:OPENAI_API_KEY");
+        assert!(config.profile("paper-openai").is_none());
+
+        let vlm = config.model_for(ModelTask::Vlm).unwrap();
+        assert_eq!((vlm.id.as_str(), vlm.model.as_str()), ("vlm", "vision-1"));
+        assert!(!vlm.capabilities.chat);
+
+        assert_eq!(config.embedding_profile, "embedding-ollama");
+        assert_eq!(config.embedding_fallback_profile, "embedding-openai");
+        assert!(config.model_for(ModelTask::Rename).is_ok());
+        assert!(config.model_for(ModelTask::Embedding).is_ok());
+    }
+
+    #[test]
+    fn model_for_checks_profile_capabilities() {
+        let mut config = AppConfig::default();
+        config.embedding_profile = config.rename_profile.clone();
+
+        let err = config.model_for(ModelTask::Embedding).unwrap_err();
+        assert!(err.to_string().contains("Embedding"));
+        config.rename_profile = "missing".into();
+        assert!(config.model_for(ModelTask::Rename).is_err());
+    }
+
+    #[test]
+    fn legacy_vision_setting_carries_into_migrated_profiles() {
+        // 旧版 VLM 跟随当前服务商时，直接把图片发给改名模型
+        for provider in ["ollama", "openai"] {
+            let raw = format!(
+                r#"{{ "provider": "{}", "vlmEnabled": true, "ollamaModel": "llava" }}"#,
+                provider
+            );
+            let config = parse_config(&raw).unwrap().config;
+            let vlm = config.model_for(ModelTask::Vlm).unwrap();
+            assert_eq!(vlm.id, provider);
+            assert!(vlm.capabilities.vision && vlm.capabilities.chat);
+        }
+
+        let config = parse_config(r#"{ "provider": "ollama", "vlmSameAsLlm": false }"#)
+            .unwrap()
+            .config;
+        assert!(!config.profile("ollama").unwrap().capabilities.vision);
+        assert_eq!(config.model_for(ModelTask::Vlm).unwrap().id, "vlm");
+    }
+
+    #[test]
+    fn bad_field_only_resets_that_field() {
+        let parsed = parse_config(
+            r#"{
+                "configVersion": 2,
+                "profiles": [],
+                "namingStyle": "snake_case",
+                "ocrParallelism": "four"
+            }"#,
+        )
+        .unwrap();
+
+        assert!(!parsed.migrated);
+        assert_eq!(parsed.dropped, vec!["ocrParallelism".to_string()]);
+        assert_eq!(parsed.config.naming_style, "snake_case");
+        assert_eq!(parsed.config.ocr_parallelism, default_ocr_parallelism());
+        assert!(parse_config("{ not json").is_err());
+    }
+
+    #[test]
+    fn missing_legacy_archive_root_migrates_to_data_dir() {
+        let parsed = parse_config(
+            r#"{ "configVersion": 1, "paperArchiveRoot": "/Users/chenghaoyang/Local/papers" }"#,
+        )
+        .unwrap();
+
+        assert!(parsed.migrated);
+        assert_eq!(
+            parsed.config.paper_archive_root,
+            default_paper_archive_root()
+        );
+        assert!(parsed.config.paper_archive_dir().ends_with("fyla/papers"));
+    }
+
+    #[test]
+    fn team_layer_sits_between_defaults_and_user() {
+        let team = TeamLayer::parse(
+            r#"{
+                "namingStyle": "snake_case",
+                "nameTemplate": "{date}-{title}",
+                "teamConfigPath": "/elsewhere.json",
+                "locked": ["nameTemplate"]
+            }"#,
+        )
+        .unwrap();
+        let layers = ConfigLayers {
+            team_path: None,
+            team: Some(team),
+            team_error: None,
+            user: Map::from_iter([
+                ("nameTemplate".into(), "{title}".into()),
+                ("includeDate".into(), true.into()),
+            ]),
+        };
+
+        let config = layers.effective(None);
+        assert_eq!(config.naming_style, "snake_case");
+        assert_eq!(config.name_template, "{date}-{title}");
+        assert!(config.include_date);
+        assert!(config.team_config_path.is_empty());
+
+        let sources = layers.sources(None);
+        assert_eq!(sources.fields["namingStyle"].layer, ConfigLayer::Team);
+        assert_eq!(sources.fields["nameTemplate"].layer, ConfigLayer::Team);
+        assert!(sources.fields["nameTemplate"].locked);
+        assert_eq!(sources.fields["includeDate"].layer, ConfigLayer::User);
+        assert_eq!(sources.fields["ocrEngine"].layer, ConfigLayer::Default);
+
+        let team = layers.team.as_ref().unwrap();
+        assert!(team.locked_changes(&config).is_empty());
+        let edited = AppConfig {
+            name_template: "{title}".into(),
+            ..config
+        };
+        assert_eq!(team.locked_changes(&edited)[0].field, "nameTemplate");
+    }
+
+    #[test]
+    fn profiles_merge_with_the_team_layer_by_id() {
+        let team = TeamLayer::parse(
+            r#"{ "profiles": [
+                { "id": "team-gpt", "name": "GPT", "provider": "openai", "model": "gpt-4o" },
+                { "id": "team-local", "name": "Local", "provider": "ollama", "model": "qwen" }
+            ] }"#,
+        )
+        .unwrap();
+        let mut layers = ConfigLayers {
+            team_path: None,
+            team: Some(team),
+            team_error: None,
+            user: Map::new(),
+        };
+        let mut config = layers.effective(None);
+        assert_eq!(config.profiles.len(), 2);
+
+        config.profiles[1].model = "qwen3".into();
+        config.profiles.push(ModelProfile {
+            id: "mine".into(),
+            ..ModelProfile::default()
+        });
+        let Value::Object(full) = serde_json::to_value(&config).unwrap() else {
+            unreachable!()
+        };
+        layers.user = layers.user_layer(full);
+        let stored = layers.user["profiles"].as_array().unwrap();
+        let ids = stored
+            .iter()
+            .map(|profile| profile["id"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["team-local", "mine"]);
+
+        // 团队更新了未改动的配置，用户这边随之变化
+        layers.team.as_mut().unwrap().values["profiles"][0]["model"] = "gpt-5".into();
+        let merged = layers.effective(None);
+        let models = merged
+            .profiles
+            .iter()
+            .map(|profile| (profile.id.as_str(), profile.model.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            models,
+            [("team-gpt", "gpt-5"), ("team-local", "qwen3"), ("mine", "")]
+        );
+
+        // 删掉团队配置后记为 removed，之后团队文件里的同名配置不再出现
+        let mut config = merged;
+        config.profiles.remove(0);
+        let Value::Object(full) = serde_json::to_value(&config).unwrap() else {
+            unreachable!()
+        };
+        layers.user = layers.user_layer(full);
+        assert_eq!(
+            layers.user["profiles"].as_array().unwrap().last(),
+            Some(&json!({ "id": "team-gpt", "removed": true }))
+        );
+        let ids = layers
+            .effective(None)
+            .profiles
+            .into_iter()
+            .map(|profile| profile.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["team-local", "mine"]);
+        layers.team = None;
+        assert_eq!(layers.effective(None).profiles.len(), 2);
+    }
+
+    #[test]
+    fn user_values_equal_to_defaults_stay_in_the_user_file() {
+        let team = TeamLayer::parse(r#"{ "namingStyle": "snake_case" }"#).unwrap();
+        let layers = ConfigLayers {
+            team_path: None,
+            team: Some(team),
+            team_error: None,
+            user: Map::from_iter([("namingStyle".into(), "kebab-case".into())]),
+        };
+        // 用户明确选了内置默认值，团队文件不能覆盖它
+        let config = layers.effective(None);
+        assert_eq!(config.naming_style, "kebab-case");
+        let Value::Object(full) = serde_json::to_value(&config).unwrap() else {
+            unreachable!()
+        };
+        let user = layers.user_layer(full);
+        assert_eq!(user["namingStyle"], "kebab-case");
+        assert!(!user.contains_key("ocrEngine"));
+    }
+
+    #[test]
+    fn folder_override_only_sets_naming_fields() {
+        let dir = std::env::temp_dir().join(format!("fyla-folder-config-{}", std::process::id()));
+        let nested = dir.join("2024").join("receipts");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            dir.join(FOLDER_CONFIG_FILE),
+            r#"{ "namingStyle": "chinese", "profiles": [], "redactionMode": "off" }"#,
+        )
+        .unwrap();
+
+        let folder = FolderLayer::find(&nested).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(folder.path, dir.join(FOLDER_CONFIG_FILE));
+        assert_eq!(
+            folder.values.keys().collect::<Vec<_>>(),
+            vec!["namingStyle"]
+        );
+    }
+
+    #[test]
+    fn validate_reports_invalid_fields() {
+        assert!(AppConfig::default().validate().is_empty());
+
+        let mut config = AppConfig {
+            name_template: "{type}/{title}".into(),
+            vlm_max_image_edge: 10,
+            ocr_page_range: "1-x".into(),
+            redaction_patterns: "ACME-\\d+\n([".into(),
+            model_prices: vec![ModelPrice {
+                model: "gpt-4.1".into(),
+                input_per_million: 2.0,
+                output_per_million: -8.0,
+            }],
+            ..AppConfig::default()
+        };
+        config.profiles[0].base_url = "localhost:11434".into();
+        config.profiles[0].sampling.temperature = Some(3.0);
+
+        let fields = config
+            .validate()
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                "profiles[0].baseUrl",
+                "profiles[0].sampling.temperature",
+                "nameTemplate",
+                "vlmMaxImageEdge",
+                "ocrPageRange",
+                "redactionPatterns",
+                "modelPrices[0].outputPerMillion",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_template_variables_warn_without_blocking_save() {
+        let config = AppConfig {
+            name_template: "{type}-{titel} {draft".into(),
+            ..AppConfig::default()
+        };
+        assert!(config.validate().is_empty());
+        let warnings = config.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "nameTemplate");
+        assert!(warnings[0].message.contains("{titel}"));
+
+        assert!(AppConfig::default().warnings().is_empty());
+        let known = AppConfig {
+            name_template: "{date}-{title}-{place}".into(),
+            ..AppConfig::default()
+        };
+        assert!(known.warnings().is_empty());
+    }
+
+    #[test]
+    fn path_keyed_extractor_pins_move_to_the_content_hash() {
+        let mut pins = HashMap::from([
+            ("/papers/a.pdf".to_string(), "pdftotext".to_string()),
+            ("/papers/b.pdf".to_string(), "ocr".to_string()),
+            ("hash-b".to_string(), "builtin".to_string()),
+        ]);
+        rekey_path_pin(&mut pins, "/papers/a.pdf", "hash-a");
+        rekey_path_pin(&mut pins, "/papers/b.pdf", "hash-b");
+        rekey_path_pin(&mut pins, "/papers/c.pdf", "hash-c");
+
+        assert_eq!(
+            pins,
+            HashMap::from([
+                ("hash-a".to_string(), "pdftotext".to_string()),
+                ("hash-b".to_string(), "builtin".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn saving_over_a_newer_file_keeps_what_this_version_cannot_read() {
+        let stored = json!({
+            "configVersion": CONFIG_VERSION + 1,
+            "futureField": { "enabled": true },
+            "ocrParallelism": "four",
+            "namingStyle": "Train-Case",
+        });
+        let Value::Object(stored) = stored else {
+            unreachable!()
+        };
+        let mut user = Map::from_iter([
+            ("configVersion".to_string(), json!(CONFIG_VERSION)),
+            ("namingStyle".to_string(), json!("snake_case")),
+        ]);
+        keep_newer_fields(&mut user, stored.clone());
+
+        assert_eq!(user["configVersion"], json!(CONFIG_VERSION + 1));
+        assert_eq!(user["futureField"], json!({ "enabled": true }));
+        assert_eq!(user["ocrParallelism"], "four");
+        assert_eq!(user["namingStyle"], "snake_case");
+
+        let mut current = Map::new();
+        let mut older = stored;
+        older.insert("configVersion".into(), json!(CONFIG_VERSION));
+        keep_newer_fields(&mut current, older);
+        assert!(current.is_empty());
+    }
 }
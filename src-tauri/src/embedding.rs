@@ -1,11 +1,12 @@
-use crate::config::AppConfig;
+use crate::config::{AppConfig, ModelProfile, ModelTask};
+use crate::http::{self, HttpTask};
+use crate::redact::{self, Redactor};
+use crate::usage::{self, TokenUsage};
+use crate::{mock, secrets};
 use anyhow::{Context, Result, anyhow};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::time::Duration;
-
-const REQUEST_TIMEOUT_SECS: u64 = 45;
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -21,6 +22,7 @@ pub struct PaperEmbeddingStatus {
 pub enum EmbeddingProviderKind {
     Ollama,
     OpenAI,
+    Mock,
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +32,19 @@ pub struct ResolvedEmbeddingRuntime {
     pub api_key: Option<String>,
     pub model: String,
     pub signature: String,
+    /// Masks sensitive values before texts are sent; follows the global policy unless
+    /// narrowed with [`Self::redacting_for`].
+    pub redactor: Option<Redactor>,
+    /// Client the runtime was resolved with; embedding requests reuse it.
+    pub client: Client,
+}
+
+impl ResolvedEmbeddingRuntime {
+    /// Applies the redaction policy for texts taken from `path`, including folder overrides.
+    pub fn redacting_for(mut self, config: &AppConfig, path: Option<&str>) -> Self {
+        self.redactor = redact::redactor_for(config, path, &self.base_url);
+        self
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -50,12 +65,20 @@ struct OllamaEmbedResponse {
     embeddings: Vec<Vec<f32>>,
     #[serde(default)]
     embedding: Vec<f32>,
+    prompt_eval_count: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
 struct OpenAIEmbeddingResponse {
     #[serde(default)]
     data: Vec<OpenAIEmbeddingItem>,
+    usage: Option<OpenAIEmbeddingUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIEmbeddingUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,22 +89,27 @@ struct OpenAIEmbeddingItem {
 }
 
 pub async fn get_status(config: &AppConfig) -> PaperEmbeddingStatus {
-    let configured_provider = normalized_embedding_provider(config);
-    match resolve_runtime(config).await {
-        Ok(runtime) => {
+    let configured_provider = config
+        .model_for(ModelTask::Embedding)
+        .map(|profile| profile.provider.clone())
+        .unwrap_or_default();
+    let resolved = match http_client() {
+        Ok(client) => resolve_runtime_with_source(config, &client).await,
+        Err(err) => Err(err),
+    };
+    match resolved {
+        Ok((runtime, used_fallback)) => {
             let resolved_provider = provider_name(&runtime.provider).to_string();
             let model_name = runtime.model.clone();
-            let state = if configured_provider == "auto"
-                && runtime.provider == EmbeddingProviderKind::OpenAI
-            {
+            let state = if used_fallback {
                 "fallback".into()
             } else {
                 "ready".into()
             };
-            let message = if runtime.provider == EmbeddingProviderKind::Ollama {
-                "将使用本地 Ollama embedding。".into()
-            } else {
-                "将使用 OpenAI 兼容 embedding。".into()
+            let message = match runtime.provider {
+                EmbeddingProviderKind::Ollama => "将使用本地 Ollama embedding。".into(),
+                EmbeddingProviderKind::OpenAI => "将使用 OpenAI 兼容 embedding。".into(),
+                EmbeddingProviderKind::Mock => "将使用离线模拟 embedding。".into(),
             };
 
             PaperEmbeddingStatus {
@@ -102,8 +130,14 @@ pub async fn get_status(config: &AppConfig) -> PaperEmbeddingStatus {
     }
 }
 
+/// Resolves and probes the embedding profile with the transport settings in `config`,
+/// which may not be saved yet.
 pub async fn test_connection(config: &AppConfig) -> Result<String> {
-    let runtime = resolve_runtime(config).await?;
+    let client = http::one_off_client(config, HttpTask::Test)?;
+    let runtime = resolve_runtime_with_source(config, &client)
+        .await?
+        .0
+        .redacting_for(config, None);
     let probe = vec!["test paper embedding".to_string()];
     let vectors = embed_texts(&runtime, &probe).await?;
     let dims = vectors.first().map(|item| item.len()).unwrap_or(0);
@@ -116,25 +150,47 @@ pub async fn test_connection(config: &AppConfig) -> Result<String> {
         EmbeddingProviderKind::OpenAI => {
             format!("Embedding 连接成功：OpenAI 兼容 / {}（{} 维）", model, dims)
         }
+        EmbeddingProviderKind::Mock => {
+            format!("模拟 Embedding 可用：{}（{} 维）", model, dims)
+        }
     })
 }
 
 pub async fn resolve_runtime(config: &AppConfig) -> Result<ResolvedEmbeddingRuntime> {
-    match normalized_embedding_provider(config).as_str() {
-        "ollama" => resolve_ollama_runtime(config).await,
-        "openai" => resolve_openai_runtime(config),
-        _ => match resolve_ollama_runtime(config).await {
-            Ok(runtime) => Ok(runtime),
-            Err(ollama_err) => resolve_openai_runtime(config).map_err(|openai_err| {
-                anyhow!(
-                    "未检测到可用的本地 Ollama embedding 模型，请先执行 `ollama pull {}`，或在设置页补齐论文 Embedding 的 Base URL / API Key / Model。Ollama: {}；OpenAI: {}",
-                    embedding_ollama_model(config),
-                    ollama_err,
-                    openai_err
-                )
-            }),
-        },
-    }
+    let runtime = resolve_runtime_with_source(config, &http_client()?)
+        .await?
+        .0;
+    Ok(runtime.redacting_for(config, None))
+}
+
+/// Resolves the embedding profile, trying the fallback profile when the primary one is
+/// unavailable. The flag tells whether the fallback was used.
+async fn resolve_runtime_with_source(
+    config: &AppConfig,
+    client: &Client,
+) -> Result<(ResolvedEmbeddingRuntime, bool)> {
+    let primary = config.model_for(ModelTask::Embedding)?;
+    let primary_err = match resolve_profile_runtime(primary, client).await {
+        Ok(runtime) => return Ok((runtime, false)),
+        Err(err) => err,
+    };
+    let Some(fallback) = config.embedding_fallback() else {
+        return Err(primary_err);
+    };
+    let fallback_result = match fallback {
+        Ok(fallback) => resolve_profile_runtime(fallback, client).await,
+        Err(err) => Err(err),
+    };
+    fallback_result
+        .map(|runtime| (runtime, true))
+        .map_err(|fallback_err| {
+            anyhow!(
+                "Embedding 模型配置「{}」不可用：{}；备用配置也不可用：{}",
+                primary.name,
+                primary_err,
+                fallback_err
+            )
+        })
 }
 
 pub async fn embed_texts(
@@ -145,10 +201,48 @@ pub async fn embed_texts(
         return Ok(Vec::new());
     }
 
-    match runtime.provider {
-        EmbeddingProviderKind::Ollama => embed_with_ollama(runtime, texts).await,
-        EmbeddingProviderKind::OpenAI => embed_with_openai(runtime, texts).await,
-    }
+    let provider = provider_name(&runtime.provider);
+    usage::ensure_budget(provider, &runtime.base_url, &runtime.model)?;
+    let redacted;
+    let texts = match runtime.redactor.clone() {
+        Some(mut redactor) => {
+            redacted = texts
+                .iter()
+                .map(|text| redactor.redact(text))
+                .collect::<Vec<_>>();
+            if redactor.masked_count() > 0 {
+                log::info!(
+                    target: "redact",
+                    "masked {} values before embedding",
+                    redactor.masked_count()
+                );
+            }
+            &redacted[..]
+        }
+        None => texts,
+    };
+    let (vectors, prompt_tokens) = match runtime.provider {
+        EmbeddingProviderKind::Ollama => embed_with_ollama(runtime, texts).await?,
+        EmbeddingProviderKind::OpenAI => embed_with_openai(runtime, texts).await?,
+        EmbeddingProviderKind::Mock => (mock::embed(texts), None),
+    };
+    let input_chars = texts.iter().map(|text| text.chars().count()).sum();
+    let token_usage = match prompt_tokens {
+        Some(prompt_tokens) => TokenUsage {
+            prompt_tokens,
+            completion_tokens: 0,
+            estimated: false,
+        },
+        None => TokenUsage::estimate(input_chars, 0),
+    };
+    usage::record_call(
+        ModelTask::Embedding,
+        provider,
+        &runtime.model,
+        &runtime.base_url,
+        token_usage,
+    );
+    Ok(vectors)
 }
 
 pub async fn embed_query(runtime: &ResolvedEmbeddingRuntime, query: &str) -> Result<Vec<f32>> {
@@ -171,94 +265,102 @@ pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     dot / (norm_a * norm_b)
 }
 
-fn normalized_embedding_provider(config: &AppConfig) -> String {
-    match config.paper_embedding_provider.trim() {
-        "ollama" => "ollama".into(),
-        "openai" => "openai".into(),
-        _ => "auto".into(),
-    }
-}
-
 fn provider_name(provider: &EmbeddingProviderKind) -> &'static str {
     match provider {
         EmbeddingProviderKind::Ollama => "ollama",
         EmbeddingProviderKind::OpenAI => "openai",
+        EmbeddingProviderKind::Mock => "mock",
     }
 }
 
-fn embedding_ollama_url(config: &AppConfig) -> String {
-    let url = config.paper_embedding_ollama_url.trim();
-    if url.is_empty() {
-        "http://localhost:11434".into()
+async fn resolve_profile_runtime(
+    profile: &ModelProfile,
+    client: &Client,
+) -> Result<ResolvedEmbeddingRuntime> {
+    if profile.is_mock() {
+        Ok(resolve_mock_runtime(profile, client))
+    } else if profile.is_openai() {
+        resolve_openai_runtime(profile, client)
     } else {
-        url.into()
+        resolve_ollama_runtime(profile, client).await
     }
 }
 
-fn embedding_ollama_model(config: &AppConfig) -> String {
-    let model = config.paper_embedding_ollama_model.trim();
-    if model.is_empty() {
-        "nomic-embed-text".into()
-    } else {
-        model.into()
-    }
-}
-
-fn embedding_openai_base_url(config: &AppConfig) -> String {
-    config.paper_embedding_openai_base_url.trim().to_string()
-}
-
-fn embedding_openai_key(config: &AppConfig) -> String {
-    config.paper_embedding_openai_key.trim().to_string()
-}
-
-fn embedding_openai_model(config: &AppConfig) -> String {
-    let model = config.paper_embedding_openai_model.trim();
+async fn resolve_ollama_runtime(
+    profile: &ModelProfile,
+    client: &Client,
+) -> Result<ResolvedEmbeddingRuntime> {
+    let base_url = profile.endpoint().to_string();
+    let model = profile.model.trim().to_string();
     if model.is_empty() {
-        "text-embedding-3-small".into()
-    } else {
-        model.into()
+        return Err(anyhow!(
+            "请在模型配置「{}」中填写 embedding 模型",
+            profile.name
+        ));
     }
-}
-
-async fn resolve_ollama_runtime(config: &AppConfig) -> Result<ResolvedEmbeddingRuntime> {
-    let base_url = embedding_ollama_url(config);
-    let model = embedding_ollama_model(config);
-    let client = http_client()?;
-    let available = ollama_model_available(&client, &base_url, &model).await?;
+    let available = ollama_model_available(client, &base_url, &model).await?;
     if !available {
-        return Err(anyhow!("Ollama 已连接，但 embedding 模型 {} 未安装", model));
+        return Err(anyhow!(
+            "Ollama 已连接，但 embedding 模型 {} 未安装，请先执行 `ollama pull {}`",
+            model,
+            model
+        ));
     }
 
     Ok(ResolvedEmbeddingRuntime {
         provider: EmbeddingProviderKind::Ollama,
-        base_url: base_url.trim_end_matches('/').to_string(),
+        signature: format!("ollama|{}|{}", base_url, model),
+        base_url,
         api_key: None,
-        signature: format!("ollama|{}|{}", base_url.trim_end_matches('/'), model),
         model,
+        redactor: None,
+        client: client.clone(),
     })
 }
 
-fn resolve_openai_runtime(config: &AppConfig) -> Result<ResolvedEmbeddingRuntime> {
-    let base_url = embedding_openai_base_url(config);
-    let api_key = embedding_openai_key(config);
-    let model = embedding_openai_model(config);
+fn resolve_openai_runtime(
+    profile: &ModelProfile,
+    client: &Client,
+) -> Result<ResolvedEmbeddingRuntime> {
+    let base_url = profile.endpoint().to_string();
+    let api_key = secrets::api_key(profile)?;
+    let model = profile.model.trim().to_string();
 
     if base_url.is_empty() || api_key.is_empty() || model.is_empty() {
         return Err(anyhow!(
-            "请补齐论文 Embedding 的 Base URL / API Key / Model"
+            "请补齐模型配置「{}」的 Base URL / API Key / Model",
+            profile.name
         ));
     }
 
     Ok(ResolvedEmbeddingRuntime {
         provider: EmbeddingProviderKind::OpenAI,
-        base_url: base_url.trim_end_matches('/').to_string(),
+        signature: format!("openai|{}|{}", base_url, model),
+        base_url,
         api_key: Some(api_key),
-        signature: format!("openai|{}|{}", base_url.trim_end_matches('/'), model),
         model,
+        redactor: None,
+        client: client.clone(),
     })
 }
 
+/// A mock runtime never touches the network, so it is always available.
+fn resolve_mock_runtime(profile: &ModelProfile, client: &Client) -> ResolvedEmbeddingRuntime {
+    let model = match profile.model.trim() {
+        "" => "mock".to_string(),
+        model => model.to_string(),
+    };
+    ResolvedEmbeddingRuntime {
+        provider: EmbeddingProviderKind::Mock,
+        signature: format!("mock|{}", model),
+        base_url: String::new(),
+        api_key: None,
+        model,
+        redactor: None,
+        client: client.clone(),
+    }
+}
+
 async fn ollama_model_available(client: &Client, base_url: &str, model: &str) -> Result<bool> {
     let url = format!("{}/api/tags", base_url.trim_end_matches('/'));
     let resp = client.get(&url).send().await.map_err(|err| {
@@ -277,11 +379,12 @@ async fn ollama_model_available(client: &Client, base_url: &str, model: &str) ->
     Ok(data.models.iter().any(|item| item.name.starts_with(model)))
 }
 
+/// Returns the vectors and the prompt tokens reported by the server, if any.
 async fn embed_with_ollama(
     runtime: &ResolvedEmbeddingRuntime,
     texts: &[String],
-) -> Result<Vec<Vec<f32>>> {
-    let client = http_client()?;
+) -> Result<(Vec<Vec<f32>>, Option<u64>)> {
+    let client = &runtime.client;
     let url = format!("{}/api/embed", runtime.base_url.trim_end_matches('/'));
     let resp = client
         .post(&url)
@@ -302,10 +405,10 @@ async fn embed_with_ollama(
     if resp.status().is_success() {
         let data: OllamaEmbedResponse = resp.json().await.context("解析 Ollama embedding 失败")?;
         if !data.embeddings.is_empty() {
-            return Ok(data.embeddings);
+            return Ok((data.embeddings, data.prompt_eval_count));
         }
         if !data.embedding.is_empty() {
-            return Ok(vec![data.embedding]);
+            return Ok((vec![data.embedding], data.prompt_eval_count));
         }
     } else if resp.status().as_u16() != 404 {
         let status = resp.status();
@@ -338,14 +441,15 @@ async fn embed_with_ollama(
         };
         outputs.push(embedding);
     }
-    Ok(outputs)
+    // 旧接口不返回用量，按文本长度估算
+    Ok((outputs, None))
 }
 
 async fn embed_with_openai(
     runtime: &ResolvedEmbeddingRuntime,
     texts: &[String],
-) -> Result<Vec<Vec<f32>>> {
-    let client = http_client()?;
+) -> Result<(Vec<Vec<f32>>, Option<u64>)> {
+    let client = &runtime.client;
     let url = format!("{}/embeddings", runtime.base_url.trim_end_matches('/'));
     let resp = client
         .post(&url)
@@ -367,18 +471,91 @@ async fn embed_with_openai(
         return Err(anyhow!("Embedding API 请求失败 {}: {}", status, text));
     }
 
-    let mut items = resp
+    let response = resp
         .json::<OpenAIEmbeddingResponse>()
         .await
-        .context("解析 embedding API 响应失败")?
-        .data;
+        .context("解析 embedding API 响应失败")?;
+    let mut items = response.data;
     items.sort_by_key(|item| item.index);
-    Ok(items.into_iter().map(|item| item.embedding).collect())
+    let vectors = items.into_iter().map(|item| item.embedding).collect();
+    Ok((vectors, response.usage.map(|usage| usage.prompt_tokens)))
 }
 
 fn http_client() -> Result<Client> {
-    Client::builder()
-        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
-        .build()
-        .context("初始化 HTTP 客户端失败")
+    http::client(HttpTask::Embedding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{StubResponse, StubServer, block_on, compatible_response};
+
+    fn profile(provider: &str, base_url: String, model: &str) -> ModelProfile {
+        ModelProfile {
+            provider: provider.into(),
+            base_url,
+            api_key: "sk-test".into(),
+            model: model.into(),
+            ..ModelProfile::default()
+        }
+    }
+
+    #[test]
+    fn embed_texts_keeps_input_order_for_both_providers() {
+        let server = StubServer::compatible("", 1);
+        let texts = vec!["a".to_string(), "abc".to_string()];
+        for profile in [
+            profile("openai", server.openai_url(), "text-embedding-3-small"),
+            profile("ollama", server.ollama_url(), "nomic-embed-text"),
+        ] {
+            let runtime =
+                block_on(resolve_profile_runtime(&profile, &http_client().unwrap())).unwrap();
+            let vectors = block_on(embed_texts(&runtime, &texts)).unwrap();
+            assert_eq!(vectors, vec![vec![1.0, 1.0], vec![3.0, 1.0]]);
+        }
+        let paths = server
+            .requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/v1/embeddings", "/api/tags", "/api/embed"]);
+
+        let missing = profile("ollama", server.ollama_url(), "bge-m3");
+        let err = block_on(resolve_profile_runtime(&missing, &http_client().unwrap())).unwrap_err();
+        assert!(err.to_string().contains("ollama pull bge-m3"));
+    }
+
+    #[test]
+    fn embed_texts_sends_redacted_texts() {
+        let server = StubServer::compatible("", 1);
+        let config = AppConfig {
+            redaction_mode: "all".into(),
+            ..AppConfig::default()
+        };
+        let runtime = resolve_openai_runtime(
+            &profile("openai", server.openai_url(), "m"),
+            &http_client().unwrap(),
+        )
+        .unwrap()
+        .redacting_for(&config, None);
+        block_on(embed_texts(&runtime, &["mail bob@corp.io".to_string()])).unwrap();
+        let body = &server.requests()[0].body;
+        assert_eq!(body["input"], json!(["mail [EMAIL_1]"]));
+    }
+
+    #[test]
+    fn embed_texts_surfaces_rate_limits() {
+        let server = StubServer::start(|request| match request.path.as_str() {
+            "/v1/embeddings" => StubResponse::Status(429, "slow down".into()),
+            _ => compatible_response(request, "", 1),
+        });
+        let runtime = resolve_openai_runtime(
+            &profile("openai", server.openai_url(), "m"),
+            &http_client().unwrap(),
+        )
+        .unwrap();
+        let err = block_on(embed_texts(&runtime, &["a".to_string()])).unwrap_err();
+        assert!(err.to_string().contains("429"));
+        assert!(err.to_string().contains("slow down"));
+    }
 }
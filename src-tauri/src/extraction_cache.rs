@@ -0,0 +1,276 @@
+use crate::config;
+use crate::ocr::OcrSettings;
+use crate::pdf::{self, PdfExtracted, PdfPageText};
+use crate::pdf_password;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+static CACHE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Bump whenever extraction or cleanup changes output, so stale entries are re-extracted.
+const CACHE_VERSION: u32 = 2;
+
+/// Extraction results for one PDF, keyed by the SHA-256 of its bytes so renamed or
+/// archived copies share an entry. Review text and chat pages are filled in independently.
+/// Entries are plain JSON, so text of password-protected PDFs is never stored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExtractionCacheEntry {
+    pub version: u32,
+    pub file_hash: String,
+    /// [`OcrSettings::fingerprint`] the text was extracted with; other settings miss.
+    pub ocr_settings: String,
+    pub review: Option<CachedReviewText>,
+    pub pages: Option<Vec<PdfPageText>>,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedReviewText {
+    pub extractor: String,
+    pub text: String,
+    pub warning: Option<String>,
+    /// Extractor pinned when this text was produced; a different pin misses the cache.
+    pub pinned: Option<String>,
+}
+
+/// Paper review text for `path`, extracted once per file content and OCR settings.
+/// Password-protected files are never written to the cache, so a hit is used without
+/// checking for a password first.
+pub fn review_text(path: &str, ocr_settings: &OcrSettings) -> Result<PdfExtracted> {
+    let hash = file_hash(path)?;
+    let root = cache_root();
+    let settings = ocr_settings.fingerprint();
+    let pinned = config::get_pdf_extractor_pin(path, &hash);
+    if let Some(review) = read_entry(&root, &hash, &settings)
+        .and_then(|entry| entry.review)
+        .filter(|review| review.pinned == pinned)
+    {
+        log::debug!(target: "extract-cache", "review hit {} for {}", hash, path);
+        return Ok(PdfExtracted {
+            extractor: review.extractor,
+            text: review.text,
+            warning: review.warning,
+        });
+    }
+
+    let extracted = pdf::extract_pdf_text_for_paper(path, pinned.as_deref(), ocr_settings)?;
+    if !cacheable(&root, path, &hash) {
+        return Ok(extracted);
+    }
+    let review = CachedReviewText {
+        extractor: extracted.extractor.clone(),
+        text: extracted.text.clone(),
+        warning: extracted.warning.clone(),
+        pinned,
+    };
+    update_entry(&root, &hash, &settings, |entry| entry.review = Some(review));
+    Ok(extracted)
+}
+
+/// Page-level chat text for `path`, extracted once per file content and OCR settings.
+pub fn chat_pages(path: &str, ocr_settings: &OcrSettings) -> Result<Vec<PdfPageText>> {
+    let hash = file_hash(path)?;
+    let root = cache_root();
+    let settings = ocr_settings.fingerprint();
+    if let Some(pages) = read_entry(&root, &hash, &settings).and_then(|entry| entry.pages) {
+        log::debug!(target: "extract-cache", "pages hit {} for {}", hash, path);
+        return Ok(pages);
+    }
+
+    let pages = pdf::extract_pdf_pages_for_chat(path, ocr_settings)?;
+    if !cacheable(&root, path, &hash) {
+        return Ok(pages);
+    }
+    let cached = pages.clone();
+    update_entry(&root, &hash, &settings, |entry| entry.pages = Some(cached));
+    Ok(pages)
+}
+
+/// The extractor pinned for the PDF at `path`, looked up by its content hash.
+pub fn pinned_extractor(path: &str) -> Option<String> {
+    let hash = file_hash(path).ok()?;
+    config::get_pdf_extractor_pin(path, &hash)
+}
+
+/// Hex SHA-256 of the file contents.
+pub fn file_hash(path: &str) -> Result<String> {
+    let mut file = std::fs::File::open(path).with_context(|| format!("读取文件失败: {}", path))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Whether text of `path` may be cached: not when it needs a password to open. Entries
+/// written for such a file before this rule existed are removed. Only files whose
+/// trailer names `/Encrypt` are parsed for this (see [`pdf_password::unlock`]).
+fn cacheable(root: &Path, path: &str, hash: &str) -> bool {
+    if matches!(pdf_password::unlock(path, None), Ok(None)) {
+        return true;
+    }
+    log::debug!(target: "extract-cache", "not caching password-protected {}", path);
+    let _ = std::fs::remove_file(entry_path(root, hash));
+    false
+}
+
+fn cache_root() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("fyla")
+        .join("extraction-cache")
+}
+
+fn entry_path(root: &Path, hash: &str) -> PathBuf {
+    root.join(format!("{}.json", hash))
+}
+
+/// Reads an entry; unreadable entries and entries from another cache version or other
+/// OCR settings are misses.
+fn read_entry(root: &Path, hash: &str, ocr_settings: &str) -> Option<ExtractionCacheEntry> {
+    let raw = std::fs::read_to_string(entry_path(root, hash)).ok()?;
+    let entry = serde_json::from_str::<ExtractionCacheEntry>(&raw).ok()?;
+    (entry.version == CACHE_VERSION
+        && entry.file_hash == hash
+        && entry.ocr_settings == ocr_settings)
+        .then_some(entry)
+}
+
+/// Re-reads the entry under a lock and applies `update`, so review and chat extractions
+/// finishing at the same time both land. Best effort: a failed write only costs a
+/// re-extraction next time.
+fn update_entry(
+    root: &Path,
+    hash: &str,
+    ocr_settings: &str,
+    update: impl FnOnce(&mut ExtractionCacheEntry),
+) {
+    let _lock = CACHE_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut entry = read_entry(root, hash, ocr_settings).unwrap_or_default();
+    update(&mut entry);
+    entry.version = CACHE_VERSION;
+    entry.file_hash = hash.to_string();
+    entry.ocr_settings = ocr_settings.to_string();
+    entry.updated_at = chrono::Utc::now().to_rfc3339();
+    if let Err(err) = write_entry(root, hash, &entry) {
+        log::warn!(target: "extract-cache", "failed to write {}: {}", hash, err);
+    }
+}
+
+fn write_entry(root: &Path, hash: &str, entry: &ExtractionCacheEntry) -> Result<()> {
+    std::fs::create_dir_all(root)?;
+    // 先写临时文件再改名，读取方不会读到半个文件
+    let path = entry_path(root, hash);
+    let temp = root.join(format!("{}.{}.tmp", hash, std::process::id()));
+    std::fs::write(&temp, serde_json::to_string(entry)?)?;
+    std::fs::rename(&temp, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "fyla-extract-cache-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        root
+    }
+
+    #[test]
+    fn stored_entries_round_trip_and_merge() {
+        let root = temp_root("merge");
+        let pages = vec![PdfPageText {
+            page: 1,
+            text: "page one".into(),
+        }];
+        update_entry(&root, "abc", "auto|eng||300dpi", |entry| {
+            entry.pages = Some(pages)
+        });
+        update_entry(&root, "abc", "auto|eng||300dpi", |entry| {
+            entry.review = Some(CachedReviewText {
+                extractor: "pdftotext".into(),
+                text: "full text".into(),
+                warning: None,
+                pinned: None,
+            })
+        });
+
+        let entry =
+            read_entry(&root, "abc", "auto|eng||300dpi").expect("merged entry should be readable");
+        let other_languages = read_entry(&root, "abc", "auto|chi_sim+eng||300dpi");
+        let _ = std::fs::remove_dir_all(&root);
+        assert!(other_languages.is_none());
+        assert_eq!(entry.pages.unwrap()[0].text, "page one");
+        assert_eq!(entry.review.unwrap().text, "full text");
+    }
+
+    #[test]
+    fn entries_from_other_versions_are_ignored() {
+        let root = temp_root("version");
+        let stale = ExtractionCacheEntry {
+            version: CACHE_VERSION + 1,
+            file_hash: "abc".into(),
+            ..Default::default()
+        };
+        write_entry(&root, "abc", &stale).unwrap();
+
+        let entry = read_entry(&root, "abc", "");
+        let _ = std::fs::remove_dir_all(&root);
+        assert!(entry.is_none());
+    }
+
+    #[test]
+    fn password_protected_pdfs_are_not_cached() {
+        let root = temp_root("locked");
+        let path = root.join("statement.pdf");
+        std::fs::create_dir_all(&root).unwrap();
+        pdf_password::write_encrypted_pdf(&path, "statement-2024");
+        let path = path.to_string_lossy().to_string();
+        let hash = file_hash(&path).unwrap();
+        // 旧版本写下的明文条目也要清掉
+        write_entry(&root, &hash, &ExtractionCacheEntry::default()).unwrap();
+
+        let cached = cacheable(&root, &path, &hash);
+        let leftover = entry_path(&root, &hash).exists();
+        let _ = std::fs::remove_dir_all(&root);
+        assert!(!cached);
+        assert!(!leftover);
+
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/sample-text.pdf");
+        assert!(cacheable(&temp_root("plain"), sample, "abc"));
+    }
+
+    #[test]
+    fn file_hash_is_sha256_of_contents() {
+        let path = std::env::temp_dir().join(format!("fyla-hash-{}.txt", std::process::id()));
+        std::fs::write(&path, "abc").unwrap();
+        let hash = file_hash(&path.to_string_lossy()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            hash,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
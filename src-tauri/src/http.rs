@@ -0,0 +1,186 @@
+use crate::config::{self, AppConfig, HttpTimeouts};
+use anyhow::{Context, Result, anyhow};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, NoProxy, Proxy};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Hosts that never go through the configured proxy, so local Ollama keeps working.
+const LOCAL_HOSTS: &str = "localhost,127.0.0.1,::1";
+
+/// What a client is used for; each task has its own timeouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpTask {
+    /// Connection tests from the settings page.
+    Test,
+    Rename,
+    Vlm,
+    Embedding,
+    /// Streamed replies: only the connection and the pause between chunks are bounded.
+    Stream,
+}
+
+impl HttpTask {
+    /// `(connect, total or idle)` timeouts in seconds.
+    fn timeouts(self, overrides: &HttpTimeouts) -> (u32, u32) {
+        let connect = overrides.connect.unwrap_or(15);
+        match self {
+            HttpTask::Test => (connect, overrides.test.unwrap_or(10)),
+            HttpTask::Rename => (connect, overrides.rename.unwrap_or(60)),
+            HttpTask::Vlm => (connect, overrides.vlm.unwrap_or(60)),
+            HttpTask::Embedding => (connect, overrides.embedding.unwrap_or(45)),
+            HttpTask::Stream => (connect, overrides.stream_idle.unwrap_or(90)),
+        }
+    }
+}
+
+/// Transport settings taken from the config.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct HttpSettings {
+    proxy: String,
+    no_proxy: String,
+    ca_bundle: String,
+    headers: String,
+    timeouts: HttpTimeouts,
+}
+
+impl HttpSettings {
+    fn from_config(config: &AppConfig) -> Self {
+        Self {
+            proxy: config.http_proxy.trim().to_string(),
+            no_proxy: config.http_no_proxy.trim().to_string(),
+            ca_bundle: config.http_ca_bundle.trim().to_string(),
+            headers: config.http_headers.clone(),
+            timeouts: config.http_timeouts.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ClientCache {
+    settings: HttpSettings,
+    clients: HashMap<HttpTask, Client>,
+}
+
+fn cache() -> &'static Mutex<ClientCache> {
+    static CACHE: OnceLock<Mutex<ClientCache>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Shared client for `task`, built from the effective config's transport settings. It is
+/// reused while those settings stay the same, so requests share the connection pool; a
+/// change in config.json or the team file rebuilds the clients on next use.
+pub fn client(task: HttpTask) -> Result<Client> {
+    let settings = HttpSettings::from_config(&config::load_config());
+    let mut cache = cache().lock().unwrap();
+    if cache.settings != settings {
+        cache.settings = settings;
+        cache.clients.clear();
+    }
+    if let Some(client) = cache.clients.get(&task) {
+        return Ok(client.clone());
+    }
+    let client = build_client(&cache.settings, task)?;
+    cache.clients.insert(task, client.clone());
+    Ok(client)
+}
+
+/// An uncached client for `task` built from `config`, for trying settings that may not
+/// be saved yet.
+pub fn one_off_client(config: &AppConfig, task: HttpTask) -> Result<Client> {
+    build_client(&HttpSettings::from_config(config), task)
+}
+
+fn build_client(settings: &HttpSettings, task: HttpTask) -> Result<Client> {
+    let (connect, limit) = task.timeouts(&settings.timeouts);
+    let mut builder = Client::builder().connect_timeout(Duration::from_secs(u64::from(connect)));
+    builder = match task {
+        HttpTask::Stream => builder.read_timeout(Duration::from_secs(u64::from(limit))),
+        _ => builder.timeout(Duration::from_secs(u64::from(limit))),
+    };
+
+    if !settings.proxy.is_empty() {
+        let no_proxy = format!("{},{}", LOCAL_HOSTS, settings.no_proxy);
+        let proxy = Proxy::all(settings.proxy.as_str())
+            .map_err(|e| anyhow!("代理地址无效：{}", e))?
+            .no_proxy(NoProxy::from_string(&no_proxy));
+        builder = builder.proxy(proxy);
+    }
+    if !settings.ca_bundle.is_empty() {
+        let pem = std::fs::read(&settings.ca_bundle)
+            .with_context(|| format!("无法读取 CA 证书 {}", settings.ca_bundle))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| anyhow!("CA 证书 {} 无效：{}", settings.ca_bundle, e))?;
+        builder = builder.tls_certs_merge(certs);
+    }
+    let headers = parse_headers(&settings.headers)?;
+    if !headers.is_empty() {
+        builder = builder.default_headers(headers);
+    }
+
+    builder.build().context("初始化 HTTP 客户端失败")
+}
+
+/// Parses extra headers, one `Name: value` per line. Blank lines and lines starting with
+/// `#` are skipped.
+pub fn parse_headers(text: &str) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = line.split_once(':').and_then(|(name, value)| {
+            let name = HeaderName::from_bytes(name.trim().as_bytes()).ok()?;
+            let value = HeaderValue::from_str(value.trim()).ok()?;
+            Some((name, value))
+        });
+        let Some((name, value)) = parsed else {
+            return Err(anyhow!("第 {} 行应为「Name: value」", line_number + 1));
+        };
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_headers_reads_lines_and_rejects_malformed_ones() {
+        let headers = parse_headers("X-Team: search\n\n# comment\nX-Trace:  on ").unwrap();
+        assert_eq!(headers["x-team"], "search");
+        assert_eq!(headers["x-trace"], "on");
+        assert!(parse_headers("X-Team search").is_err());
+        assert!(parse_headers("Bad Name: value").is_err());
+    }
+
+    #[test]
+    fn task_timeouts_fall_back_to_defaults() {
+        let overrides = HttpTimeouts {
+            embedding: Some(120),
+            connect: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(HttpTask::Embedding.timeouts(&overrides), (5, 120));
+        assert_eq!(HttpTask::Stream.timeouts(&overrides), (5, 90));
+        assert_eq!(HttpTask::Test.timeouts(&HttpTimeouts::default()), (15, 10));
+    }
+
+    #[test]
+    fn one_off_client_uses_the_submitted_settings() {
+        let mut config = AppConfig {
+            http_ca_bundle: "/nonexistent/fyla-ca.pem".into(),
+            ..AppConfig::default()
+        };
+        let err = one_off_client(&config, HttpTask::Test).unwrap_err();
+        assert!(err.to_string().contains("CA 证书"));
+
+        config.http_ca_bundle.clear();
+        config.http_proxy = "http://[".into();
+        let err = one_off_client(&config, HttpTask::Test).unwrap_err();
+        assert!(err.to_string().contains("代理地址无效"));
+    }
+}
@@ -1,21 +1,43 @@
 mod autostart;
 mod config;
 mod embedding;
+mod extraction_cache;
+mod http;
 mod llm;
+mod logging;
+mod metadata;
+mod mock;
 mod ocr;
+mod office;
 mod paper;
+mod paper_archive;
 mod paper_chat;
 mod pdf;
+mod pdf_cleanup;
+mod pdf_diagnostics;
+mod pdf_layout;
+mod pdf_password;
+mod pdf_render;
+mod photo;
+mod redact;
 mod renamer;
 mod scanner;
+mod secrets;
 mod service;
+mod sidecar;
 mod streaming;
-#[cfg(test)]
+mod subprocess;
+// 这些用例直接跑 Vision OCR，只在 macOS 上有意义
+#[cfg(all(test, target_os = "macos"))]
 mod test_ocr;
+#[cfg(test)]
+mod test_server;
+mod usage;
+mod vlm_image;
 mod watcher;
 
 use arboard::Clipboard;
-use config::{AppConfig, load_config};
+use config::{AppConfig, ModelTask, load_config};
 use renamer::{FileInfo, RenameResult, RenameTask};
 use std::sync::Mutex;
 use std::time::Instant;
@@ -23,15 +45,32 @@ use tauri::Manager;
 use tauri_plugin_shell::ShellExt;
 
 struct TrayState(Mutex<Option<tauri::tray::TrayIcon>>);
+/// Problems found in config.json at startup, shown once by the frontend.
+struct ConfigProblems(Mutex<Vec<String>>);
 
 #[tauri::command]
 fn get_config() -> AppConfig {
-    load_config()
+    secrets::masked(load_config())
 }
 
 #[tauri::command]
-fn save_config(config: AppConfig) -> Result<(), String> {
-    config::save_config(&config).map_err(|e| e.to_string())
+fn save_config(config: AppConfig) -> Result<Vec<String>, String> {
+    let warnings = config::save_config(&config).map_err(|e| e.to_string())?;
+    pdf_password::forget_configured_passwords();
+    // 团队配置也可能带价格表，按生效值重新应用；HTTP 客户端取用时自行比对网络设置
+    usage::configure(&load_config());
+    Ok(warnings.iter().map(ToString::to_string).collect())
+}
+
+/// Reports which config layer each field comes from, optionally for files in `folder`.
+#[tauri::command]
+fn get_config_sources(folder: Option<String>) -> config::ConfigSources {
+    config::config_sources(folder.as_deref().map(std::path::Path::new))
+}
+
+#[tauri::command]
+fn take_config_problems(state: tauri::State<'_, ConfigProblems>) -> Vec<String> {
+    std::mem::take(&mut *state.0.lock().unwrap())
 }
 
 #[tauri::command]
@@ -42,14 +81,53 @@ async fn scan_folder(path: String, extensions: String) -> Result<Vec<FileInfo>,
 }
 
 #[tauri::command]
-async fn extract_file_text(path: String) -> Result<String, String> {
+async fn extract_file_text(path: String) -> Result<ExtractedContent, String> {
     tokio::task::spawn_blocking(move || {
-        extract_file_content_inner(&path).map_err(|e| e.to_string())
+        let ocr_settings = ocr::OcrSettings::from_config(&load_config());
+        extract_file_content_inner(&path, &ocr_settings).map_err(|e| e.to_string())
     })
     .await
     .map_err(|e| e.to_string())?
 }
 
+/// Checks a password for an encrypted PDF and keeps it for the rest of the session.
+#[tauri::command]
+async fn unlock_pdf(path: String, password: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        pdf_password::remember_file_password(&path, &password).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Runs every PDF extractor on a file and reports how each one did.
+#[tauri::command]
+async fn diagnose_pdf_extraction(path: String) -> Result<pdf_diagnostics::PdfDiagnostics, String> {
+    tokio::task::spawn_blocking(move || {
+        let ocr_settings = ocr::OcrSettings::from_config(&load_config());
+        pdf_diagnostics::diagnose(&path, &ocr_settings).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Pins the extractor used when this PDF is reviewed again; `None` restores automatic selection.
+#[tauri::command]
+async fn set_pdf_extractor_pin(path: String, extractor: Option<String>) -> Result<(), String> {
+    if let Some(label) = extractor.as_deref()
+        && pdf::PdfExtractor::from_label(label).is_none()
+    {
+        return Err(format!("未知的 PDF 提取器: {}", label));
+    }
+    tokio::task::spawn_blocking(move || {
+        let hash = extraction_cache::file_hash(&path)?;
+        config::set_pdf_extractor_pin(&path, &hash, extractor.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn read_paper_archive_markdown(path: String) -> Result<String, String> {
     tokio::task::spawn_blocking(move || {
@@ -69,14 +147,18 @@ async fn read_paper_archive_markdown(path: String) -> Result<String, String> {
         let result =
             std::fs::read_to_string(archive_path).map_err(|e| format!("读取论文归档失败: {}", e));
         match &result {
-            Ok(content) => eprintln!(
-                "[paper-perf] read_paper_archive_markdown path={} chars={} elapsedMs={}",
+            Ok(content) => log::info!(
+                target: "paper-perf",
+                job = logging::job_id(&path).as_str();
+                "read_paper_archive_markdown path={} chars={} elapsedMs={}",
                 path,
                 content.len(),
                 started_at.elapsed().as_millis()
             ),
-            Err(err) => eprintln!(
-                "[paper-perf] read_paper_archive_markdown.error path={} elapsedMs={} error={}",
+            Err(err) => log::warn!(
+                target: "paper-perf",
+                job = logging::job_id(&path).as_str();
+                "read_paper_archive_markdown.error path={} elapsedMs={} error={}",
                 path,
                 started_at.elapsed().as_millis(),
                 err
@@ -88,6 +170,42 @@ async fn read_paper_archive_markdown(path: String) -> Result<String, String> {
     .map_err(|e| e.to_string())?
 }
 
+/// Zips the logs, a masked copy of the config and version info to `dest` for bug reports.
+#[tauri::command]
+async fn export_diagnostics(app: tauri::AppHandle, dest: String) -> Result<(), String> {
+    let app_version = app.package_info().version.to_string();
+    tokio::task::spawn_blocking(move || {
+        logging::export_diagnostics(std::path::Path::new(dest.trim()), &app_version)
+            .map_err(|e| format!("{:#}", e))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Sums recorded model usage between two `YYYY-MM-DD` dates, by day or by month.
+#[tauri::command]
+async fn get_usage_summary(
+    from: Option<String>,
+    to: Option<String>,
+    by_month: bool,
+) -> Result<usage::UsageSummary, String> {
+    tokio::task::spawn_blocking(move || usage::summarize(from.as_deref(), to.as_deref(), by_month))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Moves the paper archive to `new_root` and rewrites every record that points into it.
+#[tauri::command]
+async fn relocate_paper_archive(
+    new_root: String,
+) -> Result<paper_archive::RelocateSummary, String> {
+    tokio::task::spawn_blocking(move || {
+        paper_archive::relocate(std::path::Path::new(new_root.trim())).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
 async fn read_file_bytes(path: String) -> Result<Vec<u8>, String> {
     tokio::task::spawn_blocking(move || {
@@ -106,14 +224,18 @@ async fn read_file_bytes(path: String) -> Result<Vec<u8>, String> {
 
         let result = std::fs::read(file_path).map_err(|e| format!("读取文件字节失败: {}", e));
         match &result {
-            Ok(bytes) => eprintln!(
-                "[paper-perf] read_file_bytes path={} bytes={} elapsedMs={}",
+            Ok(bytes) => log::info!(
+                target: "paper-perf",
+                job = logging::job_id(&path).as_str();
+                "read_file_bytes path={} bytes={} elapsedMs={}",
                 path,
                 bytes.len(),
                 started_at.elapsed().as_millis()
             ),
-            Err(err) => eprintln!(
-                "[paper-perf] read_file_bytes.error path={} elapsedMs={} error={}",
+            Err(err) => log::warn!(
+                target: "paper-perf",
+                job = logging::job_id(&path).as_str();
+                "read_file_bytes.error path={} elapsedMs={} error={}",
                 path,
                 started_at.elapsed().as_millis(),
                 err
@@ -125,17 +247,38 @@ async fn read_file_bytes(path: String) -> Result<Vec<u8>, String> {
     .map_err(|e| e.to_string())?
 }
 
-fn extract_file_content_inner(path: &str) -> anyhow::Result<String> {
+/// Extracted body text plus the metadata embedded in the document.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtractedContent {
+    text: String,
+    metadata: metadata::DocumentMetadata,
+}
+
+fn extract_file_content_inner(
+    path: &str,
+    ocr_settings: &ocr::OcrSettings,
+) -> anyhow::Result<ExtractedContent> {
+    let text = extract_file_text_inner(path, ocr_settings)?;
+    Ok(ExtractedContent {
+        text,
+        metadata: metadata::read_document_metadata(path),
+    })
+}
+
+fn extract_file_text_inner(path: &str, ocr_settings: &ocr::OcrSettings) -> anyhow::Result<String> {
     let ext = std::path::Path::new(path)
         .extension()
         .map(|e| e.to_ascii_lowercase().to_string_lossy().to_string())
         .unwrap_or_default();
 
     match ext.as_str() {
-        "pdf" => pdf::extract_pdf_text(path),
-        "docx" | "pptx" | "xlsx" | "xls" | "txt" | "md" | "markdown" => pdf::extract_text(path),
+        "pdf" => pdf::extract_pdf_text(path, ocr_settings),
+        "docx" | "pptx" | "xlsx" | "xls" | "txt" | "md" | "markdown" => {
+            pdf::extract_text(path, ocr_settings)
+        }
         "jpg" | "jpeg" | "png" | "heic" | "webp" | "tiff" => {
-            let ocr_text = ocr::ocr_from_file(path).unwrap_or_default();
+            let ocr_text = ocr::ocr_from_file(path, ocr_settings).unwrap_or_default();
             let p = std::path::Path::new(path);
             let name = p.file_name().unwrap_or_default().to_string_lossy();
             let meta = std::fs::metadata(p)?;
@@ -148,20 +291,12 @@ fn extract_file_content_inner(path: &str) -> anyhow::Result<String> {
                 })
                 .unwrap_or_default();
 
-            // EXIF 元数据
-            let exif_info = read_exif_info(p);
-
             let mut info = format!(
                 "文件名: {}\n文件类型: {}\n文件大小: {}KB\n修改日期: {}",
                 name, ext, size_kb, modified
             );
-            if let Some(ref exif) = exif_info {
-                if let Some(ref dt) = exif.datetime {
-                    info.push_str(&format!("\n拍摄时间: {}", dt));
-                }
-                if let Some(ref cam) = exif.camera {
-                    info.push_str(&format!("\n相机: {}", cam));
-                }
+            if let Some(photo) = photo::read_photo_info(path) {
+                info.push_str(&photo_info_lines(&photo));
             }
             if !ocr_text.trim().is_empty() {
                 info.push_str(&format!("\n\nOCR识别文字:\n{}", ocr_text));
@@ -193,8 +328,15 @@ async fn generate_filename(
     text: String,
     config: AppConfig,
     file_path: Option<String>,
+    metadata: Option<metadata::DocumentMetadata>,
 ) -> Result<String, String> {
-    let context = file_path.as_deref().map(collect_file_context);
+    let config = match file_path.as_deref() {
+        Some(path) => config::for_path(&config, std::path::Path::new(path)),
+        None => config,
+    };
+    let context = file_path
+        .as_deref()
+        .map(|path| collect_file_context(path, metadata));
 
     // 如果 VLM 已启用且文件是图片，优先走 VLM 多模态
     if config.vlm_enabled
@@ -209,9 +351,35 @@ async fn generate_filename(
             "jpg" | "jpeg" | "png" | "heic" | "webp" | "tiff"
         ) {
             // VLM failure falls back to LLM below
-            if let Ok(name) = try_vlm_filename(path, &ext, &config, context.as_ref()).await {
+            if let Ok(name) = try_vlm_filename(path, &config, context.as_ref()).await {
                 return Ok(name);
             }
+        } else if ext == "pdf" {
+            // 扫描版 PDF 的文字层为空或只有 OCR 结果，渲染首页交给 VLM
+            let pdf_path = path.clone();
+            let first_page = tokio::task::spawn_blocking(move || {
+                if !pdf::first_page_is_scanned(&pdf_path)? {
+                    return Ok(None);
+                }
+                paper_chat::rendered_pdf_page(&pdf_path, 1).map(Some)
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+            match first_page {
+                Ok(Some(image)) => {
+                    let image = image.to_string_lossy().to_string();
+                    match try_vlm_filename(&image, &config, context.as_ref()).await {
+                        Ok(name) => return Ok(name),
+                        Err(err) => {
+                            log::warn!(target: "vlm", "scanned PDF naming failed for {}: {}", path, err)
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    log::warn!(target: "vlm", "failed to render first page of {}: {:#}", path, err)
+                }
+            }
         }
     }
 
@@ -222,27 +390,24 @@ async fn generate_filename(
 
 async fn try_vlm_filename(
     path: &str,
-    ext: &str,
     config: &AppConfig,
     context: Option<&llm::FileContext>,
 ) -> Result<String, String> {
-    let image_data = std::fs::read(path).map_err(|e| format!("读取图片失败: {}", e))?;
-    use base64::Engine;
-    let image_base64 = base64::engine::general_purpose::STANDARD.encode(&image_data);
-    let mime = match ext {
-        "jpg" | "jpeg" => "image/jpeg",
-        "png" => "image/png",
-        "heic" => "image/heic",
-        "webp" => "image/webp",
-        "tiff" => "image/tiff",
-        _ => "image/jpeg",
-    };
-    llm::generate_filename_vlm(&image_base64, mime, config, context)
+    let image_path = path.to_string();
+    let max_edge = config.vlm_max_image_edge;
+    let image = tokio::task::spawn_blocking(move || vlm_image::prepare(&image_path, max_edge))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("处理图片失败: {:#}", e))?;
+    llm::generate_filename_vlm(&image.base64(), image.mime, config, context)
         .await
         .map_err(|e| e.to_string())
 }
 
-fn collect_file_context(path: &str) -> llm::FileContext {
+fn collect_file_context(
+    path: &str,
+    metadata: Option<metadata::DocumentMetadata>,
+) -> llm::FileContext {
     let p = std::path::Path::new(path);
     let original_name = p
         .file_name()
@@ -271,14 +436,30 @@ fn collect_file_context(path: &str) -> llm::FileContext {
         Err(_) => (String::new(), String::new()),
     };
     llm::FileContext {
+        path: path.to_string(),
         original_name,
         parent_dir,
         sibling_names,
         modified_at,
         file_size,
+        metadata: metadata.unwrap_or_else(|| metadata::read_document_metadata(path)),
+        photo: is_image_path(path)
+            .then(|| photo::read_photo_info(path))
+            .flatten(),
     }
 }
 
+fn is_image_path(path: &str) -> bool {
+    let ext = std::path::Path::new(path)
+        .extension()
+        .map(|e| e.to_ascii_lowercase().to_string_lossy().to_string())
+        .unwrap_or_default();
+    matches!(
+        ext.as_str(),
+        "jpg" | "jpeg" | "png" | "heic" | "webp" | "tiff"
+    )
+}
+
 fn get_sibling_names(path: &std::path::Path, limit: usize) -> Vec<String> {
     let parent = path.parent().unwrap_or(std::path::Path::new("."));
     let current = path.file_name();
@@ -313,6 +494,7 @@ async fn generate_names_stream(
     config: AppConfig,
     on_event: tauri::ipc::Channel<llm::StreamEvent>,
 ) -> Result<(), String> {
+    let ocr_settings = ocr::OcrSettings::from_config(&config);
     for path_str in &paths {
         let file_name = std::path::Path::new(path_str)
             .file_name()
@@ -325,8 +507,8 @@ async fn generate_names_stream(
         });
 
         // Extract text
-        let text = match extract_file_content_inner(path_str) {
-            Ok(t) => t,
+        let extracted = match extract_file_content_inner(path_str, &ocr_settings) {
+            Ok(content) => content,
             Err(e) => {
                 let _ = on_event.send(llm::StreamEvent::Error {
                     file_name,
@@ -336,13 +518,27 @@ async fn generate_names_stream(
             }
         };
 
-        let context = collect_file_context(path_str);
-        let prompt = llm::build_prompt_public(&text, &config, Some(&context));
+        // 文件夹里的 .fyla.json 可以覆盖命名规则
+        let config = config::for_path(&config, std::path::Path::new(path_str));
+        let profile = match config.model_for(ModelTask::Rename) {
+            Ok(profile) => profile,
+            Err(e) => {
+                let _ = on_event.send(llm::StreamEvent::Error {
+                    file_name,
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let context = collect_file_context(path_str, Some(extracted.metadata));
+        let (prompt, redactor) =
+            llm::build_outgoing_prompt(&extracted.text, &config, profile, Some(&context));
 
         // Stream LLM call
-        let result = match config.provider.as_str() {
-            "openai" => llm::call_openai_stream(&prompt, &config, &file_name, &on_event).await,
-            _ => llm::call_ollama_stream(&prompt, &config, &file_name, &on_event).await,
+        let result = match profile.provider.as_str() {
+            "mock" => llm::call_mock_stream(&prompt, profile, &file_name, &on_event).await,
+            "openai" => llm::call_openai_stream(&prompt, profile, &file_name, &on_event).await,
+            _ => llm::call_ollama_stream(&prompt, profile, &file_name, &on_event).await,
         };
 
         match result {
@@ -350,6 +546,8 @@ async fn generate_names_stream(
                 let ext = std::path::Path::new(&file_name)
                     .extension()
                     .map(|e| format!(".{}", e.to_string_lossy()));
+                let raw = llm::restore_reply(&raw, redactor.as_ref());
+                let raw = llm::fill_metadata_placeholders(&raw, Some(&context));
                 let cleaned = llm::clean_filename(&raw, ext.as_deref());
                 if cleaned.is_empty() {
                     let _ = on_event.send(llm::StreamEvent::Error {
@@ -396,9 +594,18 @@ async fn move_and_rename(
     .map_err(|e| e.to_string())?
 }
 
+/// Tests the profile with `profile_id`, or the rename profile when none is given.
 #[tauri::command]
-async fn test_connection(config: AppConfig) -> Result<String, String> {
-    llm::test_connection(&config)
+async fn test_connection(config: AppConfig, profile_id: Option<String>) -> Result<String, String> {
+    let profile = match profile_id.as_deref() {
+        Some(id) => config
+            .profile(id)
+            .ok_or_else(|| "未找到该模型配置".to_string())?,
+        None => config
+            .model_for(ModelTask::Rename)
+            .map_err(|e| e.to_string())?,
+    };
+    llm::test_connection(profile, &config)
         .await
         .map_err(|e| e.to_string())
 }
@@ -451,8 +658,10 @@ fn prepare_paper_chat_session(
     let title_for_log = title.clone();
     let result = paper_chat::prepare_paper_chat_session(source_path, saved_path, title, config);
     match &result {
-        Ok(session) => eprintln!(
-            "[paper-perf] prepare_paper_chat_session title={} sourcePath={} sessionId={} strategy={} cachePrepared={} elapsedMs={}",
+        Ok(session) => log::info!(
+            target: "paper-perf",
+            job = logging::job_id(&source_path_for_log).as_str();
+            "prepare_paper_chat_session title={} sourcePath={} sessionId={} strategy={} cachePrepared={} elapsedMs={}",
             title_for_log,
             source_path_for_log,
             session.session_id,
@@ -460,8 +669,10 @@ fn prepare_paper_chat_session(
             session.cache_prepared,
             started_at.elapsed().as_millis()
         ),
-        Err(err) => eprintln!(
-            "[paper-perf] prepare_paper_chat_session.error title={} sourcePath={} elapsedMs={} error={}",
+        Err(err) => log::warn!(
+            target: "paper-perf",
+            job = logging::job_id(&source_path_for_log).as_str();
+            "prepare_paper_chat_session.error title={} sourcePath={} elapsedMs={} error={}",
             title_for_log,
             source_path_for_log,
             started_at.elapsed().as_millis(),
@@ -614,11 +825,6 @@ fn is_autostart_enabled() -> bool {
     autostart::is_enabled()
 }
 
-struct ExifInfo {
-    datetime: Option<String>,
-    camera: Option<String>,
-}
-
 fn should_guard_webview_navigation(label: &str) -> bool {
     matches!(label, "main" | "paper-chat")
 }
@@ -660,7 +866,7 @@ fn guard_webview_navigation<R: tauri::Runtime>(
     if matches!(url.scheme(), "http" | "https") {
         #[allow(deprecated)]
         if let Err(err) = webview.app_handle().shell().open(url.as_str(), None) {
-            eprintln!("failed to open external URL in browser: {err}");
+            log::warn!(target: "webview", "failed to open external URL in browser: {err}");
         }
         return false;
     }
@@ -668,24 +874,31 @@ fn guard_webview_navigation<R: tauri::Runtime>(
     true
 }
 
-fn read_exif_info(path: &std::path::Path) -> Option<ExifInfo> {
-    let file = std::fs::File::open(path).ok()?;
-    let mut reader = std::io::BufReader::new(file);
-    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
-
-    let datetime = exif
-        .get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
-        .map(|f| f.display_value().to_string());
-    let camera = exif
-        .get_field(exif::Tag::Model, exif::In::PRIMARY)
-        .map(|f| f.display_value().to_string());
-
-    Some(ExifInfo { datetime, camera })
+fn photo_info_lines(photo: &photo::PhotoInfo) -> String {
+    let mut lines = String::new();
+    if let Some(ref taken) = photo.taken_at {
+        lines.push_str(&format!("\n拍摄时间: {}", taken));
+    }
+    if let Some(camera) = photo.camera() {
+        lines.push_str(&format!("\n相机: {}", camera));
+    }
+    if let Some(ref lens) = photo.lens {
+        lines.push_str(&format!("\n镜头: {}", lens));
+    }
+    if let Some(ref place) = photo.place {
+        lines.push_str(&format!("\n拍摄地点: {}, {}", place.city, place.country));
+    }
+    if photo.is_screenshot {
+        lines.push_str("\n这是一张屏幕截图");
+    }
+    lines
 }
 
 /// Initializes and runs the Tauri application with tray, plugins, and event handlers.
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // 日志写入数据目录下的轮转文件，打包后的托盘应用也能事后查看
+    logging::init();
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
@@ -701,9 +914,17 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             get_config,
             save_config,
+            get_config_sources,
+            take_config_problems,
             scan_folder,
             extract_file_text,
+            unlock_pdf,
+            diagnose_pdf_extraction,
+            set_pdf_extractor_pin,
             read_paper_archive_markdown,
+            relocate_paper_archive,
+            get_usage_summary,
+            export_diagnostics,
             read_file_bytes,
             generate_filename,
             generate_names_stream,
@@ -820,8 +1041,17 @@ pub fn run() {
             // 注册 Finder 右键服务（NSServices）
             service::init(app.handle());
 
+            // 按版本迁移 config.json 并把明文 API Key 移入钥匙串；解析问题留给前端提示
+            let problems = config::upgrade_stored_config().unwrap_or_else(|err| {
+                log::warn!(target: "config", "migration failed: {:#}", err);
+                Vec::new()
+            });
+            app.manage(ConfigProblems(Mutex::new(problems)));
+
             // 启动时如果配置了 watch 文件夹，自动开始监听
             let config = load_config();
+            usage::open_ledger(&usage::default_ledger_path());
+            usage::configure(&config);
             if !config.watch_folder.is_empty() {
                 let handle = app.handle().clone();
                 let folder = config.watch_folder.clone();
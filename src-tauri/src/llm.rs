@@ -1,16 +1,29 @@
-use crate::config::AppConfig;
+use crate::config::{AppConfig, ModelProfile, ModelTask};
+use crate::http::{self, HttpTask};
+use crate::metadata::DocumentMetadata;
+use crate::mock;
+use crate::photo::PhotoInfo;
+use crate::redact::{self, Redactor};
+use crate::secrets;
+use crate::streaming;
+use crate::usage::{self, UsageMeter};
 use anyhow::{Result, anyhow};
 use reqwest::Client;
 use serde::Serialize;
 use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
 
 #[derive(Debug, Clone)]
 pub struct FileContext {
+    pub path: String,
     pub original_name: String,
     pub parent_dir: String,
     pub sibling_names: Vec<String>,
     pub modified_at: String,
     pub file_size: String,
+    pub metadata: DocumentMetadata,
+    pub photo: Option<PhotoInfo>,
 }
 
 /// Streaming event sent via Tauri Channel
@@ -65,25 +78,151 @@ fn context_section(context: Option<&FileContext>) -> String {
             - 所在目录: {}\n\
             - 修改时间: {}\n\
             - 文件大小: {}\n\
+            {}{}\
             \n## 同目录已有文件（参考命名风格）\n{}\n",
-            ctx.original_name, ctx.parent_dir, ctx.modified_at, ctx.file_size, siblings
+            ctx.original_name,
+            ctx.parent_dir,
+            ctx.modified_at,
+            ctx.file_size,
+            metadata_section(&ctx.metadata),
+            photo_section(ctx.photo.as_ref()),
+            siblings
         )
     } else {
         String::new()
     }
 }
 
-fn template_section(name_template: &str, naming_style: &str) -> String {
-    let effective = if name_template.trim().is_empty() {
+fn metadata_section(metadata: &DocumentMetadata) -> String {
+    if metadata.is_empty() {
+        return String::new();
+    }
+    let mut lines = String::from("\n## 文档内嵌元数据（来自文件属性，通常比正文更可靠）\n");
+    let fields = [
+        ("标题", &metadata.title),
+        ("作者", &metadata.author),
+        ("主题", &metadata.subject),
+        ("关键词", &metadata.keywords),
+        ("创建日期", &metadata.created),
+        ("修改日期", &metadata.modified),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value {
+            lines.push_str(&format!("- {}: {}\n", label, value));
+        }
+    }
+    lines
+}
+
+fn photo_section(photo: Option<&PhotoInfo>) -> String {
+    let Some(photo) = photo else {
+        return String::new();
+    };
+    let mut lines = String::from("\n## 照片拍摄信息（EXIF/XMP）\n");
+    if let Some(taken) = photo.taken_at.as_deref() {
+        lines.push_str(&format!("- 拍摄时间: {}\n", taken));
+    }
+    if let Some(camera) = photo.camera() {
+        lines.push_str(&format!("- 相机: {}\n", camera));
+    }
+    if let Some(lens) = photo.lens.as_deref() {
+        lines.push_str(&format!("- 镜头: {}\n", lens));
+    }
+    if let Some(place) = photo.place.as_ref() {
+        lines.push_str(&format!("- 拍摄地点: {}, {}\n", place.city, place.country));
+    }
+    if photo.is_screenshot {
+        lines.push_str("- 这是一张屏幕截图，{type} 应选截图（Screenshot）\n");
+    }
+    lines
+}
+
+/// Document metadata values for `{author}` and `{date}`. The model is asked to keep these
+/// placeholders when the body text does not name them, and
+/// [`fill_metadata_placeholders`] fills them in from here afterwards.
+fn metadata_template_vars(context: Option<&FileContext>) -> Vec<(&'static str, String)> {
+    let Some(ctx) = context else {
+        return Vec::new();
+    };
+    let mut vars = Vec::new();
+    if let Some(author) = ctx.metadata.author.as_deref() {
+        // 多作者时只取第一作者，避免文件名过长
+        let first_author = author
+            .split([',', ';', '，', '、'])
+            .next()
+            .unwrap_or(author);
+        vars.push(("{author}", first_author.trim().to_string()));
+    }
+    // 照片的 {date} 已按拍摄时间直接替换
+    if let Some(date) = ctx.metadata.compact_date()
+        && ctx.photo.as_ref().and_then(PhotoInfo::taken_date).is_none()
+    {
+        vars.push(("{date}", date));
+    }
+    vars
+}
+
+/// Replaces the `{author}` / `{date}` placeholders the model left in `name` with document
+/// metadata; values the model found in the body text are kept as they are.
+pub fn fill_metadata_placeholders(name: &str, context: Option<&FileContext>) -> String {
+    metadata_template_vars(context)
+        .into_iter()
+        .fold(name.to_string(), |name, (placeholder, value)| {
+            name.replace(placeholder, &value)
+        })
+}
+
+/// Substitutes template variables whose values come from photo EXIF/XMP, which is more
+/// reliable than anything a model reads from the image.
+fn fill_known_template_vars(template: &str, context: Option<&FileContext>) -> String {
+    let Some(ctx) = context else {
+        return template.to_string();
+    };
+    let mut filled = template.to_string();
+    if let Some(photo) = ctx.photo.as_ref() {
+        if let Some(taken) = photo.taken_date() {
+            filled = filled.replace("{date}", &taken.replace('-', ""));
+            filled = filled.replace("{taken}", &taken);
+        }
+        if let Some(camera) = photo.camera() {
+            filled = filled.replace("{camera}", &camera);
+        }
+        if let Some(place) = photo.place.as_ref() {
+            filled = filled.replace("{place}", &place.city);
+        }
+    }
+    filled
+}
+
+fn template_section(
+    name_template: &str,
+    naming_style: &str,
+    context: Option<&FileContext>,
+) -> String {
+    let trimmed = if name_template.trim().is_empty() {
         "{type}-{title}"
     } else {
         name_template.trim()
     };
+    let effective = fill_known_template_vars(trimmed, context);
+    let metadata_rule = match metadata_template_vars(context)
+        .iter()
+        .map(|(placeholder, _)| *placeholder)
+        .filter(|placeholder| effective.contains(placeholder))
+        .collect::<Vec<_>>()
+        .join("、")
+    {
+        placeholders if placeholders.is_empty() => String::new(),
+        placeholders => format!(
+            "- 正文中找不到的 {} 原样保留占位符，程序会用文件属性补全\n",
+            placeholders
+        ),
+    };
     let type_list = if naming_style == "chinese" {
-        "发票、收据、合同、报告、论文、简历、信函、手册、表单、证书、演示文稿、电子表格、照片、文档"
+        "发票、收据、合同、报告、论文、简历、信函、手册、表单、证书、演示文稿、电子表格、照片、截图、文档"
     } else {
         "Invoice, Receipt, Contract, Report, Paper, Resume, Letter, \
-        Manual, Form, Certificate, Presentation, Spreadsheet, Photo, Document"
+        Manual, Form, Certificate, Presentation, Spreadsheet, Photo, Screenshot, Document"
     };
     format!(
         "\n## 命名模板\n\
@@ -93,8 +232,12 @@ fn template_section(name_template: &str, naming_style: &str) -> String {
         - {{date}} 替换为文档日期（YYYYMMDD）\n\
         - {{author}} 替换为作者/发送方\n\
         - {{number}} 替换为文档编号\n\
-        - 找不到的字段直接省略，不要留占位符\n",
-        effective, type_list
+        - {{taken}} 替换为照片拍摄日期（YYYY-MM-DD）\n\
+        - {{camera}} 替换为拍摄设备型号\n\
+        - {{place}} 替换为拍摄地点（城市）\n\
+        {}\
+        - 其他找不到的字段直接省略，不要留占位符\n",
+        effective, type_list, metadata_rule
     )
 }
 
@@ -113,7 +256,7 @@ fn build_prompt(text: &str, config: &AppConfig, context: Option<&FileContext>) -
         format!("\n\n## 用户自定义规则\n{}", config.custom_rules)
     };
 
-    let template = template_section(&config.name_template, &config.naming_style);
+    let template = template_section(&config.name_template, &config.naming_style, context);
     let ctx = context_section(context);
 
     let abbr_rule = if config.naming_style == "chinese" {
@@ -135,28 +278,158 @@ fn build_prompt(text: &str, config: &AppConfig, context: Option<&FileContext>) -
     )
 }
 
-/// Builds the LLM prompt from file text and config (public wrapper for Tauri commands).
-pub fn build_prompt_public(
+/// Builds the LLM prompt from file text and config, masking sensitive values when the
+/// redaction policy covers this file and the `profile` endpoint. The redactor restores
+/// the reply.
+pub fn build_outgoing_prompt(
     text: &str,
     config: &AppConfig,
+    profile: &ModelProfile,
     context: Option<&FileContext>,
-) -> String {
-    build_prompt(text, config, context)
+) -> (String, Option<Redactor>) {
+    redact_prompt(
+        build_prompt(text, config, context),
+        config,
+        context,
+        profile.endpoint(),
+    )
+}
+
+fn redact_prompt(
+    prompt: String,
+    config: &AppConfig,
+    context: Option<&FileContext>,
+    endpoint: &str,
+) -> (String, Option<Redactor>) {
+    let path = context.map(|ctx| ctx.path.as_str());
+    let Some(mut redactor) = redact::redactor_for(config, path, endpoint) else {
+        return (prompt, None);
+    };
+    let redacted = redactor.redact(&prompt);
+    if redactor.masked_count() > 0 {
+        log::info!(
+            target: "redact",
+            "masked {} values before sending to {}",
+            redactor.masked_count(),
+            streaming::host_for_logging(endpoint)
+        );
+    }
+    (redacted, Some(redactor))
+}
+
+/// Puts masked values back into a model reply.
+pub fn restore_reply(reply: &str, redactor: Option<&Redactor>) -> String {
+    redactor.map_or_else(|| reply.to_string(), |redactor| redactor.restore(reply))
+}
+
+/// Adds the profile's sampling defaults to a chat request body. `num_predict` is the
+/// Ollama output limit of the task, used unless the profile sets `max_tokens`; OpenAI
+/// requests keep the provider's limit by default.
+pub fn apply_sampling(body: &mut Value, profile: &ModelProfile, num_predict: Option<u32>) {
+    let sampling = &profile.sampling;
+    let mut params = serde_json::Map::new();
+    if let Some(temperature) = sampling.temperature {
+        params.insert("temperature".into(), json!(temperature));
+    }
+    if let Some(top_p) = sampling.top_p {
+        params.insert("top_p".into(), json!(top_p));
+    }
+    if profile.is_openai() {
+        if let Some(max_tokens) = sampling.max_tokens {
+            params.insert("max_tokens".into(), json!(max_tokens));
+        }
+        for (key, value) in params {
+            body[key] = value;
+        }
+    } else {
+        if let Some(limit) = sampling.max_tokens.or(num_predict) {
+            params.insert("num_predict".into(), json!(limit));
+        }
+        if !params.is_empty() {
+            body["options"] = Value::Object(params);
+        }
+    }
+}
+
+/// OpenAI-compatible endpoints that rejected `stream_options`, remembered for the session.
+fn endpoints_without_stream_options() -> &'static Mutex<HashSet<String>> {
+    static ENDPOINTS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    ENDPOINTS.get_or_init(Default::default)
+}
+
+/// Posts a streamed OpenAI-compatible chat request to `url`. Usage reporting
+/// (`stream_options.include_usage`) is asked for unless the endpoint rejected it before;
+/// a 400 that names `stream_options` is retried once without it and the endpoint
+/// remembered. Send failures go through `on_send_error`; other failed statuses become
+/// errors with the response body.
+pub async fn send_openai_stream(
+    client: &Client,
+    url: &str,
+    api_key: &str,
+    mut body: Value,
+    mut on_send_error: impl FnMut(reqwest::Error) -> anyhow::Error,
+) -> Result<reqwest::Response> {
+    let rejected = |url: &str| {
+        endpoints_without_stream_options()
+            .lock()
+            .is_ok_and(|endpoints| endpoints.contains(url))
+    };
+    let mut with_usage = !rejected(url);
+    loop {
+        if with_usage {
+            body["stream_options"] = json!({ "include_usage": true });
+        } else if let Some(fields) = body.as_object_mut() {
+            fields.remove("stream_options");
+        }
+        let resp = client
+            .post(url)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .json(&body)
+            .send()
+            .await
+            .map_err(&mut on_send_error)?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+        let text = resp.text().await.unwrap_or_default();
+        if with_usage
+            && status == reqwest::StatusCode::BAD_REQUEST
+            && text.contains("stream_options")
+        {
+            log::warn!(
+                target: "llm",
+                "{} rejected stream_options, streaming without usage: {}",
+                streaming::host_for_logging(url),
+                text
+            );
+            if let Ok(mut endpoints) = endpoints_without_stream_options().lock() {
+                endpoints.insert(url.to_string());
+            }
+            with_usage = false;
+            continue;
+        }
+        return Err(anyhow!("API 请求失败 {}: {}", status, text));
+    }
 }
 
-/// Tests LLM provider connectivity and model availability.
-pub async fn test_connection(config: &AppConfig) -> Result<String> {
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()?;
+/// Tests connectivity of a model profile and whether its model is available, using the
+/// transport settings in `config`, which may not be saved yet.
+pub async fn test_connection(profile: &ModelProfile, config: &AppConfig) -> Result<String> {
+    let client = http::one_off_client(config, HttpTask::Test)?;
 
-    match config.provider.as_str() {
+    match profile.provider.as_str() {
+        "mock" => Ok(format!(
+            "模拟模型 {} 可用（离线，不发送网络请求）",
+            profile.model
+        )),
         "openai" => {
-            let base = config.openai_base_url.trim_end_matches('/');
+            let base = profile.endpoint();
             let url = format!("{}/models", base);
+            let api_key = secrets::api_key(profile)?;
             let resp = client
                 .get(&url)
-                .header("Authorization", format!("Bearer {}", config.openai_key))
+                .header("Authorization", format!("Bearer {}", api_key))
                 .send()
                 .await
                 .map_err(|e| anyhow!("无法连接 API ({}): {}", base, e))?;
@@ -169,10 +442,10 @@ pub async fn test_connection(config: &AppConfig) -> Result<String> {
             }
         }
         _ => {
-            let url = format!("{}/api/tags", config.ollama_url.trim_end_matches('/'));
+            let url = format!("{}/api/tags", profile.endpoint());
             let resp = client.get(&url).send().await.map_err(|e| {
                 if e.is_connect() {
-                    anyhow!("无法连接 Ollama（{}），请确认已启动", config.ollama_url)
+                    anyhow!("无法连接 Ollama（{}），请确认已启动", profile.endpoint())
                 } else {
                     anyhow!("Ollama 连接错误: {}", e)
                 }
@@ -183,15 +456,12 @@ pub async fn test_connection(config: &AppConfig) -> Result<String> {
                     .as_array()
                     .map(|arr| arr.iter().filter_map(|m| m["name"].as_str()).collect())
                     .unwrap_or_default();
-                if models.iter().any(|m| m.starts_with(&config.ollama_model)) {
-                    Ok(format!(
-                        "Ollama 连接成功，模型 {} 可用",
-                        config.ollama_model
-                    ))
+                if models.iter().any(|m| m.starts_with(&profile.model)) {
+                    Ok(format!("Ollama 连接成功，模型 {} 可用", profile.model))
                 } else {
                     Err(anyhow!(
                         "Ollama 已连接，但模型 {} 未找到。可用: {}",
-                        config.ollama_model,
+                        profile.model,
                         models.join(", ")
                     ))
                 }
@@ -208,18 +478,19 @@ pub async fn generate_filename(
     config: &AppConfig,
     context: Option<&FileContext>,
 ) -> Result<String> {
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(60))
-        .build()?;
+    let profile = config.model_for(ModelTask::Rename)?;
+    let client = http::client(HttpTask::Rename)?;
 
-    let prompt = build_prompt(text, config, context);
+    let (prompt, redactor) = build_outgoing_prompt(text, config, profile, context);
 
     let max_retries = 2;
     let mut last_err = anyhow!("未知错误");
 
     for attempt in 0..=max_retries {
-        match call_llm(&client, &prompt, config).await {
+        match call_llm(&client, &prompt, profile).await {
             Ok(result) => {
+                let result = restore_reply(&result, redactor.as_ref());
+                let result = fill_metadata_placeholders(&result, context);
                 let ext = context.and_then(|ctx| {
                     std::path::Path::new(&ctx.original_name)
                         .extension()
@@ -245,74 +516,178 @@ pub async fn generate_filename(
     Err(anyhow!("重试 {} 次后仍失败: {}", max_retries + 1, last_err))
 }
 
-async fn call_llm(client: &Client, prompt: &str, config: &AppConfig) -> Result<String> {
-    match config.provider.as_str() {
-        "openai" => call_openai(client, prompt, config).await,
-        _ => call_ollama(client, prompt, config).await,
+async fn call_llm(client: &Client, prompt: &str, profile: &ModelProfile) -> Result<String> {
+    match profile.provider.as_str() {
+        "mock" => mock::complete(profile, ModelTask::Rename, prompt).await,
+        "openai" => call_openai(client, prompt, profile).await,
+        _ => call_ollama(client, prompt, profile).await,
+    }
+}
+
+/// JSON schema of a rename reply, shared by OpenAI `response_format` and Ollama `format`.
+fn filename_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "filename": { "type": "string" }
+        },
+        "required": ["filename"],
+        "additionalProperties": false
+    })
+}
+
+/// Reads `filename` from a structured reply; models that ignored the schema get their
+/// raw text back, which `clean_filename` tidies up.
+fn parse_filename_reply(content: &str) -> String {
+    let trimmed = content.trim();
+    let json = match (trimmed.find('{'), trimmed.rfind('}')) {
+        (Some(start), Some(end)) if end > start => &trimmed[start..=end],
+        _ => trimmed,
+    };
+    if let Ok(parsed) = serde_json::from_str::<Value>(json)
+        && let Some(filename) = parsed["filename"].as_str()
+    {
+        return filename.to_string();
+    }
+    content.to_string()
+}
+
+/// Ollama endpoints that rejected the JSON schema `format`, remembered for the session.
+fn endpoints_without_format() -> &'static Mutex<HashSet<String>> {
+    static ENDPOINTS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    ENDPOINTS.get_or_init(Default::default)
+}
+
+/// Whether an Ollama 400 body says the server cannot take a JSON schema as `format`.
+/// Versions before 0.5 only accept `"json"` and fail to decode the schema object.
+fn rejects_format_schema(body: &str) -> bool {
+    let Ok(parsed) = serde_json::from_str::<Value>(body) else {
+        return false;
+    };
+    let error = parsed["error"].as_str().unwrap_or_default();
+    error.contains("ChatRequest.format") || error.starts_with("invalid format")
+}
+
+/// Posts an Ollama rename `body` to `/api/chat`, constrained to [`filename_schema`]
+/// unless the endpoint rejected it before; a 400 saying the schema is not understood is
+/// retried once as plain text and the endpoint remembered. Send failures go through
+/// `on_send_error`; other failed statuses become errors with the response body.
+async fn send_ollama_rename(
+    client: &Client,
+    profile: &ModelProfile,
+    body: Value,
+    mut on_send_error: impl FnMut(reqwest::Error) -> anyhow::Error,
+) -> Result<reqwest::Response> {
+    let url = format!("{}/api/chat", profile.endpoint());
+    let mut structured = !endpoints_without_format()
+        .lock()
+        .is_ok_and(|endpoints| endpoints.contains(profile.endpoint()));
+    loop {
+        let mut body = body.clone();
+        if structured {
+            body["format"] = filename_schema();
+        }
+        // JSON 外壳要多占几个 token
+        apply_sampling(&mut body, profile, Some(if structured { 120 } else { 80 }));
+        let resp = client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(&mut on_send_error)?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+        let text = resp.text().await.unwrap_or_default();
+        if structured && status == reqwest::StatusCode::BAD_REQUEST && rejects_format_schema(&text)
+        {
+            // 旧版 Ollama 不认识 JSON schema 形式的 format，退回纯文本
+            log::warn!(
+                target: "llm",
+                "ollama rejected the filename schema, retrying as plain text model={} reason={}",
+                profile.model,
+                text
+            );
+            if let Ok(mut endpoints) = endpoints_without_format().lock() {
+                endpoints.insert(profile.endpoint().to_string());
+            }
+            structured = false;
+            continue;
+        }
+        return Err(anyhow!("Ollama 请求失败 {}: {}", status, text));
     }
 }
 
-async fn call_ollama(client: &Client, prompt: &str, config: &AppConfig) -> Result<String> {
-    let url = format!("{}/api/chat", config.ollama_url.trim_end_matches('/'));
+/// Filename typed so far in a streamed reply, without the JSON wrapper of a structured
+/// one, so the progress display shows the name rather than `{"filename": "…`.
+fn partial_filename(accumulated: &str) -> &str {
+    let Some(rest) = accumulated.trim_start().strip_prefix('{') else {
+        return accumulated;
+    };
+    rest.split_once(':')
+        .and_then(|(_, value)| value.trim_start().strip_prefix('"'))
+        .and_then(|value| value.split('"').next())
+        .unwrap_or_default()
+}
+
+async fn call_ollama(client: &Client, prompt: &str, profile: &ModelProfile) -> Result<String> {
     let body = json!({
-        "model": config.ollama_model,
+        "model": profile.model,
         "messages": [{"role": "user", "content": prompt}],
-        "stream": false,
-        "options": { "num_predict": 80 }
+        "stream": false
     });
-
-    let resp = client.post(&url).json(&body).send().await.map_err(|e| {
+    let resp = send_ollama_rename(client, profile, body, |e| {
         if e.is_timeout() {
             anyhow!("Ollama 请求超时（60秒），模型可能过大或服务未响应")
         } else if e.is_connect() {
             anyhow!(
                 "无法连接 Ollama（{}），请确认 Ollama 已启动",
-                config.ollama_url
+                profile.endpoint()
             )
         } else {
             anyhow!("Ollama 网络错误: {}", e)
         }
-    })?;
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        return Err(anyhow!("Ollama 请求失败 {}: {}", status, text));
-    }
+    })
+    .await?;
 
     let data: Value = resp.json().await?;
     let content = data["message"]["content"]
         .as_str()
         .ok_or_else(|| anyhow!("Ollama 响应格式错误"))?
         .to_string();
-    Ok(content)
+    usage::record_response(
+        ModelTask::Rename,
+        profile,
+        prompt.chars().count(),
+        &data,
+        &content,
+    );
+    Ok(parse_filename_reply(&content))
 }
 
-async fn call_openai(client: &Client, prompt: &str, config: &AppConfig) -> Result<String> {
-    let base = config.openai_base_url.trim_end_matches('/');
+async fn call_openai(client: &Client, prompt: &str, profile: &ModelProfile) -> Result<String> {
+    let base = profile.endpoint();
     let url = format!("{}/chat/completions", base);
-    let body = json!({
-        "model": config.openai_model,
+    let mut body = json!({
+        "model": profile.model,
         "messages": [{"role": "user", "content": prompt}],
         "response_format": {
             "type": "json_schema",
             "json_schema": {
                 "name": "filename_result",
                 "strict": true,
-                "schema": {
-                    "type": "object",
-                    "properties": {
-                        "filename": { "type": "string" }
-                    },
-                    "required": ["filename"],
-                    "additionalProperties": false
-                }
+                "schema": filename_schema()
             }
         }
     });
+    apply_sampling(&mut body, profile, None);
 
+    usage::ensure_budget(&profile.provider, profile.endpoint(), &profile.model)?;
+    let api_key = secrets::api_key(profile)?;
     let resp = client
         .post(&url)
-        .header("Authorization", format!("Bearer {}", config.openai_key))
+        .header("Authorization", format!("Bearer {}", api_key))
         .json(&body)
         .send()
         .await
@@ -328,15 +703,15 @@ async fn call_openai(client: &Client, prompt: &str, config: &AppConfig) -> Resul
     let content = data["choices"][0]["message"]["content"]
         .as_str()
         .ok_or_else(|| anyhow!("API 响应格式错误"))?;
-
-    // 尝试从 JSON 响应中提取 filename 字段
-    if let Ok(parsed) = serde_json::from_str::<Value>(content)
-        && let Some(filename) = parsed["filename"].as_str()
-    {
-        return Ok(filename.to_string());
-    }
-    // fallback: 直接返回原始内容
-    Ok(content.to_string())
+    usage::record_response(
+        ModelTask::Rename,
+        profile,
+        prompt.chars().count(),
+        &data,
+        content,
+    );
+
+    Ok(parse_filename_reply(content))
 }
 
 /// Generates a filename from an image using a vision-language model, with retries.
@@ -346,14 +721,22 @@ pub async fn generate_filename_vlm(
     config: &AppConfig,
     context: Option<&FileContext>,
 ) -> Result<String> {
-    let prompt = build_vlm_prompt(config, context);
+    let profile = config.model_for(ModelTask::Vlm)?;
+    let (prompt, redactor) = redact_prompt(
+        build_vlm_prompt(config, context),
+        config,
+        context,
+        profile.endpoint(),
+    );
 
     let max_retries = 2;
     let mut last_err = anyhow!("未知错误");
 
     for attempt in 0..=max_retries {
-        match call_vlm(&prompt, image_base64, mime, config).await {
+        match call_vlm(&prompt, image_base64, mime, profile).await {
             Ok(result) => {
+                let result = restore_reply(&result, redactor.as_ref());
+                let result = fill_metadata_placeholders(&result, context);
                 let ext = context.and_then(|ctx| {
                     std::path::Path::new(&ctx.original_name)
                         .extension()
@@ -398,7 +781,7 @@ fn build_vlm_prompt(config: &AppConfig, context: Option<&FileContext>) -> String
         format!("\n\n## 用户自定义规则\n{}", config.custom_rules)
     };
 
-    let template = template_section(&config.name_template, &config.naming_style);
+    let template = template_section(&config.name_template, &config.naming_style, context);
     let ctx = context_section(context);
 
     let abbr_rule = if config.naming_style == "chinese" {
@@ -424,37 +807,24 @@ pub async fn call_vlm(
     prompt: &str,
     image_base64: &str,
     mime: &str,
-    config: &AppConfig,
+    profile: &ModelProfile,
 ) -> Result<String> {
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(60))
-        .build()?;
-
-    let (base_url, api_key, model) = if config.vlm_same_as_llm {
-        match config.provider.as_str() {
-            "openai" => (
-                config.openai_base_url.clone(),
-                config.openai_key.clone(),
-                config.openai_model.clone(),
-            ),
-            _ => {
-                // Ollama 也支持 vision，走 /api/chat
-                return call_vlm_ollama(&client, prompt, image_base64, mime, config).await;
-            }
-        }
-    } else {
-        (
-            config.vlm_base_url.clone(),
-            config.vlm_key.clone(),
-            config.vlm_model.clone(),
-        )
-    };
+    if profile.is_mock() {
+        return mock::complete(profile, ModelTask::Vlm, prompt).await;
+    }
+    let client = http::client(HttpTask::Vlm)?;
 
-    let base = base_url.trim_end_matches('/');
-    let url = format!("{}/chat/completions", base);
+    if !profile.is_openai() {
+        // Ollama 也支持 vision，走 /api/chat
+        return call_vlm_ollama(&client, prompt, image_base64, mime, profile).await;
+    }
+    usage::ensure_budget(&profile.provider, profile.endpoint(), &profile.model)?;
+    let api_key = secrets::api_key(profile)?;
+
+    let url = format!("{}/chat/completions", profile.endpoint());
     let data_url = format!("data:{};base64,{}", mime, image_base64);
-    let body = json!({
-        "model": model,
+    let mut body = json!({
+        "model": profile.model,
         "messages": [{
             "role": "user",
             "content": [
@@ -463,6 +833,7 @@ pub async fn call_vlm(
             ]
         }]
     });
+    apply_sampling(&mut body, profile, None);
 
     let resp = client
         .post(&url)
@@ -479,10 +850,17 @@ pub async fn call_vlm(
     }
 
     let data: Value = resp.json().await?;
-    data["choices"][0]["message"]["content"]
+    let content = data["choices"][0]["message"]["content"]
         .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| anyhow!("VLM 响应格式错误"))
+        .ok_or_else(|| anyhow!("VLM 响应格式错误"))?;
+    usage::record_response(
+        ModelTask::Vlm,
+        profile,
+        prompt.chars().count(),
+        &data,
+        content,
+    );
+    Ok(content.to_string())
 }
 
 async fn call_vlm_ollama(
@@ -490,11 +868,11 @@ async fn call_vlm_ollama(
     prompt: &str,
     image_base64: &str,
     _mime: &str,
-    config: &AppConfig,
+    profile: &ModelProfile,
 ) -> Result<String> {
-    let url = format!("{}/api/chat", config.ollama_url.trim_end_matches('/'));
-    let body = json!({
-        "model": config.ollama_model,
+    let url = format!("{}/api/chat", profile.endpoint());
+    let mut body = json!({
+        "model": profile.model,
         "messages": [{
             "role": "user",
             "content": prompt,
@@ -502,6 +880,7 @@ async fn call_vlm_ollama(
         }],
         "stream": false
     });
+    apply_sampling(&mut body, profile, None);
 
     let resp = client
         .post(&url)
@@ -516,50 +895,49 @@ async fn call_vlm_ollama(
     }
 
     let data: Value = resp.json().await?;
-    data["message"]["content"]
+    let content = data["message"]["content"]
         .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| anyhow!("Ollama VLM 响应格式错误"))
+        .ok_or_else(|| anyhow!("Ollama VLM 响应格式错误"))?;
+    usage::record_response(
+        ModelTask::Vlm,
+        profile,
+        prompt.chars().count(),
+        &data,
+        content,
+    );
+    Ok(content.to_string())
 }
 
 /// Streams an Ollama chat completion, sending partial tokens via a Tauri channel.
 pub async fn call_ollama_stream(
     prompt: &str,
-    config: &AppConfig,
+    profile: &ModelProfile,
     file_name: &str,
     on_event: &tauri::ipc::Channel<StreamEvent>,
 ) -> Result<String> {
     use futures_util::StreamExt;
 
-    let url = format!("{}/api/chat", config.ollama_url.trim_end_matches('/'));
     let body = json!({
-        "model": config.ollama_model,
+        "model": profile.model,
         "messages": [{"role": "user", "content": prompt}],
-        "stream": true,
-        "options": { "num_predict": 80 }
+        "stream": true
     });
 
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(60))
-        .build()?;
+    let client = http::client(HttpTask::Rename)?;
 
-    let resp = client.post(&url).json(&body).send().await.map_err(|e| {
+    let resp = send_ollama_rename(&client, profile, body, |e| {
         if e.is_connect() {
             anyhow!(
                 "无法连接 Ollama（{}），请确认 Ollama 已启动",
-                config.ollama_url
+                profile.endpoint()
             )
         } else {
             anyhow!("Ollama 网络错误: {}", e)
         }
-    })?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        return Err(anyhow!("Ollama 请求失败 {}: {}", status, text));
-    }
+    })
+    .await?;
 
+    let mut meter = UsageMeter::new(ModelTask::Rename, profile, prompt.chars().count());
     let mut stream = resp.bytes_stream();
     let mut accumulated = String::new();
     let mut buffer = Vec::new();
@@ -577,13 +955,16 @@ pub async fn call_ollama_stream(
                 continue;
             }
 
-            if let Ok(json) = serde_json::from_str::<Value>(line)
-                && let Some(token) = json["message"]["content"].as_str()
-            {
+            let Ok(json) = serde_json::from_str::<Value>(line) else {
+                continue;
+            };
+            meter.observe(&json);
+            if let Some(token) = json["message"]["content"].as_str() {
+                meter.add_output(token);
                 accumulated.push_str(token);
                 let _ = on_event.send(StreamEvent::Partial {
                     file_name: file_name.to_string(),
-                    partial: accumulated.clone(),
+                    partial: partial_filename(&accumulated).to_string(),
                 });
             }
         }
@@ -595,50 +976,46 @@ pub async fn call_ollama_stream(
         let line = line.trim();
         if !line.is_empty()
             && let Ok(json) = serde_json::from_str::<Value>(line)
-            && let Some(token) = json["message"]["content"].as_str()
         {
-            accumulated.push_str(token);
+            meter.observe(&json);
+            if let Some(token) = json["message"]["content"].as_str() {
+                meter.add_output(token);
+                accumulated.push_str(token);
+            }
         }
     }
 
-    Ok(accumulated)
+    Ok(parse_filename_reply(&accumulated))
 }
 
 /// Streams an OpenAI-compatible SSE chat completion, sending partial tokens via a Tauri channel.
 pub async fn call_openai_stream(
     prompt: &str,
-    config: &AppConfig,
+    profile: &ModelProfile,
     file_name: &str,
     on_event: &tauri::ipc::Channel<StreamEvent>,
 ) -> Result<String> {
     use futures_util::StreamExt;
 
-    let base = config.openai_base_url.trim_end_matches('/');
+    let base = profile.endpoint();
     let url = format!("{}/chat/completions", base);
-    let body = json!({
-        "model": config.openai_model,
+    let mut body = json!({
+        "model": profile.model,
         "messages": [{"role": "user", "content": prompt}],
         "stream": true
     });
+    apply_sampling(&mut body, profile, None);
 
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(60))
-        .build()?;
+    let client = http::client(HttpTask::Rename)?;
 
-    let resp = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", config.openai_key))
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| anyhow!("无法连接 API ({}): {}", base, e))?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        return Err(anyhow!("API 请求失败 {}: {}", status, text));
-    }
+    usage::ensure_budget(&profile.provider, profile.endpoint(), &profile.model)?;
+    let api_key = secrets::api_key(profile)?;
+    let resp = send_openai_stream(&client, &url, &api_key, body, |e| {
+        anyhow!("无法连接 API ({}): {}", base, e)
+    })
+    .await?;
 
+    let mut meter = UsageMeter::new(ModelTask::Rename, profile, prompt.chars().count());
     let mut stream = resp.bytes_stream();
     let mut accumulated = String::new();
     let mut buffer = String::new();
@@ -656,10 +1033,15 @@ pub async fn call_openai_stream(
             if line == "data: [DONE]" {
                 break;
             }
-            if let Some(data) = line.strip_prefix("data: ")
-                && let Ok(json) = serde_json::from_str::<Value>(data)
-                && let Some(token) = json["choices"][0]["delta"]["content"].as_str()
-            {
+            let Some(json) = line
+                .strip_prefix("data: ")
+                .and_then(|data| serde_json::from_str::<Value>(data).ok())
+            else {
+                continue;
+            };
+            meter.observe(&json);
+            if let Some(token) = json["choices"][0]["delta"]["content"].as_str() {
+                meter.add_output(token);
                 accumulated.push_str(token);
                 let _ = on_event.send(StreamEvent::Partial {
                     file_name: file_name.to_string(),
@@ -672,6 +1054,25 @@ pub async fn call_openai_stream(
     Ok(accumulated)
 }
 
+/// Streams the scripted reply of a `mock` profile, sending partials like the real providers.
+pub async fn call_mock_stream(
+    prompt: &str,
+    profile: &ModelProfile,
+    file_name: &str,
+    on_event: &tauri::ipc::Channel<StreamEvent>,
+) -> Result<String> {
+    let mut accumulated = String::new();
+    mock::stream(profile, ModelTask::Rename, prompt, None, |delta| {
+        accumulated.push_str(delta);
+        let _ = on_event.send(StreamEvent::Partial {
+            file_name: file_name.to_string(),
+            partial: accumulated.clone(),
+        });
+    })
+    .await?;
+    Ok(accumulated)
+}
+
 /// Sanitizes raw LLM output into a valid filename, stripping quotes and illegal characters.
 pub fn clean_filename(raw: &str, ext: Option<&str>) -> String {
     let mut name = raw
@@ -695,3 +1096,219 @@ pub fn clean_filename(raw: &str, ext: Option<&str>) -> String {
 
     name
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{self, StubResponse, StubServer, block_on, compatible_response};
+
+    #[test]
+    fn test_connection_checks_models_and_tags_endpoints() {
+        let server = StubServer::compatible("", 1);
+        let mut profile = ModelProfile {
+            provider: "openai".into(),
+            base_url: server.openai_url(),
+            api_key: "sk-test".into(),
+            model: "gpt-4o-mini".into(),
+            ..ModelProfile::default()
+        };
+        assert!(block_on(test_connection(&profile, &AppConfig::default())).is_ok());
+
+        profile.provider = "ollama".into();
+        profile.base_url = server.ollama_url();
+        profile.model = "llama3.2".into();
+        let message = block_on(test_connection(&profile, &AppConfig::default())).unwrap();
+        assert!(message.contains("llama3.2"));
+        profile.model = "qwen2.5".into();
+        let err = block_on(test_connection(&profile, &AppConfig::default())).unwrap_err();
+        assert!(err.to_string().contains("未找到"));
+
+        let requests = server.requests();
+        assert!(requests.iter().all(|request| request.method == "GET"));
+        assert_eq!(requests[0].path, "/v1/models");
+        assert_eq!(requests[1].path, "/api/tags");
+    }
+
+    #[test]
+    fn metadata_fills_only_the_placeholders_the_model_left() {
+        let context = FileContext {
+            path: "/tmp/contract.docx".into(),
+            original_name: "contract.docx".into(),
+            parent_dir: "tmp".into(),
+            sibling_names: Vec::new(),
+            modified_at: String::new(),
+            file_size: String::new(),
+            metadata: DocumentMetadata {
+                author: Some("张三, 李四".into()),
+                created: Some("2024-03-18".into()),
+                ..DocumentMetadata::default()
+            },
+            photo: None,
+        };
+        let section = template_section("{type}-{author}-{date}", "kebab-case", Some(&context));
+        assert!(section.contains("按「{type}-{author}-{date}」格式输出"));
+        assert!(section.contains("正文中找不到的 {author}、{date} 原样保留占位符"));
+
+        assert_eq!(
+            fill_metadata_placeholders("contract-acme-{date}", Some(&context)),
+            "contract-acme-20240318"
+        );
+        assert_eq!(
+            fill_metadata_placeholders("contract-{author}-20231201", Some(&context)),
+            "contract-张三-20231201"
+        );
+        assert_eq!(
+            fill_metadata_placeholders("contract-{author}", None),
+            "contract-{author}"
+        );
+    }
+
+    #[test]
+    fn parse_filename_reply_reads_schema_output_and_keeps_free_text() {
+        assert_eq!(
+            parse_filename_reply(r#"{"filename": "Invoice-Acme-20240815"}"#),
+            "Invoice-Acme-20240815"
+        );
+        assert_eq!(
+            parse_filename_reply("```json\n{\"filename\":\"发票-Acme\"}\n```"),
+            "发票-Acme"
+        );
+        assert_eq!(parse_filename_reply("Invoice-Acme"), "Invoice-Acme");
+    }
+
+    #[test]
+    fn ollama_rename_uses_format_schema_and_falls_back_without_it() {
+        let structured = StubServer::start(|request| {
+            compatible_response(request, r#"{"filename":"Invoice-Acme"}"#, 1)
+        });
+        let profile = ModelProfile {
+            provider: "ollama".into(),
+            base_url: structured.ollama_url(),
+            model: "llama3.2".into(),
+            ..ModelProfile::default()
+        };
+        let client = http::client(HttpTask::Rename).unwrap();
+        let name = block_on(call_llm(&client, "prompt", &profile)).unwrap();
+        assert_eq!(name, "Invoice-Acme");
+        let body = &structured.requests()[0].body;
+        assert_eq!(body["format"], filename_schema());
+        assert_eq!(body["options"]["num_predict"], 120);
+
+        // 不支持结构化输出的旧版本：带 format 时返回 400
+        let legacy = StubServer::start(|request| {
+            if request.body.get("format").is_some() {
+                StubResponse::Status(
+                    400,
+                    r#"{"error":"json: cannot unmarshal object into Go struct field ChatRequest.format of type string"}"#.into(),
+                )
+            } else {
+                compatible_response(request, "Invoice-Legacy", 1)
+            }
+        });
+        let profile = ModelProfile {
+            base_url: legacy.ollama_url(),
+            ..profile
+        };
+        for _ in 0..2 {
+            let name = block_on(call_llm(&client, "prompt", &profile)).unwrap();
+            assert_eq!(name, "Invoice-Legacy");
+        }
+        // 记住该端点后第二次直接发纯文本
+        let sent = legacy
+            .requests()
+            .iter()
+            .map(|request| request.body.get("format").is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(sent, [true, false, false]);
+
+        // 只是提到 format 的其他 400 照常报错，也不记住端点
+        let other = StubServer::start(|_| {
+            StubResponse::Status(
+                400,
+                r#"{"error":"invalid message format: content must be a string"}"#.into(),
+            )
+        });
+        let profile = ModelProfile {
+            base_url: other.ollama_url(),
+            ..profile
+        };
+        for _ in 0..2 {
+            let err = block_on(call_llm(&client, "prompt", &profile)).unwrap_err();
+            assert!(err.to_string().contains("invalid message format"));
+        }
+        let sent = other
+            .requests()
+            .iter()
+            .map(|request| request.body.get("format").is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(sent, [true, true]);
+    }
+
+    #[test]
+    fn ollama_stream_rename_uses_format_schema_and_reads_the_filename() {
+        let server = StubServer::start(|request| {
+            compatible_response(request, r#"{"filename": "Invoice-Acme"}"#, 4)
+        });
+        let profile = ModelProfile {
+            provider: "ollama".into(),
+            base_url: server.ollama_url(),
+            model: "llama3.2".into(),
+            ..ModelProfile::default()
+        };
+        let (channel, events) = test_server::recording_channel();
+        let name = block_on(call_ollama_stream("prompt", &profile, "a.pdf", &channel)).unwrap();
+        assert_eq!(name, "Invoice-Acme");
+        let body = &server.requests()[0].body;
+        assert_eq!(body["format"], filename_schema());
+        assert_eq!(body["options"]["num_predict"], 120);
+        // 进度里只显示文件名，不显示 JSON 外壳
+        let partials = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| event["data"]["partial"].as_str().map(str::to_string))
+            .collect::<Vec<_>>();
+        assert_eq!(partials.last().map(String::as_str), Some("Invoice-Acme"));
+        assert!(partials.iter().all(|partial| !partial.contains('{')));
+    }
+
+    #[test]
+    fn openai_streams_retry_without_stream_options_when_rejected() {
+        let server = StubServer::start(|request| {
+            if request.body.get("stream_options").is_some() {
+                StubResponse::Status(
+                    400,
+                    "Unrecognized request argument supplied: stream_options".into(),
+                )
+            } else {
+                compatible_response(request, "ok", 1)
+            }
+        });
+        let url = format!("{}/chat/completions", server.openai_url());
+        let client = http::client(HttpTask::Stream).unwrap();
+        let body = json!({ "model": "m", "messages": [], "stream": true });
+        for _ in 0..2 {
+            let resp = block_on(send_openai_stream(&client, &url, "sk", body.clone(), |e| {
+                anyhow!(e)
+            }))
+            .unwrap();
+            assert!(resp.status().is_success());
+        }
+        // 第二次直接不带 stream_options
+        let sent = server
+            .requests()
+            .iter()
+            .map(|request| request.body.get("stream_options").is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(sent, [true, false, false]);
+
+        let other = StubServer::start(|_| StubResponse::Status(400, "bad model".into()));
+        let url = format!("{}/chat/completions", other.openai_url());
+        let err = block_on(send_openai_stream(&client, &url, "sk", body, |e| {
+            anyhow!(e)
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("bad model"));
+        assert_eq!(other.requests().len(), 1);
+    }
+}
@@ -0,0 +1,712 @@
+use crate::pdf_password;
+use anyhow::{Result, anyhow};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const PDF_FULL_READ_LIMIT: u64 = 16 * 1024 * 1024;
+const PDF_WINDOW_BYTES: u64 = 4 * 1024 * 1024;
+const MAX_FIELD_CHARS: usize = 200;
+
+/// Title, author and dates embedded in the document itself (PDF Info/XMP, OOXML core.xml).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// Creation date normalized to `YYYY-MM-DD`.
+    pub created: Option<String>,
+    /// Last modification date normalized to `YYYY-MM-DD`.
+    pub modified: Option<String>,
+    pub source: Option<String>,
+}
+
+impl DocumentMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.author.is_none()
+            && self.subject.is_none()
+            && self.keywords.is_none()
+            && self.created.is_none()
+            && self.modified.is_none()
+    }
+
+    /// Returns the document date as `YYYYMMDD`, preferring the creation date.
+    pub fn compact_date(&self) -> Option<String> {
+        self.created
+            .as_deref()
+            .or(self.modified.as_deref())
+            .map(|date| date.replace('-', ""))
+    }
+
+    /// Fills empty fields from `other`, keeping values that are already present.
+    fn merge_missing(&mut self, other: DocumentMetadata) {
+        if self.title.is_none() {
+            self.title = other.title;
+        }
+        if self.author.is_none() {
+            self.author = other.author;
+        }
+        if self.subject.is_none() {
+            self.subject = other.subject;
+        }
+        if self.keywords.is_none() {
+            self.keywords = other.keywords;
+        }
+        if self.created.is_none() {
+            self.created = other.created;
+        }
+        if self.modified.is_none() {
+            self.modified = other.modified;
+        }
+        if self.source.is_none() {
+            self.source = other.source;
+        }
+    }
+}
+
+/// Reads embedded metadata for PDF and Office documents; other formats return empty metadata.
+pub fn read_document_metadata(path: &str) -> DocumentMetadata {
+    let ext = Path::new(path)
+        .extension()
+        .map(|e| e.to_ascii_lowercase().to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let result = match ext.as_str() {
+        "pdf" => read_pdf_metadata(path),
+        "docx" | "pptx" | "xlsx" => read_ooxml_metadata(path),
+        _ => return DocumentMetadata::default(),
+    };
+
+    match result {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            log::warn!(target: "metadata", "failed to read metadata for {}: {}", path, err);
+            DocumentMetadata::default()
+        }
+    }
+}
+
+fn read_pdf_metadata(path: &str) -> Result<DocumentMetadata> {
+    let bytes = read_pdf_metadata_window(path)?;
+    // 加密 PDF 的 Info 字符串是密文，只能依赖明文 XMP
+    let mut metadata = if pdf_password::has_encrypt_marker(&bytes) {
+        DocumentMetadata::default()
+    } else {
+        parse_pdf_info(&bytes)
+    };
+    if let Some(xmp) = find_xmp_packet(&bytes) {
+        // XMP 通常比 Info 字典更完整（尤其是多作者），但 Info 字典里已有的值优先保留
+        metadata.merge_missing(parse_xmp(&xmp));
+    }
+    Ok(metadata)
+}
+
+/// Reads the whole file for small PDFs, otherwise only the head and tail where Info/XMP live.
+fn read_pdf_metadata_window(path: &str) -> Result<Vec<u8>> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    if len <= PDF_FULL_READ_LIMIT {
+        let mut bytes = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut bytes)?;
+        return Ok(bytes);
+    }
+
+    let mut head = vec![0u8; PDF_WINDOW_BYTES as usize];
+    file.read_exact(&mut head)?;
+    file.seek(SeekFrom::Start(len - PDF_WINDOW_BYTES))?;
+    let mut tail = vec![0u8; PDF_WINDOW_BYTES as usize];
+    file.read_exact(&mut tail)?;
+    head.push(b'\n');
+    head.extend_from_slice(&tail);
+    Ok(head)
+}
+
+fn parse_pdf_info(bytes: &[u8]) -> DocumentMetadata {
+    let Some(dict) = find_pdf_info_dict(bytes) else {
+        return DocumentMetadata::default();
+    };
+
+    let title = pdf_dict_string(dict, b"/Title").and_then(clean_field);
+    let author = pdf_dict_string(dict, b"/Author").and_then(clean_field);
+    let subject = pdf_dict_string(dict, b"/Subject").and_then(clean_field);
+    let keywords = pdf_dict_string(dict, b"/Keywords").and_then(clean_field);
+    let created = pdf_dict_string(dict, b"/CreationDate").and_then(|raw| parse_pdf_date(&raw));
+    let modified = pdf_dict_string(dict, b"/ModDate").and_then(|raw| parse_pdf_date(&raw));
+
+    let metadata = DocumentMetadata {
+        title,
+        author,
+        subject,
+        keywords,
+        created,
+        modified,
+        source: None,
+    };
+    if metadata.is_empty() {
+        metadata
+    } else {
+        DocumentMetadata {
+            source: Some("pdf-info".into()),
+            ..metadata
+        }
+    }
+}
+
+/// Locates the Info dictionary through the trailer `/Info N G R` reference, falling back to
+/// the last dictionary that carries typical Info keys.
+fn find_pdf_info_dict(bytes: &[u8]) -> Option<&[u8]> {
+    if let Some(reference) = rfind_bytes(bytes, b"/Info")
+        .and_then(|idx| parse_indirect_ref(&bytes[idx + b"/Info".len()..]))
+        && let Some(dict) = find_object_dict(bytes, reference)
+    {
+        return Some(dict);
+    }
+
+    for key in [b"/CreationDate".as_slice(), b"/Author", b"/Producer"] {
+        if let Some(idx) = rfind_bytes(bytes, key) {
+            let start = rfind_bytes(&bytes[..idx], b"<<")?;
+            let end = find_dict_end(bytes, start)?;
+            return Some(&bytes[start..end]);
+        }
+    }
+    None
+}
+
+fn parse_indirect_ref(input: &[u8]) -> Option<(u32, u32)> {
+    let text = String::from_utf8_lossy(&input[..input.len().min(32)]).to_string();
+    let mut parts = text.split_whitespace();
+    let number = parts.next()?.parse::<u32>().ok()?;
+    let generation = parts.next()?.parse::<u32>().ok()?;
+    parts
+        .next()
+        .filter(|token| token.starts_with('R'))
+        .map(|_| (number, generation))
+}
+
+fn find_object_dict(bytes: &[u8], (number, generation): (u32, u32)) -> Option<&[u8]> {
+    let header = format!("{} {} obj", number, generation);
+    let mut search_from = 0usize;
+    while let Some(offset) = find_bytes(&bytes[search_from..], header.as_bytes()) {
+        let idx = search_from + offset;
+        // 避免把 "12 0 obj" 误当成 "2 0 obj"
+        let boundary_ok = idx == 0 || !bytes[idx - 1].is_ascii_digit();
+        if boundary_ok {
+            let body = &bytes[idx + header.len()..];
+            let start = find_bytes(body, b"<<")?;
+            let absolute = idx + header.len() + start;
+            let end = find_dict_end(bytes, absolute)?;
+            return Some(&bytes[absolute..end]);
+        }
+        search_from = idx + header.len();
+    }
+    None
+}
+
+/// Returns the index just past the `>>` that closes the dictionary opened at `start`.
+fn find_dict_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0i32;
+    let mut idx = start;
+    while idx + 1 < bytes.len() {
+        match bytes[idx] {
+            b'(' => {
+                idx = skip_literal_string(bytes, idx)?;
+                continue;
+            }
+            b'<' if bytes[idx + 1] == b'<' => {
+                depth += 1;
+                idx += 2;
+                continue;
+            }
+            b'>' if bytes[idx + 1] == b'>' => {
+                depth -= 1;
+                idx += 2;
+                if depth == 0 {
+                    return Some(idx);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+    None
+}
+
+fn skip_literal_string(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0i32;
+    let mut idx = start;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' => idx += 1,
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx + 1);
+                }
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+    None
+}
+
+fn pdf_dict_string(dict: &[u8], key: &[u8]) -> Option<String> {
+    let mut search_from = 0usize;
+    let value_start = loop {
+        let idx = search_from + find_bytes(&dict[search_from..], key)?;
+        let after = idx + key.len();
+        // "/Title" 不能匹配 "/TitleFoo"
+        if dict
+            .get(after)
+            .map(|byte| !byte.is_ascii_alphanumeric())
+            .unwrap_or(false)
+        {
+            break after;
+        }
+        search_from = after;
+    };
+
+    let rest = &dict[value_start..];
+    let offset = rest.iter().position(|byte| !byte.is_ascii_whitespace())?;
+    let rest = &rest[offset..];
+    match rest.first()? {
+        b'(' => {
+            let end = skip_literal_string(rest, 0)?;
+            Some(decode_pdf_text_bytes(&unescape_pdf_literal(
+                &rest[1..end - 1],
+            )))
+        }
+        b'<' if rest.get(1) != Some(&b'<') => {
+            let end = rest.iter().position(|byte| *byte == b'>')?;
+            Some(decode_pdf_text_bytes(&decode_hex_string(&rest[1..end])))
+        }
+        _ => None,
+    }
+}
+
+fn unescape_pdf_literal(raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut idx = 0usize;
+    while idx < raw.len() {
+        let byte = raw[idx];
+        if byte != b'\\' {
+            out.push(byte);
+            idx += 1;
+            continue;
+        }
+
+        idx += 1;
+        let Some(&next) = raw.get(idx) else { break };
+        match next {
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'b' => out.push(0x08),
+            b'f' => out.push(0x0c),
+            b'\r' | b'\n' => {
+                // 行尾续行：跳过换行本身（含 \r\n）
+                if next == b'\r' && raw.get(idx + 1) == Some(&b'\n') {
+                    idx += 1;
+                }
+            }
+            b'0'..=b'7' => {
+                let mut value = 0u32;
+                let mut digits = 0;
+                while digits < 3 {
+                    match raw.get(idx) {
+                        Some(digit @ b'0'..=b'7') => {
+                            value = value * 8 + (digit - b'0') as u32;
+                            idx += 1;
+                            digits += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                out.push((value & 0xff) as u8);
+                continue;
+            }
+            other => out.push(other),
+        }
+        idx += 1;
+    }
+    out
+}
+
+fn decode_hex_string(raw: &[u8]) -> Vec<u8> {
+    let digits = raw
+        .iter()
+        .filter_map(|byte| (*byte as char).to_digit(16))
+        .map(|value| value as u8)
+        .collect::<Vec<_>>();
+    digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+/// Decodes a PDF text string: UTF-16BE with BOM, UTF-8 with BOM, otherwise PDFDocEncoding
+/// (approximated as Latin-1, which matches for the printable range).
+fn decode_pdf_text_bytes(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xfe, 0xff]) {
+        let units = bytes[2..]
+            .chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+        return String::from_utf16_lossy(&units);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+        return String::from_utf8_lossy(rest).to_string();
+    }
+    bytes.iter().map(|byte| *byte as char).collect()
+}
+
+/// Converts `D:YYYYMMDDHHmmSS...` (or a bare `YYYYMMDD`) into `YYYY-MM-DD`.
+fn parse_pdf_date(raw: &str) -> Option<String> {
+    let trimmed = raw.trim();
+    let value = trimmed.strip_prefix("D:").unwrap_or(trimmed);
+    let digits = value
+        .chars()
+        .take_while(|ch| ch.is_ascii_digit())
+        .collect::<String>();
+    if digits.len() < 4 {
+        return None;
+    }
+    let year = &digits[..4];
+    let month = digits.get(4..6).unwrap_or("01");
+    let day = digits.get(6..8).unwrap_or("01");
+    normalize_ymd(year, month, day)
+}
+
+/// Converts ISO-8601 dates such as `2024-07-14T09:30:00+09:00` into `YYYY-MM-DD`.
+fn parse_iso_date(raw: &str) -> Option<String> {
+    let date = raw.trim().split(['T', ' ']).next()?;
+    let mut parts = date.split('-');
+    let year = parts.next()?;
+    let month = parts.next().unwrap_or("01");
+    let day = parts.next().unwrap_or("01");
+    normalize_ymd(year, month, day)
+}
+
+fn normalize_ymd(year: &str, month: &str, day: &str) -> Option<String> {
+    let year = year.parse::<i32>().ok()?;
+    let month = month.parse::<u32>().ok()?;
+    let day = day.parse::<u32>().ok()?;
+    // 很多生成器会写 1601/1970 之类的占位时间，视为无效
+    if !(1980..=2100).contains(&year) {
+        return None;
+    }
+    chrono::NaiveDate::from_ymd_opt(year, month, day)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+fn find_xmp_packet(bytes: &[u8]) -> Option<String> {
+    let start = rfind_bytes(bytes, b"<x:xmpmeta")?;
+    let end_marker = b"</x:xmpmeta>";
+    let end = start + find_bytes(&bytes[start..], end_marker)? + end_marker.len();
+    Some(String::from_utf8_lossy(&bytes[start..end]).to_string())
+}
+
+/// Extracts Dublin Core / XMP basic fields from an XMP packet.
+pub fn parse_xmp(xmp: &str) -> DocumentMetadata {
+    let title = xmp_values(xmp, "dc:title").into_iter().next();
+    let creators = xmp_values(xmp, "dc:creator");
+    let author = (!creators.is_empty()).then(|| creators.join(", "));
+    let subject = xmp_values(xmp, "dc:description").into_iter().next();
+    let keywords = xmp_values(xmp, "pdf:Keywords").into_iter().next();
+    let created = xmp_values(xmp, "xmp:CreateDate")
+        .into_iter()
+        .chain(xmp_values(xmp, "photoshop:DateCreated"))
+        .find_map(|value| parse_iso_date(&value));
+    let modified = xmp_values(xmp, "xmp:ModifyDate")
+        .into_iter()
+        .find_map(|value| parse_iso_date(&value));
+
+    let metadata = DocumentMetadata {
+        title: title.and_then(clean_field),
+        author: author.and_then(clean_field),
+        subject: subject.and_then(clean_field),
+        keywords: keywords.and_then(clean_field),
+        created,
+        modified,
+        source: None,
+    };
+    if metadata.is_empty() {
+        metadata
+    } else {
+        DocumentMetadata {
+            source: Some("xmp".into()),
+            ..metadata
+        }
+    }
+}
+
+/// Returns the values of an XMP property in either element form (including `rdf:li` lists)
+/// or attribute form (`<rdf:Description xmp:CreateDate="...">`).
+pub fn xmp_values(xmp: &str, name: &str) -> Vec<String> {
+    let mut values = Vec::new();
+
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut search_from = 0usize;
+    while let Some(offset) = xmp[search_from..].find(&open) {
+        let tag_start = search_from + offset;
+        let after_name = tag_start + open.len();
+        let Some(next_char) = xmp[after_name..].chars().next() else {
+            break;
+        };
+        if !(next_char == '>' || next_char == '/' || next_char.is_whitespace()) {
+            search_from = after_name;
+            continue;
+        }
+        let Some(tag_end) = xmp[after_name..].find('>').map(|idx| after_name + idx) else {
+            break;
+        };
+        if xmp[..tag_end].ends_with('/') {
+            search_from = tag_end + 1;
+            continue;
+        }
+        let Some(body_end) = xmp[tag_end..].find(&close).map(|idx| tag_end + idx) else {
+            break;
+        };
+        let body = &xmp[tag_end + 1..body_end];
+        let items = xmp_list_items(body);
+        if items.is_empty() {
+            let text = xml_unescape(strip_tags(body).trim());
+            if !text.is_empty() {
+                values.push(text);
+            }
+        } else {
+            values.extend(items);
+        }
+        search_from = body_end + close.len();
+    }
+
+    let attribute = format!("{}=\"", name);
+    let mut search_from = 0usize;
+    while let Some(offset) = xmp[search_from..].find(&attribute) {
+        let value_start = search_from + offset + attribute.len();
+        let Some(value_end) = xmp[value_start..].find('"').map(|idx| value_start + idx) else {
+            break;
+        };
+        let value = xml_unescape(xmp[value_start..value_end].trim());
+        if !value.is_empty() {
+            values.push(value);
+        }
+        search_from = value_end + 1;
+    }
+
+    values
+}
+
+fn xmp_list_items(body: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut search_from = 0usize;
+    while let Some(offset) = body[search_from..].find("<rdf:li") {
+        let tag_start = search_from + offset;
+        let Some(tag_end) = body[tag_start..].find('>').map(|idx| tag_start + idx) else {
+            break;
+        };
+        let Some(item_end) = body[tag_end..].find("</rdf:li>").map(|idx| tag_end + idx) else {
+            break;
+        };
+        let text = xml_unescape(strip_tags(&body[tag_end + 1..item_end]).trim());
+        if !text.is_empty() {
+            items.push(text);
+        }
+        search_from = item_end + "</rdf:li>".len();
+    }
+    items
+}
+
+fn strip_tags(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut in_tag = false;
+    for ch in input.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => out.push(ch),
+            _ => {}
+        }
+    }
+    out
+}
+
+fn xml_unescape(input: &str) -> String {
+    quick_xml::escape::unescape(input)
+        .map(|value| value.to_string())
+        .unwrap_or_else(|_| input.to_string())
+}
+
+fn read_ooxml_metadata(path: &str) -> Result<DocumentMetadata> {
+    let file = std::fs::File::open(path)?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| anyhow!("无法读取文档压缩包: {}", e))?;
+    let mut xml = String::new();
+    match archive.by_name("docProps/core.xml") {
+        Ok(mut entry) => {
+            entry.read_to_string(&mut xml)?;
+        }
+        Err(_) => return Ok(DocumentMetadata::default()),
+    }
+    Ok(parse_core_xml(&xml))
+}
+
+/// Parses OOXML `docProps/core.xml` (Dublin Core properties shared by DOCX/PPTX/XLSX).
+pub fn parse_core_xml(xml: &str) -> DocumentMetadata {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut metadata = DocumentMetadata::default();
+    let mut current: Option<Vec<u8>> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(tag)) => current = Some(tag.name().as_ref().to_vec()),
+            Ok(Event::End(_)) => current = None,
+            Ok(Event::Text(text)) => {
+                let Some(name) = current.as_deref() else {
+                    continue;
+                };
+                let Ok(value) = text.unescape() else {
+                    continue;
+                };
+                let value = value.trim().to_string();
+                match name {
+                    b"dc:title" => metadata.title = clean_field(value),
+                    b"dc:creator" => metadata.author = clean_field(value),
+                    b"dc:subject" => metadata.subject = clean_field(value),
+                    b"cp:keywords" => metadata.keywords = clean_field(value),
+                    b"dcterms:created" => metadata.created = parse_iso_date(&value),
+                    b"dcterms:modified" => metadata.modified = parse_iso_date(&value),
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    if !metadata.is_empty() {
+        metadata.source = Some("core.xml".into());
+    }
+    metadata
+}
+
+/// Drops placeholder values that authoring tools write by default.
+fn clean_field(value: String) -> Option<String> {
+    let compact = value
+        .chars()
+        .filter(|ch| !ch.is_control() || *ch == ' ')
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if compact.is_empty() {
+        return None;
+    }
+
+    let lower = compact.to_lowercase();
+    let is_placeholder = matches!(
+        lower.as_str(),
+        "untitled" | "unknown" | "author" | "user" | "admin" | "administrator" | "microsoft word"
+    ) || lower.starts_with("microsoft word - ")
+        || lower.ends_with(".doc")
+        || lower.ends_with(".docx")
+        || lower.ends_with(".dvi")
+        || lower.ends_with(".tex");
+    if is_placeholder {
+        return None;
+    }
+
+    Some(compact.chars().take(MAX_FIELD_CHARS).collect())
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() || haystack.len() < needle.len() {
+        return None;
+    }
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn rfind_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() || haystack.len() < needle.len() {
+        return None;
+    }
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pdf_info_reads_trailer_referenced_dictionary() {
+        let pdf = b"%PDF-1.4\n5 0 obj\n<< /Title (Quarterly \\(Q3\\) Report) /Author <FEFF0041006400610020004C006F00760065006C006100630065> /CreationDate (D:20240105123000+08'00') >>\nendobj\ntrailer\n<< /Size 6 /Info 5 0 R >>\n%%EOF";
+        let metadata = parse_pdf_info(pdf);
+
+        assert_eq!(metadata.title.as_deref(), Some("Quarterly (Q3) Report"));
+        assert_eq!(metadata.author.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(metadata.created.as_deref(), Some("2024-01-05"));
+        assert_eq!(metadata.compact_date().as_deref(), Some("20240105"));
+    }
+
+    #[test]
+    fn parse_xmp_joins_creators_and_reads_attribute_dates() {
+        let xmp = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description xmp:CreateDate="2023-11-02T08:00:00Z">
+            <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Attention Is All You Need</rdf:li></rdf:Alt></dc:title>
+            <dc:creator><rdf:Seq><rdf:li>Ashish Vaswani</rdf:li><rdf:li>Noam Shazeer</rdf:li></rdf:Seq></dc:creator>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let metadata = parse_xmp(xmp);
+
+        assert_eq!(metadata.title.as_deref(), Some("Attention Is All You Need"));
+        assert_eq!(
+            metadata.author.as_deref(),
+            Some("Ashish Vaswani, Noam Shazeer")
+        );
+        assert_eq!(metadata.created.as_deref(), Some("2023-11-02"));
+    }
+
+    #[test]
+    fn parse_core_xml_reads_dublin_core_fields() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/">
+<dc:title>Acme &amp; Co Service Agreement</dc:title><dc:creator>张三</dc:creator>
+<cp:lastModifiedBy>李四</cp:lastModifiedBy>
+<dcterms:created xsi:type="dcterms:W3CDTF">2024-03-18T02:11:00Z</dcterms:created></cp:coreProperties>"#;
+        let metadata = parse_core_xml(xml);
+
+        assert_eq!(
+            metadata.title.as_deref(),
+            Some("Acme & Co Service Agreement")
+        );
+        assert_eq!(metadata.author.as_deref(), Some("张三"));
+        assert_eq!(metadata.created.as_deref(), Some("2024-03-18"));
+        assert_eq!(metadata.source.as_deref(), Some("core.xml"));
+
+        // 最后修改人是编辑者而非作者，不作为作者的后备
+        let edited_only = parse_core_xml(
+            r#"<cp:coreProperties><cp:lastModifiedBy>李四</cp:lastModifiedBy></cp:coreProperties>"#,
+        );
+        assert_eq!(edited_only.author, None);
+    }
+
+    #[test]
+    fn placeholder_fields_are_dropped() {
+        assert_eq!(clean_field("Microsoft Word - draft.docx".into()), None);
+        assert_eq!(clean_field("  ".into()), None);
+        assert_eq!(parse_pdf_date("D:16010101000000Z"), None);
+    }
+}
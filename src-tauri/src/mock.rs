@@ -0,0 +1,292 @@
+use crate::config::{MockSettings, ModelProfile, ModelTask};
+use crate::paper::{FYLA_MARKDOWN_MARKER, FYLA_META_MARKER};
+use crate::paper_chat::FYLA_CHAT_JSON_MARKER;
+use crate::usage::UsageMeter;
+use anyhow::{Result, anyhow};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Dimensions of mock embeddings.
+const EMBEDDING_DIMS: usize = 64;
+
+/// Reply of a `mock` profile: the first matching rule, otherwise a built-in reply shaped
+/// like what `task` expects, derived from the prompt so the same input gives the same output.
+pub fn reply(profile: &ModelProfile, task: ModelTask, prompt: &str) -> String {
+    let settings = settings(profile);
+    let rule = settings.rules.iter().find(|rule| {
+        (rule.task.trim().is_empty() || rule.task.trim() == task.key())
+            && prompt.contains(rule.contains.as_str())
+    });
+    if let Some(rule) = rule {
+        return rule.reply.clone();
+    }
+
+    let tag = short_hash(prompt);
+    match task {
+        ModelTask::Rename | ModelTask::Vlm => format!("mock-{}", tag),
+        ModelTask::PaperReview => {
+            let meta = json!({
+                "title": format!("Mock Paper {}", tag),
+                "year": "2024",
+                "venue": "Mock",
+                "summary": "离线模拟解读，用于测试流程。",
+            });
+            format!(
+                "{}\n{}\n{}\n# Mock Paper {}\n\n## 摘要\n\n离线模拟解读，用于测试流程。\n\n\
+                 ## 方法\n\n模拟模型根据输入生成固定内容。\n",
+                FYLA_META_MARKER, meta, FYLA_MARKDOWN_MARKER, tag
+            )
+        }
+        ModelTask::PaperChat => format!(
+            "离线模拟回答（{}），论文第 1 页介绍了研究背景 @论文PDF/第1页。\n{}\n{}",
+            tag,
+            FYLA_CHAT_JSON_MARKER,
+            json!({ "suggestedQuestions": ["方法的核心思路是什么？", "实验用了哪些数据集？"] })
+        ),
+        ModelTask::Embedding | ModelTask::EmbeddingFallback => String::new(),
+    }
+}
+
+/// Non-streamed call: one chunk's delay, then the reply or the injected failure.
+pub async fn complete(profile: &ModelProfile, task: ModelTask, prompt: &str) -> Result<String> {
+    let settings = settings(profile);
+    tokio::time::sleep(Duration::from_millis(u64::from(settings.chunk_delay_ms))).await;
+    if settings.fail_after_chunks == Some(0) {
+        return Err(failure(&settings));
+    }
+    let reply = reply(profile, task, prompt);
+    let mut meter = UsageMeter::new(task, profile, prompt.chars().count());
+    meter.add_output(&reply);
+    Ok(reply)
+}
+
+/// Streams the reply in chunks with the configured delays and failure. Returns `None` when
+/// `cancel_rx` asks to stop.
+pub async fn stream<F>(
+    profile: &ModelProfile,
+    task: ModelTask,
+    prompt: &str,
+    mut cancel_rx: Option<&mut watch::Receiver<bool>>,
+    mut on_delta: F,
+) -> Result<Option<String>>
+where
+    F: FnMut(&str),
+{
+    let settings = settings(profile);
+    let reply = reply(profile, task, prompt);
+    let delay = Duration::from_millis(u64::from(settings.chunk_delay_ms));
+    let mut meter = UsageMeter::new(task, profile, prompt.chars().count());
+    let mut sent = 0;
+
+    for chunk in chunks(&reply, settings.chunk_chars as usize) {
+        if settings.fail_after_chunks == Some(sent) {
+            return Err(failure(&settings));
+        }
+        if wait_or_cancel(delay, cancel_rx.as_deref_mut()).await {
+            return Ok(None);
+        }
+        meter.add_output(chunk);
+        on_delta(chunk);
+        sent += 1;
+    }
+    // 正好在最后一块之后失败，模拟回复已完整但连接异常断开
+    if settings.fail_after_chunks == Some(sent) {
+        return Err(failure(&settings));
+    }
+    Ok(Some(reply))
+}
+
+/// Bag-of-words vectors: texts sharing words get similar vectors, so retrieval behaves
+/// plausibly without an embedding model.
+pub fn embed(texts: &[String]) -> Vec<Vec<f32>> {
+    texts
+        .iter()
+        .map(|text| {
+            let mut vector = vec![0.0f32; EMBEDDING_DIMS];
+            for word in text.split(|ch: char| !ch.is_alphanumeric()) {
+                if word.is_empty() {
+                    continue;
+                }
+                let digest = Sha256::digest(word.to_lowercase().as_bytes());
+                vector[digest[0] as usize % EMBEDDING_DIMS] += 1.0;
+            }
+            let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|value| *value /= norm);
+            }
+            vector
+        })
+        .collect()
+}
+
+fn settings(profile: &ModelProfile) -> MockSettings {
+    profile.mock.clone().unwrap_or_default()
+}
+
+fn failure(settings: &MockSettings) -> anyhow::Error {
+    match settings.fail_message.trim() {
+        "" => anyhow!("模拟模型按配置返回失败"),
+        message => anyhow!("{}", message),
+    }
+}
+
+fn short_hash(text: &str) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    digest[..4]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Splits `text` into pieces of `size` characters; 0 keeps it whole.
+fn chunks(text: &str, size: usize) -> Vec<&str> {
+    if size == 0 || text.is_empty() {
+        return vec![text];
+    }
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (count, (index, _)) in text.char_indices().enumerate() {
+        if count > 0 && count % size == 0 {
+            pieces.push(&text[start..index]);
+            start = index;
+        }
+    }
+    pieces.push(&text[start..]);
+    pieces
+}
+
+/// Sleeps for `delay`; returns true if cancellation was requested meanwhile.
+async fn wait_or_cancel(delay: Duration, cancel_rx: Option<&mut watch::Receiver<bool>>) -> bool {
+    let Some(cancel_rx) = cancel_rx else {
+        tokio::time::sleep(delay).await;
+        return false;
+    };
+    if *cancel_rx.borrow() {
+        return true;
+    }
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => false,
+        changed = cancel_rx.changed() => changed.is_ok() && *cancel_rx.borrow(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AppConfig, MockRule};
+    use crate::llm::{self, FileContext};
+    use crate::metadata::DocumentMetadata;
+    use crate::test_server::block_on;
+
+    fn profile(settings: MockSettings) -> ModelProfile {
+        ModelProfile {
+            provider: "mock".into(),
+            model: "mock".into(),
+            mock: Some(settings),
+            ..ModelProfile::default()
+        }
+    }
+
+    #[test]
+    fn rules_match_by_task_and_text_and_stream_in_chunks() {
+        let profile = profile(MockSettings {
+            rules: vec![
+                MockRule {
+                    contains: "invoice".into(),
+                    task: "paperChat".into(),
+                    reply: "wrong task".into(),
+                },
+                MockRule {
+                    contains: "invoice".into(),
+                    task: String::new(),
+                    reply: "发票-Acme-20240815".into(),
+                },
+            ],
+            chunk_chars: 4,
+            ..MockSettings::default()
+        });
+        assert_eq!(
+            reply(&profile, ModelTask::Rename, "an invoice from Acme"),
+            "发票-Acme-20240815"
+        );
+        assert_eq!(
+            reply(&profile, ModelTask::Rename, "notes"),
+            reply(&profile, ModelTask::Rename, "notes")
+        );
+
+        let mut deltas = Vec::new();
+        let full = block_on(stream(
+            &profile,
+            ModelTask::Rename,
+            "invoice",
+            None,
+            |delta| deltas.push(delta.to_string()),
+        ))
+        .unwrap();
+        assert_eq!(full.as_deref(), Some("发票-Acme-20240815"));
+        assert_eq!(deltas, ["发票-A", "cme-", "2024", "0815"]);
+    }
+
+    #[test]
+    fn stream_fails_after_configured_chunks() {
+        let profile = profile(MockSettings {
+            rules: vec![MockRule {
+                contains: String::new(),
+                task: String::new(),
+                reply: "abcdef".into(),
+            }],
+            chunk_chars: 2,
+            fail_after_chunks: Some(2),
+            fail_message: "connection reset".into(),
+            ..MockSettings::default()
+        });
+        let mut received = String::new();
+        let err = block_on(stream(&profile, ModelTask::PaperChat, "q", None, |delta| {
+            received.push_str(delta)
+        }))
+        .unwrap_err();
+        assert_eq!(received, "abcd");
+        assert_eq!(err.to_string(), "connection reset");
+    }
+
+    #[test]
+    fn rename_pipeline_cleans_the_mock_reply() {
+        let mut config = AppConfig {
+            rename_profile: "mock".into(),
+            ..AppConfig::default()
+        };
+        config.profiles.push(ModelProfile {
+            id: "mock".into(),
+            ..profile(MockSettings {
+                rules: vec![MockRule {
+                    contains: "Acme".into(),
+                    task: "rename".into(),
+                    reply: "\"发票: Acme/2024-08.pdf\"".into(),
+                }],
+                ..MockSettings::default()
+            })
+        });
+        let context = FileContext {
+            path: "/tmp/scan.pdf".into(),
+            original_name: "scan.pdf".into(),
+            parent_dir: "tmp".into(),
+            sibling_names: Vec::new(),
+            modified_at: String::new(),
+            file_size: String::new(),
+            metadata: DocumentMetadata::default(),
+            photo: None,
+        };
+
+        let name = block_on(llm::generate_filename(
+            "Invoice from Acme Corp",
+            &config,
+            Some(&context),
+        ))
+        .unwrap();
+        assert_eq!(name, "发票- Acme-2024-08");
+        let name = block_on(llm::generate_filename("meeting notes", &config, None)).unwrap();
+        assert!(name.starts_with("mock-"));
+    }
+}
@@ -1,30 +1,395 @@
+use crate::config::AppConfig;
+use crate::pdf::PdfPageText;
+use crate::subprocess::{self, ProcessLimits};
+use crate::{pdf_password, sidecar};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+#[cfg(target_os = "macos")]
 use std::ffi::{CStr, CString};
+#[cfg(target_os = "macos")]
 use std::os::raw::c_char;
 
+#[cfg(target_os = "macos")]
 unsafe extern "C" {
-    fn recognize_text_from_path(path: *const c_char) -> *mut c_char;
-    fn recognize_text_from_pdf(path: *const c_char) -> *mut c_char;
-    fn extract_text_from_pdf(path: *const c_char) -> *mut c_char;
+    fn recognize_text_from_path(path: *const c_char, languages: *const c_char) -> *mut c_char;
+    fn pdf_page_count(path: *const c_char, password: *const c_char) -> i32;
+    fn recognize_text_from_pdf_page(
+        path: *const c_char,
+        page_number: i32,
+        password: *const c_char,
+        languages: *const c_char,
+    ) -> *mut c_char;
+    fn extract_text_from_pdf(path: *const c_char, password: *const c_char) -> *mut c_char;
 }
 
-/// Recognizes text from an image file using macOS Vision framework OCR.
-pub fn ocr_from_file(path: &str) -> Result<String, String> {
-    let c_path = CString::new(path).map_err(|e| format!("路径编码错误: {}", e))?;
-    unsafe { read_and_free(recognize_text_from_path(c_path.as_ptr())) }
+const TESSERACT_NAME: &str = "tesseract";
+const PDFTOPPM_NAME: &str = "pdftoppm";
+const PDFINFO_NAME: &str = "pdfinfo";
+const OCR_RENDER_DPI: &str = "300";
+const MAX_OCR_PARALLELISM: usize = 8;
+/// Pages recognized when no page range is configured; `all` lifts the limit.
+pub const DEFAULT_OCR_PAGE_LIMIT: u32 = 30;
+const TESSERACT_LIMITS: ProcessLimits = ProcessLimits::new(120, 8 * 1024 * 1024);
+const PDFTOPPM_LIMITS: ProcessLimits = ProcessLimits::new(60, 1024 * 1024);
+const PDFINFO_LIMITS: ProcessLimits = ProcessLimits::new(15, 1024 * 1024);
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A text recognition backend for images and scanned PDFs.
+pub trait OcrEngine: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Recognizes text in an image file.
+    fn recognize_image(&self, path: &str) -> Result<String, String>;
+
+    /// Returns the number of pages in a PDF.
+    fn pdf_page_count(&self, path: &str) -> Result<u32, String>;
+
+    /// Recognizes text on one PDF page (1-based).
+    fn recognize_pdf_page(&self, path: &str, page: u32) -> Result<String, String>;
 }
 
-/// Recognizes text from the first page of a PDF (fallback for scanned PDFs).
-pub fn ocr_from_pdf(path: &str) -> Result<String, String> {
-    let c_path = CString::new(path).map_err(|e| format!("路径编码错误: {}", e))?;
-    unsafe { read_and_free(recognize_text_from_pdf(c_path.as_ptr())) }
+/// OCR settings taken from the app config.
+#[derive(Debug, Clone)]
+pub struct OcrSettings {
+    /// `auto`, `vision` or `tesseract`.
+    pub engine: String,
+    /// Tesseract language codes, e.g. `chi_sim`, `eng`.
+    pub languages: Vec<String>,
+    pub tessdata_dir: Option<PathBuf>,
+    /// Pages to OCR in scanned PDFs, e.g. `1-20,25`; empty means the first
+    /// [`DEFAULT_OCR_PAGE_LIMIT`] pages and `all` means every page.
+    pub page_range: String,
+    /// Number of pages recognized concurrently.
+    pub parallelism: usize,
+}
+
+impl OcrSettings {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let languages = config
+            .ocr_languages
+            .split(['+', ',', ' '])
+            .map(str::trim)
+            .filter(|code| !code.is_empty())
+            .map(str::to_string)
+            .collect();
+        let tessdata_dir = Some(config.ocr_tessdata_dir.trim())
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+        Self {
+            engine: config.ocr_engine.trim().to_lowercase(),
+            languages,
+            tessdata_dir,
+            page_range: config.ocr_page_range.trim().to_string(),
+            parallelism: (config.ocr_parallelism as usize).clamp(1, MAX_OCR_PARALLELISM),
+        }
+    }
+
+    /// The settings that change OCR output, for keying cached extraction results.
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "{}|{}|{}|{}dpi",
+            self.engine,
+            self.languages.join("+"),
+            self.page_range,
+            OCR_RENDER_DPI
+        )
+    }
+}
+
+/// Picks the OCR engine for the current settings; `auto` prefers Vision on macOS and
+/// tesseract everywhere else.
+pub fn select_engine(settings: &OcrSettings) -> Result<Box<dyn OcrEngine>, String> {
+    match settings.engine.as_str() {
+        "vision" => vision_engine(settings),
+        "tesseract" => TesseractEngine::resolve(settings).map(|engine| Box::new(engine) as _),
+        _ => {
+            if cfg!(target_os = "macos") {
+                vision_engine(settings)
+            } else {
+                TesseractEngine::resolve(settings).map(|engine| Box::new(engine) as _)
+            }
+        }
+    }
+}
+
+/// Recognizes text from an image file with the configured OCR engine.
+pub fn ocr_from_file(path: &str, settings: &OcrSettings) -> Result<String, String> {
+    let engine = select_engine(settings)?;
+    log::debug!(target: "ocr", "{} recognizing image {}", engine.name(), path);
+    engine.recognize_image(path)
+}
+
+/// Recognizes text from the first page of a PDF (enough for naming a scanned PDF).
+pub fn ocr_from_pdf(path: &str, settings: &OcrSettings) -> Result<String, String> {
+    let engine = select_engine(settings)?;
+    log::debug!(target: "ocr", "{} recognizing scanned PDF {}", engine.name(), path);
+    engine.recognize_pdf_page(path, 1)
+}
+
+/// Recognizes every page of a scanned PDF within the configured page range, running
+/// up to `parallelism` pages at once. Pages without recognized text are omitted.
+pub fn ocr_pdf_pages(path: &str, settings: &OcrSettings) -> Result<Vec<PdfPageText>, String> {
+    let engine = select_engine(settings)?;
+    let page_count = engine.pdf_page_count(path)?;
+    let pages = parse_page_range(&settings.page_range, page_count);
+    if pages.is_empty() {
+        return Err(format!(
+            "OCR 页码范围 \"{}\" 不包含任何页面（共 {} 页）",
+            settings.page_range, page_count
+        ));
+    }
+    if settings.page_range.is_empty() && page_count > DEFAULT_OCR_PAGE_LIMIT {
+        log::warn!(
+            target: "ocr",
+            "{} has {} pages; recognizing only the first {} (set the OCR page range to `all` for every page)",
+            path,
+            page_count,
+            DEFAULT_OCR_PAGE_LIMIT
+        );
+    }
+
+    let workers = settings.parallelism.min(pages.len());
+    log::debug!(
+        target: "ocr",
+        "{} recognizing {} of {} pages in {} with {} workers",
+        engine.name(),
+        pages.len(),
+        page_count,
+        path,
+        workers
+    );
+
+    let results = recognize_pages(engine.as_ref(), path, &pages, workers);
+    if subprocess::is_cancelled() {
+        return Err("OCR 已取消".into());
+    }
+    let mut first_error = None;
+    let mut recognized = Vec::new();
+    for (page, result) in pages.iter().copied().zip(results) {
+        match result {
+            Ok(text) if !text.trim().is_empty() => recognized.push(PdfPageText { page, text }),
+            Ok(_) => {}
+            Err(err) => {
+                log::warn!(target: "ocr", "page {} of {} failed: {}", page, path, err);
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+
+    match first_error {
+        Some(err) if recognized.is_empty() => Err(err),
+        _ => Ok(recognized),
+    }
+}
+
+/// Runs page OCR on a small worker pool; results keep the order of `pages`.
+fn recognize_pages(
+    engine: &dyn OcrEngine,
+    path: &str,
+    pages: &[u32],
+    workers: usize,
+) -> Vec<Result<String, String>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![Ok(String::new()); pages.len()]);
+    // 工作线程继承调用方的取消信号和 PDF 密码，取消后不再领取新页面
+    let cancel = subprocess::current_cancel();
+    let password = pdf_password::current_password();
+    std::thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            let cancel = cancel.clone();
+            let password = password.clone();
+            scope.spawn(|| {
+                let work = || {
+                    while !subprocess::is_cancelled() {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(&page) = pages.get(index) else {
+                            break;
+                        };
+                        let result = engine.recognize_pdf_page(path, page);
+                        if let Ok(mut slots) = results.lock() {
+                            slots[index] = result;
+                        }
+                    }
+                };
+                pdf_password::with_password(password, || match cancel {
+                    Some(cancel) => subprocess::with_cancel(cancel, work),
+                    None => work(),
+                })
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Expands a page range such as `1-3,7,10-` into sorted, de-duplicated page numbers
+/// within `1..=page_count`. An empty spec selects the first [`DEFAULT_OCR_PAGE_LIMIT`]
+/// pages, `all` selects every page; malformed parts are skipped.
+pub fn parse_page_range(spec: &str, page_count: u32) -> Vec<u32> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return (1..=page_count.min(DEFAULT_OCR_PAGE_LIMIT)).collect();
+    }
+    if spec.eq_ignore_ascii_case("all") {
+        return (1..=page_count).collect();
+    }
+
+    let mut pages = Vec::new();
+    for part in spec
+        .split([',', ';'])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => {
+                let start = match start.trim() {
+                    "" => Some(1),
+                    value => value.parse::<u32>().ok(),
+                };
+                let end = match end.trim() {
+                    "" => Some(page_count),
+                    value => value.parse::<u32>().ok(),
+                };
+                match (start, end) {
+                    (Some(start), Some(end)) => (start, end),
+                    _ => continue,
+                }
+            }
+            None => match part.parse::<u32>() {
+                Ok(page) => (page, page),
+                Err(_) => continue,
+            },
+        };
+        pages.extend(start.max(1)..=end.min(page_count));
+    }
+    pages.sort_unstable();
+    pages.dedup();
+    pages
+}
+
+// --- Vision (macOS) ---
+
+#[cfg(target_os = "macos")]
+struct VisionEngine {
+    languages: CString,
+}
+
+#[cfg(target_os = "macos")]
+fn vision_engine(settings: &OcrSettings) -> Result<Box<dyn OcrEngine>, String> {
+    let languages = settings
+        .languages
+        .iter()
+        .map(|code| vision_language(code))
+        .collect::<Vec<_>>()
+        .join(",");
+    let languages = CString::new(languages).map_err(|e| format!("OCR 语言编码错误: {}", e))?;
+    Ok(Box::new(VisionEngine { languages }))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn vision_engine(_settings: &OcrSettings) -> Result<Box<dyn OcrEngine>, String> {
+    Err("Vision OCR 仅在 macOS 上可用".into())
+}
+
+#[cfg(target_os = "macos")]
+impl OcrEngine for VisionEngine {
+    fn name(&self) -> &'static str {
+        "vision"
+    }
+
+    fn recognize_image(&self, path: &str) -> Result<String, String> {
+        let c_path = CString::new(path).map_err(|e| format!("路径编码错误: {}", e))?;
+        unsafe {
+            read_and_free(recognize_text_from_path(
+                c_path.as_ptr(),
+                self.languages.as_ptr(),
+            ))
+        }
+    }
+
+    fn pdf_page_count(&self, path: &str) -> Result<u32, String> {
+        let c_path = CString::new(path).map_err(|e| format!("路径编码错误: {}", e))?;
+        let c_password = current_c_password()?;
+        let count = unsafe { pdf_page_count(c_path.as_ptr(), password_ptr(&c_password)) };
+        u32::try_from(count).map_err(|_| format!("无法打开 PDF: {}", path))
+    }
+
+    fn recognize_pdf_page(&self, path: &str, page: u32) -> Result<String, String> {
+        let c_path = CString::new(path).map_err(|e| format!("路径编码错误: {}", e))?;
+        let page = i32::try_from(page).map_err(|_| format!("页码超出范围: {}", page))?;
+        let c_password = current_c_password()?;
+        unsafe {
+            read_and_free(recognize_text_from_pdf_page(
+                c_path.as_ptr(),
+                page,
+                password_ptr(&c_password),
+                self.languages.as_ptr(),
+            ))
+        }
+    }
+}
+
+/// Maps tesseract language codes to Vision identifiers; unknown codes pass through so
+/// Vision identifiers can be configured directly.
+#[cfg(target_os = "macos")]
+fn vision_language(code: &str) -> String {
+    match code {
+        "chi_sim" => "zh-Hans",
+        "chi_tra" => "zh-Hant",
+        "eng" => "en-US",
+        "jpn" => "ja-JP",
+        "kor" => "ko-KR",
+        "fra" => "fr-FR",
+        "deu" => "de-DE",
+        "spa" => "es-ES",
+        "ita" => "it-IT",
+        "por" => "pt-BR",
+        "rus" => "ru-RU",
+        other => other,
+    }
+    .to_string()
 }
 
 /// Extracts the embedded text layer from a PDF using macOS PDFKit.
+#[cfg(target_os = "macos")]
 pub fn pdf_text_from_pdf(path: &str) -> Result<String, String> {
     let c_path = CString::new(path).map_err(|e| format!("路径编码错误: {}", e))?;
-    unsafe { read_and_free(extract_text_from_pdf(c_path.as_ptr())) }
+    let c_password = current_c_password()?;
+    unsafe {
+        read_and_free(extract_text_from_pdf(
+            c_path.as_ptr(),
+            password_ptr(&c_password),
+        ))
+    }
 }
 
+#[cfg(not(target_os = "macos"))]
+pub fn pdf_text_from_pdf(_path: &str) -> Result<String, String> {
+    Err("PDFKit 仅在 macOS 上可用".into())
+}
+
+/// Password of the PDF being read, as a C string for the native bridge.
+#[cfg(target_os = "macos")]
+fn current_c_password() -> Result<Option<CString>, String> {
+    pdf_password::current_password()
+        .map(CString::new)
+        .transpose()
+        .map_err(|e| format!("PDF 密码编码错误: {}", e))
+}
+
+#[cfg(target_os = "macos")]
+fn password_ptr(password: &Option<CString>) -> *const c_char {
+    password
+        .as_ref()
+        .map_or(std::ptr::null(), |password| password.as_ptr())
+}
+
+#[cfg(target_os = "macos")]
 unsafe fn read_and_free(ptr: *mut c_char) -> Result<String, String> {
     if ptr.is_null() {
         return Err("OCR 返回空指针".into());
@@ -35,3 +400,185 @@ unsafe fn read_and_free(ptr: *mut c_char) -> Result<String, String> {
     unsafe { libc::free(ptr as *mut _) };
     Ok(result)
 }
+
+// --- Tesseract sidecar ---
+
+struct TesseractEngine {
+    binary: PathBuf,
+    languages: String,
+    tessdata_dir: Option<PathBuf>,
+}
+
+impl TesseractEngine {
+    fn resolve(settings: &OcrSettings) -> Result<Self, String> {
+        let triple_name = format!("{}-{}", TESSERACT_NAME, sidecar::TARGET_TRIPLE);
+        let binary = sidecar::resolve_bundled_or_system(
+            "FYLA_TESSERACT_PATH",
+            &[TESSERACT_NAME, &triple_name],
+            TESSERACT_NAME,
+        )
+        .ok_or_else(|| "未找到 tesseract（可通过 FYLA_TESSERACT_PATH 指定）".to_string())?;
+
+        let languages = if settings.languages.is_empty() {
+            "eng".to_string()
+        } else {
+            settings.languages.join("+")
+        };
+        Ok(Self {
+            binary,
+            languages,
+            tessdata_dir: settings.tessdata_dir.clone(),
+        })
+    }
+
+    fn run(&self, image_path: &Path) -> Result<String, String> {
+        let mut command = Command::new(&self.binary);
+        command
+            .arg(image_path)
+            .arg("stdout")
+            .args(["-l", &self.languages]);
+        if let Some(dir) = &self.tessdata_dir {
+            command.arg("--tessdata-dir").arg(dir);
+        }
+
+        let output = subprocess::run(&mut command, TESSERACT_LIMITS)
+            .map_err(|e| format!("调用 tesseract 失败 ({}): {:#}", self.binary.display(), e))?;
+        if !output.succeeded_or_truncated() {
+            return Err(format!("tesseract 识别失败: {}", output.stderr_text()));
+        }
+        if output.stdout_truncated {
+            log::warn!(target: "ocr", "tesseract output for {} was truncated", image_path.display());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+impl OcrEngine for TesseractEngine {
+    fn name(&self) -> &'static str {
+        "tesseract"
+    }
+
+    fn recognize_image(&self, path: &str) -> Result<String, String> {
+        self.run(Path::new(path))
+    }
+
+    fn pdf_page_count(&self, path: &str) -> Result<u32, String> {
+        let binary = sidecar::resolve_poppler_tool(PDFINFO_NAME, "FYLA_PDFINFO_PATH")
+            .ok_or_else(|| "扫描版 PDF 需要 pdfinfo 才能统计页数".to_string())?;
+        let input = pdf_password::poppler_input(path).map_err(|e| format!("{:#}", e))?;
+        let output = subprocess::run(Command::new(&binary).arg(&input), PDFINFO_LIMITS)
+            .map_err(|e| format!("调用 pdfinfo 失败 ({}): {:#}", binary.display(), e))?;
+        if !output.status.success() {
+            return Err(format!("pdfinfo 读取失败: {}", output.stderr_text()));
+        }
+        parse_pdfinfo_pages(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| "pdfinfo 未返回页数".to_string())
+    }
+
+    fn recognize_pdf_page(&self, path: &str, page: u32) -> Result<String, String> {
+        let work_dir = ocr_temp_dir()?;
+        let result = render_pdf_page(path, page, &work_dir).and_then(|image| self.run(&image));
+        let _ = std::fs::remove_dir_all(&work_dir);
+        result
+    }
+}
+
+fn parse_pdfinfo_pages(output: &str) -> Option<u32> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("Pages:"))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+fn ocr_temp_dir() -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join(format!(
+        "fyla-ocr-{}-{}",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).map_err(|e| format!("创建 OCR 临时目录失败: {}", e))?;
+    Ok(dir)
+}
+
+/// Renders one PDF page to PNG with poppler's `pdftoppm` so tesseract can read it.
+fn render_pdf_page(path: &str, page: u32, work_dir: &Path) -> Result<PathBuf, String> {
+    let binary = sidecar::resolve_poppler_tool(PDFTOPPM_NAME, "FYLA_PDFTOPPM_PATH")
+        .ok_or_else(|| "扫描版 PDF 需要 pdftoppm 才能用 tesseract 识别".to_string())?;
+
+    let input = pdf_password::poppler_input(path).map_err(|e| format!("{:#}", e))?;
+    let page_arg = page.to_string();
+    let prefix = work_dir.join("page");
+    let mut command = Command::new(&binary);
+    command
+        .args([
+            "-f",
+            &page_arg,
+            "-l",
+            &page_arg,
+            "-r",
+            OCR_RENDER_DPI,
+            "-gray",
+            "-png",
+        ])
+        .arg(&input)
+        .arg(&prefix);
+    let output = subprocess::run(&mut command, PDFTOPPM_LIMITS)
+        .map_err(|e| format!("调用 pdftoppm 失败 ({}): {:#}", binary.display(), e))?;
+    if !output.status.success() {
+        return Err(format!("pdftoppm 渲染失败: {}", output.stderr_text()));
+    }
+
+    // pdftoppm 会按总页数补零（page-1.png / page-01.png），直接取生成的唯一文件
+    std::fs::read_dir(work_dir)
+        .map_err(|e| format!("读取 OCR 临时目录失败: {}", e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|candidate| candidate.extension().is_some_and(|ext| ext == "png"))
+        .ok_or_else(|| "pdftoppm 未生成页面图片".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ocr_settings_split_language_list() {
+        let config = AppConfig {
+            ocr_engine: " Tesseract ".into(),
+            ocr_languages: "chi_sim+eng, jpn".into(),
+            ocr_tessdata_dir: "  ".into(),
+            ..AppConfig::default()
+        };
+        let settings = OcrSettings::from_config(&config);
+
+        assert_eq!(settings.engine, "tesseract");
+        assert_eq!(settings.languages, vec!["chi_sim", "eng", "jpn"]);
+        assert!(settings.tessdata_dir.is_none());
+        assert_eq!(settings.parallelism, 2);
+    }
+
+    #[test]
+    fn parse_page_range_expands_and_clamps_parts() {
+        assert_eq!(parse_page_range("", 3), vec![1, 2, 3]);
+        assert_eq!(parse_page_range("all", 2), vec![1, 2]);
+        assert_eq!(parse_page_range("2-4, 1, 3", 10), vec![1, 2, 3, 4]);
+        assert_eq!(parse_page_range("8-, -2", 9), vec![1, 2, 8, 9]);
+        assert_eq!(parse_page_range("0-2, x, 12", 5), vec![1, 2]);
+        assert!(parse_page_range("7-9", 5).is_empty());
+    }
+
+    #[test]
+    fn parse_page_range_limits_an_empty_spec_but_not_all() {
+        let limited = parse_page_range("", 500);
+        assert_eq!(limited.len(), DEFAULT_OCR_PAGE_LIMIT as usize);
+        assert_eq!(limited.last(), Some(&DEFAULT_OCR_PAGE_LIMIT));
+        assert_eq!(parse_page_range("ALL", 500).len(), 500);
+        assert_eq!(parse_page_range("400-", 500).len(), 101);
+    }
+
+    #[test]
+    fn parse_pdfinfo_pages_reads_page_line() {
+        let output = "Title:          Thesis\nPages:          42\nEncrypted:      no\n";
+        assert_eq!(parse_pdfinfo_pages(output), Some(42));
+        assert_eq!(parse_pdfinfo_pages("Title: x"), None);
+    }
+}
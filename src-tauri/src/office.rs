@@ -0,0 +1,465 @@
+use anyhow::{Result, anyhow};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use std::collections::HashMap;
+use std::io::Read;
+
+const MAX_SHEETS: usize = 40;
+const MAX_SAMPLE_ROWS: usize = 20;
+const MAX_CELL_CHARS: usize = 40;
+const MAX_LINE_CHARS: usize = 240;
+
+/// One part of a structural summary: `essential` lines are always kept (header row, slide
+/// title), `extras` are filled in round-robin with whatever budget is left.
+#[derive(Debug, Default)]
+struct SummarySection {
+    essential: Vec<String>,
+    extras: Vec<String>,
+}
+
+/// Summarizes every sheet of a workbook: name, dimensions, header row and sample rows.
+pub fn summarize_workbook(path: &str, budget: usize) -> Result<String> {
+    use calamine::{Reader as _, open_workbook_auto};
+    let mut workbook = open_workbook_auto(path).map_err(|e| anyhow!("无法读取 xlsx: {}", e))?;
+
+    let sheet_names = workbook.sheet_names().to_vec();
+    if sheet_names.is_empty() {
+        return Err(anyhow!("xlsx 没有 sheet"));
+    }
+
+    let mut sections = Vec::new();
+    for name in sheet_names.iter().take(MAX_SHEETS) {
+        let range = match workbook.worksheet_range(name) {
+            Ok(range) => range,
+            Err(err) => {
+                log::warn!(target: "office", "skip sheet {}: {}", name, err);
+                continue;
+            }
+        };
+        sections.push(sheet_section(name, &range));
+    }
+    if sheet_names.len() > MAX_SHEETS {
+        sections.push(SummarySection {
+            essential: vec![format!(
+                "（另有 {} 个工作表未列出）",
+                sheet_names.len() - MAX_SHEETS
+            )],
+            extras: Vec::new(),
+        });
+    }
+
+    let header = format!("[Workbook] 共 {} 个工作表", sheet_names.len());
+    Ok(format!(
+        "{}\n{}",
+        header,
+        allocate_budget(&sections, budget)
+    ))
+}
+
+fn sheet_section(name: &str, range: &calamine::Range<calamine::Data>) -> SummarySection {
+    let (height, width) = range.get_size();
+    let dimension = match (range.start(), range.end()) {
+        (Some(start), Some(end)) => format!(
+            "{}{}:{}{}",
+            column_label(start.1),
+            start.0 + 1,
+            column_label(end.1),
+            end.0 + 1
+        ),
+        _ => "空".to_string(),
+    };
+
+    let mut rows = range
+        .rows()
+        .map(|row| {
+            row.iter()
+                .map(|cell| match cell {
+                    calamine::Data::Empty => String::new(),
+                    other => truncate_chars(other.to_string().trim(), MAX_CELL_CHARS),
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|cells| cells.iter().any(|cell| !cell.is_empty()));
+
+    let mut essential = vec![format!(
+        "[Sheet] {}（{} 行 × {} 列，{}）",
+        name, height, width, dimension
+    )];
+    if let Some(header) = rows.next() {
+        essential.push(format!("表头: {}", join_cells(&header)));
+    }
+    let extras = rows
+        .take(MAX_SAMPLE_ROWS)
+        .map(|cells| join_cells(&cells))
+        .collect();
+
+    SummarySection { essential, extras }
+}
+
+fn join_cells(cells: &[String]) -> String {
+    let joined = cells
+        .iter()
+        .filter(|cell| !cell.is_empty())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\t");
+    truncate_chars(&joined, MAX_LINE_CHARS)
+}
+
+/// Converts a zero-based column index into its spreadsheet letter (0 → A, 27 → AB).
+fn column_label(index: u32) -> String {
+    let mut label = Vec::new();
+    let mut n = index as i64;
+    loop {
+        label.push((b'A' + (n % 26) as u8) as char);
+        n = n / 26 - 1;
+        if n < 0 {
+            break;
+        }
+    }
+    label.iter().rev().collect()
+}
+
+/// Summarizes a deck slide by slide: title, body text and speaker notes.
+pub fn summarize_presentation(path: &str, budget: usize) -> Result<String> {
+    let file = std::fs::File::open(path)?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| anyhow!("无法读取 pptx: {}", e))?;
+
+    let mut slide_numbers = archive
+        .file_names()
+        .filter_map(|name| {
+            name.strip_prefix("ppt/slides/slide")
+                .and_then(|s| s.strip_suffix(".xml"))
+                .and_then(|s| s.parse::<usize>().ok())
+        })
+        .collect::<Vec<_>>();
+    slide_numbers.sort_unstable();
+
+    let mut sections = Vec::new();
+    for num in &slide_numbers {
+        let slide_xml = read_zip_entry(&mut archive, &format!("ppt/slides/slide{}.xml", num))?;
+        let shapes = parse_shapes(&slide_xml);
+
+        let notes = read_zip_entry(
+            &mut archive,
+            &format!("ppt/slides/_rels/slide{}.xml.rels", num),
+        )
+        .ok()
+        .and_then(|rels| notes_target(&rels))
+        .and_then(|target| read_zip_entry(&mut archive, &target).ok())
+        .map(|xml| notes_text(&parse_shapes(&xml)))
+        .unwrap_or_default();
+
+        sections.push(slide_section(*num, &shapes, &notes));
+    }
+
+    if sections.is_empty() {
+        return Ok(String::new());
+    }
+    let header = format!("[Presentation] 共 {} 页", slide_numbers.len());
+    Ok(format!(
+        "{}\n{}",
+        header,
+        allocate_budget(&sections, budget)
+    ))
+}
+
+fn read_zip_entry(archive: &mut zip::ZipArchive<std::fs::File>, name: &str) -> Result<String> {
+    let mut xml = String::new();
+    archive
+        .by_name(name)
+        .map_err(|e| anyhow!("pptx 读取失败: {}", e))?
+        .read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+fn slide_section(num: usize, shapes: &[Shape], notes: &str) -> SummarySection {
+    let title = shapes
+        .iter()
+        .find(|shape| shape.is_title())
+        .map(|shape| shape.text(" "))
+        .filter(|title| !title.is_empty());
+    let body = shapes
+        .iter()
+        .filter(|shape| !shape.is_title() && !shape.is_decoration())
+        .map(|shape| shape.text(" / "))
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join(" / ");
+
+    let essential = vec![match title {
+        Some(title) => format!("[Slide {}] {}", num, truncate_chars(&title, MAX_LINE_CHARS)),
+        None => format!("[Slide {}]", num),
+    }];
+    let mut extras = Vec::new();
+    if !body.is_empty() {
+        extras.push(format!("  内容: {}", truncate_chars(&body, MAX_LINE_CHARS)));
+    }
+    if !notes.is_empty() {
+        extras.push(format!("  备注: {}", truncate_chars(notes, MAX_LINE_CHARS)));
+    }
+    SummarySection { essential, extras }
+}
+
+/// A text-bearing shape with its placeholder type (`title`, `ctrTitle`, `body`, ...).
+#[derive(Debug, Default)]
+struct Shape {
+    placeholder: Option<String>,
+    paragraphs: Vec<String>,
+}
+
+impl Shape {
+    fn is_title(&self) -> bool {
+        matches!(self.placeholder.as_deref(), Some("title" | "ctrTitle"))
+    }
+
+    fn is_decoration(&self) -> bool {
+        matches!(
+            self.placeholder.as_deref(),
+            Some("sldNum" | "dt" | "ftr" | "hdr" | "sldImg")
+        )
+    }
+
+    fn text(&self, separator: &str) -> String {
+        self.paragraphs.join(separator)
+    }
+}
+
+/// Collects text per `<p:sp>` shape, keeping paragraph boundaries and placeholder type.
+fn parse_shapes(xml: &str) -> Vec<Shape> {
+    let mut reader = Reader::from_str(xml);
+    let mut shapes = Vec::new();
+    let mut current: Option<Shape> = None;
+    let mut paragraph = String::new();
+    let mut in_text = false;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(tag)) => match tag.name().as_ref() {
+                b"p:sp" => current = Some(Shape::default()),
+                b"a:t" => in_text = true,
+                b"p:ph" => set_placeholder(&mut current, &tag),
+                _ => {}
+            },
+            Ok(Event::Empty(tag)) => match tag.name().as_ref() {
+                b"p:ph" => set_placeholder(&mut current, &tag),
+                b"a:br" => paragraph.push(' '),
+                _ => {}
+            },
+            Ok(Event::Text(text)) if in_text => {
+                if let Ok(value) = text.unescape() {
+                    paragraph.push_str(&value);
+                }
+            }
+            Ok(Event::End(tag)) => match tag.name().as_ref() {
+                b"a:t" => in_text = false,
+                b"a:p" => {
+                    let line = paragraph.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if let Some(shape) = current.as_mut()
+                        && !line.is_empty()
+                    {
+                        shape.paragraphs.push(line);
+                    }
+                    paragraph.clear();
+                }
+                b"p:sp" => {
+                    if let Some(shape) = current.take()
+                        && !shape.paragraphs.is_empty()
+                    {
+                        shapes.push(shape);
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    shapes
+}
+
+fn set_placeholder(current: &mut Option<Shape>, tag: &BytesStart) {
+    let Some(shape) = current.as_mut() else {
+        return;
+    };
+    let placeholder = tag
+        .attributes()
+        .flatten()
+        .find(|attr| attr.key.as_ref() == b"type")
+        .and_then(|attr| attr.unescape_value().ok().map(|value| value.to_string()))
+        // 没有 type 属性的占位符默认是正文
+        .unwrap_or_else(|| "body".to_string());
+    shape.placeholder = Some(placeholder);
+}
+
+/// Resolves the notes slide path from a slide's relationship file.
+fn notes_target(rels: &str) -> Option<String> {
+    let mut reader = Reader::from_str(rels);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Empty(tag)) | Ok(Event::Start(tag))
+                if tag.name().as_ref() == b"Relationship" =>
+            {
+                let attrs = tag
+                    .attributes()
+                    .flatten()
+                    .filter_map(|attr| {
+                        let value = attr.unescape_value().ok()?.to_string();
+                        Some((attr.key.as_ref().to_vec(), value))
+                    })
+                    .collect::<HashMap<_, _>>();
+                let is_notes = attrs
+                    .get(b"Type".as_slice())
+                    .map(|kind| kind.ends_with("/notesSlide"))
+                    .unwrap_or(false);
+                if is_notes && let Some(target) = attrs.get(b"Target".as_slice()) {
+                    return Some(resolve_slide_relative(target));
+                }
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => {}
+        }
+    }
+}
+
+fn resolve_slide_relative(target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut parts = vec!["ppt", "slides"];
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                parts.pop();
+            }
+            "." | "" => {}
+            other => parts.push(other),
+        }
+    }
+    parts.join("/")
+}
+
+fn notes_text(shapes: &[Shape]) -> String {
+    shapes
+        .iter()
+        .filter(|shape| shape.placeholder.as_deref() == Some("body"))
+        .map(|shape| shape.text(" "))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Spends the character budget on every section's essentials first, then hands out extra
+/// lines round-robin so later sheets/slides are not starved by the first one.
+fn allocate_budget(sections: &[SummarySection], budget: usize) -> String {
+    let mut chosen: Vec<Vec<&str>> = vec![Vec::new(); sections.len()];
+    let mut used = 0usize;
+    let mut omitted = 0usize;
+
+    for (idx, section) in sections.iter().enumerate() {
+        for line in &section.essential {
+            let cost = line.chars().count() + 1;
+            if used + cost > budget {
+                omitted += 1;
+                continue;
+            }
+            used += cost;
+            chosen[idx].push(line);
+        }
+    }
+
+    let mut cursors = vec![0usize; sections.len()];
+    loop {
+        let mut progressed = false;
+        for (idx, section) in sections.iter().enumerate() {
+            if chosen[idx].is_empty() {
+                continue;
+            }
+            let Some(line) = section.extras.get(cursors[idx]) else {
+                continue;
+            };
+            let cost = line.chars().count() + 1;
+            if used + cost > budget {
+                continue;
+            }
+            used += cost;
+            cursors[idx] += 1;
+            chosen[idx].push(line);
+            progressed = true;
+        }
+        if !progressed {
+            break;
+        }
+    }
+
+    let mut lines = chosen.into_iter().flatten().collect::<Vec<_>>().join("\n");
+    if omitted > 0 {
+        lines.push_str(&format!("\n[...另有 {} 个部分因长度省略...]", omitted));
+    }
+    lines
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated = text.chars().take(max_chars).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_shapes_separates_titles_body_and_slide_numbers() {
+        let xml = r#"<p:sld><p:cSld><p:spTree>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr>
+              <p:txBody><a:p><a:r><a:t>Q3 Roadmap</a:t></a:r></a:p></p:txBody></p:sp>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph idx="1"/></p:nvPr></p:nvSpPr>
+              <p:txBody><a:p><a:r><a:t>Ship </a:t></a:r><a:r><a:t>sync</a:t></a:r></a:p><a:p><a:r><a:t>Hire two</a:t></a:r></a:p></p:txBody></p:sp>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="sldNum"/></p:nvPr></p:nvSpPr>
+              <p:txBody><a:p><a:r><a:t>3</a:t></a:r></a:p></p:txBody></p:sp>
+        </p:spTree></p:cSld></p:sld>"#;
+        let section = slide_section(3, &parse_shapes(xml), "Mention the budget");
+
+        assert_eq!(section.essential, vec!["[Slide 3] Q3 Roadmap"]);
+        assert_eq!(
+            section.extras,
+            vec!["  内容: Ship sync / Hire two", "  备注: Mention the budget"]
+        );
+    }
+
+    #[test]
+    fn notes_target_resolves_relative_path() {
+        let rels = r#"<Relationships><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slideLayout" Target="../slideLayouts/slideLayout2.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/notesSlide" Target="../notesSlides/notesSlide7.xml"/></Relationships>"#;
+        assert_eq!(
+            notes_target(rels).as_deref(),
+            Some("ppt/notesSlides/notesSlide7.xml")
+        );
+    }
+
+    #[test]
+    fn allocate_budget_keeps_every_essential_before_extras() {
+        let sections = vec![
+            SummarySection {
+                essential: vec!["[Sheet] A".into()],
+                extras: vec!["a1".into(), "a2".into(), "a3".into()],
+            },
+            SummarySection {
+                essential: vec!["[Sheet] B".into()],
+                extras: vec!["b1".into(), "b2".into()],
+            },
+        ];
+        let summary = allocate_budget(&sections, 30);
+
+        assert_eq!(summary, "[Sheet] A\na1\na2\n[Sheet] B\nb1");
+    }
+
+    #[test]
+    fn column_label_handles_multi_letter_columns() {
+        assert_eq!(column_label(0), "A");
+        assert_eq!(column_label(25), "Z");
+        assert_eq!(column_label(27), "AB");
+    }
+}
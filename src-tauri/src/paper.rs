@@ -1,5 +1,11 @@
-use crate::config::AppConfig;
-use crate::{llm, pdf, streaming};
+use crate::config::{AppConfig, ModelProfile, ModelTask};
+use crate::http::{self, HttpTask};
+use crate::ocr::OcrSettings;
+use crate::pdf::PdfExtracted;
+use crate::redact::{self, Redactor};
+use crate::secrets;
+use crate::usage::{self, UsageMeter};
+use crate::{extraction_cache, llm, logging, mock, streaming, subprocess};
 use anyhow::{Result, anyhow};
 use futures_util::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
@@ -10,8 +16,8 @@ use std::time::Instant;
 use tokio::sync::{Semaphore, watch};
 
 const PAPER_CONCURRENCY: usize = 3;
-const FYLA_META_MARKER: &str = "<<<FYLA_META>>>";
-const FYLA_MARKDOWN_MARKER: &str = "<<<FYLA_MARKDOWN>>>";
+pub const FYLA_META_MARKER: &str = "<<<FYLA_META>>>";
+pub const FYLA_MARKDOWN_MARKER: &str = "<<<FYLA_MARKDOWN>>>";
 const STOPPED_REASON: &str = "__FYLA_PAPER_REVIEW_STOPPED__";
 const DEFAULT_REVIEW_PROMPT_TEMPLATE: &str =
     include_str!("../../src/lib/paper-review-prompt-template.txt");
@@ -118,6 +124,20 @@ struct PaperModelResponse {
     summary: String,
 }
 
+impl PaperModelResponse {
+    /// Puts values masked by the redaction pass back into the generated text.
+    fn restore_redacted(&mut self, redactor: &Redactor) {
+        for field in [
+            &mut self.title,
+            &mut self.venue,
+            &mut self.markdown,
+            &mut self.summary,
+        ] {
+            *field = redactor.restore(field);
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct PaperMetaBlock {
     #[serde(default)]
@@ -152,7 +172,7 @@ struct PreviewUpdate {
 }
 
 pub async fn test_connection(config: &AppConfig) -> Result<String> {
-    llm::test_connection(&paper_runtime_config(config)).await
+    llm::test_connection(config.model_for(ModelTask::PaperReview)?, config).await
 }
 
 pub async fn generate_reviews_stream(
@@ -161,7 +181,25 @@ pub async fn generate_reviews_stream(
     project_name: Option<String>,
     on_event: tauri::ipc::Channel<PaperStreamEvent>,
 ) -> Result<(), String> {
-    let runtime_config = paper_runtime_config(&config);
+    generate_reviews(
+        paths,
+        config,
+        project_name,
+        on_event,
+        extraction_cache::review_text,
+    )
+    .await
+}
+
+/// Reviews `paths` with the text `extract` returns for each PDF; tests pass a fixed text
+/// instead of running the PDF extractors.
+async fn generate_reviews(
+    paths: Vec<String>,
+    config: AppConfig,
+    project_name: Option<String>,
+    on_event: tauri::ipc::Channel<PaperStreamEvent>,
+    extract: fn(&str, &OcrSettings) -> Result<PdfExtracted>,
+) -> Result<(), String> {
     let project_name = normalize_optional_project(project_name);
     let registered_paths = paths.clone();
     let total = paths.len();
@@ -174,11 +212,11 @@ pub async fn generate_reviews_stream(
     let mut completed = 0usize;
     let mut failed = 0usize;
     let mut tasks = stream::iter(paths.into_iter().map(|path| {
-        let config = runtime_config.clone();
+        let config = config.clone();
         let on_event = on_event.clone();
         let project_name = project_name.clone();
         let semaphore = semaphore.clone();
-        async move { review_single(path, config, project_name, on_event, semaphore).await }
+        review_single(path, config, project_name, on_event, semaphore, extract)
     }))
     .buffer_unordered(total.max(1));
 
@@ -211,6 +249,7 @@ async fn review_single(
     project_name: Option<String>,
     on_event: tauri::ipc::Channel<PaperStreamEvent>,
     semaphore: Arc<Semaphore>,
+    extract: fn(&str, &OcrSettings) -> Result<PdfExtracted>,
 ) -> Result<()> {
     let started_at = Instant::now();
     let file_name = Path::new(&path)
@@ -223,6 +262,19 @@ async fn review_single(
     if *cancel_rx.borrow() {
         return send_cancelled(path, file_name, "queued".into(), 0, on_event);
     }
+    let profile = match config.model_for(ModelTask::PaperReview) {
+        Ok(profile) => profile.clone(),
+        Err(err) => {
+            return send_error(
+                path,
+                file_name,
+                "queued".into(),
+                err.to_string(),
+                0,
+                on_event,
+            );
+        }
+    };
 
     let _permit = tokio::select! {
         _ = cancel_rx.changed() => {
@@ -257,24 +309,37 @@ async fn review_single(
     });
 
     let extract_path = path.clone();
-    let extracted =
-        match tokio::task::spawn_blocking(move || pdf::extract_pdf_text_for_paper(&extract_path))
-            .await
-            .map_err(|err| anyhow!(err.to_string()))
-            .and_then(|value| value)
-        {
-            Ok(value) => value,
-            Err(err) => {
-                return send_error(
-                    path,
-                    file_name,
-                    "extracting".into(),
-                    err.to_string(),
-                    started_at.elapsed().as_millis() as u64,
-                    on_event,
-                );
-            }
-        };
+    // 停止解读时一并结束 pdftotext / pymupdf4llm / OCR 子进程，释放并发槽位
+    let extract_cancel = cancel_rx.clone();
+    let ocr_settings = OcrSettings::from_config(&config);
+    let extracted = match tokio::task::spawn_blocking(move || {
+        subprocess::with_cancel(extract_cancel, || extract(&extract_path, &ocr_settings))
+    })
+    .await
+    .map_err(|err| anyhow!(err.to_string()))
+    .and_then(|value| value)
+    {
+        Ok(value) => value,
+        Err(_) if should_cancel(&cancel_rx) => {
+            return send_cancelled(
+                path,
+                file_name,
+                "extracting".into(),
+                started_at.elapsed().as_millis() as u64,
+                on_event,
+            );
+        }
+        Err(err) => {
+            return send_error(
+                path,
+                file_name,
+                "extracting".into(),
+                err.to_string(),
+                started_at.elapsed().as_millis() as u64,
+                on_event,
+            );
+        }
+    };
 
     if should_cancel(&cancel_rx) {
         return send_cancelled(
@@ -302,31 +367,28 @@ async fn review_single(
         &paper_text,
         &config.paper_review_prompt_template,
     );
-    let (model_name, host) = match config.provider.as_str() {
-        "openai" => (
-            config.openai_model.as_str(),
-            streaming::host_for_logging(&config.openai_base_url),
-        ),
-        _ => (
-            config.ollama_model.as_str(),
-            streaming::host_for_logging(&config.ollama_url),
-        ),
+    let mut redactor = redact::redactor_for(&config, Some(&path), profile.endpoint());
+    let prompt = match redactor.as_mut() {
+        Some(redactor) => redactor.redact(&prompt),
+        None => prompt,
     };
-    eprintln!(
-        "[paper-perf] review_single.input file={} path={} extractor={} extractedChars={} reviewChars={} promptChars={} provider={} model={} host={}",
+    log::info!(
+        target: "paper-perf",
+        job = logging::job_id(&path).as_str();
+        "review_single.input file={} path={} extractor={} extractedChars={} reviewChars={} promptChars={} provider={} model={} host={}",
         file_name,
         path,
         extracted.extractor,
         extracted.text.chars().count(),
         paper_text.chars().count(),
         prompt.chars().count(),
-        config.provider,
-        model_name,
-        host
+        profile.provider,
+        profile.model,
+        streaming::host_for_logging(profile.endpoint())
     );
     let mut preview = PreviewAccumulator::default();
     let response =
-        call_review_model_stream(&config, &file_name, &prompt, &mut cancel_rx, |delta| {
+        call_review_model_stream(&profile, &file_name, &prompt, &mut cancel_rx, |delta| {
             let update = preview.push(delta);
             if update.ready {
                 let _ = on_event.send(PaperStreamEvent::ItemPreviewReady {
@@ -346,7 +408,12 @@ async fn review_single(
         .await;
 
     let response = match response {
-        Ok(value) => value,
+        Ok(mut value) => {
+            if let Some(redactor) = redactor.as_ref() {
+                value.response.restore_redacted(redactor);
+            }
+            value
+        }
         Err(err) => {
             if is_review_stopped_error(&err) || should_cancel(&cancel_rx) {
                 return send_cancelled(
@@ -385,7 +452,7 @@ async fn review_single(
     }
 
     let parsed = response;
-    let save_root = effective_archive_root(&config);
+    let save_root = config.paper_archive_dir();
     let save_plan = build_save_plan(&save_root, project_name.as_deref(), &parsed.response);
     let markdown = parsed.response.markdown.trim().to_string();
     if markdown.is_empty() {
@@ -479,7 +546,7 @@ fn send_cancelled(
 }
 
 async fn call_review_model_stream<F>(
-    config: &AppConfig,
+    profile: &ModelProfile,
     file_name: &str,
     prompt: &str,
     cancel_rx: &mut watch::Receiver<bool>,
@@ -488,16 +555,23 @@ async fn call_review_model_stream<F>(
 where
     F: FnMut(&str),
 {
-    match config.provider.as_str() {
+    match profile.provider.as_str() {
+        "mock" => {
+            let task = ModelTask::PaperReview;
+            let raw = mock::stream(profile, task, prompt, Some(cancel_rx), &mut on_delta)
+                .await?
+                .ok_or_else(|| anyhow!(STOPPED_REASON))?;
+            parse_streamed_review(&raw, file_name)
+        }
         "openai" => {
-            call_openai_review_stream(config, file_name, prompt, cancel_rx, &mut on_delta).await
+            call_openai_review_stream(profile, file_name, prompt, cancel_rx, &mut on_delta).await
         }
-        _ => call_ollama_review_stream(config, file_name, prompt, cancel_rx, &mut on_delta).await,
+        _ => call_ollama_review_stream(profile, file_name, prompt, cancel_rx, &mut on_delta).await,
     }
 }
 
 async fn call_openai_review_stream<F>(
-    config: &AppConfig,
+    profile: &ModelProfile,
     file_name: &str,
     prompt: &str,
     cancel_rx: &mut watch::Receiver<bool>,
@@ -506,46 +580,39 @@ async fn call_openai_review_stream<F>(
 where
     F: FnMut(&str),
 {
-    let base = config.openai_base_url.trim_end_matches('/');
+    let base = profile.endpoint();
     let mut trace = streaming::StreamTrace::new(
         "paper-review",
         "openai",
-        &config.openai_model,
+        &profile.model,
         base,
         file_name,
         prompt.chars().count(),
     );
     let url = format!("{}/chat/completions", base);
-    let body = json!({
-        "model": config.openai_model,
+    let mut body = json!({
+        "model": profile.model,
         "messages": [{"role": "user", "content": prompt}],
         "stream": true
     });
-    let client = streaming::build_streaming_http_client()?;
-    let resp = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", config.openai_key))
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_connect() {
-                anyhow!("无法连接 API ({}): {}", base, e)
-            } else if streaming::is_timeout_like_error(&e) {
-                let message = streaming::stream_idle_timeout_message();
-                trace.log_error(&e, message);
-                anyhow!(message)
-            } else {
-                anyhow!("API 网络错误 ({}): {}", base, e)
-            }
-        })?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        return Err(anyhow!("API 请求失败 {}: {}", status, text));
-    }
+    llm::apply_sampling(&mut body, profile, None);
+    usage::ensure_budget(&profile.provider, profile.endpoint(), &profile.model)?;
+    let api_key = secrets::api_key(profile)?;
+    let client = http::client(HttpTask::Stream)?;
+    let resp = llm::send_openai_stream(&client, &url, &api_key, body, |e| {
+        if e.is_connect() {
+            anyhow!("无法连接 API ({}): {}", base, e)
+        } else if streaming::is_timeout_like_error(&e) {
+            let message = streaming::stream_idle_timeout_message();
+            trace.log_error(&e, message);
+            anyhow!(message)
+        } else {
+            anyhow!("API 网络错误 ({}): {}", base, e)
+        }
+    })
+    .await?;
 
+    let mut meter = UsageMeter::new(ModelTask::PaperReview, profile, prompt.chars().count());
     let mut raw = String::new();
     let mut buffer = String::new();
     let mut stream = resp.bytes_stream();
@@ -584,9 +651,11 @@ where
                     continue;
                 }
                 let value: Value = serde_json::from_str(payload)?;
+                meter.observe(&value);
                 let delta = extract_openai_delta_text(&value);
                 if !delta.is_empty() {
                     trace.record_delta(&delta);
+                    meter.add_output(&delta);
                     on_delta(&delta);
                     raw.push_str(&delta);
                 }
@@ -599,7 +668,7 @@ where
 }
 
 async fn call_ollama_review_stream<F>(
-    config: &AppConfig,
+    profile: &ModelProfile,
     file_name: &str,
     prompt: &str,
     cancel_rx: &mut watch::Receiver<bool>,
@@ -608,25 +677,25 @@ async fn call_ollama_review_stream<F>(
 where
     F: FnMut(&str),
 {
-    let url = format!("{}/api/chat", config.ollama_url.trim_end_matches('/'));
+    let url = format!("{}/api/chat", profile.endpoint());
     let mut trace = streaming::StreamTrace::new(
         "paper-review",
         "ollama",
-        &config.ollama_model,
-        &config.ollama_url,
+        &profile.model,
+        profile.endpoint(),
         file_name,
         prompt.chars().count(),
     );
-    let body = json!({
-        "model": config.ollama_model,
+    let mut body = json!({
+        "model": profile.model,
         "messages": [{"role": "user", "content": prompt}],
-        "stream": true,
-        "options": { "num_predict": 8192 }
+        "stream": true
     });
-    let client = streaming::build_streaming_http_client()?;
+    llm::apply_sampling(&mut body, profile, Some(8192));
+    let client = http::client(HttpTask::Stream)?;
     let resp = client.post(&url).json(&body).send().await.map_err(|e| {
         if e.is_connect() {
-            anyhow!("无法连接 Ollama（{}），请确认已启动", config.ollama_url)
+            anyhow!("无法连接 Ollama（{}），请确认已启动", profile.endpoint())
         } else if streaming::is_timeout_like_error(&e) {
             let message = streaming::stream_idle_timeout_message();
             trace.log_error(&e, message);
@@ -642,6 +711,7 @@ where
         return Err(anyhow!("Ollama 请求失败 {}: {}", status, text));
     }
 
+    let mut meter = UsageMeter::new(ModelTask::PaperReview, profile, prompt.chars().count());
     let mut raw = String::new();
     let mut buffer = String::new();
     let mut stream = resp.bytes_stream();
@@ -675,9 +745,11 @@ where
                 continue;
             }
             let value: Value = serde_json::from_str(trimmed)?;
+            meter.observe(&value);
             let delta = value["message"]["content"].as_str().unwrap_or_default();
             if !delta.is_empty() {
                 trace.record_delta(delta);
+                meter.add_output(delta);
                 on_delta(delta);
                 raw.push_str(delta);
             }
@@ -1035,26 +1107,6 @@ fn trim_references_for_prompt(text: &str) -> String {
     kept.join("\n").trim().to_string()
 }
 
-fn paper_runtime_config(config: &AppConfig) -> AppConfig {
-    let mut runtime = config.clone();
-    runtime.provider = config.paper_provider.clone();
-    runtime.ollama_url = config.paper_ollama_url.clone();
-    runtime.ollama_model = config.paper_ollama_model.clone();
-    runtime.openai_key = config.paper_openai_key.clone();
-    runtime.openai_model = config.paper_openai_model.clone();
-    runtime.openai_base_url = config.paper_openai_base_url.clone();
-    runtime
-}
-
-fn effective_archive_root(config: &AppConfig) -> PathBuf {
-    let value = config.paper_archive_root.trim();
-    if value.is_empty() {
-        PathBuf::from("/Users/chenghaoyang/Local/papers")
-    } else {
-        PathBuf::from(value)
-    }
-}
-
 #[derive(Debug)]
 struct SavePlan {
     path: PathBuf,
@@ -1198,6 +1250,38 @@ fn normalize_optional_project(value: Option<String>) -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_server::{self, StubResponse, StubServer, block_on};
+    use std::time::Duration;
+
+    fn review_reply() -> String {
+        format!(
+            "{}\n{{\"title\":\"Attention Is All You Need\",\"year\":\"2017\",\
+             \"venue\":\"NeurIPS\",\"summary\":\"提出 Transformer。\"}}\n{}\n\
+             # Attention Is All You Need\n\n## 方法\n\n只用注意力机制。\n",
+            FYLA_META_MARKER, FYLA_MARKDOWN_MARKER
+        )
+    }
+
+    fn stub_profile(provider: &str, base_url: String) -> ModelProfile {
+        ModelProfile {
+            provider: provider.into(),
+            base_url,
+            api_key: "sk-test".into(),
+            model: "llama3.2".into(),
+            ..ModelProfile::default()
+        }
+    }
+
+    fn review(profile: &ModelProfile, deltas: &mut Vec<String>) -> Result<ParsedPaperReview> {
+        let (_cancel_tx, mut cancel_rx) = watch::channel(false);
+        block_on(call_review_model_stream(
+            profile,
+            "attention.pdf",
+            "prompt",
+            &mut cancel_rx,
+            |delta| deltas.push(delta.to_string()),
+        ))
+    }
 
     #[test]
     fn blank_template_uses_default_review_prompt_template() {
@@ -1220,4 +1304,160 @@ mod tests {
         assert!(prompt.contains("文件名：demo.pdf"));
         assert!(prompt.contains("以下是从 PDF 中提取的全文文本：\npaper body"));
     }
+
+    #[test]
+    fn mock_review_stream_parses_without_fallback() {
+        let profile = ModelProfile {
+            provider: "mock".into(),
+            model: "mock".into(),
+            mock: Some(crate::config::MockSettings {
+                chunk_chars: 16,
+                ..Default::default()
+            }),
+            ..ModelProfile::default()
+        };
+        let (_cancel_tx, mut cancel_rx) = watch::channel(false);
+        let mut streamed = String::new();
+        let parsed = block_on(call_review_model_stream(
+            &profile,
+            "demo.pdf",
+            "prompt",
+            &mut cancel_rx,
+            |delta| streamed.push_str(delta),
+        ))
+        .unwrap();
+
+        assert!(streamed.contains(FYLA_MARKDOWN_MARKER));
+        assert_eq!(parsed.parse_warning, None);
+        assert!(parsed.response.title.starts_with("Mock Paper"));
+        assert!(parsed.response.markdown.starts_with("# Mock Paper"));
+    }
+
+    #[test]
+    fn review_streams_parse_markers_split_across_chunks() {
+        // 7 个字符一块，标记和 JSON 都会被切开
+        let server = StubServer::compatible(&review_reply(), 7);
+        for profile in [
+            stub_profile("openai", server.openai_url()),
+            stub_profile("ollama", server.ollama_url()),
+        ] {
+            let mut deltas = Vec::new();
+            let parsed = review(&profile, &mut deltas).unwrap();
+            assert!(deltas.len() > 10);
+            assert_eq!(deltas.concat(), review_reply());
+            assert_eq!(parsed.parse_warning, None);
+            assert_eq!(parsed.response.title, "Attention Is All You Need");
+            assert_eq!(parsed.response.venue, "NeurIPS");
+            assert!(parsed.response.markdown.starts_with("# Attention"));
+        }
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1/chat/completions");
+        assert_eq!(requests[0].body["stream_options"]["include_usage"], true);
+        assert_eq!(requests[1].path, "/api/chat");
+        assert_eq!(requests[1].body["stream"], true);
+    }
+
+    #[test]
+    fn review_stream_reports_cancel_disconnect_and_rate_limit() {
+        let server = StubServer::start(|request| match request.body["model"].as_str() {
+            Some("slow") => {
+                test_server::openai_stream(&review_reply(), 20, Duration::from_millis(100))
+            }
+            Some("broken") => match test_server::openai_stream(&review_reply(), 20, Duration::ZERO)
+            {
+                StubResponse::Stream {
+                    content_type,
+                    chunks,
+                    delay,
+                    ..
+                } => StubResponse::Stream {
+                    content_type,
+                    chunks: chunks[..2].to_vec(),
+                    delay,
+                    disconnect: true,
+                },
+                other => other,
+            },
+            _ => StubResponse::Status(429, r#"{"error":"rate limited"}"#.into()),
+        });
+        let mut profile = stub_profile("openai", server.openai_url());
+
+        profile.model = "slow".into();
+        let (cancel_tx, mut cancel_rx) = watch::channel(false);
+        let mut received = 0;
+        let err = block_on(call_review_model_stream(
+            &profile,
+            "attention.pdf",
+            "prompt",
+            &mut cancel_rx,
+            |_| {
+                received += 1;
+                let _ = cancel_tx.send(true);
+            },
+        ))
+        .unwrap_err();
+        assert!(is_review_stopped_error(&err));
+        assert_eq!(received, 1);
+
+        profile.model = "broken".into();
+        let err = review(&profile, &mut Vec::new()).unwrap_err();
+        assert_eq!(err.to_string(), streaming::stream_interrupted_message());
+
+        profile.model = "busy".into();
+        let err = review(&profile, &mut Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("429"));
+        assert!(err.to_string().contains("rate limited"));
+    }
+
+    #[test]
+    fn generate_reviews_stream_sends_events_and_saves_the_review() {
+        let server = StubServer::compatible(&review_reply(), 7);
+        let root = std::env::temp_dir().join(format!("fyla-review-stream-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let mut config = AppConfig {
+            paper_archive_root: root.join("archive").to_string_lossy().to_string(),
+            paper_review_profile: "stub".into(),
+            ..AppConfig::default()
+        };
+        config.profiles.push(ModelProfile {
+            id: "stub".into(),
+            ..stub_profile("openai", server.openai_url())
+        });
+        let source = root.join("attention.pdf").to_string_lossy().to_string();
+        let (channel, events) = test_server::recording_channel();
+
+        block_on(generate_reviews(
+            vec![source.clone()],
+            config,
+            None,
+            channel,
+            |_, _| {
+                Ok(PdfExtracted {
+                    extractor: "stub".into(),
+                    text: "Attention Is All You Need. ".repeat(40),
+                    warning: None,
+                })
+            },
+        ))
+        .unwrap();
+
+        let events = events.lock().unwrap().clone();
+        let kinds = events
+            .iter()
+            .map(|event| event["event"].as_str().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(kinds.first(), Some(&"itemStarted"));
+        assert!(kinds.contains(&"itemPreviewDelta"));
+        assert_eq!(&kinds[kinds.len() - 2..], ["itemDone", "batchFinished"]);
+        let done = &events[kinds.len() - 2]["data"]["result"];
+        assert_eq!(done["title"], "Attention Is All You Need");
+        let saved = std::fs::read_to_string(done["savedPath"].as_str().unwrap()).unwrap();
+        assert!(saved.starts_with("# Attention Is All You Need"));
+        assert_eq!(events[kinds.len() - 1]["data"]["completed"], 1);
+
+        let requests = server.requests();
+        let prompt = requests[0].body["messages"][0]["content"].as_str().unwrap();
+        assert!(prompt.contains("Attention Is All You Need."));
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
@@ -0,0 +1,236 @@
+use crate::config::{self, AppConfig};
+use crate::paper_chat;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelocateSummary {
+    pub archive_root: String,
+    pub moved_files: usize,
+    pub updated_history: usize,
+    pub updated_sessions: usize,
+}
+
+/// Maps paths under the old archive root to the same relative path under the new one.
+struct ArchiveMove {
+    from: PathBuf,
+    to: PathBuf,
+}
+
+impl ArchiveMove {
+    fn relocate(&self, path: &str) -> Option<String> {
+        let relative = Path::new(path).strip_prefix(&self.from).ok()?;
+        Some(self.to.join(relative).to_string_lossy().into_owned())
+    }
+}
+
+/// Moves every file of the paper archive to `new_root`, then points the config, paper
+/// history, chat sessions and chat caches at the new location. Files are moved back if
+/// the records cannot be rewritten, so the archive never ends up half relocated.
+pub fn relocate(new_root: &Path) -> Result<RelocateSummary> {
+    if !new_root.is_absolute() {
+        return Err(anyhow!("请选择绝对路径作为论文归档目录"));
+    }
+    let previous_config = config::load_config();
+    let old_root = previous_config.paper_archive_dir();
+    let archive = ArchiveMove {
+        from: old_root.clone(),
+        to: new_root.to_path_buf(),
+    };
+    if archive.to == archive.from {
+        return Err(anyhow!("新目录与当前论文归档目录相同"));
+    }
+    if archive.to.starts_with(&archive.from) || archive.from.starts_with(&archive.to) {
+        return Err(anyhow!("新目录不能位于当前归档目录之内，也不能包含它"));
+    }
+
+    let mut files = Vec::new();
+    if old_root.is_dir() {
+        collect_files(&old_root, &mut files)?;
+    }
+    let plan = files
+        .into_iter()
+        .map(|from| {
+            let to = archive
+                .to
+                .join(from.strip_prefix(&archive.from).unwrap_or(&from));
+            (from, to)
+        })
+        .collect::<Vec<_>>();
+    if let Some((_, existing)) = plan.iter().find(|(_, to)| to.exists()) {
+        return Err(anyhow!("目标位置已存在同名文件：{}", existing.display()));
+    }
+
+    let mut moved = Vec::with_capacity(plan.len());
+    for (from, to) in &plan {
+        if let Err(err) = move_file(from, to) {
+            move_back(&moved);
+            return Err(anyhow!("移动 {} 失败：{}", from.display(), err));
+        }
+        moved.push((from.clone(), to.clone()));
+    }
+
+    let next_config = AppConfig {
+        paper_archive_root: archive.to.to_string_lossy().into_owned(),
+        ..previous_config.clone()
+    };
+    if let Err(err) = config::save_config(&next_config) {
+        move_back(&moved);
+        return Err(err);
+    }
+
+    let mut rekeyed = Vec::new();
+    let records = config::rewrite_paper_records(
+        |entry| match archive.relocate(&entry.saved_path) {
+            Some(moved) => {
+                entry.saved_path = moved;
+                true
+            }
+            None => false,
+        },
+        |session| {
+            let old_key = session.paper_key.clone();
+            let changed = paper_chat::relocate_session(session, &|path| archive.relocate(path));
+            if changed && old_key != session.paper_key {
+                rekeyed.push((old_key, session.paper_key.clone()));
+            }
+            changed
+        },
+    );
+    let (updated_history, updated_sessions) = match records {
+        Ok(counts) => counts,
+        Err(err) => {
+            if let Err(restore_err) = config::save_config(&previous_config) {
+                log::warn!(
+                    target: "paper-archive",
+                    "failed to restore config: {:#}",
+                    restore_err
+                );
+            }
+            move_back(&moved);
+            return Err(anyhow!("论文记录未能更新，已撤销移动：{:#}", err));
+        }
+    };
+
+    // 缓存只影响速度，失败时丢弃即可
+    rekeyed.sort();
+    rekeyed.dedup();
+    for (old_key, new_key) in &rekeyed {
+        paper_chat::relocate_cache(old_key, new_key, &|path| archive.relocate(path));
+    }
+    remove_empty_dirs(&old_root);
+
+    Ok(RelocateSummary {
+        archive_root: next_config.paper_archive_root,
+        moved_files: moved.len(),
+        updated_history,
+        updated_sessions,
+    })
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Renames `from` to `to`, copying across volumes while keeping the modification time
+/// (chat caches compare it to decide whether a report changed).
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    let modified = fs::metadata(from)?.modified()?;
+    fs::copy(from, to)?;
+    let copied = fs::File::options()
+        .write(true)
+        .open(to)
+        .and_then(|file| file.set_modified(modified))
+        .and_then(|_| fs::remove_file(from));
+    if let Err(err) = copied {
+        let _ = fs::remove_file(to);
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn move_back(moved: &[(PathBuf, PathBuf)]) {
+    for (from, to) in moved.iter().rev() {
+        if let Err(err) = move_file(to, from) {
+            log::warn!(
+                target: "paper-archive",
+                "failed to move {} back to {}: {}",
+                to.display(),
+                from.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Removes directories left empty by the move, keeping anything that still has files.
+fn remove_empty_dirs(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            remove_empty_dirs(&entry.path());
+        }
+    }
+    let _ = fs::remove_dir(dir);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relocate_maps_only_paths_inside_the_old_root() {
+        let archive = ArchiveMove {
+            from: PathBuf::from("/old/papers"),
+            to: PathBuf::from("/new/archive"),
+        };
+        assert_eq!(
+            archive.relocate("/old/papers/inbox/2024-nips-attention.md"),
+            Some(
+                Path::new("/new/archive/inbox/2024-nips-attention.md")
+                    .to_string_lossy()
+                    .into_owned()
+            )
+        );
+        assert_eq!(archive.relocate("/old/papers-2/a.md"), None);
+        assert_eq!(archive.relocate("/home/me/Downloads/paper.pdf"), None);
+        assert_eq!(archive.relocate(""), None);
+    }
+
+    #[test]
+    fn move_file_keeps_modified_time_and_creates_parents() {
+        let dir = std::env::temp_dir().join(format!("fyla-archive-{}", std::process::id()));
+        let from = dir.join("old").join("a.md");
+        let to = dir.join("new").join("projects").join("a.md");
+        fs::create_dir_all(from.parent().unwrap()).unwrap();
+        fs::write(&from, "# report").unwrap();
+        let modified = fs::metadata(&from).unwrap().modified().unwrap();
+
+        move_file(&from, &to).unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "# report");
+        assert_eq!(fs::metadata(&to).unwrap().modified().unwrap(), modified);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
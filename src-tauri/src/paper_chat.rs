@@ -1,11 +1,19 @@
 use crate::config::{
-    self, AppConfig, PaperChatAttachment, PaperChatCitation, PaperChatMessageEntry,
-    PaperChatSessionEntry,
+    self, AppConfig, ModelProfile, ModelTask, PaperChatAttachment, PaperChatCitation,
+    PaperChatMessageEntry, PaperChatSessionEntry,
+};
+use crate::http::{self, HttpTask};
+use crate::ocr::OcrSettings;
+use crate::secrets;
+use crate::usage::{self, UsageMeter};
+use crate::{
+    embedding, extraction_cache, llm, logging, mock, pdf_render, redact, streaming, subprocess,
+    vlm_image,
 };
-use crate::{embedding, llm, pdf, streaming};
 use anyhow::{Context, Result, anyhow};
 use base64::Engine;
 use futures_util::StreamExt;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use std::collections::{HashMap, HashSet};
@@ -15,7 +23,7 @@ use std::time::Instant;
 use tauri::{AppHandle, Emitter};
 use tokio::sync::watch;
 
-const FYLA_CHAT_JSON_MARKER: &str = "<<<FYLA_CHAT_JSON>>>";
+pub const FYLA_CHAT_JSON_MARKER: &str = "<<<FYLA_CHAT_JSON>>>";
 const PAPER_CHAT_CACHE_VERSION: u32 = 5;
 const CHAT_HISTORY_LIMIT: usize = 16;
 const FULLTEXT_HISTORY_LIMIT: usize = 8;
@@ -29,6 +37,7 @@ const OPENAI_SAFE_TOKEN_LIMIT: u32 = 60_000;
 const OLLAMA_SAFE_TOKEN_LIMIT: u32 = 16_000;
 const STOPPED_REASON: &str = "__FYLA_PAPER_CHAT_STOPPED__";
 const MAX_IMAGES_PER_TURN: usize = 3;
+const MAX_FIGURE_PAGES_PER_TURN: usize = 2;
 
 fn paper_chat_cancel_registry() -> &'static Mutex<HashMap<String, watch::Sender<bool>>> {
     static REGISTRY: OnceLock<Mutex<HashMap<String, watch::Sender<bool>>>> = OnceLock::new();
@@ -260,7 +269,7 @@ fn build_prepared_session(
     let report_path_empty = session.saved_path.trim().is_empty();
     let source_modified_ms = file_modified_ms(&session.source_path);
     let report_modified_ms = file_modified_ms(&session.saved_path);
-    let cached = read_cache(paper_key)?;
+    let cached = read_cache(&cache_root(), paper_key)?;
     let cache_prepared = cached
         .as_ref()
         .map(|cache| {
@@ -427,6 +436,35 @@ pub fn stop_paper_chat_stream(app: AppHandle, session_id: String) -> Result<()>
     Ok(())
 }
 
+/// Where a chat turn keeps its session and retrieval cache, and how it announces session
+/// changes. The commands use the app's data files and `sessionUpdated` event.
+trait ChatStore: Send + Sync {
+    fn session(&self, session_id: &str) -> Option<PaperChatSessionEntry>;
+    fn save_session(&self, session: PaperChatSessionEntry) -> Result<PaperChatSessionEntry>;
+    fn session_updated(&self, session: &PaperChatSessionEntry);
+    fn cache_root(&self) -> PathBuf;
+}
+
+struct AppChatStore(AppHandle);
+
+impl ChatStore for AppChatStore {
+    fn session(&self, session_id: &str) -> Option<PaperChatSessionEntry> {
+        config::get_paper_chat_session(session_id)
+    }
+
+    fn save_session(&self, session: PaperChatSessionEntry) -> Result<PaperChatSessionEntry> {
+        config::upsert_paper_chat_session(session)
+    }
+
+    fn session_updated(&self, session: &PaperChatSessionEntry) {
+        let _ = self.0.emit("sessionUpdated", session);
+    }
+
+    fn cache_root(&self) -> PathBuf {
+        cache_root()
+    }
+}
+
 pub async fn stream_paper_chat_reply(
     app: AppHandle,
     session_id: String,
@@ -438,7 +476,7 @@ pub async fn stream_paper_chat_reply(
     on_event: tauri::ipc::Channel<PaperChatStreamEvent>,
 ) -> Result<(), String> {
     stream_paper_chat_reply_inner(
-        app,
+        &AppChatStore(app),
         session_id,
         question,
         attachments,
@@ -476,7 +514,7 @@ pub async fn retry_paper_chat_turn(
         .ok_or_else(|| "当前没有可重试的问题".to_string())?;
 
     stream_paper_chat_reply_inner(
-        app,
+        &AppChatStore(app),
         session_id,
         last_user.content,
         last_user.attachments,
@@ -490,7 +528,7 @@ pub async fn retry_paper_chat_turn(
 }
 
 async fn stream_paper_chat_reply_inner(
-    app: AppHandle,
+    store: &impl ChatStore,
     session_id: String,
     question: String,
     attachments: Vec<PaperChatAttachment>,
@@ -500,7 +538,8 @@ async fn stream_paper_chat_reply_inner(
     on_event: tauri::ipc::Channel<PaperChatStreamEvent>,
 ) -> Result<()> {
     let mut cancel_rx = register_stream_cancel(&session_id);
-    let mut session = config::get_paper_chat_session(&session_id)
+    let mut session = store
+        .session(&session_id)
         .ok_or_else(|| anyhow!("未找到当前论文聊天会话"))?;
 
     let history_for_prompt = session.messages.clone();
@@ -541,8 +580,8 @@ async fn stream_paper_chat_reply_inner(
     session.messages.push(assistant_message.clone());
     trim_session_messages(&mut session.messages);
     session.updated_at = iso_now();
-    session = config::upsert_paper_chat_session(session)?;
-    let _ = app.emit("sessionUpdated", &session);
+    session = store.save_session(session)?;
+    store.session_updated(&session);
 
     let _ = on_event.send(PaperChatStreamEvent::AnswerStarted {
         session_id: session_id.clone(),
@@ -550,21 +589,25 @@ async fn stream_paper_chat_reply_inner(
         assistant_message: assistant_message.clone(),
     });
 
-    let runtime_config = paper_runtime_config(&config);
+    // 配置缺失时在流式阶段报错，与模型调用失败走同一条错误路径
+    let chat_profile = config.model_for(ModelTask::PaperChat).cloned();
 
     let cache = match ensure_cache(
+        &store.cache_root(),
         &session.paper_key,
         &session.source_path,
         &session.saved_path,
         &session.title,
         &config,
+        &cancel_rx,
     )
     .await
     {
         Ok(cache) => cache,
         Err(err) => {
             let err_message = err.to_string();
-            let mut latest = config::get_paper_chat_session(&session_id)
+            let mut latest = store
+                .session(&session_id)
                 .ok_or_else(|| anyhow!("未找到当前论文聊天会话"))?;
             if let Some(message) = latest
                 .messages
@@ -576,8 +619,8 @@ async fn stream_paper_chat_reply_inner(
                 message.status = "error".into();
             }
             latest.updated_at = iso_now();
-            latest = config::upsert_paper_chat_session(latest)?;
-            let _ = app.emit("sessionUpdated", &latest);
+            latest = store.save_session(latest)?;
+            store.session_updated(&latest);
             let _ = on_event.send(PaperChatStreamEvent::AnswerError {
                 session_id: session_id.clone(),
                 message_id: assistant_message.id.clone(),
@@ -604,8 +647,10 @@ async fn stream_paper_chat_reply_inner(
                     selection_context.as_ref(),
                     &config,
                 ) {
-                    eprintln!(
-                        "[paper-perf] stream_paper_chat_reply.fallback sessionId={} paperKey={} strategy=fulltext reason={}",
+                    log::warn!(
+                        target: "paper-perf",
+                        job = logging::job_id(&session_id).as_str();
+                        "stream_paper_chat_reply.fallback sessionId={} paperKey={} strategy=fulltext reason={}",
                         session_id,
                         session.paper_key,
                         reason_text
@@ -616,7 +661,10 @@ async fn stream_paper_chat_reply_inner(
                 }
             };
 
-            match embedding::resolve_runtime(&config).await {
+            let embedding_runtime = embedding::resolve_runtime(&config)
+                .await
+                .map(|runtime| runtime.redacting_for(&config, Some(&session.source_path)));
+            match embedding_runtime {
                 Ok(embedding_runtime) => match retrieve_relevant_chunks(
                     &question,
                     &cache,
@@ -643,10 +691,27 @@ async fn stream_paper_chat_reply_inner(
         &requested_attachments,
         &question,
         &session.title,
+        &session.source_path,
         &config,
     )
     .await?;
-    let messages = build_chat_messages(
+    let figure_context = if config.vlm_enabled {
+        build_figure_page_context(
+            &question,
+            &session.source_path,
+            &cache.pdf_pages,
+            &session.title,
+            &config,
+        )
+        .await
+    } else {
+        None
+    };
+    let image_context = match (image_context, figure_context) {
+        (Some(images), Some(figures)) => Some(format!("{}\n\n{}", images, figures)),
+        (images, figures) => images.or(figures),
+    };
+    let mut messages = build_chat_messages(
         &session.title,
         &question,
         &prompt_context,
@@ -654,25 +719,43 @@ async fn stream_paper_chat_reply_inner(
         &history_for_prompt,
         &selected_attachments,
     );
+    let mut redactor = redact::redactor_for(
+        &config,
+        Some(&session.source_path),
+        chat_profile
+            .as_ref()
+            .map_or("", |profile| profile.endpoint()),
+    );
+    if let Some(redactor) = redactor.as_mut() {
+        for message in &mut messages {
+            message.content = redactor.redact(&message.content);
+        }
+    }
 
     let mut accumulator = ChatStreamAccumulator::default();
-    let raw = call_chat_model_stream(&runtime_config, &session_id, &messages, &mut cancel_rx, |delta| {
-        if let Some(answer_delta) = accumulator.push(delta) {
-            let _ = on_event.send(PaperChatStreamEvent::AnswerDelta {
-                session_id: session_id.clone(),
-                message_id: assistant_message.id.clone(),
-                delta: answer_delta,
-            });
+    let raw = match chat_profile {
+        Ok(profile) => {
+            call_chat_model_stream(&profile, &session_id, &messages, &mut cancel_rx, |delta| {
+                if let Some(answer_delta) = accumulator.push(delta) {
+                    let _ = on_event.send(PaperChatStreamEvent::AnswerDelta {
+                        session_id: session_id.clone(),
+                        message_id: assistant_message.id.clone(),
+                        delta: answer_delta,
+                    });
+                }
+            })
+            .await
         }
-    })
-    .await;
+        Err(err) => Err(err),
+    };
 
     let result = match raw {
         Ok(full_raw) => {
             let parsed = parse_chat_payload(&full_raw);
-            let answer = parsed.answer.trim().to_string();
+            let answer = llm::restore_reply(parsed.answer.trim(), redactor.as_ref());
             let citations = Vec::new();
-            let mut latest = config::get_paper_chat_session(&session_id)
+            let mut latest = store
+                .session(&session_id)
                 .ok_or_else(|| anyhow!("未找到当前论文聊天会话"))?;
 
             if let Some(message) = latest
@@ -685,8 +768,8 @@ async fn stream_paper_chat_reply_inner(
                 message.status = "done".into();
             }
             latest.updated_at = iso_now();
-            latest = config::upsert_paper_chat_session(latest)?;
-            let _ = app.emit("sessionUpdated", &latest);
+            latest = store.save_session(latest)?;
+            store.session_updated(&latest);
 
             let _ = on_event.send(PaperChatStreamEvent::AnswerDone {
                 session_id: session_id.clone(),
@@ -699,11 +782,13 @@ async fn stream_paper_chat_reply_inner(
         }
         Err(err) => {
             if is_stream_stopped_error(&err) {
-                let mut latest = config::get_paper_chat_session(&session_id)
+                let mut latest = store
+                    .session(&session_id)
                     .ok_or_else(|| anyhow!("未找到当前论文聊天会话"))?;
-                let mut stopped_content = extract_visible_answer(&accumulator.raw)
-                    .trim_end()
-                    .to_string();
+                let mut stopped_content = llm::restore_reply(
+                    extract_visible_answer(&accumulator.raw).trim_end(),
+                    redactor.as_ref(),
+                );
                 if let Some(message) = latest
                     .messages
                     .iter_mut()
@@ -718,8 +803,8 @@ async fn stream_paper_chat_reply_inner(
                     }
                 }
                 latest.updated_at = iso_now();
-                latest = config::upsert_paper_chat_session(latest)?;
-                let _ = app.emit("sessionUpdated", &latest);
+                latest = store.save_session(latest)?;
+                store.session_updated(&latest);
                 let _ = on_event.send(PaperChatStreamEvent::AnswerStopped {
                     session_id: session_id.clone(),
                     message_id: assistant_message.id.clone(),
@@ -727,7 +812,8 @@ async fn stream_paper_chat_reply_inner(
                 });
                 Ok(())
             } else {
-                let mut latest = config::get_paper_chat_session(&session_id)
+                let mut latest = store
+                    .session(&session_id)
                     .ok_or_else(|| anyhow!("未找到当前论文聊天会话"))?;
                 if let Some(message) = latest
                     .messages
@@ -739,8 +825,8 @@ async fn stream_paper_chat_reply_inner(
                     message.status = "error".into();
                 }
                 latest.updated_at = iso_now();
-                latest = config::upsert_paper_chat_session(latest)?;
-                let _ = app.emit("sessionUpdated", &latest);
+                latest = store.save_session(latest)?;
+                store.session_updated(&latest);
 
                 let _ = on_event.send(PaperChatStreamEvent::AnswerError {
                     session_id: session_id.clone(),
@@ -767,6 +853,64 @@ fn build_paper_key(source_path: &str, saved_path: &str, title: &str) -> String {
     format!("paper-chat-{}", stable_hash(raw))
 }
 
+/// Rewrites the paths of a chat session whose files moved and re-derives its paper key.
+/// `relocate` maps an old path to its new location, or `None` if it did not move.
+/// Returns whether anything changed.
+pub fn relocate_session(
+    session: &mut PaperChatSessionEntry,
+    relocate: &dyn Fn(&str) -> Option<String>,
+) -> bool {
+    let mut changed = false;
+    for path in [&mut session.source_path, &mut session.saved_path] {
+        if let Some(moved) = relocate(path) {
+            *path = moved;
+            changed = true;
+        }
+    }
+    for attachment in session
+        .messages
+        .iter_mut()
+        .flat_map(|message| &mut message.attachments)
+    {
+        if let Some(moved) = attachment.path.as_deref().and_then(relocate) {
+            attachment.path = Some(moved);
+            changed = true;
+        }
+    }
+    if changed {
+        session.paper_key =
+            build_paper_key(&session.source_path, &session.saved_path, &session.title);
+    }
+    changed
+}
+
+/// Moves the retrieval cache of a relocated paper to its new key. A cache that cannot be
+/// rewritten is dropped; it is rebuilt on the next chat.
+pub fn relocate_cache(old_key: &str, new_key: &str, relocate: &dyn Fn(&str) -> Option<String>) {
+    let root = cache_root();
+    let result = (|| -> Result<()> {
+        let Some(mut cache) = read_cache(&root, old_key)? else {
+            return Ok(());
+        };
+        for path in [&mut cache.source_path, &mut cache.saved_path] {
+            if let Some(moved) = relocate(path) {
+                *path = moved;
+            }
+        }
+        if cache.session_id == old_key {
+            cache.session_id = new_key.to_string();
+        }
+        cache.paper_key = new_key.to_string();
+        write_cache(&root, new_key, &cache)
+    })();
+    if let Err(err) = result {
+        log::warn!(target: "paper-chat", "drop cache {} after relocation: {:#}", old_key, err);
+    }
+    if old_key != new_key {
+        let _ = std::fs::remove_file(cache_path(&root, old_key));
+    }
+}
+
 fn make_session_id(paper_key: &str) -> String {
     let now = chrono::Utc::now();
     format!(
@@ -778,17 +922,19 @@ fn make_session_id(paper_key: &str) -> String {
 }
 
 async fn ensure_cache(
+    cache_root: &Path,
     paper_key: &str,
     source_path: &str,
     saved_path: &str,
     title: &str,
     config: &AppConfig,
+    cancel_rx: &watch::Receiver<bool>,
 ) -> Result<PaperChatCacheEntry> {
     let started_at = Instant::now();
     let source_modified_ms = file_modified_ms(source_path);
     let report_modified_ms = file_modified_ms(saved_path);
 
-    if let Some(cache) = read_cache(paper_key)? {
+    if let Some(cache) = read_cache(cache_root, paper_key)? {
         if cache_is_current(
             &cache,
             paper_key,
@@ -800,8 +946,9 @@ async fn ensure_cache(
         )
         .await?
         {
-            eprintln!(
-                "[paper-perf] ensure_cache.hit paperKey={} strategy={} elapsedMs={}",
+            log::debug!(
+                target: "paper-perf",
+                "ensure_cache.hit paperKey={} strategy={} elapsedMs={}",
                 paper_key,
                 cache.retrieval_strategy,
                 started_at.elapsed().as_millis()
@@ -812,7 +959,19 @@ async fn ensure_cache(
 
     let mut pdf_warning = None;
     let mut pdf_pages = if is_existing_file(source_path) {
-        match pdf::extract_pdf_pages_for_chat(source_path) {
+        // 停止回答时一并结束分页提取的子进程
+        let extract_path = source_path.to_string();
+        let extract_cancel = cancel_rx.clone();
+        let ocr_settings = OcrSettings::from_config(config);
+        let extracted = tokio::task::spawn_blocking(move || {
+            subprocess::with_cancel(extract_cancel, || {
+                extraction_cache::chat_pages(&extract_path, &ocr_settings)
+            })
+        })
+        .await
+        .map_err(|err| anyhow!(err.to_string()))
+        .and_then(|value| value);
+        match extracted {
             Ok(pages) => pages
                 .into_iter()
                 .map(|page| PaperChatPdfPage {
@@ -856,7 +1015,10 @@ async fn ensure_cache(
     let mut fallback_reason = None::<String>;
 
     if requested_strategy == RetrievalStrategy::Embedding {
-        match embedding::resolve_runtime(config).await {
+        let embedding_runtime = embedding::resolve_runtime(config)
+            .await
+            .map(|runtime| runtime.redacting_for(config, Some(source_path)));
+        match embedding_runtime {
             Ok(embedding_runtime) => {
                 embedding_provider_signature = embedding_runtime.signature.clone();
 
@@ -917,8 +1079,9 @@ async fn ensure_cache(
                             section.embedding.clear();
                         }
                     } else {
-                        eprintln!(
-                            "[paper-perf] ensure_cache.error paperKey={} strategy={} elapsedMs={} error={}",
+                        log::warn!(
+                            target: "paper-perf",
+                            "ensure_cache.error paperKey={} strategy={} elapsedMs={} error={}",
                             paper_key,
                             requested_strategy.as_str(),
                             started_at.elapsed().as_millis(),
@@ -942,8 +1105,9 @@ async fn ensure_cache(
                     fallback_reason = Some(err.to_string());
                     retrieval_strategy = RetrievalStrategy::Fulltext;
                 } else {
-                    eprintln!(
-                        "[paper-perf] ensure_cache.error paperKey={} strategy={} elapsedMs={} error={}",
+                    log::warn!(
+                        target: "paper-perf",
+                        "ensure_cache.error paperKey={} strategy={} elapsedMs={} error={}",
                         paper_key,
                         requested_strategy.as_str(),
                         started_at.elapsed().as_millis(),
@@ -973,10 +1137,11 @@ async fn ensure_cache(
         report_warning,
         updated_at: iso_now(),
     };
-    write_cache(paper_key, &cache)?;
+    write_cache(cache_root, paper_key, &cache)?;
     if let Some(reason) = fallback_reason.as_deref() {
-        eprintln!(
-            "[paper-perf] ensure_cache.fallback paperKey={} requestedStrategy={} finalStrategy={} tokenEstimate={} elapsedMs={} reason={}",
+        log::warn!(
+            target: "paper-perf",
+            "ensure_cache.fallback paperKey={} requestedStrategy={} finalStrategy={} tokenEstimate={} elapsedMs={} reason={}",
             paper_key,
             requested_strategy.as_str(),
             retrieval_strategy.as_str(),
@@ -985,8 +1150,9 @@ async fn ensure_cache(
             reason
         );
     } else {
-        eprintln!(
-            "[paper-perf] ensure_cache.build paperKey={} strategy={} tokenEstimate={} elapsedMs={}",
+        log::info!(
+            target: "paper-perf",
+            "ensure_cache.build paperKey={} strategy={} tokenEstimate={} elapsedMs={}",
             paper_key,
             retrieval_strategy.as_str(),
             token_estimate,
@@ -1130,8 +1296,10 @@ fn effective_fulltext_token_limit(config: &AppConfig) -> u32 {
 }
 
 fn provider_safe_token_limit(config: &AppConfig) -> u32 {
-    let runtime = paper_runtime_config(config);
-    if runtime.provider == "ollama" {
+    let is_ollama = config
+        .model_for(ModelTask::PaperChat)
+        .is_ok_and(|profile| !profile.is_openai());
+    if is_ollama {
         OLLAMA_SAFE_TOKEN_LIMIT
     } else {
         OPENAI_SAFE_TOKEN_LIMIT
@@ -1172,6 +1340,7 @@ async fn build_turn_image_context(
     attachments: &[PaperChatAttachment],
     question: &str,
     title: &str,
+    source_path: &str,
     config: &AppConfig,
 ) -> Result<Option<String>> {
     let image_attachments = attachments
@@ -1183,6 +1352,7 @@ async fn build_turn_image_context(
     if image_attachments.is_empty() {
         return Ok(None);
     }
+    let profile = config.model_for(ModelTask::Vlm)?;
 
     let mut sections = Vec::new();
     for (index, attachment) in image_attachments.iter().enumerate() {
@@ -1191,21 +1361,19 @@ async fn build_turn_image_context(
             .as_deref()
             .ok_or_else(|| anyhow!("图片附件缺少可读取路径"))?;
         let image_name = attachment_display_name(attachment);
-        let mime = attachment
-            .mime
-            .clone()
-            .unwrap_or_else(|| infer_image_mime(Some(image_path), attachment.name.as_deref(), None));
-        let bytes = std::fs::read(image_path)
+        let image = prepare_vlm_image(image_path, config)
+            .await
             .with_context(|| format!("读取图片失败: {}", image_name))?;
-        let image_base64 = base64::engine::general_purpose::STANDARD.encode(bytes);
-        let analysis = llm::call_vlm(
-            &build_paper_chat_image_prompt(title, question, index, image_attachments.len(), &image_name),
-            &image_base64,
-            &mime,
-            config,
-        )
-        .await
-        .with_context(|| format!("分析图片失败: {}", image_name))?;
+        let prompt = build_paper_chat_image_prompt(
+            title,
+            question,
+            index,
+            image_attachments.len(),
+            &image_name,
+        );
+        let analysis = analyze_chat_image(&prompt, &image, profile, source_path, config)
+            .await
+            .with_context(|| format!("分析图片失败: {}", image_name))?;
         let content = analysis.trim();
         sections.push(format!(
             "[图{} | {}]\n{}",
@@ -1221,6 +1389,222 @@ async fn build_turn_image_context(
     )))
 }
 
+/// Renders the pages holding the figures and tables the question names ("Figure 3",
+/// "表 2") and has the VLM describe them. Best effort: a page that cannot be found,
+/// rendered or analyzed is skipped and the answer falls back to the text layer.
+async fn build_figure_page_context(
+    question: &str,
+    pdf_path: &str,
+    pages: &[PaperChatPdfPage],
+    title: &str,
+    config: &AppConfig,
+) -> Option<String> {
+    if !is_existing_file(pdf_path) {
+        return None;
+    }
+    let profile = match config.model_for(ModelTask::Vlm) {
+        Ok(profile) => profile,
+        Err(err) => {
+            log::warn!(target: "paper-chat", "skip figure pages: {}", err);
+            return None;
+        }
+    };
+    let mut targets: Vec<(String, u32)> = Vec::new();
+    for reference in parse_figure_references(question) {
+        let Some(page) = find_figure_page(pages, &reference) else {
+            continue;
+        };
+        if let Some((label, _)) = targets.iter_mut().find(|(_, existing)| *existing == page) {
+            label.push_str(&format!("、{}", reference.label()));
+        } else if targets.len() < MAX_FIGURE_PAGES_PER_TURN {
+            targets.push((reference.label(), page));
+        }
+    }
+
+    let mut sections = Vec::new();
+    for (label, page) in targets {
+        let render_path = pdf_path.to_string();
+        let rendered =
+            tokio::task::spawn_blocking(move || rendered_pdf_page(&render_path, page)).await;
+        let image_path = match rendered {
+            Ok(Ok(image_path)) => image_path,
+            Ok(Err(err)) => {
+                log::warn!(target: "paper-chat", "render page {} of {} failed: {:#}", page, pdf_path, err);
+                continue;
+            }
+            Err(err) => {
+                log::warn!(target: "paper-chat", "render page {} of {} failed: {}", page, pdf_path, err);
+                continue;
+            }
+        };
+        let image = match prepare_vlm_image(&image_path.to_string_lossy(), config).await {
+            Ok(image) => image,
+            Err(err) => {
+                log::warn!(
+                    target: "paper-chat",
+                    "prepare rendered page {} failed: {:#}",
+                    image_path.display(),
+                    err
+                );
+                continue;
+            }
+        };
+        let image_name = format!("{}（第 {} 页）", label, page);
+        let prompt = build_paper_chat_image_prompt(title, question, 0, 1, &image_name);
+        match analyze_chat_image(&prompt, &image, profile, pdf_path, config).await {
+            Ok(analysis) if !analysis.trim().is_empty() => {
+                sections.push(format!(
+                    "[{} | 论文第 {} 页]\n{}",
+                    label,
+                    page,
+                    analysis.trim()
+                ));
+            }
+            Ok(_) => {}
+            Err(err) => log::warn!(target: "paper-chat", "analyze page {} failed: {}", page, err),
+        }
+    }
+
+    (!sections.is_empty()).then(|| {
+        format!(
+            "[问题提到的图表所在页面的视觉分析]\n{}",
+            sections.join("\n\n---\n\n")
+        )
+    })
+}
+
+/// Asks the VLM about one image. The prompt carries the user's question, so it is masked
+/// under the same policy as the chat messages and the analysis restored.
+async fn analyze_chat_image(
+    prompt: &str,
+    image: &vlm_image::PreparedImage,
+    profile: &ModelProfile,
+    source_path: &str,
+    config: &AppConfig,
+) -> Result<String> {
+    let mut redactor = redact::redactor_for(config, Some(source_path), profile.endpoint());
+    let prompt = match redactor.as_mut() {
+        Some(redactor) => redactor.redact(prompt),
+        None => prompt.to_string(),
+    };
+    let analysis = llm::call_vlm(&prompt, &image.base64(), image.mime, profile).await?;
+    Ok(llm::restore_reply(&analysis, redactor.as_ref()))
+}
+
+/// Downscaled, metadata-free copy of an image for the VLM; decoding runs off the runtime.
+async fn prepare_vlm_image(path: &str, config: &AppConfig) -> Result<vlm_image::PreparedImage> {
+    let path = path.to_string();
+    let max_edge = config.vlm_max_image_edge;
+    tokio::task::spawn_blocking(move || vlm_image::prepare(&path, max_edge)).await?
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FigureKind {
+    Figure,
+    Table,
+}
+
+/// A figure or table the user refers to by number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FigureReference {
+    kind: FigureKind,
+    number: String,
+}
+
+impl FigureReference {
+    fn label(&self) -> String {
+        match self.kind {
+            FigureKind::Figure => format!("Figure {}", self.number),
+            FigureKind::Table => format!("Table {}", self.number),
+        }
+    }
+
+    /// Whether `line` is this figure's caption, e.g. "Figure 3: ..." or "表2 ...".
+    fn is_caption(&self, line: &str) -> bool {
+        let prefixes: &[&str] = match self.kind {
+            FigureKind::Figure => &["figure", "fig.", "fig", "图"],
+            FigureKind::Table => &["table", "tab.", "tab", "表"],
+        };
+        let lowered = line.trim_start().to_lowercase();
+        prefixes.iter().any(|prefix| {
+            lowered
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.trim_start().strip_prefix(self.number.as_str()))
+                .is_some_and(|rest| !rest.starts_with(|ch: char| ch.is_ascii_digit()))
+        })
+    }
+}
+
+const NON_FIGURE_PREFIXES: [char; 10] =
+    ['代', '列', '发', '报', '外', '课', '试', '地', '意', '企'];
+
+fn figure_reference_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?i)\b(fig(?:ure)?s?|tab(?:le)?s?)\.?\s*(\d+)\b|([图表])\s*(\d+)")
+            .expect("figure reference regex")
+    })
+}
+
+/// Figures and tables named in `text`, in order of first mention.
+fn parse_figure_references(text: &str) -> Vec<FigureReference> {
+    let mut references = Vec::new();
+    for captures in figure_reference_regex().captures_iter(text) {
+        let groups = (
+            captures.get(1),
+            captures.get(2),
+            captures.get(3),
+            captures.get(4),
+        );
+        let (kind, number) = match groups {
+            (Some(word), Some(number), _, _) => {
+                let kind = if word.as_str().to_lowercase().starts_with("fig") {
+                    FigureKind::Figure
+                } else {
+                    FigureKind::Table
+                };
+                (kind, number.as_str())
+            }
+            (_, _, Some(word), Some(number)) => {
+                // “代表 3”“试图 2”这类词里的图/表不是图表编号
+                let previous = text[..word.start()].chars().next_back();
+                if previous.is_some_and(|ch| NON_FIGURE_PREFIXES.contains(&ch)) {
+                    continue;
+                }
+                let kind = if word.as_str() == "图" {
+                    FigureKind::Figure
+                } else {
+                    FigureKind::Table
+                };
+                (kind, number.as_str())
+            }
+            _ => continue,
+        };
+        let reference = FigureReference {
+            kind,
+            number: number.trim_start_matches('0').to_string(),
+        };
+        if !reference.number.is_empty() && !references.contains(&reference) {
+            references.push(reference);
+        }
+    }
+    references
+}
+
+/// Page that holds `reference`: the page with its caption, else the first page that
+/// mentions it.
+fn find_figure_page(pages: &[PaperChatPdfPage], reference: &FigureReference) -> Option<u32> {
+    pages
+        .iter()
+        .find(|page| page.text.lines().any(|line| reference.is_caption(line)))
+        .or_else(|| {
+            pages
+                .iter()
+                .find(|page| parse_figure_references(&page.text).contains(reference))
+        })
+        .map(|page| page.page)
+}
+
 fn build_available_attachments(cache: &PaperChatCacheEntry) -> Vec<PaperChatAttachment> {
     build_available_attachments_for_files(
         !cache.pdf_pages.is_empty(),
@@ -1674,7 +2058,7 @@ fn build_chat_messages(
 }
 
 async fn call_chat_model_stream<F>(
-    config: &AppConfig,
+    profile: &ModelProfile,
     trace_item: &str,
     messages: &[ChatMessage],
     cancel_rx: &mut watch::Receiver<bool>,
@@ -1683,18 +2067,39 @@ async fn call_chat_model_stream<F>(
 where
     F: FnMut(&str),
 {
-    match config.provider.as_str() {
+    match profile.provider.as_str() {
+        "mock" => {
+            let prompt = messages
+                .iter()
+                .map(|message| message.content.as_str())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let mut accumulator = ChatStreamAccumulator::default();
+            mock::stream(
+                profile,
+                ModelTask::PaperChat,
+                &prompt,
+                Some(cancel_rx),
+                |delta| {
+                    if let Some(answer_delta) = accumulator.push(delta) {
+                        on_delta(&answer_delta);
+                    }
+                },
+            )
+            .await?
+            .ok_or_else(|| anyhow!(STOPPED_REASON))
+        }
         "openai" => {
-            call_openai_chat_stream(config, trace_item, messages, cancel_rx, &mut on_delta).await
+            call_openai_chat_stream(profile, trace_item, messages, cancel_rx, &mut on_delta).await
         }
         _ => {
-            call_ollama_chat_stream(config, trace_item, messages, cancel_rx, &mut on_delta).await
+            call_ollama_chat_stream(profile, trace_item, messages, cancel_rx, &mut on_delta).await
         }
     }
 }
 
 async fn call_openai_chat_stream<F>(
-    config: &AppConfig,
+    profile: &ModelProfile,
     trace_item: &str,
     messages: &[ChatMessage],
     cancel_rx: &mut watch::Receiver<bool>,
@@ -1703,7 +2108,7 @@ async fn call_openai_chat_stream<F>(
 where
     F: FnMut(&str),
 {
-    let base = config.openai_base_url.trim_end_matches('/');
+    let base = profile.endpoint();
     let input_chars = messages
         .iter()
         .map(|message| message.content.chars().count())
@@ -1711,42 +2116,35 @@ where
     let mut trace = streaming::StreamTrace::new(
         "paper-chat",
         "openai",
-        &config.openai_model,
+        &profile.model,
         base,
         trace_item,
         input_chars,
     );
     let url = format!("{}/chat/completions", base);
-    let body = json!({
-        "model": config.openai_model,
+    let mut body = json!({
+        "model": profile.model,
         "messages": messages,
         "stream": true
     });
-    let client = streaming::build_streaming_http_client()?;
-    let resp = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", config.openai_key))
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_connect() {
-                anyhow!("无法连接 API ({}): {}", base, e)
-            } else if streaming::is_timeout_like_error(&e) {
-                let message = streaming::stream_idle_timeout_message();
-                trace.log_error(&e, message);
-                anyhow!(message)
-            } else {
-                anyhow!("API 网络错误 ({}): {}", base, e)
-            }
-        })?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        return Err(anyhow!("API 请求失败 {}: {}", status, text));
-    }
+    llm::apply_sampling(&mut body, profile, None);
+    usage::ensure_budget(&profile.provider, profile.endpoint(), &profile.model)?;
+    let api_key = secrets::api_key(profile)?;
+    let client = http::client(HttpTask::Stream)?;
+    let resp = llm::send_openai_stream(&client, &url, &api_key, body, |e| {
+        if e.is_connect() {
+            anyhow!("无法连接 API ({}): {}", base, e)
+        } else if streaming::is_timeout_like_error(&e) {
+            let message = streaming::stream_idle_timeout_message();
+            trace.log_error(&e, message);
+            anyhow!(message)
+        } else {
+            anyhow!("API 网络错误 ({}): {}", base, e)
+        }
+    })
+    .await?;
 
+    let mut meter = UsageMeter::new(ModelTask::PaperChat, profile, input_chars);
     let mut raw = String::new();
     let mut buffer = String::new();
     let mut stream = resp.bytes_stream();
@@ -1786,9 +2184,11 @@ where
                     continue;
                 }
                 let value: Value = serde_json::from_str(payload)?;
+                meter.observe(&value);
                 let delta = extract_openai_delta_text(&value);
                 if !delta.is_empty() {
                     trace.record_delta(&delta);
+                    meter.add_output(&delta);
                     raw.push_str(&delta);
                     if let Some(answer_delta) = accumulator.push(&delta) {
                         on_delta(&answer_delta);
@@ -1803,7 +2203,7 @@ where
 }
 
 async fn call_ollama_chat_stream<F>(
-    config: &AppConfig,
+    profile: &ModelProfile,
     trace_item: &str,
     messages: &[ChatMessage],
     cancel_rx: &mut watch::Receiver<bool>,
@@ -1812,7 +2212,7 @@ async fn call_ollama_chat_stream<F>(
 where
     F: FnMut(&str),
 {
-    let url = format!("{}/api/chat", config.ollama_url.trim_end_matches('/'));
+    let url = format!("{}/api/chat", profile.endpoint());
     let input_chars = messages
         .iter()
         .map(|message| message.content.chars().count())
@@ -1820,21 +2220,21 @@ where
     let mut trace = streaming::StreamTrace::new(
         "paper-chat",
         "ollama",
-        &config.ollama_model,
-        &config.ollama_url,
+        &profile.model,
+        profile.endpoint(),
         trace_item,
         input_chars,
     );
-    let body = json!({
-        "model": config.ollama_model,
+    let mut body = json!({
+        "model": profile.model,
         "messages": messages,
-        "stream": true,
-        "options": { "num_predict": 4096 }
+        "stream": true
     });
-    let client = streaming::build_streaming_http_client()?;
+    llm::apply_sampling(&mut body, profile, Some(4096));
+    let client = http::client(HttpTask::Stream)?;
     let resp = client.post(&url).json(&body).send().await.map_err(|e| {
         if e.is_connect() {
-            anyhow!("无法连接 Ollama（{}），请确认已启动", config.ollama_url)
+            anyhow!("无法连接 Ollama（{}），请确认已启动", profile.endpoint())
         } else if streaming::is_timeout_like_error(&e) {
             let message = streaming::stream_idle_timeout_message();
             trace.log_error(&e, message);
@@ -1850,6 +2250,7 @@ where
         return Err(anyhow!("Ollama 请求失败 {}: {}", status, text));
     }
 
+    let mut meter = UsageMeter::new(ModelTask::PaperChat, profile, input_chars);
     let mut raw = String::new();
     let mut buffer = String::new();
     let mut stream = resp.bytes_stream();
@@ -1884,9 +2285,11 @@ where
                 continue;
             }
             let value: Value = serde_json::from_str(trimmed)?;
+            meter.observe(&value);
             let delta = value["message"]["content"].as_str().unwrap_or_default();
             if !delta.is_empty() {
                 trace.record_delta(delta);
+                meter.add_output(delta);
                 raw.push_str(delta);
                 if let Some(answer_delta) = accumulator.push(delta) {
                     on_delta(&answer_delta);
@@ -2484,6 +2887,8 @@ fn infer_image_mime(path: Option<&str>, name: Option<&str>, mime_hint: Option<&s
         Some("gif") => "image/gif".into(),
         Some("bmp") => "image/bmp".into(),
         Some("tif") | Some("tiff") => "image/tiff".into(),
+        Some("heic") => "image/heic".into(),
+        Some("heif") => "image/heif".into(),
         _ => "image/png".into(),
     }
 }
@@ -2496,6 +2901,8 @@ fn image_extension_from_mime(mime: &str) -> &'static str {
         "image/gif" => "gif",
         "image/bmp" => "bmp",
         "image/tif" | "image/tiff" => "tiff",
+        "image/heic" => "heic",
+        "image/heif" => "heif",
         _ => "png",
     }
 }
@@ -2561,6 +2968,12 @@ fn managed_image_root() -> PathBuf {
         .join("paper-chat-images")
 }
 
+/// Renders `page` of the PDF at `path` under the managed image root, reusing an earlier
+/// render of the same file content.
+pub fn rendered_pdf_page(path: &str, page: u32) -> Result<PathBuf> {
+    pdf_render::render_page_cached(path, page, &managed_image_root().join("pdf-pages"))
+}
+
 fn managed_image_session_dir(session_id: &str) -> PathBuf {
     managed_image_root().join(session_id)
 }
@@ -2569,12 +2982,12 @@ fn managed_image_attachment_dir(session_id: &str, attachment_id: &str) -> PathBu
     managed_image_session_dir(session_id).join(sanitize_file_name_component(attachment_id))
 }
 
-fn cache_path(session_id: &str) -> PathBuf {
-    cache_root().join(format!("{}.json", session_id))
+fn cache_path(root: &Path, session_id: &str) -> PathBuf {
+    root.join(format!("{}.json", session_id))
 }
 
-fn read_cache(session_id: &str) -> Result<Option<PaperChatCacheEntry>> {
-    let path = cache_path(session_id);
+fn read_cache(root: &Path, session_id: &str) -> Result<Option<PaperChatCacheEntry>> {
+    let path = cache_path(root, session_id);
     if !path.exists() {
         return Ok(None);
     }
@@ -2582,8 +2995,8 @@ fn read_cache(session_id: &str) -> Result<Option<PaperChatCacheEntry>> {
     Ok(serde_json::from_str(&raw).ok())
 }
 
-fn write_cache(session_id: &str, cache: &PaperChatCacheEntry) -> Result<()> {
-    let path = cache_path(session_id);
+fn write_cache(root: &Path, session_id: &str, cache: &PaperChatCacheEntry) -> Result<()> {
+    let path = cache_path(root, session_id);
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
     }
@@ -2681,13 +3094,321 @@ fn extract_openai_delta_text(value: &Value) -> String {
     String::new()
 }
 
-fn paper_runtime_config(config: &AppConfig) -> AppConfig {
-    let mut runtime = config.clone();
-    runtime.provider = config.paper_provider.clone();
-    runtime.ollama_url = config.paper_ollama_url.clone();
-    runtime.ollama_model = config.paper_ollama_model.clone();
-    runtime.openai_key = config.paper_openai_key.clone();
-    runtime.openai_model = config.paper_openai_model.clone();
-    runtime.openai_base_url = config.paper_openai_base_url.clone();
-    runtime
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server;
+    use std::time::Duration;
+
+    fn pdf_page(page: u32, text: &str) -> PaperChatPdfPage {
+        PaperChatPdfPage {
+            page,
+            text: text.into(),
+            embedding: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn relocate_session_rewrites_paths_and_rekeys() {
+        let mut session = create_empty_session_entry(
+            &build_paper_key("/tmp/a.pdf", "/old/inbox/a.md", "A"),
+            "/tmp/a.pdf",
+            "/old/inbox/a.md",
+            "A",
+        );
+        session.messages.push(PaperChatMessageEntry {
+            attachments: vec![PaperChatAttachment {
+                path: Some("/old/inbox/a.md".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let session_id = session.session_id.clone();
+        let relocate = |path: &str| {
+            path.strip_prefix("/old/")
+                .map(|rest| format!("/new/{}", rest))
+        };
+
+        assert!(relocate_session(&mut session, &relocate));
+        assert_eq!(session.saved_path, "/new/inbox/a.md");
+        assert_eq!(session.source_path, "/tmp/a.pdf");
+        assert_eq!(
+            session.messages[0].attachments[0].path.as_deref(),
+            Some("/new/inbox/a.md")
+        );
+        assert_eq!(
+            session.paper_key,
+            build_paper_key("", "/new/inbox/a.md", "")
+        );
+        assert_eq!(session.session_id, session_id);
+        assert!(!relocate_session(&mut session, &relocate));
+    }
+
+    #[test]
+    fn parse_figure_references_reads_english_and_chinese_forms() {
+        let references =
+            parse_figure_references("Compare Fig. 3 with 图 3 和表2，试图 4 代表 5 个");
+
+        assert_eq!(
+            references
+                .iter()
+                .map(FigureReference::label)
+                .collect::<Vec<_>>(),
+            vec!["Figure 3", "Table 2"]
+        );
+    }
+
+    #[test]
+    fn find_figure_page_prefers_caption_over_first_mention() {
+        let pages = vec![
+            pdf_page(2, "As shown in Figure 3, the loss drops."),
+            pdf_page(4, "Figure 30: unrelated\nFigure 3: Training loss curves"),
+        ];
+        let figure = FigureReference {
+            kind: FigureKind::Figure,
+            number: "3".into(),
+        };
+        let table = FigureReference {
+            kind: FigureKind::Table,
+            number: "1".into(),
+        };
+
+        assert_eq!(find_figure_page(&pages, &figure), Some(4));
+        assert_eq!(find_figure_page(&pages, &table), None);
+    }
+
+    fn stub_chat(
+        profile: &ModelProfile,
+        cancel_after_first: bool,
+        deltas: &mut Vec<String>,
+    ) -> Result<String> {
+        let messages = [ChatMessage {
+            role: "user".into(),
+            content: "这篇论文的方法是什么？".into(),
+        }];
+        let (cancel_tx, mut cancel_rx) = watch::channel(false);
+        test_server::block_on(call_chat_model_stream(
+            profile,
+            "session",
+            &messages,
+            &mut cancel_rx,
+            |delta| {
+                deltas.push(delta.to_string());
+                if cancel_after_first {
+                    let _ = cancel_tx.send(true);
+                }
+            },
+        ))
+    }
+
+    #[test]
+    fn chat_streams_hide_the_json_marker_from_answer_deltas() {
+        let answer = "方法是只用注意力 @论文PDF/第3页。";
+        let reply = format!(
+            "{}\n{}\n{{\"suggestedQuestions\":[\"训练用了多久？\"]}}",
+            answer, FYLA_CHAT_JSON_MARKER
+        );
+        let server = test_server::StubServer::compatible(&reply, 4);
+        for (provider, base_url) in [
+            ("openai", server.openai_url()),
+            ("ollama", server.ollama_url()),
+        ] {
+            let profile = ModelProfile {
+                provider: provider.into(),
+                base_url,
+                api_key: "sk-test".into(),
+                model: "llama3.2".into(),
+                ..ModelProfile::default()
+            };
+            let mut deltas = Vec::new();
+            let raw = stub_chat(&profile, false, &mut deltas).unwrap();
+            assert_eq!(deltas.concat().trim(), answer);
+            let parsed = parse_chat_payload(&raw);
+            assert_eq!(parsed.answer, answer);
+            assert_eq!(parsed.suggested_questions, vec!["训练用了多久？"]);
+        }
+    }
+
+    #[test]
+    fn chat_stream_stops_when_cancelled_mid_stream() {
+        let server = test_server::StubServer::start(|_| {
+            test_server::ollama_stream("一段很长的回答。", 2, Duration::from_millis(100))
+        });
+        let profile = ModelProfile {
+            provider: "ollama".into(),
+            base_url: server.ollama_url(),
+            model: "llama3.2".into(),
+            ..ModelProfile::default()
+        };
+        let mut deltas = Vec::new();
+        let err = stub_chat(&profile, true, &mut deltas).unwrap_err();
+        assert_eq!(err.to_string(), STOPPED_REASON);
+        assert_eq!(deltas, vec!["一段"]);
+    }
+
+    /// Keeps sessions in memory and the retrieval cache in a temporary directory.
+    struct TempChatStore {
+        root: PathBuf,
+        sessions: Mutex<HashMap<String, PaperChatSessionEntry>>,
+        updates: Mutex<Vec<String>>,
+    }
+
+    impl ChatStore for TempChatStore {
+        fn session(&self, session_id: &str) -> Option<PaperChatSessionEntry> {
+            self.sessions.lock().unwrap().get(session_id).cloned()
+        }
+
+        fn save_session(&self, session: PaperChatSessionEntry) -> Result<PaperChatSessionEntry> {
+            let mut sessions = self.sessions.lock().unwrap();
+            sessions.insert(session.session_id.clone(), session.clone());
+            Ok(session)
+        }
+
+        fn session_updated(&self, session: &PaperChatSessionEntry) {
+            let status = session
+                .messages
+                .last()
+                .map_or(String::new(), |message| message.status.clone());
+            self.updates.lock().unwrap().push(status);
+        }
+
+        fn cache_root(&self) -> PathBuf {
+            self.root.join("cache")
+        }
+    }
+
+    /// Runs one chat turn about a report-only paper with `profile` as the chat model and
+    /// returns the store and the events sent to the webview.
+    fn run_chat_turn(name: &str, profile: ModelProfile) -> (TempChatStore, Vec<Value>) {
+        let root = std::env::temp_dir().join(format!("fyla-chat-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let report = root.join("report.md");
+        std::fs::write(
+            &report,
+            "# Attention\n\n## 方法\n\nTransformer 只用注意力机制。\n",
+        )
+        .unwrap();
+
+        let mut config = AppConfig {
+            paper_chat_profile: "stub".into(),
+            ..AppConfig::default()
+        };
+        config.profiles.push(ModelProfile {
+            id: "stub".into(),
+            ..profile
+        });
+        let session = PaperChatSessionEntry {
+            paper_key: "stub-paper".into(),
+            session_id: format!("{}-session-{}", name, std::process::id()),
+            source_path: root.join("missing.pdf").to_string_lossy().to_string(),
+            saved_path: report.to_string_lossy().to_string(),
+            title: "Attention".into(),
+            ..PaperChatSessionEntry::default()
+        };
+        let session_id = session.session_id.clone();
+        let store = TempChatStore {
+            root,
+            sessions: Mutex::new(HashMap::from([(session_id.clone(), session)])),
+            updates: Mutex::new(Vec::new()),
+        };
+        let (channel, events) = test_server::recording_channel();
+
+        test_server::block_on(stream_paper_chat_reply_inner(
+            &store,
+            session_id,
+            "方法是什么？".into(),
+            Vec::new(),
+            Vec::new(),
+            None,
+            config,
+            channel,
+        ))
+        .unwrap();
+        let events = events.lock().unwrap().clone();
+        (store, events)
+    }
+
+    fn event_kinds(events: &[Value]) -> Vec<&str> {
+        events
+            .iter()
+            .map(|event| event["event"].as_str().unwrap_or_default())
+            .collect()
+    }
+
+    fn streamed_answer(events: &[Value]) -> String {
+        events
+            .iter()
+            .filter(|event| event["event"] == "answerDelta")
+            .filter_map(|event| event["data"]["delta"].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn stream_paper_chat_reply_answers_from_the_report_and_saves_the_session() {
+        let answer = "方法是只用注意力。";
+        let reply = format!(
+            "{}\n{}\n{{\"suggestedQuestions\":[\"训练用了多久？\"]}}",
+            answer, FYLA_CHAT_JSON_MARKER
+        );
+        let server = test_server::StubServer::compatible(&reply, 3);
+        let (store, events) = run_chat_turn(
+            "stub",
+            ModelProfile {
+                provider: "openai".into(),
+                base_url: server.openai_url(),
+                api_key: "sk-test".into(),
+                model: "gpt-4o-mini".into(),
+                ..ModelProfile::default()
+            },
+        );
+
+        let kinds = event_kinds(&events);
+        assert_eq!(kinds.first(), Some(&"answerStarted"));
+        assert_eq!(kinds.last(), Some(&"answerDone"));
+        assert_eq!(streamed_answer(&events).trim(), answer);
+        let done = &events[events.len() - 1]["data"];
+        assert_eq!(done["content"], answer);
+        assert_eq!(done["suggestedQuestions"][0], "训练用了多久？");
+
+        let session_id = done["sessionId"].as_str().unwrap();
+        let saved = store.session(session_id).unwrap();
+        assert_eq!(saved.messages.len(), 2);
+        assert_eq!(saved.messages[1].content, answer);
+        assert_eq!(*store.updates.lock().unwrap(), ["streaming", "done"]);
+        assert!(store.cache_root().join("stub-paper.json").exists());
+        let request = &server.requests()[0].body;
+        assert!(request.to_string().contains("Transformer 只用注意力机制"));
+        let _ = std::fs::remove_dir_all(&store.root);
+    }
+
+    #[test]
+    fn stream_paper_chat_reply_runs_through_the_mock_profile() {
+        let (store, events) = run_chat_turn(
+            "mock",
+            ModelProfile {
+                provider: "mock".into(),
+                model: "mock".into(),
+                mock: Some(config::MockSettings {
+                    chunk_chars: 5,
+                    ..config::MockSettings::default()
+                }),
+                ..ModelProfile::default()
+            },
+        );
+
+        assert_eq!(event_kinds(&events).last(), Some(&"answerDone"));
+        let done = &events[events.len() - 1]["data"];
+        let content = done["content"].as_str().unwrap();
+        assert!(content.starts_with("离线模拟回答"));
+        assert!(!content.contains(FYLA_CHAT_JSON_MARKER));
+        assert_eq!(streamed_answer(&events).trim(), content);
+        assert_eq!(done["suggestedQuestions"].as_array().unwrap().len(), 2);
+        let session_id = done["sessionId"].as_str().unwrap();
+        assert_eq!(
+            store.session(session_id).unwrap().messages[1].status,
+            "done"
+        );
+        let _ = std::fs::remove_dir_all(&store.root);
+    }
 }
@@ -1,37 +1,59 @@
-use crate::ocr;
+use crate::ocr::OcrSettings;
+use crate::subprocess::{self, ProcessLimits};
+use crate::{logging, ocr, office, pdf_cleanup, pdf_layout, pdf_password, sidecar};
 use anyhow::{Context, Result, anyhow};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 const MAX_EXTRACTED_CHARS: usize = 2000;
 const MIN_PDF_TEXT_CHARS: usize = 50;
-const PDFTOTEXT_PACKAGED_NAME: &str = "pdftotext";
+const PDFTOTEXT_NAME: &str = "pdftotext";
+const PDFTOTEXT_LIMITS: ProcessLimits = ProcessLimits::new(90, 64 * 1024 * 1024);
+const PYMUPDF4LLM_LIMITS: ProcessLimits = ProcessLimits::new(300, 64 * 1024 * 1024);
 
-#[cfg(target_arch = "aarch64")]
-const PDFTOTEXT_BINARY_NAME: &str = "pdftotext-aarch64-apple-darwin";
-#[cfg(target_arch = "x86_64")]
-const PDFTOTEXT_BINARY_NAME: &str = "pdftotext-x86_64-apple-darwin";
-const PDFTOTEXT_UNIVERSAL_BINARY_NAME: &str = "pdftotext-universal-apple-darwin";
-#[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
-compile_error!("Unsupported macOS architecture for bundled pdftotext sidecar");
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum PdfExtractor {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PdfExtractor {
     Pymupdf4llm,
     Pdftotext,
+    Builtin,
     PdfKit,
     Ocr,
 }
 
 impl PdfExtractor {
-    fn label(self) -> &'static str {
+    /// Every extractor, in the order the paper review tries them.
+    pub const ALL: [Self; 5] = [
+        Self::Pymupdf4llm,
+        Self::Pdftotext,
+        Self::Builtin,
+        Self::PdfKit,
+        Self::Ocr,
+    ];
+
+    pub fn label(self) -> &'static str {
         match self {
             Self::Pymupdf4llm => "pymupdf4llm",
             Self::Pdftotext => "pdftotext",
+            Self::Builtin => "builtin",
             Self::PdfKit => "pdfkit",
             Self::Ocr => "ocr",
         }
     }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|extractor| extractor.label() == label)
+    }
+}
+
+/// How much of a scanned PDF the OCR fallback reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OcrScope {
+    /// First page only; enough to name a file.
+    FirstPage,
+    /// Every page in the configured OCR page range.
+    AllPages,
 }
 
 #[derive(Debug)]
@@ -55,7 +77,7 @@ pub struct PdfPageText {
 }
 
 /// Extracts text from PDF, DOCX, PPTX, XLSX, TXT, and MD files (truncated to 2000 chars).
-pub fn extract_text(path: &str) -> Result<String> {
+pub fn extract_text(path: &str, ocr_settings: &OcrSettings) -> Result<String> {
     let p = Path::new(path);
     let ext = p
         .extension()
@@ -65,7 +87,7 @@ pub fn extract_text(path: &str) -> Result<String> {
         .to_string();
 
     let text = match ext.as_str() {
-        "pdf" => extract_pdf_text(path)?,
+        "pdf" => extract_pdf_text(path, ocr_settings)?,
         "docx" => extract_docx(path)?,
         "pptx" => extract_pptx(path)?,
         "xlsx" | "xls" => extract_xlsx(path)?,
@@ -80,12 +102,30 @@ pub fn extract_text(path: &str) -> Result<String> {
     Ok(text)
 }
 
-pub fn extract_pdf_text(path: &str) -> Result<String> {
-    let extracted = extract_pdf_text_detailed(path)?;
+pub fn extract_pdf_text(path: &str, ocr_settings: &OcrSettings) -> Result<String> {
+    let extracted = extract_pdf_text_detailed(path, ocr_settings)?;
     Ok(smart_truncate(&extracted.text, MAX_EXTRACTED_CHARS))
 }
 
-pub fn extract_pdf_text_for_paper(path: &str) -> Result<PdfExtracted> {
+/// Paper review text, using the `pinned` extractor label when the file has one.
+pub fn extract_pdf_text_for_paper(
+    path: &str,
+    pinned: Option<&str>,
+    ocr_settings: &OcrSettings,
+) -> Result<PdfExtracted> {
+    let pinned = pinned.and_then(PdfExtractor::from_label);
+    with_unlocked_pdf(path, || extract_paper_text(path, pinned, ocr_settings))
+}
+
+fn extract_paper_text(
+    path: &str,
+    pinned: Option<PdfExtractor>,
+    ocr_settings: &OcrSettings,
+) -> Result<PdfExtracted> {
+    if let Some(pinned) = pinned {
+        return extract_with_pinned(path, pinned, ocr_settings);
+    }
+
     let pymupdf = extract_pdf_with_pymupdf4llm(path)
         .map(|text| normalize_markdown_for_review(&text))
         .and_then(|text| {
@@ -108,52 +148,137 @@ pub fn extract_pdf_text_for_paper(path: &str) -> Result<PdfExtracted> {
     match pymupdf {
         Ok(extracted) => Ok(extracted),
         Err(err) => {
-            eprintln!(
-                "[pdf] falling back to standard paper extractors for {} after pymupdf4llm failure: {}",
+            log::warn!(
+                target: "pdf",
+                "falling back to standard paper extractors for {} after pymupdf4llm failure: {}",
                 path, err
             );
-            extract_pdf_text_detailed(path)
+            extract_pdf_text_with_ocr(path, OcrScope::AllPages, ocr_settings)
         }
     }
 }
 
-pub fn extract_pdf_pages_for_chat(path: &str) -> Result<Vec<PdfPageText>> {
-    let binary = resolve_pdftotext_sidecar()
-        .with_context(|| format!("未找到 pdftotext sidecar for {}", PDFTOTEXT_BINARY_NAME))?;
+/// Review extraction with a user-pinned extractor: no fallback, so a failing pin is visible.
+fn extract_with_pinned(
+    path: &str,
+    extractor: PdfExtractor,
+    ocr_settings: &OcrSettings,
+) -> Result<PdfExtracted> {
+    log::info!(
+        target: "pdf",
+        job = logging::job_id(path).as_str();
+        "using pinned {} extractor for {}",
+        extractor.label(),
+        path
+    );
+    let raw = run_extractor(path, extractor, ocr_settings)
+        .with_context(|| format!("固定的提取器 {} 运行失败", extractor.label()))?;
+    let text = match extractor {
+        PdfExtractor::Pymupdf4llm => normalize_markdown_for_review(&raw),
+        _ => normalize_pdf_text_for_review(&raw),
+    };
+    if text.trim().is_empty() {
+        return Err(anyhow!("固定的提取器 {} 没有提取到文本", extractor.label()));
+    }
 
-    let output = Command::new(&binary)
-        .args(["-enc", "UTF-8", path, "-"])
-        .output()
-        .with_context(|| format!("调用 pdftotext 分页提取失败: {}", binary.display()))?;
+    let warning = match extractor {
+        PdfExtractor::Ocr => {
+            "本篇论文按固定设置使用 OCR 提取文本，版式、公式或表格细节可能存在偏差。".to_string()
+        }
+        _ => format!("本篇论文按固定设置使用 {} 提取文本。", extractor.label()),
+    };
+    Ok(PdfExtracted {
+        extractor: extractor.label().to_string(),
+        text,
+        warning: Some(warning),
+    })
+}
 
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-    if !stderr.is_empty() {
-        eprintln!("[pdf] paged pdftotext stderr for {}: {}", path, stderr);
-    }
-
-    if output.status.success() {
-        let raw = String::from_utf8_lossy(&output.stdout);
-        let pages = raw
-            .split('\u{c}')
-            .enumerate()
-            .filter_map(|(idx, chunk)| {
-                let normalized = normalize_pdf_text(chunk);
-                if effective_len(&normalized) < 8 {
-                    return None;
-                }
-                Some(PdfPageText {
-                    page: idx as u32 + 1,
-                    text: normalized,
-                })
+/// Runs one extractor and returns its raw output. Pages are separated by form feeds where
+/// the extractor reports them; OCR covers the configured page range. The caller is
+/// responsible for unlocking encrypted files (see [`with_unlocked_pdf`]).
+pub fn run_extractor(
+    path: &str,
+    extractor: PdfExtractor,
+    ocr_settings: &OcrSettings,
+) -> Result<String> {
+    match extractor {
+        PdfExtractor::Pymupdf4llm => extract_pdf_with_pymupdf4llm(path),
+        PdfExtractor::Pdftotext => extract_pdf_with_pdftotext(path),
+        PdfExtractor::Builtin => extract_pdf_with_builtin(path),
+        PdfExtractor::PdfKit => ocr::pdf_text_from_pdf(path).map_err(|err| anyhow!(err)),
+        PdfExtractor::Ocr => ocr::ocr_pdf_pages(path, ocr_settings)
+            .map(|pages| {
+                pages
+                    .into_iter()
+                    .map(|page| page.text)
+                    .collect::<Vec<_>>()
+                    .join("\u{c}")
             })
-            .collect::<Vec<_>>();
+            .map_err(|err| anyhow!(err)),
+    }
+}
 
-        if !pages.is_empty() {
-            return Ok(pages);
+/// Which extractor the paper review would pick given every extractor's raw output;
+/// mirrors `extract_paper_text` without a pin.
+pub fn choose_review_extractor(outputs: &[(PdfExtractor, Result<String>)]) -> Option<PdfExtractor> {
+    let output = |wanted: PdfExtractor| -> Result<String> {
+        match outputs.iter().find(|(extractor, _)| *extractor == wanted) {
+            Some((_, Ok(text))) => Ok(text.clone()),
+            Some((_, Err(err))) => Err(anyhow!(err.to_string())),
+            None => Err(anyhow!("未运行")),
         }
+    };
+
+    if let Ok(text) = output(PdfExtractor::Pymupdf4llm)
+        && effective_len(&normalize_markdown_for_review(&text)) >= MIN_PDF_TEXT_CHARS
+    {
+        return Some(PdfExtractor::Pymupdf4llm);
+    }
+    select_pdf_text(
+        output(PdfExtractor::Pdftotext),
+        output(PdfExtractor::Builtin),
+        output(PdfExtractor::PdfKit),
+        output(PdfExtractor::Ocr),
+    )
+    .ok()
+    .map(|selection| selection.extractor)
+}
+
+pub fn extract_pdf_pages_for_chat(
+    path: &str,
+    ocr_settings: &OcrSettings,
+) -> Result<Vec<PdfPageText>> {
+    with_unlocked_pdf(path, || extract_chat_pages(path, ocr_settings))
+}
+
+fn extract_chat_pages(path: &str, ocr_settings: &OcrSettings) -> Result<Vec<PdfPageText>> {
+    match extract_pdf_pages_with_pdftotext(path).map(finish_chat_pages) {
+        Ok(pages) if pages_have_text(&pages) => return Ok(pages),
+        Ok(_) => log::warn!(target: "pdf", "paged pdftotext output too short for {}", path),
+        Err(err) => log::warn!(target: "pdf", "paged pdftotext failed for {}: {}", path, err),
+    }
+
+    // 没有 sidecar 时用内置解析器，仍能拿到真实页码和双栏阅读顺序
+    match extract_pdf_pages_with_builtin(path).map(finish_chat_pages) {
+        Ok(pages) if pages_have_text(&pages) => return Ok(pages),
+        Ok(_) => log::warn!(target: "pdf", "builtin paged output too short for {}", path),
+        Err(err) => log::warn!(
+            target: "pdf",
+            "builtin paged extraction failed for {}: {}",
+            path, err
+        ),
     }
 
-    let fallback = extract_pdf_text_detailed(path)?;
+    // 文本层为空时按页 OCR，保留真实页码供聊天引用
+    log::warn!(target: "pdf", "falling back to paged OCR for {}", path);
+    match ocr::ocr_pdf_pages(path, ocr_settings).map(finish_chat_pages) {
+        Ok(pages) if !pages.is_empty() => return Ok(pages),
+        Ok(_) => log::warn!(target: "pdf", "paged OCR produced no text for {}", path),
+        Err(err) => log::warn!(target: "pdf", "paged OCR failed for {}: {}", path, err),
+    }
+
+    let fallback = extract_pdf_text_with_ocr(path, OcrScope::FirstPage, ocr_settings)?;
     if fallback.text.trim().is_empty() {
         return Err(anyhow!("无法生成 PDF 页级文本缓存"));
     }
@@ -163,18 +288,119 @@ pub fn extract_pdf_pages_for_chat(path: &str) -> Result<Vec<PdfPageText>> {
     }])
 }
 
-pub fn extract_pdf_text_detailed(path: &str) -> Result<PdfExtracted> {
+/// Returns raw per-page pdftotext output; pages are separated by form feeds.
+fn extract_pdf_pages_with_pdftotext(path: &str) -> Result<Vec<PdfPageText>> {
+    Ok(split_pdf_pages(&extract_pdf_with_pdftotext(path)?))
+}
+
+fn extract_pdf_pages_with_builtin(path: &str) -> Result<Vec<PdfPageText>> {
+    Ok(pdf_layout::extract_layout(path)?
+        .into_iter()
+        .map(|layout| PdfPageText {
+            page: layout.page,
+            text: layout.text(),
+        })
+        .collect())
+}
+
+fn split_pdf_pages(raw: &str) -> Vec<PdfPageText> {
+    raw.split('\u{c}')
+        .enumerate()
+        .map(|(idx, chunk)| PdfPageText {
+            page: idx as u32 + 1,
+            text: chunk.to_string(),
+        })
+        .collect()
+}
+
+/// Runs the page-aware cleanup, then normalizes and drops near-empty pages.
+fn finish_chat_pages(pages: Vec<PdfPageText>) -> Vec<PdfPageText> {
+    pdf_cleanup::clean_pages(pages)
+        .into_iter()
+        .filter_map(|page| {
+            let normalized = normalize_pdf_text(&page.text);
+            if effective_len(&normalized) < 8 {
+                return None;
+            }
+            Some(PdfPageText {
+                page: page.page,
+                text: normalized,
+            })
+        })
+        .collect()
+}
+
+fn pages_have_text(pages: &[PdfPageText]) -> bool {
+    pages
+        .iter()
+        .map(|page| effective_len(&page.text))
+        .sum::<usize>()
+        >= MIN_PDF_TEXT_CHARS
+}
+
+pub fn extract_pdf_text_detailed(path: &str, ocr_settings: &OcrSettings) -> Result<PdfExtracted> {
+    with_unlocked_pdf(path, || {
+        extract_pdf_text_with_ocr(path, OcrScope::FirstPage, ocr_settings)
+    })
+}
+
+/// Whether the first page of `path` has no usable text layer, i.e. is most likely a scan
+/// that only a VLM or OCR can read.
+pub fn first_page_is_scanned(path: &str) -> Result<bool> {
+    with_unlocked_pdf(path, || {
+        let first_page = run_pdftotext(path, &["-f", "1", "-l", "1"]).or_else(|err| {
+            log::warn!(target: "pdf", "first-page pdftotext failed for {}: {}", path, err);
+            extract_pdf_pages_with_builtin(path).map(|pages| {
+                pages
+                    .into_iter()
+                    .next()
+                    .map(|page| page.text)
+                    .unwrap_or_default()
+            })
+        })?;
+        Ok(effective_len(&first_page) < MIN_PDF_TEXT_CHARS)
+    })
+}
+
+/// Resolves the password of an encrypted PDF once, then runs `extract` with it visible to
+/// every extractor. Fails with [`pdf_password::PdfLockError`] when no password opens the file.
+pub fn with_unlocked_pdf<T>(path: &str, extract: impl FnOnce() -> Result<T>) -> Result<T> {
+    let password = pdf_password::unlock(path, None)?;
+    pdf_password::with_password(password, extract)
+}
+
+fn extract_pdf_text_with_ocr(
+    path: &str,
+    ocr_scope: OcrScope,
+    ocr_settings: &OcrSettings,
+) -> Result<PdfExtracted> {
     let sidecar = extract_pdf_with_pdftotext(path);
     log_pdf_attempt(path, PdfExtractor::Pdftotext, &sidecar);
 
-    let pdfkit = if effective_len_from_result(&sidecar) >= MIN_PDF_TEXT_CHARS {
+    let builtin = if effective_len_from_result(&sidecar) >= MIN_PDF_TEXT_CHARS {
         Ok(String::new())
     } else {
-        eprintln!(
-            "[pdf] falling back to PDFKit for {} after {} chars from pdftotext",
+        log::warn!(
+            target: "pdf",
+            "falling back to builtin extractor for {} after {} chars from pdftotext",
             path,
             effective_len_from_result(&sidecar)
         );
+        let result = extract_pdf_with_builtin(path);
+        log_pdf_attempt(path, PdfExtractor::Builtin, &result);
+        result
+    };
+
+    let pdfkit = if effective_len_from_result(&sidecar) >= MIN_PDF_TEXT_CHARS
+        || effective_len_from_result(&builtin) >= MIN_PDF_TEXT_CHARS
+    {
+        Ok(String::new())
+    } else {
+        log::warn!(
+            target: "pdf",
+            "falling back to PDFKit for {} after short text layer output",
+            path
+        );
         let result = ocr::pdf_text_from_pdf(path)
             .map(|text| normalize_pdf_text(&text))
             .map_err(|err| anyhow!(err));
@@ -183,24 +409,39 @@ pub fn extract_pdf_text_detailed(path: &str) -> Result<PdfExtracted> {
     };
 
     let ocr = if effective_len_from_result(&sidecar) >= MIN_PDF_TEXT_CHARS
+        || effective_len_from_result(&builtin) >= MIN_PDF_TEXT_CHARS
         || effective_len_from_result(&pdfkit) >= MIN_PDF_TEXT_CHARS
     {
         Ok(String::new())
     } else {
-        eprintln!(
-            "[pdf] falling back to OCR for {} after short text extraction output",
+        log::warn!(
+            target: "pdf",
+            "falling back to OCR for {} after short text extraction output",
             path
         );
-        let result = ocr::ocr_from_pdf(path)
-            .map(|text| normalize_pdf_text(&text))
-            .map_err(|err| anyhow!(err));
+        // 多页结果以换页符分隔，留给评审清洗按页处理
+        let result = match ocr_scope {
+            OcrScope::FirstPage => {
+                ocr::ocr_from_pdf(path, ocr_settings).map(|text| normalize_pdf_text(&text))
+            }
+            OcrScope::AllPages => ocr::ocr_pdf_pages(path, ocr_settings).map(|pages| {
+                pages
+                    .into_iter()
+                    .map(|page| page.text)
+                    .collect::<Vec<_>>()
+                    .join("\u{c}")
+            }),
+        }
+        .map_err(|err| anyhow!(err));
         log_pdf_attempt(path, PdfExtractor::Ocr, &result);
         result
     };
 
-    let selection = select_pdf_text(sidecar, pdfkit, ocr)?;
-    eprintln!(
-        "[pdf] using {} extractor for {}",
+    let selection = select_pdf_text(sidecar, builtin, pdfkit, ocr)?;
+    log::info!(
+        target: "pdf",
+        job = logging::job_id(path).as_str();
+        "using {} extractor for {}",
         selection.extractor.label(),
         path
     );
@@ -231,99 +472,113 @@ fn effective_len(text: &str) -> usize {
     text.trim().chars().count()
 }
 
+/// Prefers the first text layer (pdftotext, builtin, PDFKit) with enough content, then
+/// OCR, then whatever short text layer output exists.
 fn select_pdf_text(
     sidecar: Result<String>,
+    builtin: Result<String>,
     pdfkit: Result<String>,
     ocr: Result<String>,
 ) -> Result<PdfSelection> {
-    let sidecar_err = sidecar.as_ref().err().map(|err| err.to_string());
-    let pdfkit_err = pdfkit.as_ref().err().map(|err| err.to_string());
-    let ocr_err = ocr.as_ref().err().map(|err| err.to_string());
-
-    let sidecar_text = sidecar.unwrap_or_default();
-    if effective_len(&sidecar_text) >= MIN_PDF_TEXT_CHARS {
-        return Ok(PdfSelection {
-            extractor: PdfExtractor::Pdftotext,
-            text: sidecar_text,
-        });
+    let mut errors = Vec::new();
+    let mut text_layers = Vec::new();
+    for (extractor, result) in [
+        (PdfExtractor::Pdftotext, sidecar),
+        (PdfExtractor::Builtin, builtin),
+        (PdfExtractor::PdfKit, pdfkit),
+    ] {
+        match result {
+            Ok(text) => text_layers.push((extractor, text)),
+            Err(err) => errors.push(format!("{}: {}", extractor.label(), err)),
+        }
     }
 
-    let pdfkit_text = pdfkit.unwrap_or_default();
-    if effective_len(&pdfkit_text) >= MIN_PDF_TEXT_CHARS {
-        return Ok(PdfSelection {
-            extractor: PdfExtractor::PdfKit,
-            text: pdfkit_text,
-        });
+    if let Some(index) = text_layers
+        .iter()
+        .position(|(_, text)| effective_len(text) >= MIN_PDF_TEXT_CHARS)
+    {
+        let (extractor, text) = text_layers.swap_remove(index);
+        return Ok(PdfSelection { extractor, text });
     }
 
-    let ocr_text = ocr.unwrap_or_default();
-    if !ocr_text.trim().is_empty() {
-        return Ok(PdfSelection {
-            extractor: PdfExtractor::Ocr,
-            text: ocr_text,
-        });
+    match ocr {
+        Ok(text) if !text.trim().is_empty() => {
+            return Ok(PdfSelection {
+                extractor: PdfExtractor::Ocr,
+                text,
+            });
+        }
+        Ok(_) => {}
+        Err(err) => errors.push(format!("OCR: {}", err)),
     }
 
-    if !pdfkit_text.trim().is_empty() {
-        return Ok(PdfSelection {
-            extractor: PdfExtractor::PdfKit,
-            text: pdfkit_text,
-        });
+    if let Some((extractor, text)) = text_layers
+        .into_iter()
+        .rev()
+        .find(|(_, text)| !text.trim().is_empty())
+    {
+        return Ok(PdfSelection { extractor, text });
     }
 
-    if !sidecar_text.trim().is_empty() {
-        return Ok(PdfSelection {
-            extractor: PdfExtractor::Pdftotext,
-            text: sidecar_text,
-        });
+    if errors.is_empty() {
+        errors.push("empty output".into());
     }
-
-    Err(anyhow!(
-        "无法提取 PDF 文本；pdftotext: {}; PDFKit: {}; OCR: {}",
-        sidecar_err.unwrap_or_else(|| "empty output".into()),
-        pdfkit_err.unwrap_or_else(|| "empty output".into()),
-        ocr_err.unwrap_or_else(|| "empty output".into()),
-    ))
+    Err(anyhow!("无法提取 PDF 文本；{}", errors.join("; ")))
 }
 
 fn log_pdf_attempt(path: &str, extractor: PdfExtractor, result: &Result<String>) {
     match result {
         Ok(text) if !text.trim().is_empty() => {
-            eprintln!(
-                "[pdf] {} produced {} chars for {}",
+            log::debug!(
+                target: "pdf",
+                "{} produced {} chars for {}",
                 extractor.label(),
                 effective_len(text),
                 path
             );
         }
         Ok(_) => {
-            eprintln!(
-                "[pdf] {} produced empty output for {}",
+            log::warn!(
+                target: "pdf",
+                "{} produced empty output for {}",
                 extractor.label(),
                 path
             );
         }
         Err(err) => {
-            eprintln!("[pdf] {} failed for {}: {}", extractor.label(), path, err);
+            log::warn!(target: "pdf", "{} failed for {}: {}", extractor.label(), path, err);
         }
     }
 }
 
+/// Full-text pdftotext output in `-layout` mode: the horizontal spacing is what lets
+/// [`pdf_cleanup::clean_pages`] put two-column pages back in reading order.
 fn extract_pdf_with_pdftotext(path: &str) -> Result<String> {
-    let binary = resolve_pdftotext_sidecar()
-        .with_context(|| format!("未找到 pdftotext sidecar for {}", PDFTOTEXT_BINARY_NAME))?;
+    run_pdftotext(path, &["-layout"])
+}
 
-    let output = Command::new(&binary)
-        .args(["-enc", "UTF-8", "-nopgbrk", path, "-"])
-        .output()
-        .with_context(|| format!("调用 pdftotext 失败: {}", binary.display()))?;
+/// Runs pdftotext over `path` with `extra_args`, e.g. a page range (`-f`, `-l`).
+fn run_pdftotext(path: &str, extra_args: &[&str]) -> Result<String> {
+    let binary = resolve_pdftotext_sidecar()?;
+
+    // poppler 只接受命令行传入的密码，加密文件改读解密后的临时副本
+    let input = pdf_password::poppler_input(path)?;
+    let output = subprocess::run(
+        Command::new(&binary)
+            .args(["-enc", "UTF-8"])
+            .args(extra_args)
+            .arg(&input)
+            .arg("-"),
+        PDFTOTEXT_LIMITS,
+    )
+    .with_context(|| format!("调用 pdftotext 失败: {}", binary.display()))?;
 
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let stderr = output.stderr_text();
     if !stderr.is_empty() {
-        eprintln!("[pdf] pdftotext stderr for {}: {}", path, stderr);
+        log::debug!(target: "pdf", "pdftotext stderr for {}: {}", path, stderr);
     }
 
-    if !output.status.success() {
+    if !output.succeeded_or_truncated() {
         let code = output
             .status
             .code()
@@ -332,32 +587,53 @@ fn extract_pdf_with_pdftotext(path: &str) -> Result<String> {
         return Err(anyhow!("退出码 {}: {}", code, stderr));
     }
 
-    Ok(normalize_pdf_text(&String::from_utf8_lossy(&output.stdout)))
+    if output.stdout_truncated {
+        log::warn!(target: "pdf", "pdftotext output for {} was truncated", path);
+    }
+
+    // 保留换页符和原始空白，分栏重排和页眉页脚清理需要按页处理
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn extract_pdf_with_builtin(path: &str) -> Result<String> {
+    Ok(pdf_layout::extract_layout(path)?
+        .iter()
+        .map(|layout| layout.text())
+        .collect::<Vec<_>>()
+        .join("\u{c}"))
 }
 
 fn extract_pdf_with_pymupdf4llm(path: &str) -> Result<String> {
     let python = resolve_pymupdf4llm_python()?;
     let script = r#"
 from pathlib import Path
+import os
+import pymupdf
 import pymupdf4llm
 import sys
 
-pdf = Path(sys.argv[1])
-md = pymupdf4llm.to_markdown(str(pdf), write_images=False, embed_images=False)
+doc = pymupdf.open(str(Path(sys.argv[1])))
+if doc.needs_pass and not doc.authenticate(os.environ.get("FYLA_PDF_PASSWORD", "")):
+    sys.exit("encrypted PDF: password rejected")
+md = pymupdf4llm.to_markdown(doc, write_images=False, embed_images=False)
 sys.stdout.write(md)
 "#;
 
-    let output = Command::new(&python)
-        .args(["-c", script, path])
-        .output()
+    let mut command = Command::new(&python);
+    command.args(["-c", script, path]);
+    // 通过环境变量传密码，避免出现在进程参数里
+    if let Some(password) = pdf_password::current_password() {
+        command.env("FYLA_PDF_PASSWORD", password);
+    }
+    let output = subprocess::run(&mut command, PYMUPDF4LLM_LIMITS)
         .with_context(|| format!("调用 pymupdf4llm 失败: {}", python.display()))?;
 
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let stderr = output.stderr_text();
     if !stderr.is_empty() {
-        eprintln!("[pdf] pymupdf4llm stderr for {}: {}", path, stderr);
+        log::debug!(target: "pdf", "pymupdf4llm stderr for {}: {}", path, stderr);
     }
 
-    if !output.status.success() {
+    if !output.succeeded_or_truncated() {
         let code = output
             .status
             .code()
@@ -365,6 +641,9 @@ sys.stdout.write(md)
             .unwrap_or_else(|| "terminated by signal".into());
         return Err(anyhow!("退出码 {}: {}", code, stderr));
     }
+    if output.stdout_truncated {
+        log::warn!(target: "pdf", "pymupdf4llm output for {} was truncated", path);
+    }
 
     Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
@@ -388,44 +667,16 @@ fn resolve_pymupdf4llm_python() -> Result<PathBuf> {
     }
 }
 
+/// Same lookup as the OCR helpers: the bundled sidecar for this target triple, else a
+/// pdftotext the user installed.
 fn resolve_pdftotext_sidecar() -> Result<PathBuf> {
-    if let Ok(path) = std::env::var("FYLA_PDFTOTEXT_PATH") {
-        let candidate = PathBuf::from(path);
-        if candidate.is_file() {
-            return Ok(candidate);
-        }
-    }
-
-    let mut candidates = Vec::new();
-    if let Ok(current_exe) = std::env::current_exe() {
-        if let Some(exe_dir) = current_exe.parent() {
-            let base_dir = if exe_dir.ends_with("deps") {
-                exe_dir.parent().unwrap_or(exe_dir)
-            } else {
-                exe_dir
-            };
-            candidates.push(base_dir.join(PDFTOTEXT_PACKAGED_NAME));
-            candidates.push(base_dir.join(PDFTOTEXT_BINARY_NAME));
-            candidates.push(base_dir.join(PDFTOTEXT_UNIVERSAL_BINARY_NAME));
-        }
-    }
-
-    let manifest_binaries = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("binaries");
-    candidates.push(manifest_binaries.join(PDFTOTEXT_BINARY_NAME));
-    candidates.push(manifest_binaries.join(PDFTOTEXT_UNIVERSAL_BINARY_NAME));
-    candidates.push(manifest_binaries.join(PDFTOTEXT_PACKAGED_NAME));
-
-    candidates
-        .into_iter()
-        .find(|candidate| candidate.is_file())
-        .ok_or_else(|| {
-            anyhow!(
-                "未找到 pdftotext sidecar（checked: {}, {}, {}）",
-                PDFTOTEXT_PACKAGED_NAME,
-                PDFTOTEXT_BINARY_NAME,
-                PDFTOTEXT_UNIVERSAL_BINARY_NAME
-            )
-        })
+    sidecar::resolve_poppler_tool(PDFTOTEXT_NAME, "FYLA_PDFTOTEXT_PATH").ok_or_else(|| {
+        anyhow!(
+            "未找到 pdftotext sidecar（{}-{}，可通过 FYLA_PDFTOTEXT_PATH 指定）",
+            PDFTOTEXT_NAME,
+            sidecar::TARGET_TRIPLE
+        )
+    })
 }
 
 fn normalize_pdf_text(input: &str) -> String {
@@ -469,7 +720,12 @@ fn normalize_pdf_text(input: &str) -> String {
 }
 
 fn normalize_pdf_text_for_review(input: &str) -> String {
-    let normalized = normalize_pdf_text(input);
+    let cleaned = pdf_cleanup::clean_pages(split_pdf_pages(input))
+        .into_iter()
+        .map(|page| page.text)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let normalized = normalize_pdf_text(&cleaned);
     let lines: Vec<String> = normalized
         .lines()
         .map(|line| line.trim().to_string())
@@ -630,33 +886,7 @@ fn extract_docx(path: &str) -> Result<String> {
 }
 
 fn extract_xlsx(path: &str) -> Result<String> {
-    use calamine::{Data, Reader, open_workbook_auto};
-    let mut workbook = open_workbook_auto(path).map_err(|e| anyhow!("无法读取 xlsx: {}", e))?;
-
-    let sheet_names = workbook.sheet_names().to_vec();
-    let first = sheet_names
-        .first()
-        .ok_or_else(|| anyhow!("xlsx 没有 sheet"))?
-        .clone();
-
-    let range = workbook
-        .worksheet_range(&first)
-        .map_err(|e| anyhow!("读取 sheet 失败: {}", e))?;
-
-    let mut lines = Vec::new();
-    for row in range.rows().take(30) {
-        let cells: Vec<String> = row
-            .iter()
-            .filter_map(|c| match c {
-                Data::Empty => None,
-                other => Some(other.to_string()),
-            })
-            .collect();
-        if !cells.is_empty() {
-            lines.push(cells.join("\t"));
-        }
-    }
-    Ok(smart_truncate(&lines.join("\n"), MAX_EXTRACTED_CHARS))
+    office::summarize_workbook(path, MAX_EXTRACTED_CHARS)
 }
 
 fn extract_txt(path: &str) -> Result<String> {
@@ -667,66 +897,7 @@ fn extract_txt(path: &str) -> Result<String> {
 }
 
 fn extract_pptx(path: &str) -> Result<String> {
-    use std::io::Read;
-    let file = std::fs::File::open(path)?;
-    let mut archive = zip::ZipArchive::new(file).map_err(|e| anyhow!("无法读取 pptx: {}", e))?;
-
-    let mut slides: Vec<(usize, String)> = Vec::new();
-    for i in 0..archive.len() {
-        let mut entry = archive
-            .by_index(i)
-            .map_err(|e| anyhow!("pptx 读取失败: {}", e))?;
-        let name = entry.name().to_string();
-        if let Some(num) = name
-            .strip_prefix("ppt/slides/slide")
-            .and_then(|s| s.strip_suffix(".xml"))
-            .and_then(|s| s.parse::<usize>().ok())
-        {
-            let mut xml = String::new();
-            entry.read_to_string(&mut xml)?;
-            let text = strip_xml_tags(&xml);
-            if !text.is_empty() {
-                slides.push((num, text));
-            }
-        }
-    }
-    slides.sort_by_key(|(num, _)| *num);
-    let result = slides
-        .into_iter()
-        .map(|(num, text)| format!("[Slide {}] {}", num, text))
-        .collect::<Vec<_>>()
-        .join("\n");
-    Ok(smart_truncate(&result, MAX_EXTRACTED_CHARS))
-}
-
-fn strip_xml_tags(xml: &str) -> String {
-    let mut text = String::new();
-    let mut in_tag = false;
-    let mut last_was_space = false;
-    for ch in xml.chars() {
-        match ch {
-            '<' => {
-                in_tag = true;
-            }
-            '>' => {
-                in_tag = false;
-                text.push(' ');
-            }
-            _ if !in_tag => {
-                if ch.is_whitespace() {
-                    if !last_was_space {
-                        text.push(' ');
-                    }
-                    last_was_space = true;
-                } else {
-                    text.push(ch);
-                    last_was_space = false;
-                }
-            }
-            _ => {}
-        }
-    }
-    text.trim().to_string()
+    office::summarize_presentation(path, MAX_EXTRACTED_CHARS)
 }
 
 fn clean_text(text: &str) -> String {
@@ -753,10 +924,27 @@ mod tests {
         assert_eq!(text, "Line one\n\nLine two");
     }
 
+    #[test]
+    fn normalize_pdf_text_for_review_cleans_across_page_breaks() {
+        let raw = ["first", "second", "third"]
+            .iter()
+            .map(|word| format!("Running Title\n{word} page has a hyphen-\nated {word} term\n"))
+            .collect::<Vec<_>>()
+            .join("\u{c}");
+
+        let text = normalize_pdf_text_for_review(&raw);
+
+        assert_eq!(
+            text,
+            "first page has a hyphenated first term\n\nsecond page has a hyphenated second term\n\nthird page has a hyphenated third term"
+        );
+    }
+
     #[test]
     fn select_pdf_text_falls_back_after_primary_error() {
         let selection = select_pdf_text(
             Err(anyhow!("parser failed")),
+            Ok(String::new()),
             Ok("Recovered from PDFKit".into()),
             Ok(String::new()),
         )
@@ -770,6 +958,7 @@ mod tests {
     fn select_pdf_text_prefers_ocr_for_effectively_empty_text_layers() {
         let selection = select_pdf_text(
             Ok("too short".into()),
+            Ok("short too".into()),
             Ok("still short".into()),
             Ok("OCR extracted content".into()),
         )
@@ -779,6 +968,55 @@ mod tests {
         assert_eq!(selection.text, "OCR extracted content");
     }
 
+    #[test]
+    fn select_pdf_text_uses_builtin_when_sidecar_is_missing() {
+        let builtin = "Builtin extractor text that is long enough to be selected.";
+        let selection = select_pdf_text(
+            Err(anyhow!("sidecar missing")),
+            Ok(builtin.into()),
+            Ok("PDFKit text that is also long enough to be selected here.".into()),
+            Ok(String::new()),
+        )
+        .expect("should choose builtin extractor");
+
+        assert_eq!(selection.extractor, PdfExtractor::Builtin);
+        assert_eq!(selection.text, builtin);
+    }
+
+    #[test]
+    fn choose_review_extractor_skips_short_pymupdf_output() {
+        let outputs = vec![
+            (PdfExtractor::Pymupdf4llm, Ok("# Title".to_string())),
+            (PdfExtractor::Pdftotext, Err(anyhow!("sidecar missing"))),
+            (
+                PdfExtractor::Builtin,
+                Ok("Builtin extractor text that is long enough to be selected.".to_string()),
+            ),
+            (PdfExtractor::Ocr, Ok(String::new())),
+        ];
+
+        assert_eq!(
+            choose_review_extractor(&outputs),
+            Some(PdfExtractor::Builtin)
+        );
+    }
+
+    #[test]
+    fn builtin_extractor_reads_repo_sample_pdf() {
+        let sample = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("sample-text.pdf");
+        let pages = extract_pdf_pages_with_builtin(sample.to_string_lossy().as_ref())
+            .expect("builtin extractor should read sample pdf");
+
+        assert_eq!(pages[0].page, 1);
+        assert!(
+            pages[0]
+                .text
+                .contains("Hello PDF sidecar sample for regression tests.")
+        );
+    }
+
     #[test]
     fn bundled_pdftotext_extracts_repo_sample_pdf() {
         let sample = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
@@ -802,7 +1040,8 @@ mod tests {
             "reported PDF should not require OCR"
         );
 
-        let text = extract_pdf_text(&path).expect("full extractor should succeed");
+        let ocr_settings = OcrSettings::from_config(&crate::config::load_config());
+        let text = extract_pdf_text(&path, &ocr_settings).expect("full extractor should succeed");
         assert!(
             text.contains("Emotional intelligence of Large Language Models"),
             "expected extracted title in text preview"
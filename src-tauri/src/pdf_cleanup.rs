@@ -0,0 +1,353 @@
+use crate::pdf::PdfPageText;
+use std::collections::{HashMap, HashSet};
+
+/// Lines at the top and bottom of each page that may hold running headers or footers.
+/// Short pages get fewer, so at least a third of their lines always count as body.
+const EDGE_LINES: usize = 3;
+const MIN_GUTTER_SPACES: usize = 3;
+const MIN_COLUMN_LINES: usize = 5;
+
+/// Page-aware cleanup shared by review extraction and paged chat text: rebuilds column
+/// order for extractors that keep horizontal spacing, strips headers and footers that
+/// repeat across pages, and joins words hyphenated across line breaks.
+///
+/// Input pages should be raw extractor output; whitespace is normalized afterwards.
+pub fn clean_pages(pages: Vec<PdfPageText>) -> Vec<PdfPageText> {
+    let pages = pages
+        .into_iter()
+        .map(|page| PdfPageText {
+            page: page.page,
+            text: reflow_columns(&page.text),
+        })
+        .collect::<Vec<_>>();
+    strip_repeated_edge_lines(pages)
+        .into_iter()
+        .map(|page| PdfPageText {
+            page: page.page,
+            text: squeeze_spaces(&join_hyphenated_lines(&page.text)),
+        })
+        .collect()
+}
+
+/// Collapses the runs of spaces `-layout` output uses for alignment once columns are in
+/// reading order; leading indentation is kept for the normalizers to trim.
+fn squeeze_spaces(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let indent = line.len() - line.trim_start().len();
+            let words = line.split_whitespace().collect::<Vec<_>>().join(" ");
+            format!("{}{}", &line[..indent], words)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Splits `-layout` style lines at a shared gutter so the left column is read before the
+/// right one. Lines that cross the gutter (titles, wide captions) end the current run.
+fn reflow_columns(text: &str) -> String {
+    let lines = text.lines().collect::<Vec<_>>();
+    let Some(gutter) = detect_gutter(&lines) else {
+        return text.to_string();
+    };
+
+    let mut output = Vec::with_capacity(lines.len());
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for line in lines {
+        let chars = line.chars().collect::<Vec<_>>();
+        if chars.len() <= gutter {
+            left.push(line.trim_end().to_string());
+            continue;
+        }
+
+        let (head, tail) = chars.split_at(gutter);
+        let head = head.iter().collect::<String>();
+        let tail = tail.iter().collect::<String>();
+        let head_has_gap = head.ends_with("  ") || head.trim().is_empty();
+        if head_has_gap && !tail.starts_with(' ') {
+            if !head.trim().is_empty() {
+                left.push(head.trim_end().to_string());
+            }
+            right.push(tail.trim_end().to_string());
+        } else {
+            output.append(&mut left);
+            output.append(&mut right);
+            output.push(line.trim_end().to_string());
+        }
+    }
+    output.append(&mut left);
+    output.append(&mut right);
+    output.join("\n")
+}
+
+/// Finds the character column where a right-hand column starts on enough lines.
+fn detect_gutter(lines: &[&str]) -> Option<usize> {
+    let mut starts = HashMap::<usize, usize>::new();
+    let mut text_lines = 0usize;
+    let mut widest = 0usize;
+    for line in lines {
+        let chars = line.chars().collect::<Vec<_>>();
+        if line.trim().is_empty() {
+            continue;
+        }
+        text_lines += 1;
+        widest = widest.max(chars.len());
+
+        let mut spaces = 0usize;
+        let mut seen_text = false;
+        for (idx, ch) in chars.iter().enumerate() {
+            if *ch == ' ' {
+                spaces += 1;
+                continue;
+            }
+            if seen_text && spaces >= MIN_GUTTER_SPACES {
+                *starts.entry(idx).or_default() += 1;
+            }
+            seen_text = true;
+            spaces = 0;
+        }
+    }
+
+    // 右栏起点允许 ±1 列的抖动
+    let (gutter, hits) = starts
+        .keys()
+        .map(|&start| {
+            let hits = (start.saturating_sub(1)..=start + 1)
+                .filter_map(|candidate| starts.get(&candidate))
+                .sum::<usize>();
+            (start, hits)
+        })
+        .max_by_key(|&(start, hits)| (hits, std::cmp::Reverse(start)))?;
+
+    let enough_lines = hits >= MIN_COLUMN_LINES && hits * 5 >= text_lines * 2;
+    let inside_page = gutter * 5 >= widest && gutter * 5 <= widest * 4;
+    (enough_lines && inside_page).then_some(gutter)
+}
+
+/// Removes header/footer lines that recur at the same edge (top or bottom) on many pages;
+/// page numbers collapse to one pattern because digit runs are normalized.
+fn strip_repeated_edge_lines(pages: Vec<PdfPageText>) -> Vec<PdfPageText> {
+    if pages.len() < 3 {
+        return pages;
+    }
+
+    let mut page_counts = HashMap::<String, usize>::new();
+    for page in &pages {
+        let keys = edge_line_indexes(&page.text)
+            .into_iter()
+            .filter_map(|(_, edge, line)| edge_line_key(edge, line))
+            .collect::<HashSet<_>>();
+        for key in keys {
+            *page_counts.entry(key).or_default() += 1;
+        }
+    }
+
+    let min_pages = 3.max((pages.len() * 2).div_ceil(5));
+    let repeated = page_counts
+        .into_iter()
+        .filter(|(_, count)| *count >= min_pages)
+        .map(|(key, _)| key)
+        .collect::<HashSet<_>>();
+    if repeated.is_empty() {
+        return pages;
+    }
+
+    pages
+        .into_iter()
+        .map(|page| {
+            let drop = edge_line_indexes(&page.text)
+                .into_iter()
+                .filter(|(_, edge, line)| {
+                    edge_line_key(*edge, line).is_some_and(|key| repeated.contains(&key))
+                })
+                .map(|(idx, _, _)| idx)
+                .collect::<HashSet<_>>();
+            let text = page
+                .text
+                .lines()
+                .enumerate()
+                .filter(|(idx, _)| !drop.contains(idx))
+                .map(|(_, line)| line)
+                .collect::<Vec<_>>()
+                .join("\n");
+            PdfPageText {
+                page: page.page,
+                text,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    Top,
+    Bottom,
+}
+
+/// Non-empty lines near the top and bottom of a page, with their line index.
+fn edge_line_indexes(text: &str) -> Vec<(usize, Edge, &str)> {
+    let lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .collect::<Vec<_>>();
+    let edge_lines = EDGE_LINES.min(lines.len() / 3);
+    let top = lines[..edge_lines]
+        .iter()
+        .map(|&(idx, line)| (idx, Edge::Top, line));
+    let bottom = lines[lines.len() - edge_lines..]
+        .iter()
+        .map(|&(idx, line)| (idx, Edge::Bottom, line));
+    top.chain(bottom).collect()
+}
+
+/// Key of an edge line: its side of the page plus the text with digit runs collapsed.
+fn edge_line_key(edge: Edge, line: &str) -> Option<String> {
+    let trimmed = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    if trimmed.is_empty() || trimmed.chars().count() > 120 {
+        return None;
+    }
+
+    let mut key = String::from(match edge {
+        Edge::Top => "top:",
+        Edge::Bottom => "bottom:",
+    });
+    let mut last_digit = false;
+    for ch in trimmed.to_lowercase().chars() {
+        if ch.is_ascii_digit() {
+            if !last_digit {
+                key.push('#');
+            }
+            last_digit = true;
+        } else {
+            key.push(ch);
+            last_digit = false;
+        }
+    }
+    Some(key)
+}
+
+/// Joins `exam-` / `ple` line pairs into `example` when the next line continues in lower case.
+fn join_hyphenated_lines(text: &str) -> String {
+    let mut output: Vec<String> = Vec::new();
+    let mut pending_join = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if pending_join
+            && trimmed.chars().next().is_some_and(char::is_lowercase)
+            && let Some(previous) = output.last_mut()
+        {
+            previous.pop();
+            previous.push_str(trimmed);
+        } else {
+            output.push(line.trim_end().to_string());
+        }
+
+        let last = output.last().map(String::as_str).unwrap_or_default();
+        let mut tail = last.chars().rev();
+        pending_join = tail.next() == Some('-') && tail.next().is_some_and(char::is_alphabetic);
+    }
+    output.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(page: u32, text: &str) -> PdfPageText {
+        PdfPageText {
+            page,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn clean_pages_strips_running_headers_and_page_numbers() {
+        let pages = ["alpha", "beta", "gamma", "delta"]
+            .iter()
+            .zip(1..)
+            .map(|(word, idx)| {
+                page(
+                    idx,
+                    &format!(
+                        "Journal of Testing, Vol. 12\n{word} opens\n{word} argues\n{word} closes\n{idx}"
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let cleaned = clean_pages(pages);
+
+        assert_eq!(cleaned[2].page, 3);
+        assert_eq!(cleaned[2].text, "gamma opens\ngamma argues\ngamma closes");
+    }
+
+    #[test]
+    fn clean_pages_keeps_short_pages_and_lines_repeated_at_other_edges() {
+        let slides = ["alpha", "beta", "gamma", "delta"]
+            .iter()
+            .map(|word| format!("Key findings\n{word} matters"))
+            .collect::<Vec<_>>();
+        let pages = slides
+            .iter()
+            .zip(1..)
+            .map(|(text, idx)| page(idx, text))
+            .collect::<Vec<_>>();
+        assert_eq!(clean_pages(pages)[1].text, "Key findings\nbeta matters");
+
+        // 同一行在有的页是页眉、有的页是页脚，不算重复的页眉页脚
+        let pages = (1..=4)
+            .map(|idx| {
+                let text = if idx % 2 == 0 {
+                    format!("Summary\nbody {idx} one\nbody {idx} two")
+                } else {
+                    format!("body {idx} one\nbody {idx} two\nSummary")
+                };
+                page(idx, &text)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            clean_pages(pages)[1].text,
+            "Summary\nbody 2 one\nbody 2 two"
+        );
+    }
+
+    #[test]
+    fn join_hyphenated_lines_merges_lowercase_continuations() {
+        let text = join_hyphenated_lines("an exam-\nple of this\nWell-\nKnown term");
+        assert_eq!(text, "an example of this\nWell-\nKnown term");
+    }
+
+    #[test]
+    fn reflow_columns_reads_left_column_first() {
+        let text = [
+            "            A Two Column Title",
+            "left one            right one",
+            "left two            right two",
+            "left three          right three",
+            "left four           right four",
+            "left five           right five",
+        ]
+        .join("\n");
+
+        let reflowed = reflow_columns(&text);
+
+        assert_eq!(
+            reflowed,
+            [
+                "            A Two Column Title",
+                "left one",
+                "left two",
+                "left three",
+                "left four",
+                "left five",
+                "right one",
+                "right two",
+                "right three",
+                "right four",
+                "right five",
+            ]
+            .join("\n")
+        );
+    }
+}
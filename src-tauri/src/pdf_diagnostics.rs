@@ -0,0 +1,212 @@
+use crate::ocr::OcrSettings;
+use crate::pdf::{self, PdfExtractor};
+use crate::{extraction_cache, pdf_layout, pdf_password};
+use anyhow::Result;
+use serde::Serialize;
+use std::time::Instant;
+
+const PREVIEW_CHARS: usize = 400;
+/// Pages with fewer non-whitespace characters count as empty for coverage.
+const MIN_PAGE_CHARS: usize = 8;
+
+/// One extractor's run over a PDF, with quality heuristics on its raw output.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractorReport {
+    pub extractor: String,
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
+    pub chars: usize,
+    /// Share of non-whitespace characters that are typographic ligatures (`ﬁ`, `ﬂ`, ...).
+    pub ligature_ratio: f64,
+    /// Share of non-whitespace characters that are replacement, private-use or control
+    /// characters, or part of unmapped `(cid:N)` glyphs.
+    pub garbage_ratio: f64,
+    /// Pages with text; `None` when the output has no page breaks (pymupdf4llm, PDFKit).
+    pub pages_with_text: Option<u32>,
+    /// `pages_with_text` divided by the document's page count.
+    pub page_coverage: Option<f64>,
+    pub preview: String,
+}
+
+/// Side-by-side comparison of every PDF extractor on one file.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfDiagnostics {
+    pub path: String,
+    pub page_count: Option<u32>,
+    /// The file needed a password from the per-file entry or the keyring.
+    pub password_protected: bool,
+    /// Extractor pinned for this file's paper review, if any.
+    pub pinned: Option<String>,
+    /// Extractor the paper review would use: the pin, or the automatic choice.
+    pub selected: Option<String>,
+    pub extractors: Vec<ExtractorReport>,
+}
+
+/// Runs every extractor on `path`, including the slow ones, and reports what each produced.
+pub fn diagnose(path: &str, ocr_settings: &OcrSettings) -> Result<PdfDiagnostics> {
+    let password = pdf_password::unlock(path, None)?;
+    let password_protected = password.is_some();
+    let page_count = pdf_password::with_password(password.clone(), || pdf_layout::page_count(path));
+
+    let mut outputs = Vec::new();
+    let mut reports = Vec::new();
+    for extractor in PdfExtractor::ALL {
+        let started_at = Instant::now();
+        let result = pdf_password::with_password(password.clone(), || {
+            pdf::run_extractor(path, extractor, ocr_settings)
+        });
+        let elapsed_ms = started_at.elapsed().as_millis() as u64;
+        log::debug!(
+            target: "pdf-diagnostics",
+            "{} finished in {}ms for {}",
+            extractor.label(),
+            elapsed_ms,
+            path
+        );
+        reports.push(build_report(extractor, &result, elapsed_ms, page_count));
+        outputs.push((extractor, result));
+    }
+
+    let pinned = extraction_cache::pinned_extractor(path);
+    let selected = pinned.clone().or_else(|| {
+        pdf::choose_review_extractor(&outputs).map(|extractor| extractor.label().into())
+    });
+    Ok(PdfDiagnostics {
+        path: path.to_string(),
+        page_count,
+        password_protected,
+        pinned,
+        selected,
+        extractors: reports,
+    })
+}
+
+fn build_report(
+    extractor: PdfExtractor,
+    result: &Result<String>,
+    elapsed_ms: u64,
+    page_count: Option<u32>,
+) -> ExtractorReport {
+    let text = match result {
+        Ok(text) => text.as_str(),
+        Err(err) => {
+            return ExtractorReport {
+                extractor: extractor.label().into(),
+                error: Some(format!("{:#}", err)),
+                elapsed_ms,
+                chars: 0,
+                ligature_ratio: 0.0,
+                garbage_ratio: 0.0,
+                pages_with_text: None,
+                page_coverage: None,
+                preview: String::new(),
+            };
+        }
+    };
+
+    let quality = TextQuality::measure(text);
+    let pages_with_text = (text.contains('\u{c}') || extractor == PdfExtractor::Ocr)
+        .then(|| count_pages_with_text(text));
+    let page_coverage = pages_with_text
+        .zip(page_count.filter(|count| *count > 0))
+        .map(|(pages, total)| (pages as f64 / total as f64).min(1.0));
+    ExtractorReport {
+        extractor: extractor.label().into(),
+        error: None,
+        elapsed_ms,
+        chars: text.chars().count(),
+        ligature_ratio: quality.ligature_ratio(),
+        garbage_ratio: quality.garbage_ratio(),
+        pages_with_text,
+        page_coverage,
+        preview: preview(text),
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct TextQuality {
+    visible: usize,
+    ligatures: usize,
+    garbage: usize,
+}
+
+impl TextQuality {
+    fn measure(text: &str) -> Self {
+        let mut quality = Self::default();
+        for ch in text.chars().filter(|ch| !ch.is_whitespace()) {
+            quality.visible += 1;
+            if ('\u{fb00}'..='\u{fb06}').contains(&ch) {
+                quality.ligatures += 1;
+            } else if is_garbage_char(ch) {
+                quality.garbage += 1;
+            }
+        }
+        // 未映射字形在 pdftotext/pdf-extract 中以 (cid:123) 形式出现
+        quality.garbage += text
+            .match_indices("(cid:")
+            .filter_map(|(start, _)| text[start..].find(')').map(|end| end + 1))
+            .sum::<usize>();
+        quality.garbage = quality.garbage.min(quality.visible);
+        quality
+    }
+
+    fn ligature_ratio(&self) -> f64 {
+        ratio(self.ligatures, self.visible)
+    }
+
+    fn garbage_ratio(&self) -> f64 {
+        ratio(self.garbage, self.visible)
+    }
+}
+
+fn is_garbage_char(ch: char) -> bool {
+    ch == '\u{fffd}' || ('\u{e000}'..='\u{f8ff}').contains(&ch) || ch.is_control()
+}
+
+fn ratio(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+fn count_pages_with_text(text: &str) -> u32 {
+    text.split('\u{c}')
+        .filter(|page| page.chars().filter(|ch| !ch.is_whitespace()).count() >= MIN_PAGE_CHARS)
+        .count() as u32
+}
+
+fn preview(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(PREVIEW_CHARS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_quality_counts_ligatures_and_garbage() {
+        let quality = TextQuality::measure("ﬁnd the ﬂow \u{fffd}(cid:12) ok");
+
+        assert_eq!(quality.ligatures, 2);
+        assert_eq!(quality.garbage, 1 + "(cid:12)".len());
+        assert_eq!(
+            quality.visible,
+            "ﬁndtheﬂow\u{fffd}(cid:12)ok".chars().count()
+        );
+    }
+
+    #[test]
+    fn count_pages_with_text_skips_blank_pages() {
+        let text = "first page text\u{c}  \n \u{c}third page text\u{c}x";
+        assert_eq!(count_pages_with_text(text), 2);
+    }
+}
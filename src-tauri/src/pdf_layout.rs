@@ -0,0 +1,510 @@
+use anyhow::{Result, anyhow};
+use pdf_extract::{Document, MediaBox, OutputDev, OutputError, Transform};
+use serde::{Deserialize, Serialize};
+
+/// A run of text on a page with its bounding box in PDF points (origin at the top-left).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfTextBlock {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub text: String,
+}
+
+/// Text blocks of one page, already sorted in reading order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfPageLayout {
+    pub page: u32,
+    pub width: f64,
+    pub height: f64,
+    pub blocks: Vec<PdfTextBlock>,
+}
+
+impl PdfPageLayout {
+    /// Page text with blocks in reading order, separated by blank lines.
+    pub fn text(&self) -> String {
+        self.blocks
+            .iter()
+            .map(|block| block.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Extracts positioned text from every page in-process, without any sidecar.
+pub fn extract_layout(path: &str) -> Result<Vec<PdfPageLayout>> {
+    let path = path.to_string();
+    // pdf-extract 遇到畸形 PDF 会直接 panic，这里兜住避免拖垮整个应用
+    std::panic::catch_unwind(move || {
+        let doc = load_document(&path)?;
+        // lopdf 只能自动解开空用户密码的文件，需要打开密码的文件交给其他提取器
+        if doc.is_encrypted() && doc.encryption_state.is_none() {
+            return Err(anyhow!("内置 PDF 解析器无法打开需要密码的文件"));
+        }
+        let mut collector = LayoutCollector::default();
+        for page in doc.get_pages().into_keys() {
+            if let Err(err) = pdf_extract::output_doc_page(&doc, &mut collector, page) {
+                log::warn!(
+                    target: "pdf",
+                    "builtin extractor skipped page {} of {}: {}",
+                    page, path, err
+                );
+            }
+        }
+        Ok(collector.pages)
+    })
+    .unwrap_or_else(|_| Err(anyhow!("内置 PDF 解析器无法处理该文件")))
+}
+
+/// Number of pages according to the page tree, or `None` when lopdf cannot read it.
+pub fn page_count(path: &str) -> Option<u32> {
+    let path = path.to_string();
+    std::panic::catch_unwind(move || {
+        let doc = load_document(&path).ok()?;
+        if doc.is_encrypted() && doc.encryption_state.is_none() {
+            return None;
+        }
+        u32::try_from(doc.get_pages().len()).ok()
+    })
+    .ok()
+    .flatten()
+}
+
+/// Loads a PDF, rebuilding the cross-reference table when its offsets are broken
+/// (poppler tolerates such files, lopdf does not).
+fn load_document(path: &str) -> Result<Document> {
+    let bytes = std::fs::read(path).map_err(|e| anyhow!("读取 PDF 失败: {}", e))?;
+    match Document::load_mem(&bytes) {
+        Ok(doc) => Ok(doc),
+        Err(err) => {
+            let repaired = rebuild_xref(&bytes).ok_or_else(|| anyhow!("解析 PDF 失败: {}", err))?;
+            log::info!(target: "pdf", "rebuilt cross-reference table for {}", path);
+            Document::load_mem(&repaired).map_err(|e| anyhow!("解析 PDF 失败: {}", e))
+        }
+    }
+}
+
+/// Appends a fresh xref section built by scanning for `N G obj` headers.
+fn rebuild_xref(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut objects = std::collections::BTreeMap::new();
+    let mut line_start = 0;
+    while line_start < bytes.len() {
+        let line_end = bytes[line_start..]
+            .iter()
+            .position(|&byte| byte == b'\n' || byte == b'\r')
+            .map_or(bytes.len(), |offset| line_start + offset);
+        if let Some((number, generation)) = parse_object_header(&bytes[line_start..line_end]) {
+            objects.insert(number, (generation, line_start));
+        }
+        line_start = line_end + 1;
+    }
+
+    let trailer_start = find_last(bytes, b"trailer")? + b"trailer".len();
+    let trailer_end = find_last(&bytes[trailer_start..], b"startxref")
+        .map_or(bytes.len(), |offset| trailer_start + offset);
+    let trailer = String::from_utf8_lossy(&bytes[trailer_start..trailer_end]);
+    // 旧的 /Prev 指向错误偏移，必须去掉
+    let trailer = trailer
+        .split("/Prev")
+        .enumerate()
+        .map(|(idx, part)| {
+            if idx == 0 {
+                part
+            } else {
+                part.trim_start()
+                    .trim_start_matches(|ch: char| ch.is_ascii_digit())
+            }
+        })
+        .collect::<String>();
+    let size = objects.keys().next_back().map_or(1, |last| last + 1);
+
+    let mut repaired = bytes.to_vec();
+    repaired.push(b'\n');
+    let xref_offset = repaired.len();
+    let mut table = format!("xref\n0 {}\n", size);
+    for number in 0..size {
+        match objects.get(&number) {
+            Some((generation, offset)) => {
+                table.push_str(&format!("{:010} {:05} n \n", offset, generation))
+            }
+            None => table.push_str("0000000000 65535 f \n"),
+        }
+    }
+    table.push_str(&format!(
+        "trailer\n{}\nstartxref\n{}\n%%EOF\n",
+        trailer.trim(),
+        xref_offset
+    ));
+    repaired.extend_from_slice(table.as_bytes());
+    Some(repaired)
+}
+
+fn parse_object_header(line: &[u8]) -> Option<(u32, u16)> {
+    let line = std::str::from_utf8(line).ok()?;
+    let mut parts = line.split_whitespace();
+    let number = parts.next()?.parse().ok()?;
+    let generation = parts.next()?.parse().ok()?;
+    parts
+        .next()
+        .filter(|keyword| keyword.starts_with("obj"))
+        .map(|_| (number, generation))
+}
+
+fn find_last(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+#[derive(Debug, Clone)]
+struct Glyph {
+    x: f64,
+    end: f64,
+    baseline: f64,
+    size: f64,
+    text: String,
+}
+
+#[derive(Default)]
+struct LayoutCollector {
+    page: u32,
+    width: f64,
+    height: f64,
+    top: f64,
+    glyphs: Vec<Glyph>,
+    pages: Vec<PdfPageLayout>,
+}
+
+impl OutputDev for LayoutCollector {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        _art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.page = page_num;
+        self.width = media_box.urx - media_box.llx;
+        self.height = media_box.ury - media_box.lly;
+        self.top = media_box.ury;
+        self.glyphs.clear();
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        let glyphs = std::mem::take(&mut self.glyphs);
+        self.pages.push(PdfPageLayout {
+            page: self.page,
+            width: self.width,
+            height: self.height,
+            blocks: layout_blocks(glyphs, self.width),
+        });
+        Ok(())
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        _spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        // 与 pdf-extract 的纯文本输出一致：取变换后的等面积字号，y 轴翻转为自上而下
+        let size = font_size * (trm.m11 * trm.m22 - trm.m12 * trm.m21).abs().sqrt();
+        if char.trim().is_empty() || !size.is_finite() || size <= 0.0 {
+            return Ok(());
+        }
+        self.glyphs.push(Glyph {
+            x: trm.m31,
+            end: trm.m31 + width * size,
+            baseline: self.top - trm.m32,
+            size,
+            text: char.to_string(),
+        });
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+/// A horizontal run of glyphs on one baseline, split at column gutters.
+#[derive(Debug, Clone)]
+struct Segment {
+    x0: f64,
+    x1: f64,
+    baseline: f64,
+    size: f64,
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Left,
+    Right,
+    Full,
+}
+
+/// Groups glyphs into lines, lines into blocks, and orders blocks for reading.
+fn layout_blocks(glyphs: Vec<Glyph>, page_width: f64) -> Vec<PdfTextBlock> {
+    let segments = group_segments(glyphs);
+    let blocks = group_blocks(segments);
+    order_blocks(blocks, page_width)
+}
+
+fn group_segments(mut glyphs: Vec<Glyph>) -> Vec<Segment> {
+    glyphs.sort_by(|a, b| a.baseline.total_cmp(&b.baseline).then(a.x.total_cmp(&b.x)));
+
+    let mut lines: Vec<Vec<Glyph>> = Vec::new();
+    for glyph in glyphs {
+        match lines.last_mut() {
+            Some(line)
+                if (glyph.baseline - line[0].baseline).abs()
+                    <= 0.4 * glyph.size.max(line[0].size) =>
+            {
+                line.push(glyph)
+            }
+            _ => lines.push(vec![glyph]),
+        }
+    }
+
+    let mut segments = Vec::new();
+    for mut line in lines {
+        line.sort_by(|a, b| a.x.total_cmp(&b.x));
+        let mut current: Option<Segment> = None;
+        let mut last: Option<Glyph> = None;
+        for glyph in line {
+            // 伪粗体会把同一字符画两遍，位置几乎重合时跳过
+            if let Some(prev) = &last
+                && prev.text == glyph.text
+                && (glyph.x - prev.x).abs() < 0.1 * glyph.size
+            {
+                continue;
+            }
+
+            let gap = last.as_ref().map(|prev| glyph.x - prev.end);
+            match (current.as_mut(), gap) {
+                (Some(segment), Some(gap)) if gap <= 1.5 * glyph.size => {
+                    if gap > 0.15 * glyph.size {
+                        segment.text.push(' ');
+                    }
+                    segment.text.push_str(&glyph.text);
+                    segment.x1 = segment.x1.max(glyph.end);
+                    segment.size = segment.size.max(glyph.size);
+                }
+                _ => {
+                    segments.extend(current.take());
+                    current = Some(Segment {
+                        x0: glyph.x,
+                        x1: glyph.end,
+                        baseline: glyph.baseline,
+                        size: glyph.size,
+                        text: glyph.text.clone(),
+                    });
+                }
+            }
+            last = Some(glyph);
+        }
+        segments.extend(current);
+    }
+    segments
+}
+
+fn group_blocks(mut segments: Vec<Segment>) -> Vec<PdfTextBlock> {
+    segments.sort_by(|a, b| {
+        a.baseline
+            .total_cmp(&b.baseline)
+            .then(a.x0.total_cmp(&b.x0))
+    });
+
+    struct OpenBlock {
+        x0: f64,
+        x1: f64,
+        top: f64,
+        last_baseline: f64,
+        size: f64,
+        lines: Vec<String>,
+    }
+
+    let mut blocks: Vec<OpenBlock> = Vec::new();
+    for segment in segments {
+        let target = blocks.iter_mut().rev().find(|block| {
+            let gap = segment.baseline - block.last_baseline;
+            segment.x0 < block.x1
+                && segment.x1 > block.x0
+                && gap > 0.0
+                && gap <= 1.6 * segment.size.max(block.size)
+        });
+        match target {
+            Some(block) => {
+                block.x0 = block.x0.min(segment.x0);
+                block.x1 = block.x1.max(segment.x1);
+                block.last_baseline = segment.baseline;
+                block.size = block.size.max(segment.size);
+                block.lines.push(segment.text);
+            }
+            None => blocks.push(OpenBlock {
+                x0: segment.x0,
+                x1: segment.x1,
+                top: segment.baseline - segment.size,
+                last_baseline: segment.baseline,
+                size: segment.size,
+                lines: vec![segment.text],
+            }),
+        }
+    }
+
+    blocks
+        .into_iter()
+        .map(|block| PdfTextBlock {
+            x: block.x0,
+            y: block.top,
+            width: block.x1 - block.x0,
+            height: block.last_baseline - block.top,
+            text: block.lines.join("\n"),
+        })
+        .collect()
+}
+
+/// Sorts blocks top-down; on two-column pages the left column is read before the
+/// right one between full-width blocks such as titles and wide figures.
+fn order_blocks(mut blocks: Vec<PdfTextBlock>, page_width: f64) -> Vec<PdfTextBlock> {
+    blocks.sort_by(|a, b| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+    if page_width <= 0.0 {
+        return blocks;
+    }
+
+    let middle = page_width / 2.0;
+    let tolerance = page_width * 0.03;
+    let column_of = |block: &PdfTextBlock| {
+        if block.x + block.width <= middle + tolerance {
+            Column::Left
+        } else if block.x >= middle - tolerance {
+            Column::Right
+        } else {
+            Column::Full
+        }
+    };
+
+    // 左右两栏在纵向上有重叠才按双栏处理，避免把单栏页面的短行误判为分栏
+    let two_columns = blocks.iter().any(|left| {
+        column_of(left) == Column::Left
+            && blocks.iter().any(|right| {
+                column_of(right) == Column::Right
+                    && right.y < left.y + left.height
+                    && left.y < right.y + right.height
+            })
+    });
+    if !two_columns {
+        return blocks;
+    }
+
+    let mut ordered = Vec::with_capacity(blocks.len());
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for block in blocks {
+        match column_of(&block) {
+            Column::Left => left.push(block),
+            Column::Right => right.push(block),
+            Column::Full => {
+                ordered.append(&mut left);
+                ordered.append(&mut right);
+                ordered.push(block);
+            }
+        }
+    }
+    ordered.append(&mut left);
+    ordered.append(&mut right);
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, x: f64, baseline: f64) -> Vec<Glyph> {
+        text.chars()
+            .enumerate()
+            .map(|(idx, ch)| Glyph {
+                x: x + idx as f64 * 5.0,
+                end: x + idx as f64 * 5.0 + 5.0,
+                baseline,
+                size: 10.0,
+                text: ch.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn malformed_font_is_reported_instead_of_panicking() {
+        // /Widths 比 FirstChar..LastChar 短，pdf-extract 取字宽时会 panic；
+        // 没有 xref 表，顺带走一遍重建
+        let pdf = "%PDF-1.4\n\
+            1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n\
+            2 0 obj\n<< /Type /Pages /Kids [3 0 R] /Count 1 >>\nendobj\n\
+            3 0 obj\n<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents 4 0 R \
+            /Resources << /Font << /F1 5 0 R >> >> >>\nendobj\n\
+            4 0 obj\n<< /Length 37 >>\nstream\nBT /F1 12 Tf 72 712 Td (Hello) Tj ET\nendstream\nendobj\n\
+            5 0 obj\n<< /Type /Font /Subtype /TrueType /BaseFont /Foo \
+            /FirstChar 0 /LastChar 300 /Widths [1 2] >>\nendobj\n\
+            trailer\n<< /Size 6 /Root 1 0 R >>\n%%EOF\n";
+        let path =
+            std::env::temp_dir().join(format!("fyla-malformed-font-{}.pdf", std::process::id()));
+        std::fs::write(&path, pdf).unwrap();
+
+        let result = std::panic::catch_unwind(|| extract_layout(&path.to_string_lossy()));
+        let _ = std::fs::remove_file(&path);
+        let err = result
+            .expect("panic escaped the builtin extractor")
+            .unwrap_err();
+        assert!(err.to_string().contains("无法处理该文件"));
+    }
+
+    #[test]
+    fn layout_blocks_joins_words_and_lines() {
+        let mut glyphs = word("Hello", 50.0, 100.0);
+        glyphs.extend(word("world", 80.0, 100.0));
+        glyphs.extend(word("again", 50.0, 112.0));
+
+        let blocks = layout_blocks(glyphs, 600.0);
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].text, "Hello world\nagain");
+        assert_eq!(blocks[0].x, 50.0);
+    }
+
+    #[test]
+    fn layout_blocks_reads_left_column_before_right() {
+        let mut glyphs = word("Title of paper", 200.0, 40.0);
+        glyphs.extend(word("left one", 40.0, 100.0));
+        glyphs.extend(word("right one", 320.0, 100.0));
+        glyphs.extend(word("left two", 40.0, 112.0));
+        glyphs.extend(word("right two", 320.0, 112.0));
+
+        let text = layout_blocks(glyphs, 600.0)
+            .into_iter()
+            .map(|block| block.text)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            text,
+            vec![
+                "Title of paper",
+                "left one\nleft two",
+                "right one\nright two"
+            ]
+        );
+    }
+}
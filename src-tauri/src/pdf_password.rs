@@ -0,0 +1,428 @@
+use crate::{config, secrets};
+use anyhow::{Result, anyhow};
+use pdf_extract::Document;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Encrypted PDFs larger than this are not parsed in memory to look for a password.
+const MAX_UNLOCK_BYTES: u64 = 256 * 1024 * 1024;
+/// Bytes read around each place the trailer can be when checking for `/Encrypt`.
+const ENCRYPT_WINDOW_BYTES: u64 = 64 * 1024;
+
+static COPY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static CURRENT_PASSWORD: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// Decrypted copies made for poppler in the current `with_password` scope, by source path.
+    static UNLOCKED_COPIES: RefCell<HashMap<String, PathBuf>> = RefCell::new(HashMap::new());
+}
+
+/// Why an encrypted PDF could not be opened. Returned inside `anyhow::Error`, so callers
+/// can `downcast_ref` it; the message is also matched by the frontend to ask for a password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfLockError {
+    /// No per-file or keyring password opens the document.
+    PasswordRequired,
+    /// The password supplied for this file is wrong.
+    WrongPassword,
+}
+
+impl fmt::Display for PdfLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PasswordRequired => write!(f, "PDF 已加密，需要密码才能读取"),
+            Self::WrongPassword => write!(f, "PDF 密码不正确"),
+        }
+    }
+}
+
+impl std::error::Error for PdfLockError {}
+
+fn file_password_registry() -> &'static Mutex<HashMap<String, String>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Checks `password` against an encrypted PDF and keeps it for this session, so later
+/// renames, reviews and chat extraction of the same file open it without asking again.
+pub fn remember_file_password(path: &str, password: &str) -> Result<()> {
+    if unlock(path, Some(password))?.is_none() {
+        log::debug!(target: "pdf", "{} opens without a password", path);
+        return Ok(());
+    }
+    if let Ok(mut registry) = file_password_registry().lock() {
+        registry.insert(path.to_string(), password.to_string());
+    }
+    Ok(())
+}
+
+/// Runs `f` with `password` as the PDF password for every extractor on this thread.
+pub fn with_password<T>(password: Option<String>, f: impl FnOnce() -> T) -> T {
+    let _scope = PasswordScope {
+        previous: CURRENT_PASSWORD.with(|slot| slot.replace(password)),
+        previous_copies: UNLOCKED_COPIES.with(|copies| copies.take()),
+    };
+    f()
+}
+
+/// Restores the outer password and deletes this scope's decrypted copies, also when `f`
+/// panics.
+struct PasswordScope {
+    previous: Option<String>,
+    previous_copies: HashMap<String, PathBuf>,
+}
+
+impl Drop for PasswordScope {
+    fn drop(&mut self) {
+        let copies = UNLOCKED_COPIES
+            .with(|copies| copies.replace(std::mem::take(&mut self.previous_copies)));
+        for copy in copies.into_values() {
+            let _ = std::fs::remove_file(copy);
+        }
+        CURRENT_PASSWORD.with(|slot| *slot.borrow_mut() = self.previous.take());
+    }
+}
+
+/// PDF password of the current thread, for extractors and for handing to worker threads.
+pub fn current_password() -> Option<String> {
+    CURRENT_PASSWORD.with(|slot| slot.borrow().clone())
+}
+
+/// File to hand to poppler tools (`pdftotext`, `pdfinfo`, `pdftoppm`). Without a password
+/// that is `path` itself; with one it is a decrypted copy only the current user can read,
+/// because poppler takes passwords only on the command line, where other processes see them.
+/// The copy is deleted when the surrounding `with_password` ends.
+pub fn poppler_input(path: &str) -> Result<PathBuf> {
+    let Some(password) = current_password() else {
+        return Ok(PathBuf::from(path));
+    };
+    let cached = UNLOCKED_COPIES.with(|copies| copies.borrow().get(path).cloned());
+    if let Some(copy) = cached.filter(|copy| copy.is_file()) {
+        return Ok(copy);
+    }
+    let Some(mut doc) = load_encrypted(path)? else {
+        return Ok(PathBuf::from(path));
+    };
+    catch_unwind(AssertUnwindSafe(|| doc.decrypt(&password)))
+        .map_err(|_| anyhow!("解密 PDF 失败"))?
+        .map_err(|e| anyhow!("解密 PDF 失败: {}", e))?;
+
+    let copy = std::env::temp_dir().join(format!(
+        "fyla-unlocked-{}-{}.pdf",
+        std::process::id(),
+        COPY_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let written = options
+        .open(&copy)
+        .map_err(anyhow::Error::from)
+        .and_then(|mut file| doc.save_to(&mut file).map_err(anyhow::Error::from));
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&copy);
+        return Err(anyhow!("写入解密后的临时 PDF 失败: {}", err));
+    }
+    UNLOCKED_COPIES.with(|copies| copies.borrow_mut().insert(path.to_string(), copy.clone()));
+    Ok(copy)
+}
+
+/// Finds the password that opens `path`. Returns `None` when the file is not encrypted or
+/// opens with an empty user password (permission-only protection). Otherwise tries the
+/// explicit password, the one remembered for this file and then the configured keyring.
+pub fn unlock(path: &str, password: Option<&str>) -> Result<Option<String>> {
+    let Some(doc) = load_encrypted(path)? else {
+        return Ok(None);
+    };
+    let opens = |candidate: &str| {
+        catch_unwind(AssertUnwindSafe(|| {
+            doc.authenticate_password(candidate).is_ok()
+        }))
+        .unwrap_or(false)
+    };
+    if opens("") {
+        return Ok(None);
+    }
+
+    if let Some(password) = password {
+        return if opens(password) {
+            Ok(Some(password.to_string()))
+        } else {
+            Err(PdfLockError::WrongPassword.into())
+        };
+    }
+
+    let remembered = file_password_registry()
+        .lock()
+        .ok()
+        .and_then(|registry| registry.get(path).cloned());
+    let candidates = remembered
+        .into_iter()
+        .chain(configured_passwords())
+        .collect::<Vec<_>>();
+    match candidates.into_iter().find(|candidate| opens(candidate)) {
+        Some(found) => {
+            log::info!(target: "pdf", "unlocked encrypted PDF {}", path);
+            Ok(Some(found))
+        }
+        None => Err(PdfLockError::PasswordRequired.into()),
+    }
+}
+
+/// Keyring passwords from the settings, read from secure storage once and kept until
+/// [`forget_configured_passwords`], so a batch of encrypted files does not reload them.
+fn configured_passwords() -> Vec<String> {
+    let mut cached = configured_password_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    cached
+        .get_or_insert_with(|| {
+            let configured = secrets::pdf_passwords(&config::load_config()).unwrap_or_else(|err| {
+                log::warn!(target: "pdf", "failed to read configured PDF passwords: {:#}", err);
+                String::new()
+            });
+            keyring_passwords(&configured)
+        })
+        .clone()
+}
+
+fn configured_password_cache() -> &'static Mutex<Option<Vec<String>>> {
+    static CACHE: Mutex<Option<Vec<String>>> = Mutex::new(None);
+    &CACHE
+}
+
+/// Drops the cached keyring passwords; called when the settings are saved.
+pub fn forget_configured_passwords() {
+    *configured_password_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Parses `path` when it is an encrypted PDF; `None` for unencrypted, unparsable or
+/// oversized files, whose problems the extractors report themselves. Only files whose
+/// trailer names an `/Encrypt` dictionary are read in full.
+fn load_encrypted(path: &str) -> Result<Option<Document>> {
+    if !looks_encrypted(path)? {
+        return Ok(None);
+    }
+    let size = std::fs::metadata(path)
+        .map_err(|e| anyhow!("读取 PDF 失败: {}", e))?
+        .len();
+    if size > MAX_UNLOCK_BYTES {
+        log::warn!(target: "pdf", "skipped password check for {} ({} bytes)", path, size);
+        return Ok(None);
+    }
+    let bytes = std::fs::read(path).map_err(|e| anyhow!("读取 PDF 失败: {}", e))?;
+    // lopdf 遇到畸形 PDF 可能 panic，和其他 lopdf 调用一样兜住
+    let doc = catch_unwind(|| Document::load_mem(&bytes))
+        .ok()
+        .and_then(Result::ok);
+    Ok(doc.filter(Document::is_encrypted))
+}
+
+/// Cheap pre-check before parsing: encrypted PDFs reference an `/Encrypt` dictionary.
+pub fn has_encrypt_marker(bytes: &[u8]) -> bool {
+    bytes
+        .windows(b"/Encrypt".len())
+        .any(|window| window == b"/Encrypt")
+}
+
+/// Whether `path` names an `/Encrypt` dictionary, reading only where a trailer can be:
+/// the end of the file, the cross-reference stream `startxref` points to, and the start
+/// (the first-page trailer of linearized files). Small files are scanned whole.
+pub fn looks_encrypted(path: &str) -> Result<bool> {
+    let mut file = std::fs::File::open(path).map_err(|e| anyhow!("读取 PDF 失败: {}", e))?;
+    let len = file.metadata()?.len();
+    let mut read_at = |start: u64| -> Result<Vec<u8>> {
+        let mut window = Vec::new();
+        file.seek(SeekFrom::Start(start))?;
+        (&mut file)
+            .take(ENCRYPT_WINDOW_BYTES)
+            .read_to_end(&mut window)?;
+        Ok(window)
+    };
+    if len <= 3 * ENCRYPT_WINDOW_BYTES {
+        let mut bytes = Vec::new();
+        for start in (0..len).step_by(ENCRYPT_WINDOW_BYTES as usize) {
+            bytes.extend(read_at(start)?);
+        }
+        return Ok(has_encrypt_marker(&bytes));
+    }
+    let tail = read_at(len - ENCRYPT_WINDOW_BYTES)?;
+    if has_encrypt_marker(&tail) || has_encrypt_marker(&read_at(0)?) {
+        return Ok(true);
+    }
+    match startxref_offset(&tail) {
+        Some(offset) if offset < len => Ok(has_encrypt_marker(&read_at(offset)?)),
+        _ => Ok(false),
+    }
+}
+
+/// Offset after the last `startxref` keyword in `tail`.
+fn startxref_offset(tail: &[u8]) -> Option<u64> {
+    let keyword = b"startxref";
+    let position = tail
+        .windows(keyword.len())
+        .rposition(|window| window == keyword)?;
+    let digits = tail[position + keyword.len()..]
+        .iter()
+        .skip_while(|byte| byte.is_ascii_whitespace())
+        .take_while(|byte| byte.is_ascii_digit())
+        .map(|byte| *byte as char)
+        .collect::<String>();
+    digits.parse().ok()
+}
+
+/// One password per line; blank lines are ignored but surrounding spaces are kept,
+/// because they can be part of a password.
+fn keyring_passwords(raw: &str) -> Vec<String> {
+    raw.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Writes a one-page PDF that needs `user_password` to open (owner password `owner`).
+#[cfg(test)]
+pub fn write_encrypted_pdf(path: &std::path::Path, user_password: &str) {
+    use pdf_extract::{Dictionary, EncryptionState, EncryptionVersion, Object, Permissions};
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let mut page = Dictionary::new();
+    page.set("Type", Object::Name(b"Page".to_vec()));
+    page.set("Parent", Object::Reference(pages_id));
+    page.set("MediaBox", vec![0.into(), 0.into(), 612.into(), 792.into()]);
+    let page_id = doc.add_object(page);
+    let mut pages = Dictionary::new();
+    pages.set("Type", Object::Name(b"Pages".to_vec()));
+    pages.set("Kids", vec![Object::Reference(page_id)]);
+    pages.set("Count", 1);
+    doc.objects.insert(pages_id, Object::Dictionary(pages));
+    let mut catalog = Dictionary::new();
+    catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+    catalog.set("Pages", Object::Reference(pages_id));
+    let catalog_id = doc.add_object(catalog);
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+    let file_id = Object::string_literal(b"fyla-test-file-id".to_vec());
+    doc.trailer.set("ID", vec![file_id.clone(), file_id]);
+
+    let state = EncryptionState::try_from(EncryptionVersion::V2 {
+        document: &doc,
+        owner_password: "owner",
+        user_password,
+        key_length: 128,
+        permissions: Permissions::all(),
+    })
+    .unwrap();
+    doc.encrypt(&state).unwrap();
+    doc.save(path).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyring_passwords_skip_blank_lines() {
+        assert_eq!(
+            keyring_passwords("123456\n\n  \n birthday \n"),
+            vec!["123456".to_string(), " birthday ".to_string()]
+        );
+    }
+
+    #[test]
+    fn unlock_passes_through_unencrypted_pdf() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/sample-text.pdf");
+        assert_eq!(unlock(path, None).unwrap(), None);
+    }
+
+    #[test]
+    fn unlock_checks_explicit_password_for_encrypted_pdf() {
+        let path = std::env::temp_dir().join(format!("fyla-locked-{}.pdf", std::process::id()));
+        write_encrypted_pdf(&path, "statement-2024");
+        let path_str = path.to_string_lossy().to_string();
+
+        let wrong = unlock(&path_str, Some("000000")).expect_err("wrong password must fail");
+        let right = unlock(&path_str, Some("statement-2024"));
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            wrong.downcast_ref::<PdfLockError>(),
+            Some(&PdfLockError::WrongPassword)
+        );
+        assert_eq!(right.unwrap().as_deref(), Some("statement-2024"));
+    }
+
+    #[test]
+    fn looks_encrypted_reads_only_where_a_trailer_can_be() {
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/sample-text.pdf");
+        assert!(!looks_encrypted(sample).unwrap());
+        let locked =
+            std::env::temp_dir().join(format!("fyla-looks-locked-{}.pdf", std::process::id()));
+        write_encrypted_pdf(&locked, "statement-2024");
+        assert!(looks_encrypted(&locked.to_string_lossy()).unwrap());
+        let _ = std::fs::remove_file(&locked);
+
+        // 大文件：/Encrypt 只在 startxref 指向的交叉引用流里
+        let pad = vec![b' '; 200 * 1024];
+        let mut bytes = b"%PDF-1.5\n".to_vec();
+        bytes.extend_from_slice(&pad);
+        let xref = bytes.len();
+        bytes.extend_from_slice(b"9 0 obj\n<< /Type /XRef /Encrypt 5 0 R >>\n");
+        bytes.extend_from_slice(&pad);
+        let path = std::env::temp_dir().join(format!("fyla-xref-{}.pdf", std::process::id()));
+        let with_startxref = |offset: usize| {
+            let mut file = bytes.clone();
+            file.extend_from_slice(format!("startxref\n{}\n%%EOF\n", offset).as_bytes());
+            std::fs::write(&path, file).unwrap();
+            looks_encrypted(&path.to_string_lossy()).unwrap()
+        };
+        assert!(with_startxref(xref));
+        // 不整份扫描：文件中间的 /Encrypt 不在任何窗口里
+        assert!(!with_startxref(0));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn with_password_restores_previous_value() {
+        with_password(Some("outer".into()), || {
+            with_password(None, || assert_eq!(current_password(), None));
+            assert_eq!(current_password().as_deref(), Some("outer"));
+        });
+        assert_eq!(current_password(), None);
+    }
+
+    #[test]
+    fn poppler_input_is_a_private_decrypted_copy_for_the_scope() {
+        let path =
+            std::env::temp_dir().join(format!("fyla-locked-copy-{}.pdf", std::process::id()));
+        write_encrypted_pdf(&path, "statement-2024");
+        let path_str = path.to_string_lossy().to_string();
+
+        assert_eq!(poppler_input(&path_str).unwrap(), path);
+        let copy = with_password(Some("statement-2024".into()), || {
+            let copy = poppler_input(&path_str).unwrap();
+            assert_ne!(copy, path);
+            assert_eq!(poppler_input(&path_str).unwrap(), copy);
+            let bytes = std::fs::read(&copy).unwrap();
+            assert!(!has_encrypt_marker(&bytes));
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = std::fs::metadata(&copy).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+            copy
+        });
+        let _ = std::fs::remove_file(&path);
+        assert!(!copy.exists());
+    }
+}
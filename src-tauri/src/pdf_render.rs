@@ -0,0 +1,153 @@
+use crate::subprocess::{self, ProcessLimits};
+use crate::{extraction_cache, pdf, pdf_password, sidecar};
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(target_os = "macos")]
+use std::ffi::CString;
+#[cfg(target_os = "macos")]
+use std::os::raw::c_char;
+
+#[cfg(target_os = "macos")]
+unsafe extern "C" {
+    fn render_pdf_page_png(
+        path: *const c_char,
+        page_number: i32,
+        password: *const c_char,
+        max_edge: i32,
+        output_path: *const c_char,
+    ) -> i32;
+}
+
+const PDFTOPPM_NAME: &str = "pdftoppm";
+const PDFTOPPM_LIMITS: ProcessLimits = ProcessLimits::new(60, 1024 * 1024);
+/// Longest edge of rendered pages: body text and figure labels stay legible for a VLM
+/// without sending a full 300 dpi scan.
+pub const RENDER_MAX_EDGE: u32 = 1600;
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Renders `page` (1-based) of `path` to `root/{file hash}/page-{n}.png`, reusing an
+/// earlier render of the same file content.
+pub fn render_page_cached(path: &str, page: u32, root: &Path) -> Result<PathBuf> {
+    let hash = extraction_cache::file_hash(path)?;
+    let dir = root.join(&hash);
+    let target = dir.join(format!("page-{}.png", page));
+    if target.is_file() {
+        log::debug!(target: "pdf-render", "cache hit page {} of {}", page, path);
+        return Ok(target);
+    }
+
+    std::fs::create_dir_all(&dir).map_err(|e| anyhow!("创建页面图片目录失败: {}", e))?;
+    // 先渲染到临时文件再改名，并发请求同一页时不会读到半张图
+    let temp = dir.join(format!(
+        "page-{}.{}-{}.png",
+        page,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = render_page_png(path, page, RENDER_MAX_EDGE, &temp)
+        .and_then(|()| std::fs::rename(&temp, &target).map_err(Into::into));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result.map(|()| target)
+}
+
+/// Renders one page of `path` to a PNG at `output`, scaled so its longest edge is
+/// `max_edge` pixels. Encrypted PDFs are opened with the per-file or keyring password.
+pub fn render_page_png(path: &str, page: u32, max_edge: u32, output: &Path) -> Result<()> {
+    if page == 0 {
+        return Err(anyhow!("PDF 页码从 1 开始"));
+    }
+    pdf::with_unlocked_pdf(path, || {
+        let pdftoppm = match sidecar::resolve_poppler_tool(PDFTOPPM_NAME, "FYLA_PDFTOPPM_PATH") {
+            Some(binary) => render_with_pdftoppm(&binary, path, page, max_edge, output),
+            None => Err(anyhow!("未找到 pdftoppm")),
+        };
+        match pdftoppm {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                log::warn!(target: "pdf-render", "pdftoppm unavailable for {}: {:#}", path, err);
+                render_natively(path, page, max_edge, output)
+            }
+        }
+    })
+}
+
+fn render_with_pdftoppm(
+    binary: &Path,
+    path: &str,
+    page: u32,
+    max_edge: u32,
+    output: &Path,
+) -> Result<()> {
+    // -singlefile 时 pdftoppm 只在前缀后追加 .png
+    let prefix = output.with_extension("");
+    let page_arg = page.to_string();
+    let edge_arg = max_edge.to_string();
+    let input = pdf_password::poppler_input(path)?;
+    let result = subprocess::run(
+        Command::new(binary)
+            .args([
+                "-f",
+                &page_arg,
+                "-l",
+                &page_arg,
+                "-singlefile",
+                "-png",
+                "-scale-to",
+                &edge_arg,
+            ])
+            .arg(&input)
+            .arg(&prefix),
+        PDFTOPPM_LIMITS,
+    )
+    .map_err(|e| anyhow!("调用 pdftoppm 失败 ({}): {:#}", binary.display(), e))?;
+    if !result.succeeded() {
+        return Err(anyhow!("pdftoppm 渲染失败: {}", result.stderr_text()));
+    }
+
+    let mut written = prefix.into_os_string();
+    written.push(".png");
+    let written = PathBuf::from(written);
+    if written != output {
+        std::fs::rename(&written, output)?;
+    }
+    if !output.is_file() {
+        return Err(anyhow!("pdftoppm 未生成页面图片"));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn render_natively(path: &str, page: u32, max_edge: u32, output: &Path) -> Result<()> {
+    let c_path = CString::new(path).map_err(|_| anyhow!("路径包含非法字符"))?;
+    let c_output = CString::new(output.to_string_lossy().as_bytes())
+        .map_err(|_| anyhow!("路径包含非法字符"))?;
+    let c_password = pdf_password::current_password().and_then(|value| CString::new(value).ok());
+    let password_ptr = c_password
+        .as_ref()
+        .map_or(std::ptr::null(), |value| value.as_ptr());
+    let rendered = unsafe {
+        render_pdf_page_png(
+            c_path.as_ptr(),
+            page as i32,
+            password_ptr,
+            max_edge as i32,
+            c_output.as_ptr(),
+        )
+    };
+    if rendered == 1 && output.is_file() {
+        Ok(())
+    } else {
+        Err(anyhow!("渲染 PDF 第 {} 页失败", page))
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn render_natively(_path: &str, _page: u32, _max_edge: u32, _output: &Path) -> Result<()> {
+    Err(anyhow!("渲染 PDF 页面需要安装 pdftoppm（poppler）"))
+}
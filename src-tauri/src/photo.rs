@@ -0,0 +1,383 @@
+use crate::metadata;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const GAZETTEER_TSV: &str = include_str!("../data/gazetteer.tsv");
+const MAX_PLACE_DISTANCE_KM: f64 = 80.0;
+const XMP_SCAN_BYTES: u64 = 512 * 1024;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Capture details read from EXIF/XMP, used to name photos without a network call.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PhotoInfo {
+    /// Capture time as `YYYY-MM-DD HH:MM:SS`.
+    pub taken_at: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens: Option<String>,
+    pub software: Option<String>,
+    /// EXIF orientation (1-8).
+    pub orientation: Option<u16>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+    pub place: Option<Place>,
+    pub is_screenshot: bool,
+}
+
+/// Nearest gazetteer entry to the photo's GPS position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Place {
+    pub city: String,
+    pub country: String,
+    pub distance_km: f64,
+}
+
+impl PhotoInfo {
+    /// Capture date as `YYYY-MM-DD`.
+    pub fn taken_date(&self) -> Option<String> {
+        self.taken_at
+            .as_deref()
+            .and_then(|value| value.get(..10))
+            .map(str::to_string)
+    }
+
+    /// Make and model joined without repeating the brand ("Apple iPhone 15 Pro").
+    pub fn camera(&self) -> Option<String> {
+        match (self.make.as_deref(), self.model.as_deref()) {
+            (Some(make), Some(model)) => {
+                let brand = make.split_whitespace().next().unwrap_or(make);
+                if model.to_lowercase().starts_with(&brand.to_lowercase()) {
+                    Some(model.to_string())
+                } else {
+                    Some(format!("{} {}", brand, model))
+                }
+            }
+            (None, Some(model)) => Some(model.to_string()),
+            (Some(make), None) => Some(make.to_string()),
+            (None, None) => None,
+        }
+    }
+
+    fn merge_missing(&mut self, other: PhotoInfo) {
+        if self.taken_at.is_none() {
+            self.taken_at = other.taken_at;
+        }
+        if self.make.is_none() {
+            self.make = other.make;
+        }
+        if self.model.is_none() {
+            self.model = other.model;
+        }
+        if self.lens.is_none() {
+            self.lens = other.lens;
+        }
+        if self.software.is_none() {
+            self.software = other.software;
+        }
+        if self.orientation.is_none() {
+            self.orientation = other.orientation;
+        }
+        if self.latitude.is_none() || self.longitude.is_none() {
+            self.latitude = other.latitude;
+            self.longitude = other.longitude;
+        }
+        if self.altitude.is_none() {
+            self.altitude = other.altitude;
+        }
+        self.is_screenshot |= other.is_screenshot;
+    }
+}
+
+/// Reads EXIF and XMP from an image file, then reverse-geocodes GPS with the bundled gazetteer.
+pub fn read_photo_info(path: &str) -> Option<PhotoInfo> {
+    let mut info = read_exif(path).unwrap_or_default();
+    if let Some(xmp) = read_xmp_packet(path) {
+        info.merge_missing(parse_photo_xmp(&xmp));
+    }
+
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    info.is_screenshot |= looks_like_screenshot_name(&file_name);
+    if let Some(software) = info.software.as_deref() {
+        info.is_screenshot |= mentions_screenshot(software);
+    }
+
+    if let (Some(lat), Some(lon)) = (info.latitude, info.longitude) {
+        info.place = nearest_place(lat, lon);
+    }
+
+    (info != PhotoInfo::default()).then_some(info)
+}
+
+fn read_exif(path: &str) -> Option<PhotoInfo> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut reader = std::io::BufReader::new(file);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+
+    let ascii = |tag: exif::Tag| {
+        exif.get_field(tag, exif::In::PRIMARY)
+            .and_then(|field| match &field.value {
+                exif::Value::Ascii(values) => values
+                    .first()
+                    .map(|bytes| String::from_utf8_lossy(bytes).trim().to_string()),
+                _ => None,
+            })
+            .filter(|value| !value.is_empty())
+    };
+    let rationals = |tag: exif::Tag| {
+        exif.get_field(tag, exif::In::PRIMARY)
+            .and_then(|field| match &field.value {
+                exif::Value::Rational(values) => {
+                    Some(values.iter().map(|r| r.to_f64()).collect::<Vec<_>>())
+                }
+                _ => None,
+            })
+    };
+
+    let taken_at = ascii(exif::Tag::DateTimeOriginal)
+        .or_else(|| ascii(exif::Tag::DateTime))
+        .and_then(|raw| normalize_exif_datetime(&raw));
+    let orientation = exif
+        .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .map(|value| value as u16);
+
+    let latitude = rationals(exif::Tag::GPSLatitude)
+        .and_then(|dms| dms_to_degrees(&dms))
+        .map(|deg| apply_hemisphere(deg, ascii(exif::Tag::GPSLatitudeRef).as_deref()));
+    let longitude = rationals(exif::Tag::GPSLongitude)
+        .and_then(|dms| dms_to_degrees(&dms))
+        .map(|deg| apply_hemisphere(deg, ascii(exif::Tag::GPSLongitudeRef).as_deref()));
+    let altitude = rationals(exif::Tag::GPSAltitude).and_then(|values| values.first().copied());
+
+    // iOS 截图会把 "Screenshot" 写进 UserComment
+    let user_comment_is_screenshot = exif
+        .get_field(exif::Tag::UserComment, exif::In::PRIMARY)
+        .map(|field| mentions_screenshot(&field.display_value().to_string()))
+        .unwrap_or(false);
+
+    Some(PhotoInfo {
+        taken_at,
+        make: ascii(exif::Tag::Make),
+        model: ascii(exif::Tag::Model),
+        lens: ascii(exif::Tag::LensModel),
+        software: ascii(exif::Tag::Software),
+        orientation,
+        latitude,
+        longitude,
+        altitude,
+        place: None,
+        is_screenshot: user_comment_is_screenshot,
+    })
+}
+
+fn read_xmp_packet(path: &str) -> Option<String> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut bytes = Vec::new();
+    file.take(XMP_SCAN_BYTES).read_to_end(&mut bytes).ok()?;
+    let text = String::from_utf8_lossy(&bytes);
+    let start = text.find("<x:xmpmeta")?;
+    let end = text[start..].find("</x:xmpmeta>")? + start + "</x:xmpmeta>".len();
+    Some(text[start..end].to_string())
+}
+
+/// Extracts camera, time, GPS and screenshot hints from an XMP packet.
+fn parse_photo_xmp(xmp: &str) -> PhotoInfo {
+    let first = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| metadata::xmp_values(xmp, name).into_iter().next())
+    };
+
+    let latitude = first(&["exif:GPSLatitude"]).and_then(|raw| parse_xmp_coordinate(&raw));
+    let longitude = first(&["exif:GPSLongitude"]).and_then(|raw| parse_xmp_coordinate(&raw));
+    let is_screenshot = first(&["exif:UserComment"])
+        .map(|comment| mentions_screenshot(&comment))
+        .unwrap_or(false);
+
+    PhotoInfo {
+        taken_at: first(&[
+            "exif:DateTimeOriginal",
+            "xmp:CreateDate",
+            "photoshop:DateCreated",
+        ])
+        .and_then(|raw| normalize_exif_datetime(&raw)),
+        make: first(&["tiff:Make"]),
+        model: first(&["tiff:Model"]),
+        lens: first(&["exifEX:LensModel", "aux:Lens"]),
+        software: first(&["tiff:Software", "xmp:CreatorTool"]),
+        orientation: first(&["tiff:Orientation"]).and_then(|raw| raw.trim().parse().ok()),
+        latitude,
+        longitude,
+        altitude: None,
+        place: None,
+        is_screenshot,
+    }
+}
+
+/// Accepts `2024:07:14 09:30:00` (EXIF) and `2024-07-14T09:30:00+09:00` (XMP).
+fn normalize_exif_datetime(raw: &str) -> Option<String> {
+    let digits = raw
+        .chars()
+        .filter(|ch| ch.is_ascii_digit())
+        .take(14)
+        .collect::<String>();
+    if digits.len() < 8 {
+        return None;
+    }
+    let date = chrono::NaiveDate::parse_from_str(&digits[..8], "%Y%m%d").ok()?;
+    let time = if digits.len() >= 14 {
+        chrono::NaiveTime::parse_from_str(&digits[8..14], "%H%M%S").ok()?
+    } else {
+        chrono::NaiveTime::MIN
+    };
+    Some(date.and_time(time).format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+fn dms_to_degrees(dms: &[f64]) -> Option<f64> {
+    let degrees = *dms.first()?;
+    let minutes = dms.get(1).copied().unwrap_or(0.0);
+    let seconds = dms.get(2).copied().unwrap_or(0.0);
+    let value = degrees + minutes / 60.0 + seconds / 3600.0;
+    value.is_finite().then_some(value)
+}
+
+fn apply_hemisphere(degrees: f64, reference: Option<&str>) -> f64 {
+    match reference.map(|r| r.trim().to_ascii_uppercase()) {
+        Some(r) if r.starts_with('S') || r.starts_with('W') => -degrees,
+        _ => degrees,
+    }
+}
+
+/// Parses XMP GPS coordinates such as `35,0.7014N` or `135,46.086E`.
+fn parse_xmp_coordinate(raw: &str) -> Option<f64> {
+    let trimmed = raw.trim();
+    let hemisphere = trimmed
+        .chars()
+        .last()
+        .filter(|ch| ch.is_ascii_alphabetic())?;
+    let numbers = trimmed[..trimmed.len() - 1]
+        .split(',')
+        .map(|part| part.trim().parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let degrees = dms_to_degrees(&numbers)?;
+    Some(apply_hemisphere(degrees, Some(&hemisphere.to_string())))
+}
+
+fn mentions_screenshot(value: &str) -> bool {
+    let lower = value.to_lowercase();
+    lower.contains("screenshot") || lower.contains("screen shot") || value.contains("截屏")
+}
+
+fn looks_like_screenshot_name(file_name: &str) -> bool {
+    let lower = file_name.to_lowercase();
+    lower.starts_with("screenshot")
+        || lower.starts_with("screen shot")
+        || lower.starts_with("cleanshot")
+        || file_name.starts_with("截屏")
+        || file_name.starts_with("屏幕截图")
+        || file_name.starts_with("屏幕快照")
+}
+
+struct GazetteerEntry {
+    city: &'static str,
+    country: &'static str,
+    latitude: f64,
+    longitude: f64,
+}
+
+fn gazetteer() -> &'static [GazetteerEntry] {
+    static ENTRIES: OnceLock<Vec<GazetteerEntry>> = OnceLock::new();
+    ENTRIES.get_or_init(|| {
+        GAZETTEER_TSV
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                Some(GazetteerEntry {
+                    city: fields.next()?,
+                    country: fields.next()?,
+                    latitude: fields.next()?.parse().ok()?,
+                    longitude: fields.next()?.parse().ok()?,
+                })
+            })
+            .collect()
+    })
+}
+
+/// Finds the closest gazetteer city within `MAX_PLACE_DISTANCE_KM`.
+pub fn nearest_place(latitude: f64, longitude: f64) -> Option<Place> {
+    gazetteer()
+        .iter()
+        .map(|entry| {
+            let distance = haversine_km(latitude, longitude, entry.latitude, entry.longitude);
+            (entry, distance)
+        })
+        .filter(|(_, distance)| *distance <= MAX_PLACE_DISTANCE_KM)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entry, distance)| Place {
+            city: entry.city.to_string(),
+            country: entry.country.to_string(),
+            distance_km: (distance * 10.0).round() / 10.0,
+        })
+}
+
+fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_place_resolves_fushimi_inari_to_kyoto() {
+        let place = nearest_place(34.9671, 135.7727).expect("place");
+        assert_eq!(place.city, "Kyoto");
+        assert_eq!(place.country, "Japan");
+        assert!(place.distance_km < 10.0);
+    }
+
+    #[test]
+    fn nearest_place_ignores_open_ocean() {
+        assert_eq!(nearest_place(0.0, -140.0), None);
+    }
+
+    #[test]
+    fn parse_photo_xmp_reads_gps_camera_and_screenshot_hint() {
+        let xmp = r#"<x:xmpmeta><rdf:RDF><rdf:Description
+            exif:GPSLatitude="34,58.026N" exif:GPSLongitude="135,46.362E"
+            exif:DateTimeOriginal="2024-07-14T09:30:00+09:00" tiff:Make="Apple" tiff:Model="iPhone 15 Pro">
+            <exif:UserComment><rdf:Alt><rdf:li xml:lang="x-default">Screenshot</rdf:li></rdf:Alt></exif:UserComment>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let info = parse_photo_xmp(xmp);
+
+        assert_eq!(info.taken_date().as_deref(), Some("2024-07-14"));
+        assert_eq!(info.camera().as_deref(), Some("Apple iPhone 15 Pro"));
+        assert!((info.latitude.unwrap() - 34.9671).abs() < 0.001);
+        assert!((info.longitude.unwrap() - 135.7727).abs() < 0.001);
+        assert!(info.is_screenshot);
+    }
+
+    #[test]
+    fn camera_does_not_repeat_brand() {
+        let info = PhotoInfo {
+            make: Some("Canon".into()),
+            model: Some("Canon EOS R6".into()),
+            ..PhotoInfo::default()
+        };
+        assert_eq!(info.camera().as_deref(), Some("Canon EOS R6"));
+        assert!(looks_like_screenshot_name("截屏2024-07-14 09.30.00.png"));
+    }
+}
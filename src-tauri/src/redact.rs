@@ -0,0 +1,440 @@
+use crate::config::AppConfig;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Sensitive values the built-in detectors recognize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensitiveKind {
+    Email,
+    Phone,
+    Iban,
+    Card,
+    NationalId,
+    Custom,
+}
+
+impl SensitiveKind {
+    /// Built-in detectors, in the order they run after user patterns. Card numbers go last:
+    /// only the Luhn check separates them from long phone or ID numbers.
+    const BUILT_IN: [Self; 5] = [
+        Self::Email,
+        Self::Iban,
+        Self::NationalId,
+        Self::Phone,
+        Self::Card,
+    ];
+
+    /// Identifier used in the `redactionKinds` setting.
+    fn label(self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Phone => "phone",
+            Self::Iban => "iban",
+            Self::Card => "card",
+            Self::NationalId => "nationalId",
+            Self::Custom => "custom",
+        }
+    }
+
+    /// Tag used in placeholders, e.g. `[EMAIL_1]`.
+    fn tag(self) -> &'static str {
+        match self {
+            Self::Email => "EMAIL",
+            Self::Phone => "PHONE",
+            Self::Iban => "IBAN",
+            Self::Card => "CARD",
+            Self::NationalId => "ID",
+            Self::Custom => "CUSTOM",
+        }
+    }
+
+    fn pattern(self) -> &'static Regex {
+        static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+        let patterns = PATTERNS.get_or_init(|| {
+            Self::BUILT_IN
+                .iter()
+                .map(|kind| {
+                    // ASCII 词边界：中文紧挨号码时（“电话13800138000”）也能匹配
+                    let pattern = kind.raw_pattern().replace(r"\b", r"(?-u:\b)");
+                    Regex::new(&pattern).expect("redaction pattern")
+                })
+                .collect()
+        });
+        let index = Self::BUILT_IN
+            .iter()
+            .position(|kind| *kind == self)
+            .expect("built-in redaction kind");
+        &patterns[index]
+    }
+
+    fn raw_pattern(self) -> &'static str {
+        match self {
+            Self::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+            Self::Iban => r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b",
+            // 中国居民身份证、美国 SSN
+            Self::NationalId => r"\b\d{17}[\dXx]\b|\b\d{3}-\d{2}-\d{4}\b",
+            // 中国大陆手机号、带国家码的号码、北美格式 (555) 123-4567
+            Self::Phone => {
+                r"(?:\+\d{1,3}[ -]?)?\b1[3-9]\d{9}\b|\+\d{1,3}[ -]?\(?\d{1,4}\)?(?:[ -]?\d{2,4}){2,4}\b|\(\d{3}\) ?\d{3}[ .-]\d{4}\b|\b\d{3}[.-]\d{3}[.-]\d{4}\b"
+            }
+            Self::Card => r"\b\d(?:[ -]?\d){12,18}\b",
+            Self::Custom => "",
+        }
+    }
+
+    /// Checksums that keep order numbers and dates from being masked as cards or IDs.
+    fn accepts(self, value: &str) -> bool {
+        match self {
+            Self::Iban => iban_checksum_ok(value),
+            Self::Card => luhn_ok(value) && card_shaped(value),
+            Self::NationalId => value.contains('-') || chinese_id_checksum_ok(value),
+            _ => true,
+        }
+    }
+}
+
+/// Masks sensitive values in outgoing prompts with stable placeholders (`[EMAIL_1]`,
+/// `[CARD_2]`) and maps them back in model output. The same value always gets the same
+/// placeholder within one redactor, so every prompt of one request stays consistent.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    kinds: Vec<SensitiveKind>,
+    custom: Vec<Regex>,
+    placeholders: Vec<(String, String)>,
+}
+
+impl Redactor {
+    fn new(kinds: Vec<SensitiveKind>, custom: Vec<Regex>) -> Self {
+        Self {
+            kinds,
+            custom,
+            placeholders: Vec::new(),
+        }
+    }
+
+    /// Replaces every detected value in `text` with its placeholder.
+    pub fn redact(&mut self, text: &str) -> String {
+        // 用户规则最先执行，内置规则不会改写已经生成的占位符
+        let mut redacted = text.to_string();
+        for pattern in self.custom.clone() {
+            redacted = self.replace(&redacted, &pattern, SensitiveKind::Custom);
+        }
+        for kind in self.kinds.clone() {
+            redacted = self.replace(&redacted, kind.pattern(), kind);
+        }
+        redacted
+    }
+
+    /// Puts the original values back wherever a placeholder this redactor issued survives
+    /// in `text`. Only the exact bracketed form counts, so ordinary text such as "ID 1"
+    /// or "card 2" is left alone.
+    pub fn restore(&self, text: &str) -> String {
+        self.placeholders
+            .iter()
+            .fold(text.to_string(), |text, (placeholder, original)| {
+                text.replace(placeholder.as_str(), original)
+            })
+    }
+
+    /// Number of distinct values masked so far.
+    pub fn masked_count(&self) -> usize {
+        self.placeholders.len()
+    }
+
+    fn replace(&mut self, text: &str, pattern: &Regex, kind: SensitiveKind) -> String {
+        pattern
+            .replace_all(text, |captures: &regex::Captures| {
+                let value = &captures[0];
+                if kind.accepts(value) {
+                    self.placeholder_for(kind, value)
+                } else {
+                    value.to_string()
+                }
+            })
+            .into_owned()
+    }
+
+    fn placeholder_for(&mut self, kind: SensitiveKind, value: &str) -> String {
+        if let Some((placeholder, _)) = self.placeholders.iter().find(|(placeholder, original)| {
+            original == value && placeholder.starts_with(&format!("[{}_", kind.tag()))
+        }) {
+            return placeholder.clone();
+        }
+        let index = self
+            .placeholders
+            .iter()
+            .filter(|(placeholder, _)| placeholder.starts_with(&format!("[{}_", kind.tag())))
+            .count()
+            + 1;
+        let placeholder = format!("[{}_{}]", kind.tag(), index);
+        self.placeholders
+            .push((placeholder.clone(), value.to_string()));
+        placeholder
+    }
+}
+
+/// Redaction for a request about `path` sent to a model at `endpoint`, or `None` when the
+/// policy leaves it alone. A per-folder override wins over the global mode:
+/// `off` never masks, `remote` masks unless the endpoint is on this machine, `all` always masks.
+pub fn redactor_for(config: &AppConfig, path: Option<&str>, endpoint: &str) -> Option<Redactor> {
+    let mode = path
+        .and_then(|path| folder_mode(&config.redaction_folders, path))
+        .unwrap_or_else(|| config.redaction_mode.trim().to_string());
+    let applies = match mode.as_str() {
+        "all" => true,
+        "remote" => !is_local_endpoint(endpoint),
+        _ => false,
+    };
+    if !applies {
+        return None;
+    }
+
+    let kinds = config
+        .redaction_kinds
+        .split(',')
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    let custom = config
+        .redaction_patterns
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| match Regex::new(line) {
+            // 能匹配空串的规则会在每个位置插入占位符
+            Ok(pattern) if pattern.is_match("") => {
+                log::warn!(target: "redact", "skipping pattern that matches empty text: {}", line);
+                None
+            }
+            Ok(pattern) => Some(pattern),
+            Err(err) => {
+                log::warn!(target: "redact", "skipping invalid pattern {}: {}", line, err);
+                None
+            }
+        })
+        .collect();
+    Some(Redactor::new(
+        SensitiveKind::BUILT_IN
+            .into_iter()
+            .filter(|kind| kinds.contains(&kind.label()))
+            .collect(),
+        custom,
+    ))
+}
+
+/// Mode of the most specific `folder = mode` line that contains `path`; `~` is the home folder.
+fn folder_mode(rules: &str, path: &str) -> Option<String> {
+    let path = Path::new(path);
+    rules
+        .lines()
+        .filter_map(|line| line.rsplit_once('='))
+        .map(|(folder, mode)| (expand_home(folder.trim()), mode.trim()))
+        .filter(|(folder, mode)| !folder.as_os_str().is_empty() && !mode.is_empty())
+        .filter(|(folder, _)| path.starts_with(folder))
+        .max_by_key(|(folder, _)| folder.components().count())
+        .map(|(_, mode)| mode.to_string())
+}
+
+fn expand_home(folder: &str) -> PathBuf {
+    match (folder.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(folder),
+    }
+}
+
+/// Whether `url` points at this machine.
+pub fn is_local_endpoint(url: &str) -> bool {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = without_scheme.split('/').next().unwrap_or_default();
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = if let Some(bracketed) = authority.strip_prefix('[') {
+        bracketed.split(']').next().unwrap_or_default()
+    } else {
+        authority.split(':').next().unwrap_or_default()
+    };
+    let host = host.to_ascii_lowercase();
+    host == "localhost" || host == "::1" || host == "0.0.0.0" || host.starts_with("127.")
+}
+
+fn digits(value: &str) -> Vec<u32> {
+    value.chars().filter_map(|ch| ch.to_digit(10)).collect()
+}
+
+fn luhn_ok(value: &str) -> bool {
+    let digits = digits(value);
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| {
+            if index % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                *digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Card numbers are written in groups of four (or 4-6-5 for 15-digit cards) or start with
+/// an issuer prefix; other Luhn-valid runs are usually ISBNs, DOIs or reference numbers.
+fn card_shaped(value: &str) -> bool {
+    let groups = value.split([' ', '-']).map(str::len).collect::<Vec<_>>();
+    if let Some((last, full)) = groups.split_last()
+        && !full.is_empty()
+    {
+        return groups == [4, 6, 5] || (full.iter().all(|len| *len == 4) && (1..=4).contains(last));
+    }
+    let digits = digits(value);
+    let prefix = |len: usize| {
+        digits
+            .iter()
+            .take(len)
+            .fold(0, |acc, digit| acc * 10 + digit)
+    };
+    match digits.first() {
+        Some(4) => true,
+        Some(2) => (2221..=2720).contains(&prefix(4)),
+        Some(3) => matches!(prefix(2), 30 | 34..=38),
+        Some(5) => (51..=55).contains(&prefix(2)),
+        Some(6) => prefix(4) == 6011 || matches!(prefix(2), 62 | 64 | 65),
+        _ => false,
+    }
+}
+
+fn iban_checksum_ok(value: &str) -> bool {
+    let compact = value.replace(' ', "");
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    let (head, tail) = compact.split_at(4);
+    let mut remainder = 0u32;
+    for ch in tail.chars().chain(head.chars()) {
+        let number = match ch.to_digit(36) {
+            Some(number) => number,
+            None => return false,
+        };
+        remainder = if number >= 10 {
+            (remainder * 100 + number) % 97
+        } else {
+            (remainder * 10 + number) % 97
+        };
+    }
+    remainder == 1
+}
+
+fn chinese_id_checksum_ok(value: &str) -> bool {
+    const WEIGHTS: [u32; 17] = [7, 9, 10, 5, 8, 4, 2, 1, 6, 3, 7, 9, 10, 5, 8, 4, 2];
+    const CHECK: [char; 11] = ['1', '0', 'X', '9', '8', '7', '6', '5', '4', '3', '2'];
+    let chars = value.chars().collect::<Vec<_>>();
+    if chars.len() != 18 {
+        return false;
+    }
+    let sum: u32 = chars[..17]
+        .iter()
+        .zip(WEIGHTS)
+        .map(|(ch, weight)| ch.to_digit(10).unwrap_or(0) * weight)
+        .sum();
+    CHECK[(sum % 11) as usize] == chars[17].to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(mode: &str) -> AppConfig {
+        AppConfig {
+            redaction_mode: mode.into(),
+            ..AppConfig::default()
+        }
+    }
+
+    #[test]
+    fn redact_masks_builtin_kinds_with_stable_placeholders() {
+        let mut redactor = redactor_for(&config("all"), None, "http://localhost:11434").unwrap();
+        let text = "Contact alice@example.com or +86 138 0013 8000. \
+            Card 4111 1111 1111 1111, IBAN DE89 3704 0044 0532 0130 00, \
+            ID 11010519491231002X. Again: alice@example.com. Order 20240815. 电话13900139000";
+
+        let redacted = redactor.redact(text);
+
+        assert_eq!(
+            redacted,
+            "Contact [EMAIL_1] or [PHONE_1]. \
+            Card [CARD_1], IBAN [IBAN_1], \
+            ID [ID_1]. Again: [EMAIL_1]. Order 20240815. 电话[PHONE_2]"
+        );
+        assert_eq!(redactor.masked_count(), 6);
+    }
+
+    #[test]
+    fn redact_skips_numbers_that_fail_checksums() {
+        let mut redactor = redactor_for(&config("all"), None, "").unwrap();
+        let text = "Invoice 1234 5678 9012 3456 and ID 110105194912310021";
+        assert_eq!(redactor.redact(text), text);
+    }
+
+    #[test]
+    fn redact_leaves_isbn_and_ungrouped_reference_numbers() {
+        let mut redactor = redactor_for(&config("all"), None, "").unwrap();
+        // 都能通过 Luhn 校验，但没有卡号分组也没有发卡行前缀
+        let text = "ISBN 9780000000008, ref 1000000000009";
+        assert!(luhn_ok("9780000000008") && luhn_ok("1000000000009"));
+        assert_eq!(redactor.redact(text), text);
+        assert_eq!(redactor.redact("card 4111111111111111"), "card [CARD_1]");
+    }
+
+    #[test]
+    fn restore_only_replaces_issued_placeholders() {
+        let mut redactor = redactor_for(&config("all"), None, "").unwrap();
+        redactor.redact("from bob@corp.io");
+
+        assert_eq!(redactor.restore("Letter-[EMAIL_1]"), "Letter-bob@corp.io");
+        assert_eq!(redactor.restore("letter-email-1"), "letter-email-1");
+        assert_eq!(
+            redactor.restore("[EMAIL_2], ID 1, card 2"),
+            "[EMAIL_2], ID 1, card 2"
+        );
+    }
+
+    #[test]
+    fn custom_patterns_run_before_builtin_detectors() {
+        let config = AppConfig {
+            redaction_mode: "all".into(),
+            redaction_patterns: "ACME-\\d{4}\n(invalid\n\\d*\n".into(),
+            ..AppConfig::default()
+        };
+        let mut redactor = redactor_for(&config, None, "").unwrap();
+        assert_eq!(
+            redactor.redact("Policy ACME-2024 for a@b.co"),
+            "Policy [CUSTOM_1] for [EMAIL_1]"
+        );
+    }
+
+    #[test]
+    fn policy_follows_endpoint_and_most_specific_folder() {
+        let mut config = config("remote");
+        config.redaction_folders = "/docs = all\n/docs/public = off".into();
+
+        assert!(redactor_for(&config, None, "https://api.openai.com/v1").is_some());
+        assert!(redactor_for(&config, None, "http://127.0.0.1:11434").is_none());
+        assert!(
+            redactor_for(&config, Some("/docs/bank/a.pdf"), "http://localhost:11434").is_some()
+        );
+        assert!(
+            redactor_for(
+                &config,
+                Some("/docs/public/a.pdf"),
+                "https://api.openai.com/v1"
+            )
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,597 @@
+use crate::config::{self, AppConfig, ModelProfile};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[cfg(target_os = "macos")]
+use std::ffi::{CStr, CString};
+#[cfg(target_os = "macos")]
+use std::os::raw::c_char;
+
+#[cfg(target_os = "macos")]
+unsafe extern "C" {
+    fn keychain_set(service: *const c_char, account: *const c_char, secret: *const c_char) -> i32;
+    fn keychain_get(service: *const c_char, account: *const c_char) -> *mut c_char;
+    fn keychain_delete(service: *const c_char, account: *const c_char) -> i32;
+}
+
+static VAULT_LOCK: Mutex<()> = Mutex::new(());
+
+/// What `get_config` shows instead of a stored key. Sending it back means "unchanged".
+pub const MASKED_VALUE: &str = "••••••••";
+const KEYRING_SERVICE: &str = "com.fyla.api-keys";
+const VAULT_VERSION: u32 = 1;
+const NONCE_LEN: usize = 12;
+
+/// Where an API key lives; profiles keep one of these instead of the key itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SecretRef<'a> {
+    /// `env:NAME`, read from the process environment at call time.
+    Env(&'a str),
+    /// `keyring:NAME`, the macOS Keychain or the Secret Service on Linux.
+    Keyring(&'a str),
+    /// `vault:NAME`, the fallback file next to the config; see [`VaultFile`].
+    Vault(&'a str),
+}
+
+fn parse_reference(value: &str) -> Option<SecretRef<'_>> {
+    let value = value.trim();
+    let (scheme, name) = value.split_once(':')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    match scheme {
+        "env" => Some(SecretRef::Env(name)),
+        "keyring" => Some(SecretRef::Keyring(name)),
+        "vault" => Some(SecretRef::Vault(name)),
+        _ => None,
+    }
+}
+
+fn is_masked(value: &str) -> bool {
+    value.trim() == MASKED_VALUE
+}
+
+/// Returns the API key of `profile`: a reference is looked up, the masked placeholder
+/// maps to the saved key of the profile with the same id, and a key typed in but not
+/// yet saved is used as is.
+pub fn api_key(profile: &ModelProfile) -> Result<String> {
+    let value = profile.api_key.trim();
+    if !is_masked(value) {
+        return resolve(value);
+    }
+    let stored = config::load_config();
+    let reference = stored
+        .profile(&profile.id)
+        .map(|saved| saved.api_key.trim().to_string())
+        .unwrap_or_default();
+    if reference.is_empty() || is_masked(&reference) {
+        return Err(anyhow!("API Key 未保存，请在设置中重新填写"));
+    }
+    resolve(&reference)
+}
+
+fn resolve(value: &str) -> Result<String> {
+    match parse_reference(value) {
+        Some(SecretRef::Env(name)) => {
+            std::env::var(name).map_err(|_| anyhow!("环境变量 {} 未设置", name))
+        }
+        Some(SecretRef::Keyring(name)) => {
+            keyring_get(name)?.ok_or_else(|| anyhow!("系统钥匙串中没有找到 API Key: {}", name))
+        }
+        Some(SecretRef::Vault(name)) => vault_get_in(&vault_root(), name)?
+            .ok_or_else(|| anyhow!("本地密钥文件中没有找到 API Key: {}", name)),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// Keyring/vault name of the PDF password list.
+const PDF_PASSWORDS_NAME: &str = "pdf-passwords";
+
+/// The configured PDF passwords, one per line, read from where they are stored.
+pub fn pdf_passwords(config: &AppConfig) -> Result<String> {
+    if is_masked(&config.pdf_passwords) {
+        return Ok(String::new());
+    }
+    resolve(&config.pdf_passwords)
+}
+
+/// Hides stored keys and PDF passwords before the config is sent to the webview. `env:`
+/// references are not secret and stay readable so they can be edited.
+pub fn masked(mut config: AppConfig) -> AppConfig {
+    let values = config
+        .profiles
+        .iter_mut()
+        .map(|profile| &mut profile.api_key)
+        .chain(std::iter::once(&mut config.pdf_passwords));
+    for value in values {
+        let keep =
+            value.trim().is_empty() || matches!(parse_reference(value), Some(SecretRef::Env(_)));
+        if !keep {
+            *value = MASKED_VALUE.to_string();
+        }
+    }
+    config
+}
+
+/// Whether any profile key or the PDF password list is still stored in plaintext.
+pub fn has_plaintext(config: &AppConfig) -> bool {
+    config
+        .profiles
+        .iter()
+        .map(|profile| profile.api_key.as_str())
+        .chain(std::iter::once(config.pdf_passwords.as_str()))
+        .any(|value| {
+            let value = value.trim();
+            !value.is_empty() && !is_masked(value) && parse_reference(value).is_none()
+        })
+}
+
+/// Moves plaintext keys and PDF passwords in `config` into secure storage and replaces
+/// them with references, keeping the `previous` reference where the masked placeholder
+/// came back. Stored secrets nothing refers to any more are deleted.
+pub fn store_config_secrets(config: &mut AppConfig, previous: &AppConfig) -> Result<()> {
+    for profile in &mut config.profiles {
+        let value = profile.api_key.trim().to_string();
+        let value = if is_masked(&value) {
+            previous
+                .profile(&profile.id)
+                .map(|saved| saved.api_key.trim().to_string())
+                .unwrap_or_default()
+        } else {
+            value
+        };
+        profile.api_key = if value.is_empty() || parse_reference(&value).is_some() {
+            value
+        } else {
+            store_secret(&format!("profile-{}", profile.id), &value)?
+        };
+    }
+
+    // 密码两端的空格可能是密码的一部分，只在判断是否为空时去掉
+    let passwords = if is_masked(&config.pdf_passwords) {
+        previous.pdf_passwords.clone()
+    } else {
+        config.pdf_passwords.clone()
+    };
+    config.pdf_passwords = if passwords.trim().is_empty() {
+        String::new()
+    } else if parse_reference(&passwords).is_some() {
+        passwords.trim().to_string()
+    } else {
+        store_secret(PDF_PASSWORDS_NAME, &passwords)?
+    };
+
+    let in_use = config
+        .profiles
+        .iter()
+        .map(|profile| profile.api_key.as_str())
+        .chain(std::iter::once(config.pdf_passwords.as_str()))
+        .collect::<HashSet<_>>();
+    let old_values = previous
+        .profiles
+        .iter()
+        .map(|profile| profile.api_key.trim())
+        .chain(std::iter::once(previous.pdf_passwords.trim()));
+    for old in old_values {
+        if !in_use.contains(old) {
+            delete_stored(old);
+        }
+    }
+    Ok(())
+}
+
+/// Stores `secret` in the OS keyring, or in the vault file when no keyring is available,
+/// and returns the reference to keep in the config.
+fn store_secret(name: &str, secret: &str) -> Result<String> {
+    match keyring_set(name, secret) {
+        Ok(()) => Ok(format!("keyring:{}", name)),
+        Err(err) => {
+            log::warn!(
+                target: "secrets",
+                "keyring unavailable for {}, using vault file (file permissions only): {:#}",
+                name, err
+            );
+            vault_set_in(&vault_root(), name, secret)?;
+            Ok(format!("vault:{}", name))
+        }
+    }
+}
+
+fn delete_stored(reference: &str) {
+    let result = match parse_reference(reference) {
+        Some(SecretRef::Keyring(name)) => keyring_delete(name),
+        Some(SecretRef::Vault(name)) => vault_delete_in(&vault_root(), name),
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        log::warn!(target: "secrets", "failed to delete {}: {:#}", reference, err);
+    }
+}
+
+// --- 系统钥匙串 ---
+
+#[cfg(target_os = "macos")]
+fn keyring_set(name: &str, secret: &str) -> Result<()> {
+    let service = CString::new(KEYRING_SERVICE)?;
+    let account = CString::new(name)?;
+    let secret = CString::new(secret).map_err(|_| anyhow!("API Key 包含非法字符"))?;
+    let status = unsafe { keychain_set(service.as_ptr(), account.as_ptr(), secret.as_ptr()) };
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!("写入钥匙串失败 (OSStatus {})", status))
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn keyring_get(name: &str) -> Result<Option<String>> {
+    let service = CString::new(KEYRING_SERVICE)?;
+    let account = CString::new(name)?;
+    let ptr = unsafe { keychain_get(service.as_ptr(), account.as_ptr()) };
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    let secret = unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned();
+    unsafe { libc::free(ptr as *mut _) };
+    Ok(Some(secret))
+}
+
+#[cfg(target_os = "macos")]
+fn keyring_delete(name: &str) -> Result<()> {
+    let service = CString::new(KEYRING_SERVICE)?;
+    let account = CString::new(name)?;
+    let status = unsafe { keychain_delete(service.as_ptr(), account.as_ptr()) };
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!("删除钥匙串条目失败 (OSStatus {})", status))
+    }
+}
+
+#[cfg(target_os = "linux")]
+const SECRET_TOOL_LIMITS: crate::subprocess::ProcessLimits =
+    crate::subprocess::ProcessLimits::new(10, 64 * 1024);
+
+/// Secret Service 通过 libsecret 自带的 secret-tool 访问，没有安装时回退到本地密钥文件
+#[cfg(target_os = "linux")]
+fn secret_tool(args: &[&str], input: Option<&str>) -> Result<crate::subprocess::ProcessOutput> {
+    use crate::subprocess;
+    use std::process::Command;
+
+    let mut command = Command::new("secret-tool");
+    command.args(args);
+    match input {
+        Some(input) => {
+            subprocess::run_with_input(&mut command, input.as_bytes(), SECRET_TOOL_LIMITS)
+        }
+        None => subprocess::run(&mut command, SECRET_TOOL_LIMITS),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn keyring_set(name: &str, secret: &str) -> Result<()> {
+    let label = format!("Fyla {}", name);
+    let output = secret_tool(
+        &[
+            "store",
+            "--label",
+            &label,
+            "service",
+            KEYRING_SERVICE,
+            "account",
+            name,
+        ],
+        Some(secret),
+    )?;
+    if output.succeeded() {
+        Ok(())
+    } else {
+        Err(anyhow!("secret-tool 写入失败: {}", output.stderr_text()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn keyring_get(name: &str) -> Result<Option<String>> {
+    let output = secret_tool(
+        &["lookup", "service", KEYRING_SERVICE, "account", name],
+        None,
+    )?;
+    if output.succeeded() {
+        let secret = String::from_utf8_lossy(&output.stdout);
+        return Ok(Some(secret.trim_end_matches('\n').to_string()));
+    }
+    // 找不到条目时 secret-tool 直接以非零状态退出，不输出错误
+    let stderr = output.stderr_text();
+    if stderr.is_empty() {
+        Ok(None)
+    } else {
+        Err(anyhow!("secret-tool 读取失败: {}", stderr))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn keyring_delete(name: &str) -> Result<()> {
+    secret_tool(
+        &["clear", "service", KEYRING_SERVICE, "account", name],
+        None,
+    )?;
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn keyring_set(_name: &str, _secret: &str) -> Result<()> {
+    Err(anyhow!("当前平台没有可用的系统钥匙串"))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn keyring_get(_name: &str) -> Result<Option<String>> {
+    Ok(None)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn keyring_delete(_name: &str) -> Result<()> {
+    Ok(())
+}
+
+// --- 本地密钥文件 ---
+
+/// Fallback store for systems without a keyring. Entries are keyed by name; each value is
+/// base64 of a random nonce followed by the AES-256-GCM ciphertext, with the entry name as
+/// associated data. The key sits in `secrets.key` beside the vault, so this only keeps
+/// secrets from showing up in plain text (search, screenshots, copied files). What
+/// protects them is that both files are readable by the current user only; anyone who can
+/// read the user's files can read the secrets.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct VaultFile {
+    version: u32,
+    entries: BTreeMap<String, String>,
+}
+
+fn vault_root() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("fyla")
+}
+
+fn vault_path(root: &Path) -> PathBuf {
+    root.join("secrets.vault")
+}
+
+fn vault_key_path(root: &Path) -> PathBuf {
+    root.join("secrets.key")
+}
+
+fn read_vault(root: &Path) -> Result<VaultFile> {
+    match std::fs::read_to_string(vault_path(root)) {
+        Ok(raw) => serde_json::from_str(&raw).context("本地密钥文件已损坏"),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(VaultFile::default()),
+        Err(err) => Err(anyhow!("读取本地密钥文件失败: {}", err)),
+    }
+}
+
+fn write_vault(root: &Path, vault: &VaultFile) -> Result<()> {
+    std::fs::create_dir_all(root)?;
+    // 先写临时文件再改名，写到一半崩溃不会丢掉其他条目
+    let temp = root.join(format!("secrets.vault.{}.tmp", std::process::id()));
+    write_private(&temp, serde_json::to_string_pretty(vault)?.as_bytes())?;
+    std::fs::rename(&temp, vault_path(root))?;
+    Ok(())
+}
+
+/// Loads the vault key, creating a random one readable only by the current user.
+fn vault_cipher(root: &Path) -> Result<Aes256Gcm> {
+    let path = vault_key_path(root);
+    let key = match std::fs::read(&path) {
+        Ok(key) => key,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            std::fs::create_dir_all(root)?;
+            let key = Aes256Gcm::generate_key(OsRng).to_vec();
+            write_private(&path, &key)?;
+            key
+        }
+        Err(err) => return Err(anyhow!("读取 secrets.key 失败: {}", err)),
+    };
+    if key.len() != 32 {
+        return Err(anyhow!("secrets.key 已损坏: {}", path.display()));
+    }
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+/// Writes `bytes` to a file only the current user can read; an existing file with looser
+/// permissions is tightened first, since the permissions are all that guards the vault.
+fn write_private(path: &Path, bytes: &[u8]) -> Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(bytes)?;
+    Ok(())
+}
+
+fn vault_set_in(root: &Path, name: &str, secret: &str) -> Result<()> {
+    let _lock = VAULT_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let cipher = vault_cipher(root)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: secret.as_bytes(),
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("加密 API Key 失败"))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+
+    let mut vault = read_vault(root)?;
+    vault.version = VAULT_VERSION;
+    vault.entries.insert(
+        name.to_string(),
+        base64::engine::general_purpose::STANDARD.encode(sealed),
+    );
+    write_vault(root, &vault)
+}
+
+fn vault_get_in(root: &Path, name: &str) -> Result<Option<String>> {
+    let vault = read_vault(root)?;
+    let Some(encoded) = vault.entries.get(name) else {
+        return Ok(None);
+    };
+    let sealed = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|_| anyhow!("本地密钥文件条目已损坏: {}", name))?;
+    if sealed.len() <= NONCE_LEN {
+        return Err(anyhow!("本地密钥文件条目已损坏: {}", name));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plain = vault_cipher(root)?
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("无法解密 API Key: {}（密钥文件可能已更换）", name))?;
+    Ok(Some(String::from_utf8(plain)?))
+}
+
+fn vault_delete_in(root: &Path, name: &str) -> Result<()> {
+    let _lock = VAULT_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut vault = read_vault(root)?;
+    if vault.entries.remove(name).is_some() {
+        write_vault(root, &vault)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("fyla-secrets-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        root
+    }
+
+    #[test]
+    fn vault_round_trips_and_never_stores_plaintext() {
+        let root = temp_root("vault");
+        vault_set_in(&root, "openaiKey", "sk-test-123456").unwrap();
+        vault_set_in(&root, "vlmKey", "sk-vlm").unwrap();
+        let raw = std::fs::read_to_string(vault_path(&root)).unwrap();
+        let openai = vault_get_in(&root, "openaiKey").unwrap();
+        vault_delete_in(&root, "vlmKey").unwrap();
+        let vlm = vault_get_in(&root, "vlmKey").unwrap();
+        #[cfg(unix)]
+        let modes = {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: PathBuf| std::fs::metadata(path).unwrap().permissions().mode();
+            (mode(vault_path(&root)), mode(vault_key_path(&root)))
+        };
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(!raw.contains("sk-test"));
+        assert_eq!(openai.as_deref(), Some("sk-test-123456"));
+        #[cfg(unix)]
+        assert_eq!((modes.0 & 0o777, modes.1 & 0o777), (0o600, 0o600));
+        assert_eq!(vlm, None);
+    }
+
+    #[test]
+    fn vault_entries_are_bound_to_their_name() {
+        let root = temp_root("swap");
+        vault_set_in(&root, "openaiKey", "sk-openai").unwrap();
+        let mut vault = read_vault(&root).unwrap();
+        let sealed = vault.entries["openaiKey"].clone();
+        vault.entries.insert("vlmKey".into(), sealed);
+        write_vault(&root, &vault).unwrap();
+
+        let swapped = vault_get_in(&root, "vlmKey");
+        let _ = std::fs::remove_dir_all(&root);
+        assert!(swapped.is_err());
+    }
+
+    #[test]
+    fn references_resolve_and_mask() {
+        // SAFETY: 测试进程内只有这里读写这个变量
+        unsafe { std::env::set_var("FYLA_TEST_SECRET_KEY", "sk-from-env") };
+        let profile = |id: &str, api_key: &str| ModelProfile {
+            id: id.into(),
+            api_key: api_key.into(),
+            ..ModelProfile::default()
+        };
+        assert_eq!(
+            api_key(&profile("a", " env:FYLA_TEST_SECRET_KEY ")).unwrap(),
+            "sk-from-env"
+        );
+        assert!(api_key(&profile("a", "env:FYLA_TEST_SECRET_MISSING")).is_err());
+        assert_eq!(api_key(&profile("a", "sk-typed")).unwrap(), "sk-typed");
+
+        let config = AppConfig {
+            profiles: vec![
+                profile("stored", "keyring:profile-stored"),
+                profile("env", "env:VLM_KEY"),
+                profile("legacy", "sk-legacy"),
+                profile("local", ""),
+            ],
+            ..AppConfig::default()
+        };
+        assert!(has_plaintext(&config));
+        let masked = masked(config);
+        let keys = masked
+            .profiles
+            .iter()
+            .map(|profile| profile.api_key.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [MASKED_VALUE, "env:VLM_KEY", MASKED_VALUE, ""]);
+        assert!(!has_plaintext(&masked));
+    }
+
+    #[test]
+    fn pdf_passwords_are_masked_and_resolved() {
+        // SAFETY: 测试进程内只有这里读写这个变量
+        unsafe { std::env::set_var("FYLA_TEST_PDF_PASSWORDS", "123456\n birthday ") };
+        let config = |pdf_passwords: &str| AppConfig {
+            pdf_passwords: pdf_passwords.into(),
+            ..AppConfig::default()
+        };
+        assert!(has_plaintext(&config("123456")));
+        assert!(!has_plaintext(&config("keyring:pdf-passwords")));
+        assert_eq!(masked(config("123456")).pdf_passwords, MASKED_VALUE);
+        assert_eq!(
+            masked(config("env:FYLA_TEST_PDF_PASSWORDS")).pdf_passwords,
+            "env:FYLA_TEST_PDF_PASSWORDS"
+        );
+        assert_eq!(
+            pdf_passwords(&config("env:FYLA_TEST_PDF_PASSWORDS")).unwrap(),
+            "123456\n birthday "
+        );
+        assert_eq!(pdf_passwords(&config(MASKED_VALUE)).unwrap(), "");
+    }
+}
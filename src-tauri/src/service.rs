@@ -1,15 +1,23 @@
+#[cfg(target_os = "macos")]
 use std::ffi::CStr;
+#[cfg(target_os = "macos")]
 use std::os::raw::c_char;
+#[cfg(target_os = "macos")]
 use std::sync::OnceLock;
-use tauri::{AppHandle, Emitter};
+use tauri::AppHandle;
+#[cfg(target_os = "macos")]
+use tauri::Emitter;
 
+#[cfg(target_os = "macos")]
 unsafe extern "C" {
     fn register_services_provider();
     fn set_files_callback(cb: extern "C" fn(*const c_char));
 }
 
+#[cfg(target_os = "macos")]
 static APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
 
+#[cfg(target_os = "macos")]
 extern "C" fn on_files_from_finder(json_ptr: *const c_char) {
     if json_ptr.is_null() {
         return;
@@ -24,6 +32,7 @@ extern "C" fn on_files_from_finder(json_ptr: *const c_char) {
 }
 
 /// Registers the macOS Finder Services provider and sets up the file-receive callback.
+#[cfg(target_os = "macos")]
 pub fn init(app: &AppHandle) {
     let _ = APP_HANDLE.set(app.clone());
     unsafe {
@@ -31,3 +40,7 @@ pub fn init(app: &AppHandle) {
         register_services_provider();
     }
 }
+
+/// Finder Services only exist on macOS; other systems have nothing to register.
+#[cfg(not(target_os = "macos"))]
+pub fn init(_app: &AppHandle) {}
@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+/// Target triple the app was built for; Tauri `externalBin` sidecars carry it as a suffix.
+pub const TARGET_TRIPLE: &str = env!("FYLA_TARGET_TRIPLE");
+
+const SYSTEM_BIN_DIRS: &[&str] = &["/opt/homebrew/bin", "/usr/local/bin", "/usr/bin"];
+
+/// Finds a bundled helper binary: the env override first, then next to the executable
+/// (packaged app), then `src-tauri/binaries` (development).
+pub fn resolve_bundled(env_var: &str, names: &[&str]) -> Option<PathBuf> {
+    if let Ok(path) = std::env::var(env_var) {
+        let candidate = PathBuf::from(path);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+    }
+
+    let mut candidates = Vec::new();
+    if let Ok(current_exe) = std::env::current_exe()
+        && let Some(exe_dir) = current_exe.parent()
+    {
+        let base_dir = if exe_dir.ends_with("deps") {
+            exe_dir.parent().unwrap_or(exe_dir)
+        } else {
+            exe_dir
+        };
+        candidates.extend(names.iter().map(|name| base_dir.join(name)));
+    }
+
+    let manifest_binaries = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("binaries");
+    candidates.extend(names.iter().map(|name| manifest_binaries.join(name)));
+
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// Like [`resolve_bundled`], but also accepts a copy the user installed on `PATH` or via
+/// Homebrew (GUI apps on macOS do not inherit the shell `PATH`).
+pub fn resolve_bundled_or_system(
+    env_var: &str,
+    names: &[&str],
+    system_name: &str,
+) -> Option<PathBuf> {
+    if let Some(found) = resolve_bundled(env_var, names) {
+        return Some(found);
+    }
+
+    let path_dirs = std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+    path_dirs
+        .into_iter()
+        .chain(SYSTEM_BIN_DIRS.iter().map(PathBuf::from))
+        .map(|dir| dir.join(system_name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Finds a poppler utility (`pdfinfo`, `pdftoppm`, `pdftotext`), bundled with a
+/// target-triple suffix (or the universal one on macOS) or installed by the user.
+pub fn resolve_poppler_tool(name: &str, env_var: &str) -> Option<PathBuf> {
+    let triple_name = format!("{}-{}", name, TARGET_TRIPLE);
+    let universal_name = format!("{}-universal-apple-darwin", name);
+    let mut names = vec![name, triple_name.as_str()];
+    if cfg!(target_os = "macos") {
+        names.push(&universal_name);
+    }
+    resolve_bundled_or_system(env_var, &names, name)
+}
@@ -1,17 +1,8 @@
-use anyhow::{Result, anyhow};
-use reqwest::{Client, Url};
+use crate::logging;
+use anyhow::anyhow;
+use reqwest::Url;
 use std::error::Error as _;
-use std::time::{Duration, Instant};
-
-const STREAM_CONNECT_TIMEOUT_SECS: u64 = 15;
-const STREAM_READ_TIMEOUT_SECS: u64 = 90;
-
-pub fn build_streaming_http_client() -> Result<Client> {
-    Ok(Client::builder()
-        .connect_timeout(Duration::from_secs(STREAM_CONNECT_TIMEOUT_SECS))
-        .read_timeout(Duration::from_secs(STREAM_READ_TIMEOUT_SECS))
-        .build()?)
-}
+use std::time::Instant;
 
 pub fn stream_idle_timeout_message() -> &'static str {
     "模型长时间没有返回新内容，流式输出已超时，请重试"
@@ -65,6 +56,7 @@ pub struct StreamTrace {
     model: String,
     host: String,
     item: String,
+    job: String,
     started_at: Instant,
     first_chunk_at: Option<Instant>,
     output_chars: usize,
@@ -85,13 +77,16 @@ impl StreamTrace {
             model: model.to_string(),
             host: host_for_logging(base_url),
             item: item.to_string(),
+            job: logging::job_id(item),
             started_at: Instant::now(),
             first_chunk_at: None,
             output_chars: 0,
         };
 
-        eprintln!(
-            "[stream] start kind={} provider={} model={} host={} item={} inputChars={}",
+        log::info!(
+            target: "stream",
+            job = trace.job.as_str();
+            "start kind={} provider={} model={} host={} item={} inputChars={}",
             trace.kind, trace.provider, trace.model, trace.host, trace.item, input_chars
         );
 
@@ -106,8 +101,10 @@ impl StreamTrace {
         if self.first_chunk_at.is_none() {
             let now = Instant::now();
             self.first_chunk_at = Some(now);
-            eprintln!(
-                "[stream] first_chunk kind={} provider={} model={} host={} item={} firstChunkMs={}",
+            log::info!(
+                target: "stream",
+                job = self.job.as_str();
+                "first_chunk kind={} provider={} model={} host={} item={} firstChunkMs={}",
                 self.kind,
                 self.provider,
                 self.model,
@@ -121,8 +118,10 @@ impl StreamTrace {
     }
 
     pub fn log_complete(&self) {
-        eprintln!(
-            "[stream] complete kind={} provider={} model={} host={} item={} elapsedMs={} firstChunkMs={} outputChars={}",
+        log::info!(
+            target: "stream",
+            job = self.job.as_str();
+            "complete kind={} provider={} model={} host={} item={} elapsedMs={} firstChunkMs={} outputChars={}",
             self.kind,
             self.provider,
             self.model,
@@ -137,8 +136,10 @@ impl StreamTrace {
     }
 
     pub fn log_error(&self, err: &reqwest::Error, user_message: &str) {
-        eprintln!(
-            "[stream] error kind={} provider={} model={} host={} item={} elapsedMs={} firstChunkMs={} outputChars={} userMessage={} source={}",
+        log::warn!(
+            target: "stream",
+            job = self.job.as_str();
+            "error kind={} provider={} model={} host={} item={} elapsedMs={} firstChunkMs={} outputChars={} userMessage={} source={}",
             self.kind,
             self.provider,
             self.model,
@@ -0,0 +1,342 @@
+use anyhow::{Context, Result, anyhow};
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_STDERR_BYTES: usize = 64 * 1024;
+/// How long to wait for the pipes to close after the process exited; a grandchild that
+/// inherited stdout can otherwise keep the reader blocked indefinitely.
+const READER_JOIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+thread_local! {
+    static CURRENT_CANCEL: RefCell<Option<watch::Receiver<bool>>> = const { RefCell::new(None) };
+}
+
+/// Wall-clock and output limits for one helper process.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessLimits {
+    pub timeout: Duration,
+    pub max_stdout_bytes: usize,
+}
+
+impl ProcessLimits {
+    pub const fn new(timeout_secs: u64, max_stdout_bytes: usize) -> Self {
+        Self {
+            timeout: Duration::from_secs(timeout_secs),
+            max_stdout_bytes,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProcessOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// The process was stopped because stdout hit `max_stdout_bytes`.
+    pub stdout_truncated: bool,
+}
+
+impl ProcessOutput {
+    /// Exited successfully with its complete output.
+    pub fn succeeded(&self) -> bool {
+        self.status.success() && !self.stdout_truncated
+    }
+
+    /// Exited successfully, or was stopped only because it produced more output than
+    /// needed. Callers that accept the truncated prefix check `stdout_truncated` to
+    /// report it.
+    pub fn succeeded_or_truncated(&self) -> bool {
+        self.status.success() || self.stdout_truncated
+    }
+
+    pub fn stderr_text(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).trim().to_string()
+    }
+}
+
+/// Runs `f` with `cancel` as the cancellation signal for every [`run`] on this thread.
+pub fn with_cancel<T>(cancel: watch::Receiver<bool>, f: impl FnOnce() -> T) -> T {
+    let _scope = CancelScope {
+        previous: CURRENT_CANCEL.with(|slot| slot.replace(Some(cancel))),
+    };
+    f()
+}
+
+/// Restores the outer cancellation signal, also when `f` panics.
+struct CancelScope {
+    previous: Option<watch::Receiver<bool>>,
+}
+
+impl Drop for CancelScope {
+    fn drop(&mut self) {
+        CURRENT_CANCEL.with(|slot| *slot.borrow_mut() = self.previous.take());
+    }
+}
+
+/// Cancellation signal of the current thread, for handing to worker threads.
+pub fn current_cancel() -> Option<watch::Receiver<bool>> {
+    CURRENT_CANCEL.with(|slot| slot.borrow().clone())
+}
+
+pub fn is_cancelled() -> bool {
+    CURRENT_CANCEL.with(|slot| slot.borrow().as_ref().is_some_and(|rx| *rx.borrow()))
+}
+
+/// Spawns `command` in its own process group and waits for it, killing the whole group
+/// on timeout, cancellation or when stdout exceeds the cap.
+pub fn run(command: &mut Command, limits: ProcessLimits) -> Result<ProcessOutput> {
+    run_inner(command, None, limits)
+}
+
+/// Like [`run`], but writes `input` to the process's stdin and then closes it, so
+/// secrets never appear in the argument list.
+pub fn run_with_input(
+    command: &mut Command,
+    input: &[u8],
+    limits: ProcessLimits,
+) -> Result<ProcessOutput> {
+    run_inner(command, Some(input), limits)
+}
+
+fn run_inner(
+    command: &mut Command,
+    input: Option<&[u8]>,
+    limits: ProcessLimits,
+) -> Result<ProcessOutput> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    if is_cancelled() {
+        return Err(anyhow!("已取消，未启动 {}", program));
+    }
+
+    command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("启动 {} 失败", program))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // 输入很短，写完即关闭 stdin，子进程才能读到 EOF
+        let _ = stdin.write_all(input);
+    }
+    let overflow = Arc::new(AtomicBool::new(false));
+    let stdout = spawn_reader(
+        child.stdout.take(),
+        limits.max_stdout_bytes,
+        Some(overflow.clone()),
+    );
+    let stderr = spawn_reader(child.stderr.take(), MAX_STDERR_BYTES, None);
+
+    let started_at = Instant::now();
+    let mut stop_reason = None;
+    let status = loop {
+        if let Some(status) = child.try_wait().context("等待子进程失败")? {
+            break Some(status);
+        }
+        if overflow.load(Ordering::Relaxed) {
+            log::warn!(
+                target: "subprocess",
+                "{} exceeded {} bytes of output, stopping",
+                program, limits.max_stdout_bytes
+            );
+            kill_process_group(&mut child);
+            break Some(child.wait().context("等待子进程失败")?);
+        }
+        if started_at.elapsed() >= limits.timeout {
+            stop_reason = Some(format!(
+                "{} 运行超过 {} 秒，已终止",
+                program,
+                limits.timeout.as_secs_f32()
+            ));
+        } else if is_cancelled() {
+            stop_reason = Some(format!("已取消，{} 已终止", program));
+        }
+        if stop_reason.is_some() {
+            kill_process_group(&mut child);
+            let _ = child.wait();
+            break None;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    let reader_deadline = Instant::now() + READER_JOIN_TIMEOUT;
+    let stdout = stdout.finish(reader_deadline, &program);
+    let stderr = stderr.finish(reader_deadline, &program);
+    let Some(status) = status else {
+        let reason = stop_reason.unwrap_or_default();
+        log::warn!(target: "subprocess", "{}", reason);
+        return Err(anyhow!(reason));
+    };
+
+    Ok(ProcessOutput {
+        status,
+        stdout,
+        stderr,
+        stdout_truncated: overflow.load(Ordering::Relaxed),
+    })
+}
+
+/// A thread draining one pipe into a shared buffer.
+struct PipeReader {
+    collected: Arc<Mutex<Vec<u8>>>,
+    handle: JoinHandle<()>,
+}
+
+impl PipeReader {
+    /// Waits for the pipe to close until `deadline`, then returns what was read. A
+    /// reader still blocked after the deadline is left to finish on its own.
+    fn finish(self, deadline: Instant, program: &str) -> Vec<u8> {
+        while !self.handle.is_finished() && Instant::now() < deadline {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        if self.handle.is_finished() {
+            let _ = self.handle.join();
+        } else {
+            log::warn!(
+                target: "subprocess",
+                "{} exited but its output pipe is still open, keeping what was read",
+                program
+            );
+        }
+        std::mem::take(&mut *self.collected.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Reads a pipe to the end, keeping at most `cap` bytes. Excess output is drained so the
+/// child never blocks on a full pipe; `overflow` is raised once the cap is passed.
+fn spawn_reader<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    cap: usize,
+    overflow: Option<Arc<AtomicBool>>,
+) -> PipeReader {
+    let collected = Arc::new(Mutex::new(Vec::new()));
+    let sink = collected.clone();
+    let handle = std::thread::spawn(move || {
+        let Some(mut pipe) = pipe else {
+            return;
+        };
+        let mut buffer = [0u8; 8192];
+        loop {
+            match pipe.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => {
+                    let mut collected = sink.lock().unwrap_or_else(|e| e.into_inner());
+                    let room = cap.saturating_sub(collected.len());
+                    collected.extend_from_slice(&buffer[..read.min(room)]);
+                    if read > room
+                        && let Some(flag) = &overflow
+                    {
+                        flag.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+    });
+    PipeReader { collected, handle }
+}
+
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        // 负 pid 表示整个进程组，连带 pdftotext/python 派生的子进程一起结束
+        let pid = child.id() as libc::pid_t;
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_kills_process_after_timeout() {
+        let started_at = Instant::now();
+        let err = run(
+            Command::new("sh").args(["-c", "sleep 5"]),
+            ProcessLimits {
+                timeout: Duration::from_millis(200),
+                max_stdout_bytes: 1024,
+            },
+        )
+        .expect_err("sleep should time out");
+
+        assert!(err.to_string().contains("已终止"));
+        assert!(started_at.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn run_caps_stdout_and_stops_the_producer() {
+        let output = run(
+            Command::new("sh").args(["-c", "yes fyla"]),
+            ProcessLimits::new(10, 4096),
+        )
+        .expect("capped process should still return output");
+
+        assert!(output.stdout_truncated);
+        assert!(!output.succeeded());
+        assert!(output.succeeded_or_truncated());
+        assert_eq!(output.stdout.len(), 4096);
+    }
+
+    #[test]
+    fn run_returns_when_a_background_grandchild_keeps_stdout_open() {
+        let started_at = Instant::now();
+        let output = run(
+            Command::new("sh").args(["-c", "sleep 5 & echo done"]),
+            ProcessLimits::new(10, 1024),
+        )
+        .expect("parent exits right away");
+
+        assert!(output.succeeded());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+        assert!(started_at.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn run_stops_when_cancelled() {
+        let (sender, receiver) = watch::channel(false);
+        sender.send(true).unwrap();
+
+        let err = with_cancel(receiver, || {
+            run(
+                Command::new("sh").args(["-c", "sleep 5"]),
+                ProcessLimits::new(10, 1024),
+            )
+        })
+        .expect_err("cancelled run should fail");
+
+        assert!(err.to_string().contains("已取消"));
+        assert!(!is_cancelled());
+    }
+
+    #[test]
+    fn with_cancel_restores_the_outer_signal_after_a_panic() {
+        let (sender, receiver) = watch::channel(false);
+        sender.send(true).unwrap();
+
+        let panicked = std::panic::catch_unwind(|| with_cancel(receiver, || panic!("boom")));
+
+        assert!(panicked.is_err());
+        assert!(current_cancel().is_none());
+    }
+}
@@ -1,22 +1,27 @@
 #[cfg(test)]
 mod tests {
-    use crate::ocr;
+    use crate::config::AppConfig;
+    use crate::ocr::{self, OcrSettings};
+
+    fn settings() -> OcrSettings {
+        OcrSettings::from_config(&AppConfig::default())
+    }
 
     #[test]
     fn test_ocr_from_file_with_icon() {
-        let result = ocr::ocr_from_file("icons/icon.png");
+        let result = ocr::ocr_from_file("icons/icon.png", &settings());
         assert!(result.is_ok(), "OCR 图标不应崩溃: {:?}", result);
     }
 
     #[test]
     fn test_ocr_from_file_nonexistent() {
-        let result = ocr::ocr_from_file("/tmp/nonexistent_test_image.png");
+        let result = ocr::ocr_from_file("/tmp/nonexistent_test_image.png", &settings());
         assert!(result.is_ok(), "不存在的文件不应崩溃: {:?}", result);
     }
 
     #[test]
     fn test_ocr_from_pdf_nonexistent() {
-        let result = ocr::ocr_from_pdf("/tmp/nonexistent_test.pdf");
+        let result = ocr::ocr_from_pdf("/tmp/nonexistent_test.pdf", &settings());
         assert!(result.is_ok(), "不存在的 PDF 不应崩溃: {:?}", result);
     }
 }
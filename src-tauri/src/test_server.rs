@@ -0,0 +1,334 @@
+//! A small OpenAI/Ollama-compatible HTTP server for tests, so the streaming, marker and
+//! cancellation paths run against real HTTP instead of hand-built strings, plus a channel
+//! that records what a streaming command would send to the webview.
+
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tauri::ipc::{Channel, InvokeResponseBody, IpcResponse};
+
+/// One request received by the server.
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    pub method: String,
+    pub path: String,
+    pub body: Value,
+}
+
+#[derive(Debug, Clone)]
+pub enum StubResponse {
+    Json(Value),
+    Status(u16, String),
+    /// Chunked body written piece by piece, `delay` apart. With `disconnect` the connection
+    /// is dropped after the last piece instead of ending the body.
+    Stream {
+        content_type: &'static str,
+        chunks: Vec<String>,
+        delay: Duration,
+        disconnect: bool,
+    },
+}
+
+type Handler = dyn Fn(&StubRequest) -> StubResponse + Send + Sync;
+
+/// Stops accepting connections when dropped; requests already being served finish.
+pub struct StubServer {
+    addr: String,
+    requests: Arc<Mutex<Vec<StubRequest>>>,
+    stopped: Arc<AtomicBool>,
+    accept: Option<JoinHandle<()>>,
+}
+
+impl StubServer {
+    /// Serves every request with `handler` on a free local port until dropped.
+    pub fn start(handler: impl Fn(&StubRequest) -> StubResponse + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let recorded = requests.clone();
+        let stop = stopped.clone();
+        let accept = thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let handler = handler.clone();
+                let recorded = recorded.clone();
+                thread::spawn(move || serve(stream, handler.as_ref(), &recorded));
+            }
+        });
+        Self {
+            addr,
+            requests,
+            stopped,
+            accept: Some(accept),
+        }
+    }
+
+    /// A server answering the six endpoints the app uses, streaming `reply` in pieces of
+    /// `chunk_chars` characters when the request asks for a stream.
+    pub fn compatible(reply: &str, chunk_chars: usize) -> Self {
+        let reply = reply.to_string();
+        Self::start(move |request| compatible_response(request, &reply, chunk_chars))
+    }
+
+    /// Base URL of an OpenAI-compatible profile (with `/v1`).
+    pub fn openai_url(&self) -> String {
+        format!("http://{}/v1", self.addr)
+    }
+
+    /// Base URL of an Ollama profile.
+    pub fn ollama_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // 连一次把阻塞在 accept 上的线程唤醒，让它看到停止标记后退出并释放端口
+        let _ = TcpStream::connect(&self.addr);
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+    }
+}
+
+/// A channel that records every event as JSON, standing in for the webview side of a
+/// streaming command.
+pub fn recording_channel<T: IpcResponse>() -> (Channel<T>, Arc<Mutex<Vec<Value>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    let channel = Channel::new(move |body| {
+        if let InvokeResponseBody::Json(json) = body {
+            recorded
+                .lock()
+                .unwrap()
+                .push(serde_json::from_str(&json).unwrap_or(Value::Null));
+        }
+        Ok(())
+    });
+    (channel, events)
+}
+
+/// Default behaviour of [`StubServer::compatible`], also handy inside custom handlers.
+pub fn compatible_response(request: &StubRequest, reply: &str, chunk_chars: usize) -> StubResponse {
+    let streamed = request.body["stream"].as_bool().unwrap_or(false);
+    let model = request.body["model"].as_str().unwrap_or("stub").to_string();
+    match request.path.as_str() {
+        "/v1/models" => StubResponse::Json(json!({ "data": [{ "id": model }] })),
+        "/api/tags" => StubResponse::Json(json!({
+            "models": [{ "name": "llama3.2:latest" }, { "name": "nomic-embed-text:latest" }]
+        })),
+        "/v1/chat/completions" if streamed => openai_stream(reply, chunk_chars, Duration::ZERO),
+        "/v1/chat/completions" => StubResponse::Json(json!({
+            "choices": [{ "message": { "role": "assistant", "content": reply } }],
+            "usage": { "prompt_tokens": 10, "completion_tokens": 5 }
+        })),
+        "/api/chat" if streamed => ollama_stream(reply, chunk_chars, Duration::ZERO),
+        "/api/chat" => StubResponse::Json(json!({
+            "message": { "role": "assistant", "content": reply },
+            "done": true
+        })),
+        "/v1/embeddings" => {
+            let data = inputs(&request.body)
+                .iter()
+                .enumerate()
+                .map(|(index, text)| json!({ "index": index, "embedding": vector(text) }))
+                .collect::<Vec<_>>();
+            StubResponse::Json(json!({ "data": data, "usage": { "prompt_tokens": 7 } }))
+        }
+        "/api/embed" => {
+            let embeddings = inputs(&request.body)
+                .iter()
+                .map(|text| vector(text))
+                .collect::<Vec<_>>();
+            StubResponse::Json(json!({ "embeddings": embeddings, "prompt_eval_count": 7 }))
+        }
+        _ => StubResponse::Status(404, "not found".into()),
+    }
+}
+
+/// `reply` as OpenAI server-sent events, ending with usage and `[DONE]`.
+pub fn openai_stream(reply: &str, chunk_chars: usize, delay: Duration) -> StubResponse {
+    let mut chunks = split(reply, chunk_chars)
+        .into_iter()
+        .map(|piece| {
+            let event = json!({ "choices": [{ "delta": { "content": piece } }] });
+            format!("data: {}\n\n", event)
+        })
+        .collect::<Vec<_>>();
+    let usage = json!({ "choices": [], "usage": { "prompt_tokens": 10, "completion_tokens": 5 } });
+    chunks.push(format!("data: {}\n\n", usage));
+    chunks.push("data: [DONE]\n\n".into());
+    StubResponse::Stream {
+        content_type: "text/event-stream",
+        chunks,
+        delay,
+        disconnect: false,
+    }
+}
+
+/// `reply` as Ollama NDJSON lines, ending with a `done` line.
+pub fn ollama_stream(reply: &str, chunk_chars: usize, delay: Duration) -> StubResponse {
+    let mut chunks = split(reply, chunk_chars)
+        .into_iter()
+        .map(|piece| {
+            format!(
+                "{}\n",
+                json!({ "message": { "content": piece }, "done": false })
+            )
+        })
+        .collect::<Vec<_>>();
+    let done = json!({ "message": { "content": "" }, "done": true, "eval_count": 5 });
+    chunks.push(format!("{}\n", done));
+    StubResponse::Stream {
+        content_type: "application/x-ndjson",
+        chunks,
+        delay,
+        disconnect: false,
+    }
+}
+
+/// Current-thread runtime with IO and timers, enough for reqwest.
+pub fn block_on<T>(future: impl std::future::Future<Output = T>) -> T {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+fn serve(stream: TcpStream, handler: &Handler, recorded: &Mutex<Vec<StubRequest>>) {
+    let Some(request) = read_request(&stream) else {
+        return;
+    };
+    recorded.lock().unwrap().push(request.clone());
+    // 客户端提前断开时写入会失败，忽略即可
+    let _ = write_response(stream, handler(&request));
+}
+
+fn read_request(stream: &TcpStream) -> Option<StubRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+    Some(StubRequest {
+        method,
+        path,
+        body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+    })
+}
+
+fn write_response(mut stream: TcpStream, response: StubResponse) -> std::io::Result<()> {
+    match response {
+        StubResponse::Json(value) => {
+            write_full(&mut stream, 200, "application/json", &value.to_string())
+        }
+        StubResponse::Status(status, body) => write_full(&mut stream, status, "text/plain", &body),
+        StubResponse::Stream {
+            content_type,
+            chunks,
+            delay,
+            disconnect,
+        } => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+                content_type
+            )?;
+            stream.flush()?;
+            for chunk in chunks {
+                if !delay.is_zero() {
+                    thread::sleep(delay);
+                }
+                write!(stream, "{:x}\r\n{}\r\n", chunk.len(), chunk)?;
+                stream.flush()?;
+            }
+            if disconnect {
+                return stream.shutdown(std::net::Shutdown::Both);
+            }
+            stream.write_all(b"0\r\n\r\n")?;
+            stream.flush()
+        }
+    }
+}
+
+fn write_full(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        _ => "Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn inputs(body: &Value) -> Vec<String> {
+    match &body["input"] {
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Value::String(text) => vec![text.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// Two-dimensional vector derived from the text, enough to check order and plumbing.
+fn vector(text: &str) -> Vec<f32> {
+    vec![text.chars().count() as f32, 1.0]
+}
+
+fn split(text: &str, size: usize) -> Vec<String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    chars
+        .chunks(size.max(1))
+        .map(|piece| piece.iter().collect())
+        .collect()
+}
@@ -0,0 +1,587 @@
+use crate::config::{AppConfig, ModelPrice, ModelProfile, ModelTask};
+use crate::redact;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Tokens used by one model call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated from text length because the provider did not report usage.
+    pub estimated: bool,
+}
+
+impl TokenUsage {
+    /// Reads `usage` (OpenAI) or `prompt_eval_count` / `eval_count` (Ollama) from a
+    /// response body or the last chunk of a stream.
+    pub fn from_response(value: &Value) -> Option<Self> {
+        if let Some(usage) = value.get("usage").filter(|usage| usage.is_object()) {
+            let prompt_tokens = usage["prompt_tokens"].as_u64()?;
+            return Some(Self {
+                prompt_tokens,
+                completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+                estimated: false,
+            });
+        }
+        let prompt = value.get("prompt_eval_count").and_then(Value::as_u64);
+        let completion = value.get("eval_count").and_then(Value::as_u64);
+        (prompt.is_some() || completion.is_some()).then(|| Self {
+            prompt_tokens: prompt.unwrap_or(0),
+            completion_tokens: completion.unwrap_or(0),
+            estimated: false,
+        })
+    }
+
+    /// Rough count for calls without reported usage: about four characters per token.
+    pub fn estimate(prompt_chars: usize, completion_chars: usize) -> Self {
+        Self {
+            prompt_tokens: prompt_chars.div_ceil(4) as u64,
+            completion_tokens: completion_chars.div_ceil(4) as u64,
+            estimated: true,
+        }
+    }
+}
+
+/// Collects the usage of one call and writes it to the ledger when dropped, so streams
+/// that are stopped or fail halfway are still counted. The write runs on the blocking
+/// pool when dropped inside the async runtime.
+pub struct UsageMeter {
+    task: ModelTask,
+    provider: String,
+    model: String,
+    endpoint: String,
+    prompt_chars: usize,
+    completion_chars: usize,
+    reported: Option<TokenUsage>,
+}
+
+impl UsageMeter {
+    pub fn new(task: ModelTask, profile: &ModelProfile, prompt_chars: usize) -> Self {
+        Self {
+            task,
+            provider: profile.provider.clone(),
+            model: profile.model.clone(),
+            endpoint: profile.endpoint().to_string(),
+            prompt_chars,
+            completion_chars: 0,
+            reported: None,
+        }
+    }
+
+    /// Picks up reported usage from a response body or stream chunk.
+    pub fn observe(&mut self, value: &Value) {
+        if let Some(usage) = TokenUsage::from_response(value) {
+            self.reported = Some(usage);
+        }
+    }
+
+    pub fn add_output(&mut self, text: &str) {
+        self.completion_chars += text.chars().count();
+    }
+}
+
+impl Drop for UsageMeter {
+    fn drop(&mut self) {
+        // 没有任何输出也没有用量的请求（连接失败等）不记账
+        if self.reported.is_none() && self.completion_chars == 0 {
+            return;
+        }
+        let usage = self
+            .reported
+            .unwrap_or_else(|| TokenUsage::estimate(self.prompt_chars, self.completion_chars));
+        let task = self.task;
+        let provider = std::mem::take(&mut self.provider);
+        let model = std::mem::take(&mut self.model);
+        let endpoint = std::mem::take(&mut self.endpoint);
+        let record = move || record_call(task, &provider, &model, &endpoint, usage);
+        // 账本是同步文件读写，不占用异步运行时的线程
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(record);
+            }
+            Err(_) => record(),
+        }
+    }
+}
+
+/// Records a completed non-streamed call from its response body.
+pub fn record_response(
+    task: ModelTask,
+    profile: &ModelProfile,
+    prompt_chars: usize,
+    response: &Value,
+    completion: &str,
+) {
+    let usage = TokenUsage::from_response(response)
+        .unwrap_or_else(|| TokenUsage::estimate(prompt_chars, completion.chars().count()));
+    record_call(
+        task,
+        &profile.provider,
+        &profile.model,
+        profile.endpoint(),
+        usage,
+    );
+}
+
+/// One line of the usage ledger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageEntry {
+    /// Local time, RFC 3339.
+    pub at: String,
+    pub task: String,
+    pub provider: String,
+    pub model: String,
+    pub cloud: bool,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub estimated: bool,
+    /// Priced when recorded, so later price changes do not rewrite history.
+    pub cost: f64,
+    /// No price was configured for the model, so `cost` is 0 without meaning free.
+    #[serde(default)]
+    pub unpriced: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageGroup {
+    pub key: String,
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+    /// Calls in the group whose model had no price; their cost is not in `cost`.
+    pub unpriced_calls: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageSummary {
+    pub total: UsageGroup,
+    pub by_task: Vec<UsageGroup>,
+    pub by_model: Vec<UsageGroup>,
+    /// Per day, or per month when the query asks for it.
+    pub by_period: Vec<UsageGroup>,
+    pub monthly_budget: Option<f64>,
+    pub month_cost: f64,
+    /// Cloud models called this month without a configured price; `month_cost` and the
+    /// budget check do not include them.
+    pub unpriced_models: Vec<String>,
+}
+
+impl UsageGroup {
+    fn add(&mut self, entry: &UsageEntry) {
+        self.calls += 1;
+        self.prompt_tokens += entry.prompt_tokens;
+        self.completion_tokens += entry.completion_tokens;
+        self.cost += entry.cost;
+        if entry.unpriced {
+            self.unpriced_calls += 1;
+        }
+    }
+}
+
+/// The JSON-lines file calls are recorded in.
+struct Ledger {
+    path: PathBuf,
+}
+
+impl Ledger {
+    fn append(&self, entry: &UsageEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    fn read(&self) -> Vec<UsageEntry> {
+        let Ok(data) = fs::read_to_string(&self.path) else {
+            return Vec::new();
+        };
+        // 写到一半的行直接跳过
+        data.lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct Pricing {
+    /// Where calls are recorded; until the app opens one, calls are not recorded.
+    ledger: Option<Ledger>,
+    prices: Vec<ModelPrice>,
+    monthly_budget: Option<f64>,
+    /// `(YYYY-MM, cost)` of cloud calls this month, read from the ledger on first use.
+    month_cost: Option<(String, f64)>,
+}
+
+fn pricing() -> &'static Mutex<Pricing> {
+    static PRICING: OnceLock<Mutex<Pricing>> = OnceLock::new();
+    PRICING.get_or_init(Default::default)
+}
+
+/// Applies the price table and monthly budget in `config`.
+pub fn configure(config: &AppConfig) {
+    let mut pricing = pricing().lock().unwrap_or_else(|e| e.into_inner());
+    pricing.prices = config.model_prices.clone();
+    pricing.monthly_budget = config.monthly_budget.filter(|budget| *budget > 0.0);
+}
+
+/// Records calls in the ledger at `path` from now on; the app opens
+/// [`default_ledger_path`] at startup.
+pub fn open_ledger(path: &Path) {
+    let mut pricing = pricing().lock().unwrap_or_else(|e| e.into_inner());
+    pricing.ledger = Some(Ledger {
+        path: path.to_path_buf(),
+    });
+    pricing.month_cost = None;
+}
+
+pub fn default_ledger_path() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("fyla")
+        .join("usage-ledger.jsonl")
+}
+
+/// Cloud calls cost money: OpenAI-compatible APIs that are not on this machine.
+fn is_cloud(provider: &str, endpoint: &str) -> bool {
+    provider == "openai" && !redact::is_local_endpoint(endpoint)
+}
+
+fn current_month() -> String {
+    chrono::Local::now().format("%Y-%m").to_string()
+}
+
+fn month_cost(pricing: &mut Pricing) -> f64 {
+    let month = current_month();
+    match &pricing.month_cost {
+        Some((cached, cost)) if *cached == month => *cost,
+        _ => {
+            let cost = read_ledger(pricing)
+                .iter()
+                .filter(|entry| entry.cloud && entry.at.starts_with(&month))
+                .map(|entry| entry.cost)
+                .sum();
+            pricing.month_cost = Some((month, cost));
+            cost
+        }
+    }
+}
+
+/// Fails once this month's cloud spend reaches the budget. Local models are never blocked;
+/// cloud models without a price are let through but logged, since the budget cannot
+/// account for them.
+pub fn ensure_budget(provider: &str, endpoint: &str, model: &str) -> Result<()> {
+    if !is_cloud(provider, endpoint) {
+        return Ok(());
+    }
+    let mut pricing = pricing().lock().unwrap_or_else(|e| e.into_inner());
+    let Some(budget) = pricing.monthly_budget else {
+        return Ok(());
+    };
+    if price_for(&pricing.prices, model).is_none() {
+        log::warn!(
+            target: "usage",
+            "{} is unpriced, its calls do not count toward the monthly budget",
+            model
+        );
+    }
+    let spent = month_cost(&mut pricing);
+    if spent >= budget {
+        return Err(anyhow!(
+            "本月云端模型费用 {:.2} 已达到预算 {:.2}，已暂停云端调用；可在设置中调高预算",
+            spent,
+            budget
+        ));
+    }
+    Ok(())
+}
+
+fn price_for<'a>(prices: &'a [ModelPrice], model: &str) -> Option<&'a ModelPrice> {
+    prices
+        .iter()
+        .find(|price| price.model.trim().eq_ignore_ascii_case(model.trim()))
+}
+
+/// Appends a call to the ledger. Failures are logged: accounting never fails a task.
+pub fn record_call(
+    task: ModelTask,
+    provider: &str,
+    model: &str,
+    endpoint: &str,
+    usage: TokenUsage,
+) {
+    let mut pricing = pricing().lock().unwrap_or_else(|e| e.into_inner());
+    if let Err(err) = pricing.record(task, provider, model, endpoint, usage) {
+        log::warn!(target: "usage", "failed to write ledger: {:#}", err);
+    }
+}
+
+impl Pricing {
+    /// Calls to `mock` profiles cost nothing and are not recorded.
+    fn record(
+        &mut self,
+        task: ModelTask,
+        provider: &str,
+        model: &str,
+        endpoint: &str,
+        usage: TokenUsage,
+    ) -> Result<()> {
+        let Some(ledger) = &self.ledger else {
+            return Ok(());
+        };
+        if provider == "mock" {
+            return Ok(());
+        }
+        let price = price_for(&self.prices, model);
+        let entry = UsageEntry {
+            at: chrono::Local::now().to_rfc3339(),
+            task: task.key().to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            cloud: is_cloud(provider, endpoint),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            estimated: usage.estimated,
+            cost: price.map_or(0.0, |price| {
+                price.cost(usage.prompt_tokens, usage.completion_tokens)
+            }),
+            unpriced: price.is_none(),
+        };
+        ledger.append(&entry)?;
+        if entry.cloud {
+            let month = current_month();
+            if let Some((cached, total)) = &mut self.month_cost
+                && *cached == month
+            {
+                *total += entry.cost;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_ledger(pricing: &Pricing) -> Vec<UsageEntry> {
+    pricing
+        .ledger
+        .as_ref()
+        .map(Ledger::read)
+        .unwrap_or_default()
+}
+
+/// Sums the ledger between `from` and `to` (inclusive `YYYY-MM-DD` dates, either may be
+/// omitted), grouped by task, model and day or month.
+pub fn summarize(from: Option<&str>, to: Option<&str>, by_month: bool) -> UsageSummary {
+    let (entries, monthly_budget, month_cost) = {
+        let mut pricing = pricing().lock().unwrap_or_else(|e| e.into_inner());
+        let cost = month_cost(&mut pricing);
+        (read_ledger(&pricing), pricing.monthly_budget, cost)
+    };
+    summarize_entries(&entries, from, to, by_month, monthly_budget, month_cost)
+}
+
+fn summarize_entries(
+    entries: &[UsageEntry],
+    from: Option<&str>,
+    to: Option<&str>,
+    by_month: bool,
+    monthly_budget: Option<f64>,
+    month_cost: f64,
+) -> UsageSummary {
+    let mut total = UsageGroup::default();
+    let mut by_task = BTreeMap::<String, UsageGroup>::new();
+    let mut by_model = BTreeMap::<String, UsageGroup>::new();
+    let mut by_period = BTreeMap::<String, UsageGroup>::new();
+    let month = current_month();
+    let mut unpriced_models = entries
+        .iter()
+        .filter(|entry| entry.cloud && entry.unpriced && entry.at.starts_with(&month))
+        .map(|entry| entry.model.clone())
+        .collect::<Vec<_>>();
+    unpriced_models.sort();
+    unpriced_models.dedup();
+
+    for entry in entries {
+        let day = entry.at.get(..10).unwrap_or_default();
+        if from.is_some_and(|from| day < from) || to.is_some_and(|to| day > to) {
+            continue;
+        }
+        let period = if by_month {
+            day.get(..7).unwrap_or_default()
+        } else {
+            day
+        };
+        total.add(entry);
+        for (groups, key) in [
+            (&mut by_task, entry.task.as_str()),
+            (&mut by_model, entry.model.as_str()),
+            (&mut by_period, period),
+        ] {
+            groups
+                .entry(key.to_string())
+                .or_insert_with(|| UsageGroup {
+                    key: key.to_string(),
+                    ..Default::default()
+                })
+                .add(entry);
+        }
+    }
+
+    let by_cost = |groups: BTreeMap<String, UsageGroup>| {
+        let mut groups = groups.into_values().collect::<Vec<_>>();
+        groups.sort_by(|a, b| b.cost.total_cmp(&a.cost).then(b.calls.cmp(&a.calls)));
+        groups
+    };
+    UsageSummary {
+        total,
+        by_task: by_cost(by_task),
+        by_model: by_cost(by_model),
+        by_period: by_period.into_values().collect(),
+        monthly_budget,
+        month_cost,
+        unpriced_models,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn dropping_a_meter_survives_a_poisoned_pricing_lock() {
+        let _ = std::thread::spawn(|| {
+            let _guard = pricing().lock().unwrap_or_else(|e| e.into_inner());
+            panic!("poison the pricing lock");
+        })
+        .join();
+        assert!(pricing().is_poisoned());
+
+        let profile = ModelProfile {
+            provider: "ollama".into(),
+            model: "llama3.2".into(),
+            ..ModelProfile::default()
+        };
+        let mut meter = UsageMeter::new(ModelTask::Rename, &profile, 40);
+        meter.add_output("Invoice-Acme");
+        drop(meter);
+        assert!(ensure_budget("ollama", "http://localhost:11434", "llama3.2").is_ok());
+    }
+
+    #[test]
+    fn from_response_reads_openai_and_ollama_usage() {
+        let openai = json!({ "usage": { "prompt_tokens": 120, "completion_tokens": 30 } });
+        assert_eq!(
+            TokenUsage::from_response(&openai),
+            Some(TokenUsage {
+                prompt_tokens: 120,
+                completion_tokens: 30,
+                estimated: false,
+            })
+        );
+        let ollama = json!({ "done": true, "prompt_eval_count": 80, "eval_count": 12 });
+        assert_eq!(
+            TokenUsage::from_response(&ollama)
+                .unwrap()
+                .completion_tokens,
+            12
+        );
+        let embedding = json!({ "usage": { "prompt_tokens": 9, "total_tokens": 9 } });
+        assert_eq!(
+            TokenUsage::from_response(&embedding).unwrap().prompt_tokens,
+            9
+        );
+        assert_eq!(TokenUsage::from_response(&json!({ "usage": null })), None);
+        assert_eq!(TokenUsage::from_response(&json!({ "message": {} })), None);
+    }
+
+    #[test]
+    fn summarize_groups_by_task_model_and_period() {
+        let entry = |at: &str, task: &str, model: &str, cost: f64| UsageEntry {
+            at: at.into(),
+            task: task.into(),
+            provider: "openai".into(),
+            model: model.into(),
+            cloud: true,
+            prompt_tokens: 100,
+            completion_tokens: 10,
+            estimated: false,
+            cost,
+            unpriced: false,
+        };
+        let entries = [
+            entry("2026-03-31T23:00:00+08:00", "rename", "gpt-4o-mini", 0.5),
+            entry("2026-04-01T09:00:00+08:00", "paperReview", "gpt-4.1", 2.0),
+            entry("2026-04-02T10:00:00+08:00", "rename", "gpt-4o-mini", 0.25),
+        ];
+
+        let summary =
+            summarize_entries(&entries, Some("2026-04-01"), None, false, Some(10.0), 2.25);
+        assert_eq!(summary.total.calls, 2);
+        assert_eq!(summary.total.cost, 2.25);
+        assert_eq!(summary.by_task[0].key, "paperReview");
+        assert_eq!(summary.by_model[1].key, "gpt-4o-mini");
+        assert_eq!(summary.by_period[0].key, "2026-04-01");
+
+        let monthly = summarize_entries(&entries, None, None, true, None, 0.0);
+        let months = monthly.by_period.iter().map(|group| group.key.as_str());
+        assert_eq!(months.collect::<Vec<_>>(), vec!["2026-03", "2026-04"]);
+    }
+
+    #[test]
+    fn ledger_skips_mock_calls_and_flags_unpriced_models() {
+        let path = std::env::temp_dir().join(format!(
+            "fyla-usage-ledger-test-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let mut pricing = Pricing {
+            ledger: Some(Ledger { path: path.clone() }),
+            prices: vec![ModelPrice {
+                model: "gpt-4o-mini".into(),
+                input_per_million: 1.0,
+                output_per_million: 2.0,
+            }],
+            ..Pricing::default()
+        };
+        let usage = TokenUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 0,
+            estimated: false,
+        };
+        let endpoint = "https://api.example.com/v1";
+        for (provider, model) in [
+            ("openai", "gpt-4o-mini"),
+            ("openai", "mystery-model"),
+            ("mock", "mock"),
+        ] {
+            pricing
+                .record(ModelTask::Rename, provider, model, endpoint, usage)
+                .unwrap();
+        }
+
+        let entries = pricing.ledger.as_ref().unwrap().read();
+        let _ = fs::remove_file(&path);
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].cost, entries[0].unpriced), (1.0, false));
+        assert_eq!((entries[1].cost, entries[1].unpriced), (0.0, true));
+
+        let summary = summarize_entries(&entries, None, None, false, Some(5.0), 1.0);
+        assert_eq!(summary.total.unpriced_calls, 1);
+        assert_eq!(summary.unpriced_models, vec!["mystery-model".to_string()]);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::extraction_cache;
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[cfg(target_os = "macos")]
+use std::ffi::CString;
+#[cfg(target_os = "macos")]
+use std::os::raw::c_char;
+
+#[cfg(target_os = "macos")]
+unsafe extern "C" {
+    fn prepare_image_for_vlm(path: *const c_char, max_edge: i32, output_path: *const c_char)
+    -> i32;
+}
+
+/// Bump whenever conversion changes output, so stale cached images are regenerated.
+const CACHE_VERSION: u32 = 1;
+const JPEG_QUALITY: u8 = 85;
+/// Smallest edge limit accepted from the config; below this VLMs cannot read text.
+const MIN_MAX_EDGE: u32 = 256;
+/// Cached images not used for this long are deleted when a new one is written.
+const CACHE_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// The cache is trimmed to this size, least recently used images first.
+const CACHE_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// An image re-encoded for a vision model: JPEG, or PNG when it has transparency.
+#[derive(Debug, Clone)]
+pub struct PreparedImage {
+    pub bytes: Vec<u8>,
+    pub mime: &'static str,
+}
+
+impl PreparedImage {
+    pub fn base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.bytes)
+    }
+}
+
+/// Converts the image at `path` for upload: applies the EXIF orientation, scales it so the
+/// longest edge is at most `max_edge` pixels and re-encodes it from pixels, which drops
+/// EXIF (including GPS) and other metadata. HEIC, and GIF (the `image` crate is built
+/// without GIF support), are decoded with ImageIO on macOS only. Results are cached by
+/// file content and edge limit.
+pub fn prepare(path: &str, max_edge: u32) -> Result<PreparedImage> {
+    prepare_in(&cache_root(), path, max_edge)
+}
+
+fn prepare_in(root: &Path, path: &str, max_edge: u32) -> Result<PreparedImage> {
+    let max_edge = max_edge.max(MIN_MAX_EDGE);
+    let hash = extraction_cache::file_hash(path)?;
+    let stem = format!("{}-{}-v{}", hash, max_edge, CACHE_VERSION);
+    for (extension, mime) in [("jpg", "image/jpeg"), ("png", "image/png")] {
+        let cached = root.join(format!("{}.{}", stem, extension));
+        if let Ok(bytes) = std::fs::read(&cached) {
+            log::debug!(target: "vlm-image", "cache hit {} for {}", stem, path);
+            // 命中时刷新修改时间，清理按最近使用排序
+            let _ = std::fs::File::options()
+                .append(true)
+                .open(&cached)
+                .and_then(|file| file.set_modified(SystemTime::now()));
+            return Ok(PreparedImage { bytes, mime });
+        }
+    }
+
+    let prepared = convert(path, max_edge)?;
+    let extension = if prepared.mime == "image/png" {
+        "png"
+    } else {
+        "jpg"
+    };
+    if let Err(err) = write_cached(root, &format!("{}.{}", stem, extension), &prepared.bytes) {
+        log::warn!(target: "vlm-image", "failed to cache {}: {}", stem, err);
+    }
+    prune_cache(root, CACHE_MAX_AGE, CACHE_MAX_BYTES);
+    Ok(prepared)
+}
+
+fn convert(path: &str, max_edge: u32) -> Result<PreparedImage> {
+    if is_heif(path) {
+        return convert_natively(path, max_edge);
+    }
+    match convert_with_image_crate(path, max_edge) {
+        Ok(prepared) => Ok(prepared),
+        Err(err) => {
+            // image crate 不支持的编码（如 CMYK TIFF）交给系统解码
+            log::warn!(target: "vlm-image", "decode failed for {}: {:#}", path, err);
+            convert_natively(path, max_edge).map_err(|_| err)
+        }
+    }
+}
+
+fn convert_with_image_crate(path: &str, max_edge: u32) -> Result<PreparedImage> {
+    let mut decoder = ImageReader::open(path)
+        .map_err(|e| anyhow!("读取图片失败: {}", e))?
+        .with_guessed_format()?
+        .into_decoder()
+        .map_err(|e| anyhow!("无法解码图片: {}", e))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image =
+        DynamicImage::from_decoder(decoder).map_err(|e| anyhow!("无法解码图片: {}", e))?;
+    image.apply_orientation(orientation);
+    if image.width().max(image.height()) > max_edge {
+        image = image.resize(max_edge, max_edge, FilterType::Lanczos3);
+    }
+    encode(&image)
+}
+
+fn encode(image: &DynamicImage) -> Result<PreparedImage> {
+    let mut bytes = Vec::new();
+    if image.color().has_alpha() {
+        image
+            .to_rgba8()
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+        Ok(PreparedImage {
+            bytes,
+            mime: "image/png",
+        })
+    } else {
+        image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY))?;
+        Ok(PreparedImage {
+            bytes,
+            mime: "image/jpeg",
+        })
+    }
+}
+
+/// HEIC/HEIF by extension or by the `ftyp` brand, since phones also save HEIC as `.jpg`.
+fn is_heif(path: &str) -> bool {
+    let extension = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if matches!(extension.as_str(), "heic" | "heif") {
+        return true;
+    }
+    let mut header = [0u8; 12];
+    std::fs::File::open(path)
+        .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut header))
+        .is_ok()
+        && &header[4..8] == b"ftyp"
+        && matches!(
+            &header[8..12],
+            b"heic" | b"heix" | b"heim" | b"heis" | b"mif1" | b"msf1"
+        )
+}
+
+#[cfg(target_os = "macos")]
+fn convert_natively(path: &str, max_edge: u32) -> Result<PreparedImage> {
+    let output = std::env::temp_dir().join(format!(
+        "fyla-vlm-{}-{}.jpg",
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ));
+    let c_path = CString::new(path).map_err(|_| anyhow!("路径包含非法字符"))?;
+    let c_output = CString::new(output.to_string_lossy().as_bytes())
+        .map_err(|_| anyhow!("路径包含非法字符"))?;
+    let converted =
+        unsafe { prepare_image_for_vlm(c_path.as_ptr(), max_edge as i32, c_output.as_ptr()) };
+    let bytes = std::fs::read(&output);
+    let _ = std::fs::remove_file(&output);
+    match bytes {
+        Ok(bytes) if converted == 1 => Ok(PreparedImage {
+            bytes,
+            mime: "image/jpeg",
+        }),
+        _ => Err(anyhow!("系统无法解码图片: {}", path)),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn convert_natively(path: &str, _max_edge: u32) -> Result<PreparedImage> {
+    Err(anyhow!(
+        "当前平台无法解码该图片（HEIC、GIF 仅支持 macOS）: {}",
+        path
+    ))
+}
+
+fn cache_root() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("fyla")
+        .join("vlm-image-cache")
+}
+
+fn write_cached(root: &Path, name: &str, bytes: &[u8]) -> Result<()> {
+    std::fs::create_dir_all(root)?;
+    // 先写临时文件再改名，读取方不会读到半张图
+    let temp = root.join(format!("{}.{}.tmp", name, std::process::id()));
+    std::fs::write(&temp, bytes)?;
+    std::fs::rename(&temp, root.join(name))?;
+    Ok(())
+}
+
+/// Deletes cached images unused for longer than `max_age`, then the least recently used
+/// ones until the rest fit in `max_bytes`.
+fn prune_cache(root: &Path, max_age: Duration, max_bytes: u64) {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return;
+    };
+    let mut files = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            let used = metadata.modified().ok()?;
+            metadata
+                .is_file()
+                .then(|| (used, metadata.len(), entry.path()))
+        })
+        .collect::<Vec<_>>();
+    // 最近使用的在前，超龄或超出总量的从尾部删除
+    files.sort_by_key(|(used, _, _)| std::cmp::Reverse(*used));
+    let now = SystemTime::now();
+    let mut kept_bytes = 0u64;
+    for (used, size, path) in files {
+        let expired = now.duration_since(used).is_ok_and(|age| age > max_age);
+        if expired || kept_bytes + size > max_bytes {
+            let _ = std::fs::remove_file(&path);
+        } else {
+            kept_bytes += size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgb, RgbImage, Rgba, RgbaImage};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("fyla-vlm-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn prepare_downscales_and_reencodes_as_jpeg() {
+        let root = temp_path("cache-jpeg");
+        let source = temp_path("wide.png");
+        RgbImage::from_pixel(3000, 1000, Rgb([200, 40, 40]))
+            .save(&source)
+            .unwrap();
+
+        let prepared = prepare_in(&root, &source.to_string_lossy(), 1200).unwrap();
+        let cached = prepare_in(&root, &source.to_string_lossy(), 1200).unwrap();
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_file(&source);
+
+        assert_eq!(prepared.mime, "image/jpeg");
+        let decoded = image::load_from_memory(&prepared.bytes).unwrap();
+        assert_eq!(decoded.dimensions(), (1200, 400));
+        assert_eq!(cached.bytes, prepared.bytes);
+    }
+
+    #[test]
+    fn prepare_keeps_transparency_as_png() {
+        let root = temp_path("cache-png");
+        let source = temp_path("alpha.png");
+        RgbaImage::from_pixel(300, 200, Rgba([0, 0, 0, 0]))
+            .save(&source)
+            .unwrap();
+
+        let prepared = prepare_in(&root, &source.to_string_lossy(), 2048).unwrap();
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_file(&source);
+
+        assert_eq!(prepared.mime, "image/png");
+        let decoded = image::load_from_memory(&prepared.bytes).unwrap();
+        assert_eq!(decoded.dimensions(), (300, 200));
+    }
+
+    #[test]
+    fn prune_cache_drops_stale_and_least_recently_used_images() {
+        let root = temp_path("cache-prune");
+        std::fs::create_dir_all(&root).unwrap();
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        for (name, age_days) in [("stale.jpg", 40), ("older.jpg", 2), ("newer.jpg", 1)] {
+            let path = root.join(name);
+            std::fs::write(&path, [0u8; 100]).unwrap();
+            std::fs::File::options()
+                .append(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(now - day * age_days))
+                .unwrap();
+        }
+
+        prune_cache(&root, day * 30, 150);
+        let mut left = std::fs::read_dir(&root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        left.sort();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(left, ["newer.jpg"]);
+    }
+}
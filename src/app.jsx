@@ -8,7 +8,7 @@ import {
 } from './lib/store.js'
 import { tasks, stats, enqueueFile, confirmAll, dismissAll } from './lib/taskQueue.js'
 import { enqueuePaperPaths, hydratePaperHistory, paperStats, resetPaperTab } from './lib/paperQueue.js'
-import { getConfig, getHistory, undoRename, friendlyError, setBadgeCount, scanPaths } from './lib/tauri.js'
+import { getConfig, getHistory, undoRename, friendlyError, setBadgeCount, scanPaths, takeConfigProblems } from './lib/tauri.js'
 import { isPermissionGranted, requestPermission, sendNotification } from '@tauri-apps/plugin-notification'
 import { useAppShortcuts } from './lib/shortcuts.js'
 import { t, lang, toggleLang } from './lib/i18n.js'
@@ -42,6 +42,12 @@ export function App() {
       }
     }).catch(() => {})
 
+    if (windowLabel === 'main') {
+      takeConfigProblems().then(problems => {
+        if (problems?.length) showToast(problems.join('\n'), 10000)
+      }).catch(() => {})
+    }
+
     getHistory().then(history => {
       if (history && history.length) {
         recentActivity.value = history.map(h => ({
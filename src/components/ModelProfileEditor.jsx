@@ -0,0 +1,260 @@
+import { useState } from 'preact/hooks'
+import { config } from '../lib/store.js'
+import { testConnection } from '../lib/tauri.js'
+import { t } from '../lib/i18n.js'
+import { DEFAULT_OLLAMA_URL, DEFAULT_OPENAI_BASE_URL } from '../lib/profiles.js'
+
+const CAPABILITIES = ['chat', 'vision', 'embedding']
+
+function parseOptionalNumber(value, integer = false) {
+  if (value === '') return null
+  const number = integer ? parseInt(value, 10) : Number(value)
+  return Number.isFinite(number) ? number : null
+}
+
+export function ModelProfileEditor({ profile, expanded, onToggle, onChange, onRemove }) {
+  const [testing, setTesting] = useState(false)
+  const [testResult, setTestResult] = useState(null)
+  const isOllama = profile.provider === 'ollama'
+  const isMock = profile.provider === 'mock'
+  const sampling = profile.sampling || {}
+  const mock = profile.mock || {}
+
+  function updateCapability(key, value) {
+    onChange({ capabilities: { ...profile.capabilities, [key]: value } })
+  }
+
+  function updateSampling(key, value) {
+    onChange({ sampling: { ...sampling, [key]: value } })
+  }
+
+  function updateMock(key, value) {
+    onChange({ mock: { ...mock, [key]: value } })
+  }
+
+  async function handleTest() {
+    setTesting(true); setTestResult(null)
+    try {
+      const msg = await testConnection(config.value, profile.id)
+      setTestResult({ ok: true, msg })
+    } catch (e) {
+      setTestResult({ ok: false, msg: String(e) })
+    }
+    setTesting(false)
+  }
+
+  return (
+    <div class="settings-profile">
+      <div class="settings-row">
+        <span class="settings-label">
+          {profile.name || profile.id}
+          <small>
+            {isMock ? t('settings.mockProvider') : isOllama ? t('settings.ollamaLocal') : t('settings.openaiCompat')}
+            {profile.model ? ` · ${profile.model}` : ''}
+          </small>
+        </span>
+        <button class="btn btn-secondary" style="font-size:12px" onClick={onToggle}>
+          {expanded ? t('settings.profileCollapse') : t('settings.profileEdit')}
+        </button>
+      </div>
+
+      {expanded && (
+        <>
+          <div class="settings-row">
+            <span class="settings-label">{t('settings.profileName')}</span>
+            <input
+              class="settings-input"
+              type="text"
+              value={profile.name}
+              onInput={e => onChange({ name: e.target.value })}
+            />
+          </div>
+          <div class="settings-row">
+            <span class="settings-label">{t('settings.selectMode')}</span>
+            <div class="toggle-group">
+              {[['ollama', 'settings.ollamaLocal'], ['openai', 'settings.openaiCompat'], ['mock', 'settings.mockProvider']].map(([value, label]) => (
+                <button
+                  key={value}
+                  class={`toggle-option ${profile.provider === value ? 'active' : ''}`}
+                  onClick={() => onChange({
+                    provider: value,
+                    baseUrl: value === 'ollama' ? DEFAULT_OLLAMA_URL : value === 'openai' ? DEFAULT_OPENAI_BASE_URL : '',
+                  })}
+                >
+                  {t(label)}
+                </button>
+              ))}
+            </div>
+          </div>
+          {isMock ? (
+            <div class="settings-row">
+              <span class="settings-label">
+                {t('settings.mockStream')}
+                <small>{t('settings.mockStreamHint')}</small>
+              </span>
+              <input
+                class="settings-input"
+                style="max-width:80px"
+                type="number"
+                min="0"
+                value={mock.chunkChars ?? 0}
+                onInput={e => updateMock('chunkChars', parseOptionalNumber(e.target.value, true) ?? 0)}
+                placeholder={t('settings.mockChunkChars')}
+              />
+              <input
+                class="settings-input"
+                style="max-width:80px"
+                type="number"
+                min="0"
+                step="50"
+                value={mock.chunkDelayMs ?? 0}
+                onInput={e => updateMock('chunkDelayMs', parseOptionalNumber(e.target.value, true) ?? 0)}
+                placeholder={t('settings.mockChunkDelay')}
+              />
+              <input
+                class="settings-input"
+                style="max-width:100px"
+                type="number"
+                min="0"
+                value={mock.failAfterChunks ?? ''}
+                onInput={e => updateMock('failAfterChunks', parseOptionalNumber(e.target.value, true))}
+                placeholder={t('settings.mockFailAfter')}
+              />
+            </div>
+          ) : (
+            <div class="settings-row">
+              <span class="settings-label">
+                {isOllama ? t('settings.serverUrl') : t('settings.baseUrl')}
+                <small>{isOllama ? t('settings.serverUrlHint') : t('settings.baseUrlHint')}</small>
+              </span>
+              <input
+                class="settings-input"
+                type="text"
+                value={profile.baseUrl}
+                onInput={e => onChange({ baseUrl: e.target.value })}
+                placeholder={isOllama ? DEFAULT_OLLAMA_URL : DEFAULT_OPENAI_BASE_URL}
+              />
+            </div>
+          )}
+          {!isOllama && !isMock && (
+            <div class="settings-row">
+              <span class="settings-label">
+                {t('settings.apiKey')}
+                <small>{t('settings.apiKeyHint')}</small>
+              </span>
+              <input
+                class="settings-input"
+                type="password"
+                value={profile.apiKey}
+                onInput={e => onChange({ apiKey: e.target.value })}
+                placeholder="sk-..."
+              />
+            </div>
+          )}
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.model')}
+              <small>{isOllama ? t('settings.modelNameHint') : t('settings.modelHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              type="text"
+              value={profile.model}
+              onInput={e => onChange({ model: e.target.value })}
+              placeholder={isOllama ? 'llama3.2' : 'gpt-4o-mini'}
+            />
+          </div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.profileCapabilities')}
+              <small>{t('settings.profileCapabilitiesHint')}</small>
+            </span>
+            <div class="toggle-group">
+              {CAPABILITIES.map(key => (
+                <button
+                  key={key}
+                  class={`toggle-option ${profile.capabilities?.[key] ? 'active' : ''}`}
+                  onClick={() => updateCapability(key, !profile.capabilities?.[key])}
+                >
+                  {t(`settings.capability${key.charAt(0).toUpperCase()}${key.slice(1)}`)}
+                </button>
+              ))}
+            </div>
+          </div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.profileSampling')}
+              <small>{t('settings.profileSamplingHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              style="max-width:80px"
+              type="number"
+              min="0"
+              max="2"
+              step="0.1"
+              value={sampling.temperature ?? ''}
+              onInput={e => updateSampling('temperature', parseOptionalNumber(e.target.value))}
+              placeholder="temperature"
+            />
+            <input
+              class="settings-input"
+              style="max-width:80px"
+              type="number"
+              min="0"
+              max="1"
+              step="0.05"
+              value={sampling.topP ?? ''}
+              onInput={e => updateSampling('topP', parseOptionalNumber(e.target.value))}
+              placeholder="top_p"
+            />
+            <input
+              class="settings-input"
+              style="max-width:100px"
+              type="number"
+              min="1"
+              step="256"
+              value={sampling.maxTokens ?? ''}
+              onInput={e => updateSampling('maxTokens', parseOptionalNumber(e.target.value, true))}
+              placeholder="max_tokens"
+            />
+          </div>
+          <div class="settings-row" style="justify-content:flex-end">
+            {testResult && (
+              <span style={`font-size:12px;margin-right:auto;color:${testResult.ok ? 'var(--success)' : 'var(--danger)'}`}>
+                {testResult.msg}
+              </span>
+            )}
+            <button class="btn btn-secondary" style="font-size:12px" onClick={onRemove}>
+              {t('settings.profileDelete')}
+            </button>
+            <button class="btn btn-secondary" style="font-size:12px" disabled={testing} onClick={handleTest}>
+              {testing ? t('settings.testing') : t('settings.testConnection')}
+            </button>
+          </div>
+        </>
+      )}
+    </div>
+  )
+}
+
+// 任务选择：只列出具备对应能力的配置，当前值不满足时仍保留以便看出问题
+export function TaskProfileSelect({ c, task, label, hint, emptyLabel, onChange }) {
+  const options = (c.profiles || []).filter(profile =>
+    profile.capabilities?.[task.capability] || profile.id === c[task.key])
+
+  return (
+    <div class="settings-row">
+      <span class="settings-label">
+        {label}
+        {hint && <small>{hint}</small>}
+      </span>
+      <select class="settings-select" value={c[task.key] || ''} onChange={e => onChange(task.key, e.target.value)}>
+        {(emptyLabel || !c[task.key]) && <option value="">{emptyLabel || t('settings.profileUnset')}</option>}
+        {options.map(profile => (
+          <option key={profile.id} value={profile.id}>{profile.name || profile.id}</option>
+        ))}
+      </select>
+    </div>
+  )
+}
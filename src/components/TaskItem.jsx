@@ -1,5 +1,6 @@
 import { memo } from 'preact/compat'
-import { confirmTask, skipTask, updateTaskName, updateTaskDest, retryTask } from '../lib/taskQueue.js'
+import { useState } from 'preact/hooks'
+import { confirmTask, skipTask, updateTaskName, updateTaskDest, retryTask, unlockTask } from '../lib/taskQueue.js'
 import { pickFolder } from '../lib/tauri.js'
 import { t } from '../lib/i18n.js'
 
@@ -43,7 +44,9 @@ export const TaskItem = memo(function TaskItem({ task }) {
   const isReady = task.status === 'ready'
   const isDone = task.status === 'done'
   const isError = task.status === 'error'
+  const isLocked = isError && task.locked
   const StatusRenderer = STATUS_TEXT[task.status]
+  const [password, setPassword] = useState('')
 
   async function handlePickDest() {
     const folder = await pickFolder()
@@ -75,6 +78,19 @@ export const TaskItem = memo(function TaskItem({ task }) {
           </>
         )}
 
+        {isLocked && (
+          <div class="list-row-new">
+            <input
+              type="password"
+              value={password}
+              placeholder={t('task.pdfPassword')}
+              onInput={e => setPassword(e.target.value)}
+              onKeyDown={e => { if (e.key === 'Enter') unlockTask(task.id, password) }}
+              onClick={e => e.stopPropagation()}
+            />
+          </div>
+        )}
+
         {isDone && task.newName && (
           <>
             <svg class="list-row-arrow" width="12" height="12" viewBox="0 0 12 12" fill="none">
@@ -99,7 +115,10 @@ export const TaskItem = memo(function TaskItem({ task }) {
         {isReady && task.newName && (
           <button class="btn btn-primary btn-sm" onClick={() => confirmTask(task.id)}>{t('confirm.confirm')}</button>
         )}
-        {isError && (
+        {isLocked && (
+          <button class="btn btn-primary btn-sm" disabled={!password} onClick={() => unlockTask(task.id, password)}>{t('task.unlock')}</button>
+        )}
+        {isError && !isLocked && (
           <button class="btn btn-ghost btn-sm" onClick={() => retryTask(task.id)}>{t('task.retry')}</button>
         )}
         {(isReady || isError) && (
@@ -3,6 +3,7 @@ import { Icon } from './Icon.jsx'
 import { config } from '../lib/store.js'
 import { saveConfig, testConnection, pickFolder } from '../lib/tauri.js'
 import { t } from '../lib/i18n.js'
+import { profileForTask, withProfile, withProviderForTask } from '../lib/profiles.js'
 
 const STORAGE_KEY = 'fyla-onboard-done'
 
@@ -15,13 +16,23 @@ export function useOnboard() {
 }
 
 function update(key, value) {
-  const next = { ...config.value, [key]: value }
+  save({ ...config.value, [key]: value })
+}
+
+function save(next) {
   config.value = next
   saveConfig(next).catch(() => {})
 }
 
 function StepProvider() {
   const c = config.value
+  const profile = profileForTask(c, 'renameProfile') || {}
+  const provider = profile.provider || 'ollama'
+
+  function updateProfile(key, value) {
+    if (!profile.id) return
+    save(withProfile(config.value, profile.id, { [key]: value }))
+  }
   const [testing, setTesting] = useState(false)
   const [testResult, setTestResult] = useState(null)
 
@@ -41,29 +52,29 @@ function StepProvider() {
       <div class="welcome-form-row">
         <div class="toggle-group" style="width:100%">
           <button
-            class={`toggle-option ${c.provider === 'ollama' ? 'active' : ''}`}
-            onClick={() => update('provider', 'ollama')}
+            class={`toggle-option ${provider === 'ollama' ? 'active' : ''}`}
+            onClick={() => save(withProviderForTask(config.value, 'renameProfile', 'ollama'))}
           >
             {t('settings.ollamaLocal')}
           </button>
           <button
-            class={`toggle-option ${c.provider === 'openai' ? 'active' : ''}`}
-            onClick={() => update('provider', 'openai')}
+            class={`toggle-option ${provider === 'openai' ? 'active' : ''}`}
+            onClick={() => save(withProviderForTask(config.value, 'renameProfile', 'openai'))}
           >
             {t('settings.openaiCompat')}
           </button>
         </div>
       </div>
 
-      {c.provider === 'ollama' ? (
+      {provider === 'ollama' ? (
         <>
           <div class="welcome-form-row">
             <label class="welcome-form-label">{t('settings.serverUrl')}</label>
             <input
               class="settings-input"
               style="max-width:none"
-              value={c.ollamaUrl}
-              onInput={e => update('ollamaUrl', e.target.value)}
+              value={profile.baseUrl}
+              onInput={e => updateProfile('baseUrl', e.target.value)}
               placeholder="http://localhost:11434"
             />
           </div>
@@ -72,8 +83,8 @@ function StepProvider() {
             <input
               class="settings-input"
               style="max-width:none"
-              value={c.ollamaModel}
-              onInput={e => update('ollamaModel', e.target.value)}
+              value={profile.model}
+              onInput={e => updateProfile('model', e.target.value)}
               placeholder="llama3.2"
             />
           </div>
@@ -85,8 +96,8 @@ function StepProvider() {
             <input
               class="settings-input"
               style="max-width:none"
-              value={c.openaiBaseUrl}
-              onInput={e => update('openaiBaseUrl', e.target.value)}
+              value={profile.baseUrl}
+              onInput={e => updateProfile('baseUrl', e.target.value)}
               placeholder="https://api.openai.com/v1"
             />
           </div>
@@ -96,8 +107,8 @@ function StepProvider() {
               class="settings-input"
               style="max-width:none"
               type="password"
-              value={c.openaiKey}
-              onInput={e => update('openaiKey', e.target.value)}
+              value={profile.apiKey}
+              onInput={e => updateProfile('apiKey', e.target.value)}
               placeholder="sk-..."
             />
           </div>
@@ -106,8 +117,8 @@ function StepProvider() {
             <input
               class="settings-input"
               style="max-width:none"
-              value={c.openaiModel}
-              onInput={e => update('openaiModel', e.target.value)}
+              value={profile.model}
+              onInput={e => updateProfile('model', e.target.value)}
               placeholder="gpt-4o-mini"
             />
           </div>
@@ -3,6 +3,7 @@ import { config, currentPage, currentPaperDetailId, paperProjectName, papersActi
 import { addPaperHistory, clearPaperHistoryItems, friendlyError, generatePaperReviewsStream, getPaperHistory, removePaperHistoryItem as removePaperHistoryRecord, stopPaperReview } from './tauri.js'
 import { t } from './i18n.js'
 import { getPaperCharCountFromMarkdown } from './paperChars.js'
+import { isProfileUsable, profileForTask } from './profiles.js'
 
 export const paperTasks = signal([])
 export const paperHistory = signal([])
@@ -186,11 +187,7 @@ function pushPaperHistory(task, result) {
 }
 
 export function hasPaperConfig(c = config.value) {
-  if (c.paperProvider === 'ollama') return !!c.paperOllamaUrl && !!c.paperOllamaModel
-  if (c.paperProvider === 'openai') {
-    return !!c.paperOpenaiBaseUrl && !!c.paperOpenaiKey && !!c.paperOpenaiModel
-  }
-  return false
+  return isProfileUsable(profileForTask(c, 'paperReviewProfile'))
 }
 
 export function enqueuePaperPaths(paths, source = 'pick') {
@@ -548,6 +545,12 @@ export async function hydratePaperHistory() {
   return paperHistoryHydratePromise
 }
 
+// 归档目录移动后重新读取磁盘上的历史
+export async function reloadPaperHistory() {
+  const diskHistory = await getPaperHistory()
+  setPaperHistory(diskHistory.map(normalizePaperHistoryEntry).filter(Boolean).slice(0, PAPER_HISTORY_LIMIT))
+}
+
 async function persistPaperHistoryEntry(entry) {
   try {
     const nextHistory = await addPaperHistory(entry)
@@ -0,0 +1,127 @@
+// 模型配置（profiles）：各任务通过 id 引用同一份端点、Key 与模型
+
+export const DEFAULT_OLLAMA_URL = 'http://localhost:11434'
+export const DEFAULT_OPENAI_BASE_URL = 'https://api.openai.com/v1'
+
+// 任务字段 → 需要的能力；sameAs 为空时沿用的任务
+export const MODEL_TASKS = [
+  { key: 'renameProfile', capability: 'chat' },
+  { key: 'vlmProfile', capability: 'vision', sameAs: 'renameProfile' },
+  { key: 'paperReviewProfile', capability: 'chat' },
+  { key: 'paperChatProfile', capability: 'chat', sameAs: 'paperReviewProfile' },
+  { key: 'embeddingProfile', capability: 'embedding' },
+  { key: 'embeddingFallbackProfile', capability: 'embedding', optional: true },
+]
+
+export function defaultProfiles() {
+  return [
+    {
+      id: 'ollama',
+      name: 'Ollama · llama3.2',
+      provider: 'ollama',
+      baseUrl: DEFAULT_OLLAMA_URL,
+      apiKey: '',
+      model: 'llama3.2',
+      capabilities: { chat: true, vision: true, embedding: false },
+      sampling: {},
+    },
+    {
+      id: 'openai',
+      name: 'OpenAI · gpt-4o-mini',
+      provider: 'openai',
+      baseUrl: DEFAULT_OPENAI_BASE_URL,
+      apiKey: '',
+      model: 'gpt-4o-mini',
+      capabilities: { chat: true, vision: true, embedding: false },
+      sampling: {},
+    },
+    {
+      id: 'paper-openai',
+      name: '论文 OpenAI · gpt-4.1',
+      provider: 'openai',
+      baseUrl: DEFAULT_OPENAI_BASE_URL,
+      apiKey: '',
+      model: 'gpt-4.1',
+      capabilities: { chat: true, vision: false, embedding: false },
+      sampling: {},
+    },
+    {
+      id: 'embedding-ollama',
+      name: 'Embedding Ollama · nomic-embed-text',
+      provider: 'ollama',
+      baseUrl: DEFAULT_OLLAMA_URL,
+      apiKey: '',
+      model: 'nomic-embed-text',
+      capabilities: { chat: false, vision: false, embedding: true },
+      sampling: {},
+    },
+    {
+      id: 'embedding-openai',
+      name: 'Embedding OpenAI · text-embedding-3-small',
+      provider: 'openai',
+      baseUrl: DEFAULT_OPENAI_BASE_URL,
+      apiKey: '',
+      model: 'text-embedding-3-small',
+      capabilities: { chat: false, vision: false, embedding: true },
+      sampling: {},
+    },
+  ]
+}
+
+export function createProfile(profiles = []) {
+  let index = profiles.length + 1
+  while (profiles.some(profile => profile.id === `profile-${index}`)) index += 1
+  return {
+    id: `profile-${index}`,
+    name: `Profile ${index}`,
+    provider: 'openai',
+    baseUrl: DEFAULT_OPENAI_BASE_URL,
+    apiKey: '',
+    model: '',
+    capabilities: { chat: true, vision: false, embedding: false },
+    sampling: {},
+  }
+}
+
+export function findProfile(c, id) {
+  return (c.profiles || []).find(profile => profile.id === id) || null
+}
+
+// 任务实际使用的配置，空值按 sameAs 回退
+export function profileForTask(c, key) {
+  const task = MODEL_TASKS.find(item => item.key === key)
+  const id = c[key] || (task?.sameAs ? c[task.sameAs] : '')
+  return id ? findProfile(c, id) : null
+}
+
+// 端点、模型（云端还需 Key）齐全才可用
+export function isProfileUsable(profile) {
+  if (!profile || !profile.model?.trim()) return false
+  if (profile.provider === 'ollama' || profile.provider === 'mock') return true
+  return !!profile.baseUrl?.trim() && !!profile.apiKey?.trim()
+}
+
+export function profilesWithCapability(c, capability) {
+  return (c.profiles || []).filter(profile => profile.capabilities?.[capability])
+}
+
+export function withProfile(c, id, patch) {
+  return {
+    ...c,
+    profiles: (c.profiles || []).map(profile => (profile.id === id ? { ...profile, ...patch } : profile)),
+  }
+}
+
+// 切换服务商：复用已有的同类配置，没有则新建一份
+export function withProviderForTask(c, key, provider) {
+  const task = MODEL_TASKS.find(item => item.key === key)
+  const existing = (c.profiles || []).find(profile =>
+    profile.provider === provider && profile.capabilities?.[task.capability])
+  if (existing) return { ...c, [key]: existing.id }
+  const profile = {
+    ...createProfile(c.profiles),
+    provider,
+    baseUrl: provider === 'ollama' ? DEFAULT_OLLAMA_URL : DEFAULT_OPENAI_BASE_URL,
+  }
+  return { ...c, profiles: [...(c.profiles || []), profile], [key]: profile.id }
+}
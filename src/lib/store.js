@@ -1,16 +1,18 @@
 import { signal } from '@preact/signals'
+import { defaultProfiles } from './profiles.js'
 
 // 当前页面
 export const currentPage = signal('files')
 
 // 配置
 export const config = signal({
-  provider: 'ollama',
-  ollamaUrl: 'http://localhost:11434',
-  ollamaModel: 'llama3.2',
-  openaiKey: '',
-  openaiModel: 'gpt-4o-mini',
-  openaiBaseUrl: 'https://api.openai.com/v1',
+  profiles: defaultProfiles(),
+  renameProfile: 'ollama',
+  vlmProfile: '',
+  paperReviewProfile: 'paper-openai',
+  paperChatProfile: '',
+  embeddingProfile: 'embedding-ollama',
+  embeddingFallbackProfile: 'embedding-openai',
   customRules: '',
   namingStyle: 'kebab-case',
   includeDate: false,
@@ -19,24 +21,27 @@ export const config = signal({
   nameTemplate: '',
   autoCategorize: false,
   vlmEnabled: false,
-  vlmSameAsLlm: true,
-  vlmBaseUrl: '',
-  vlmKey: '',
-  vlmModel: '',
-  paperProvider: 'openai',
-  paperOllamaUrl: 'http://localhost:11434',
-  paperOllamaModel: 'llama3.2',
-  paperOpenaiKey: '',
-  paperOpenaiModel: 'gpt-4.1',
-  paperOpenaiBaseUrl: 'https://api.openai.com/v1',
-  paperEmbeddingProvider: 'auto',
-  paperEmbeddingOllamaUrl: 'http://localhost:11434',
-  paperEmbeddingOllamaModel: 'nomic-embed-text',
-  paperEmbeddingOpenaiKey: '',
-  paperEmbeddingOpenaiModel: 'text-embedding-3-small',
-  paperEmbeddingOpenaiBaseUrl: 'https://api.openai.com/v1',
+  vlmMaxImageEdge: 2048,
+  ocrEngine: 'auto',
+  ocrLanguages: 'chi_sim+chi_tra+eng',
+  ocrTessdataDir: '',
+  ocrPageRange: '',
+  ocrParallelism: 2,
+  pdfPasswords: '',
+  redactionMode: 'off',
+  redactionKinds: 'email,phone,iban,card,nationalId',
+  redactionPatterns: '',
+  redactionFolders: '',
   paperFulltextTokenLimit: 60000,
-  paperArchiveRoot: '/Users/chenghaoyang/Local/papers',
+  paperArchiveRoot: '',
+  teamConfigPath: '',
+  httpProxy: '',
+  httpNoProxy: '',
+  httpCaBundle: '',
+  httpHeaders: '',
+  httpTimeouts: {},
+  modelPrices: [],
+  monthlyBudget: null,
 })
 
 // 文件夹路径
@@ -1,5 +1,5 @@
 import { signal, computed } from '@preact/signals'
-import { extractFileText, generateFilename, moveAndRename, addHistory, friendlyError } from './tauri.js'
+import { extractFileText, generateFilename, moveAndRename, addHistory, friendlyError, unlockPdf } from './tauri.js'
 import { config, showToast, recentActivity } from './store.js'
 import { t } from './i18n.js'
 
@@ -67,16 +67,16 @@ function drain() {
 
 async function runTask(task) {
   try {
-    const text = await extractFileText(task.path)
+    const { text, metadata } = await extractFileText(task.path)
     updateTask(task.id, { status: 'generating' })
-    let newName = await generateFilename(text, config.value, task.path)
+    let newName = await generateFilename(text, config.value, task.path, metadata)
     // Strip duplicate extension (handle both ".pdf" and "pdf" forms)
     if (task.ext && newName.toLowerCase().endsWith(task.ext.toLowerCase())) {
       newName = newName.slice(0, -task.ext.length)
     }
     updateTask(task.id, { newName, status: 'ready' })
   } catch (e) {
-    updateTask(task.id, { status: 'error', error: friendlyError(e) })
+    updateTask(task.id, { status: 'error', error: friendlyError(e), locked: /PDF 已加密/.test(String(e)) })
   }
 }
 
@@ -182,6 +182,18 @@ export function updateTaskDest(id, destFolder) {
 }
 
 export function retryTask(id) {
-  updateTask(id, { status: 'queued', error: '' })
+  updateTask(id, { status: 'queued', error: '', locked: false })
   drain()
 }
+
+// Encrypted PDF: check the password for this file, then extract again
+export async function unlockTask(id, password) {
+  const task = tasks.value.find(t => t.id === id)
+  if (!task || !password) return
+  try {
+    await unlockPdf(task.path, password)
+    retryTask(id)
+  } catch (e) {
+    updateTask(id, { error: friendlyError(e) })
+  }
+}
@@ -2,7 +2,7 @@ import { invoke, Channel } from '@tauri-apps/api/core'
 import { getVersion } from '@tauri-apps/api/app'
 import { emitTo } from '@tauri-apps/api/event'
 import { WebviewWindow } from '@tauri-apps/api/webviewWindow'
-import { open as openDialog } from '@tauri-apps/plugin-dialog'
+import { ask as askDialog, open as openDialog, save as saveDialog } from '@tauri-apps/plugin-dialog'
 import { Command, open as openShell } from '@tauri-apps/plugin-shell'
 import { t } from './i18n.js'
 
@@ -26,6 +26,40 @@ export async function pickPdfFiles() {
   return Array.isArray(selected) ? selected : [selected]
 }
 
+export async function pickJsonFile() {
+  const selected = await openDialog({
+    directory: false,
+    multiple: false,
+    title: t('settings.teamConfigPick'),
+    filters: [{ name: 'JSON', extensions: ['json'] }],
+  })
+  return selected
+}
+
+export async function pickCertificateFile() {
+  const selected = await openDialog({
+    directory: false,
+    multiple: false,
+    title: t('settings.httpCaBundlePick'),
+    filters: [{ name: 'PEM', extensions: ['pem', 'crt', 'cer'] }],
+  })
+  return selected
+}
+
+export async function pickDiagnosticsPath() {
+  const stamp = new Date().toISOString().slice(0, 10)
+  return await saveDialog({
+    title: t('settings.exportDiagnostics'),
+    defaultPath: `fyla-diagnostics-${stamp}.zip`,
+    filters: [{ name: 'ZIP', extensions: ['zip'] }],
+  })
+}
+
+// 打包日志、脱敏后的配置和版本信息，用于反馈问题
+export async function exportDiagnostics(dest) {
+  return await invoke('export_diagnostics', { dest })
+}
+
 export async function pickImageFiles() {
   const selected = await openDialog({
     directory: false,
@@ -49,6 +83,28 @@ export async function extractFileText(path) {
   return await invoke('extract_file_text', { path })
 }
 
+export async function unlockPdf(path, password) {
+  return await invoke('unlock_pdf', { path, password })
+}
+
+export async function diagnosePdfExtraction(path) {
+  return await invoke('diagnose_pdf_extraction', { path })
+}
+
+// extractor: 'pymupdf4llm' | 'pdftotext' | 'builtin' | 'pdfkit' | 'ocr', or null to unpin
+export async function setPdfExtractorPin(path, extractor) {
+  return await invoke('set_pdf_extractor_pin', { path, extractor })
+}
+
+export async function confirmAction(message) {
+  return await askDialog(message, { kind: 'warning' })
+}
+
+// 移动论文归档目录，并改写历史、对话与缓存里的路径
+export async function relocatePaperArchive(newRoot) {
+  return await invoke('relocate_paper_archive', { newRoot })
+}
+
 export async function readPaperArchiveMarkdown(path) {
   return await invoke('read_paper_archive_markdown', { path })
 }
@@ -57,8 +113,8 @@ export async function readBinaryFile(path) {
   return await invoke('read_file_bytes', { path })
 }
 
-export async function generateFilename(text, config, filePath) {
-  return await invoke('generate_filename', { text, config, filePath })
+export async function generateFilename(text, config, filePath, metadata = null) {
+  return await invoke('generate_filename', { text, config, filePath, metadata })
 }
 
 export async function generateNamesStream(paths, config, onEvent) {
@@ -89,6 +145,20 @@ export async function saveConfig(config) {
   return await invoke('save_config', { config })
 }
 
+// 每个字段的来源：default / team / user / folder，以及是否被团队锁定
+export async function getConfigSources(folder = null) {
+  return await invoke('get_config_sources', { folder })
+}
+
+// 模型用量：from/to 为 YYYY-MM-DD，可省略；byMonth 时按月汇总
+export async function getUsageSummary(from = null, to = null, byMonth = false) {
+  return await invoke('get_usage_summary', { from, to, byMonth })
+}
+
+export async function takeConfigProblems() {
+  return await invoke('take_config_problems')
+}
+
 export async function startWatch(folder, extensions) {
   return await invoke('start_watch', { folder, extensions })
 }
@@ -97,8 +167,8 @@ export async function stopWatch() {
   return await invoke('stop_watch')
 }
 
-export async function testConnection(config) {
-  return await invoke('test_connection', { config })
+export async function testConnection(config, profileId = null) {
+  return await invoke('test_connection', { config, profileId })
 }
 
 export async function testPaperConnection(config) {
@@ -335,6 +405,8 @@ const errorMap = [
   [/模型.*未找到|model.*not found/i, 'errors.modelNotFound'],
   [/目标文件已存在|already exists/i, 'errors.fileExists'],
   [/文件不存在|not found/i, 'errors.fileNotFound'],
+  [/PDF 已加密/i, 'errors.pdfPasswordRequired'],
+  [/PDF 密码不正确/i, 'errors.pdfWrongPassword'],
   [/PDF.*extract|提取.*失败/i, 'errors.pdfExtractFailed'],
   [/AI 返回了空文件名|empty.*filename/i, 'errors.emptyFilename'],
   [/论文解读结果解析失败/i, 'errors.paperParseFailed'],
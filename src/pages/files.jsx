@@ -4,11 +4,10 @@ import { TaskItem } from '../components/TaskItem.jsx'
 import { Icon } from '../components/Icon.jsx'
 import { pickFolder, scanFolder } from '../lib/tauri.js'
 import { t } from '../lib/i18n.js'
+import { isProfileUsable, profileForTask } from '../lib/profiles.js'
 
 function hasApiConfig(c) {
-  if (c.provider === 'ollama') return !!c.ollamaUrl && !!c.ollamaModel
-  if (c.provider === 'openai') return !!c.openaiBaseUrl && !!c.openaiKey
-  return false
+  return isProfileUsable(profileForTask(c, 'renameProfile'))
 }
 
 export function FilesPage() {
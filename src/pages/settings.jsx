@@ -1,19 +1,30 @@
 import { useState, useEffect } from 'preact/hooks'
 import { config, showToast, isWatching, showWelcome } from '../lib/store.js'
 import {
+  confirmAction,
+  exportDiagnostics,
   friendlyError,
   getAppVersion,
+  getConfig,
+  getConfigSources,
   getPaperEmbeddingStatus,
+  getUsageSummary,
   saveConfig,
   startWatch,
   stopWatch,
+  pickCertificateFile,
+  pickDiagnosticsPath,
   pickFolder,
-  testConnection,
+  pickJsonFile,
+  relocatePaperArchive,
   testPaperEmbeddingConnection,
   testPaperConnection,
 } from '../lib/tauri.js'
 import { invoke } from '@tauri-apps/api/core'
 import { changelog } from '../lib/changelog.js'
+import { ModelProfileEditor, TaskProfileSelect } from '../components/ModelProfileEditor.jsx'
+import { MODEL_TASKS, createProfile, withProfile } from '../lib/profiles.js'
+import { reloadPaperHistory } from '../lib/paperQueue.js'
 import { t, lang, setLang } from '../lib/i18n.js'
 import { checkForUpdateManual } from '../lib/updater.js'
 import { clearWindowModeOverride, setWindowModeOverride } from '../lib/windowMode.js'
@@ -21,14 +32,44 @@ import defaultPaperReviewPromptTemplateRaw from '../lib/paper-review-prompt-temp
 
 const DEFAULT_PAPER_REVIEW_PROMPT_TEMPLATE = defaultPaperReviewPromptTemplateRaw.trim()
 const SETTINGS_TABS = ['general', 'ai', 'papers', 'watch', 'about']
+const TASKS = Object.fromEntries(MODEL_TASKS.map(task => [task.key, task]))
+const HTTP_TIMEOUTS = [
+  ['connect', 15],
+  ['test', 10],
+  ['rename', 60],
+  ['vlm', 60],
+  ['embedding', 45],
+  ['streamIdle', 90],
+]
+
+function formatCost(value) {
+  return (value || 0).toFixed(2)
+}
+
+function monthStart() {
+  const now = new Date()
+  return `${now.getFullYear()}-${String(now.getMonth() + 1).padStart(2, '0')}-01`
+}
+
+// 来自团队配置的字段标出来源，锁定的字段不可修改
+function SourceTag({ source }) {
+  if (source?.locked) return <span class="settings-source-tag">{t('settings.sourceLocked')}</span>
+  if (source?.layer === 'team') return <span class="settings-source-tag">{t('settings.sourceTeam')}</span>
+  return null
+}
 
 export function SettingsPage() {
   const c = config.value
-  const [testing, setTesting] = useState(false)
-  const [testResult, setTestResult] = useState(null)
+  const [expandedProfileId, setExpandedProfileId] = useState(null)
+  const [saveError, setSaveError] = useState(null)
+  const [saveWarning, setSaveWarning] = useState(null)
+  const [movingArchive, setMovingArchive] = useState(false)
+  const [sources, setSources] = useState(null)
+  const [usageSummary, setUsageSummary] = useState(null)
   const [autoStart, setAutoStart] = useState(false)
   const [showChangelog, setShowChangelog] = useState(false)
   const [checking, setChecking] = useState(false)
+  const [exportingDiagnostics, setExportingDiagnostics] = useState(false)
   const [appVersion, setAppVersion] = useState(changelog[0]?.version || '')
   const [testingPaper, setTestingPaper] = useState(false)
   const [paperTestResult, setPaperTestResult] = useState(null)
@@ -45,6 +86,15 @@ export function SettingsPage() {
     invoke('is_autostart_enabled').then(setAutoStart).catch(() => {})
   }, [])
 
+  useEffect(() => {
+    refreshSources()
+  }, [])
+
+  useEffect(() => {
+    if (activeSettingsTab !== 'ai') return
+    getUsageSummary(monthStart()).then(setUsageSummary).catch(() => {})
+  }, [activeSettingsTab, c.modelPrices, c.monthlyBudget])
+
   useEffect(() => {
     getAppVersion()
       .then(version => {
@@ -55,14 +105,7 @@ export function SettingsPage() {
 
   useEffect(() => {
     refreshPaperEmbeddingStatus()
-  }, [
-    c.paperEmbeddingProvider,
-    c.paperEmbeddingOllamaUrl,
-    c.paperEmbeddingOllamaModel,
-    c.paperEmbeddingOpenaiBaseUrl,
-    c.paperEmbeddingOpenaiKey,
-    c.paperEmbeddingOpenaiModel,
-  ])
+  }, [c.embeddingProfile, c.embeddingFallbackProfile, c.profiles])
 
   useEffect(() => {
     setPaperReviewPromptDraft(c.paperReviewPromptTemplate || DEFAULT_PAPER_REVIEW_PROMPT_TEMPLATE)
@@ -104,20 +147,86 @@ export function SettingsPage() {
   }, [])
 
   function update(key, value) {
-    const next = { ...config.value, [key]: value }
+    if (isLocked(key)) return
+    save({ ...config.value, [key]: value })
+  }
+
+  // 校验失败时保留输入，提示哪些字段未保存；警告不影响保存
+  function save(next) {
     config.value = next
-    saveConfig(next).catch(() => {})
+    saveConfig(next)
+      .then(warnings => {
+        setSaveError(null)
+        setSaveWarning(warnings?.length ? warnings.join('；') : null)
+        refreshSources()
+      })
+      .catch(err => setSaveError(friendlyError(err)))
+  }
+
+  function refreshSources() {
+    getConfigSources().then(setSources).catch(() => {})
   }
 
-  async function handleTestConnection() {
-    setTesting(true); setTestResult(null)
+  function isLocked(key) {
+    return !!sources?.fields?.[key]?.locked
+  }
+
+  // 换团队配置后重新读取生效值，未改动过的字段随新的团队默认值变化
+  async function handleSetTeamConfig(path) {
     try {
-      const msg = await testConnection(config.value)
-      setTestResult({ ok: true, msg })
-    } catch (e) {
-      setTestResult({ ok: false, msg: String(e) })
+      await saveConfig({ ...config.value, teamConfigPath: path })
+      setSaveError(null)
+      config.value = await getConfig()
+    } catch (err) {
+      setSaveError(friendlyError(err))
     }
-    setTesting(false)
+    refreshSources()
+  }
+
+  function updateHttpTimeout(key, value) {
+    const secs = value === '' ? null : parseInt(value, 10)
+    update('httpTimeouts', { ...c.httpTimeouts, [key]: Number.isFinite(secs) ? secs : null })
+  }
+
+  function updateModelPrice(index, patch) {
+    const prices = (c.modelPrices || []).map((price, i) => (i === index ? { ...price, ...patch } : price))
+    update('modelPrices', prices)
+  }
+
+  function handleAddModelPrice() {
+    update('modelPrices', [...(c.modelPrices || []), { model: '', inputPerMillion: 0, outputPerMillion: 0 }])
+  }
+
+  function handleRemoveModelPrice(index) {
+    update('modelPrices', (c.modelPrices || []).filter((_, i) => i !== index))
+  }
+
+  async function handlePickCaBundle() {
+    const path = await pickCertificateFile()
+    if (path) update('httpCaBundle', path)
+  }
+
+  async function handlePickTeamConfig() {
+    const path = await pickJsonFile()
+    if (path) handleSetTeamConfig(path)
+  }
+
+  function handleAddProfile() {
+    const profile = createProfile(config.value.profiles)
+    save({ ...config.value, profiles: [...(config.value.profiles || []), profile] })
+    setExpandedProfileId(profile.id)
+  }
+
+  function handleRemoveProfile(id) {
+    const next = {
+      ...config.value,
+      profiles: (config.value.profiles || []).filter(profile => profile.id !== id),
+    }
+    // 引用被删配置的任务清空，由各任务的回退或报错提示重新选择
+    for (const task of MODEL_TASKS) {
+      if (next[task.key] === id) next[task.key] = ''
+    }
+    save(next)
   }
 
   async function handleTestPaperConnection() {
@@ -184,9 +293,34 @@ export function SettingsPage() {
       ? 'is-success'
       : ''
 
-  async function handlePickPaperArchiveRoot() {
+  // 归档目录只能整体移动，避免历史与对话指向旧位置
+  async function handleMovePaperArchive() {
     const path = await pickFolder()
-    if (path) update('paperArchiveRoot', path)
+    if (!path || path === c.paperArchiveRoot) return
+    if (!await confirmAction(t('settings.paperArchiveMoveConfirm', { path }))) return
+    setMovingArchive(true)
+    try {
+      const summary = await relocatePaperArchive(path)
+      config.value = { ...config.value, paperArchiveRoot: summary.archiveRoot }
+      await reloadPaperHistory()
+      showToast(t('settings.paperArchiveMoved', { count: summary.movedFiles }))
+    } catch (e) {
+      showToast(t('settings.paperArchiveMoveFailed') + ': ' + friendlyError(e))
+    }
+    setMovingArchive(false)
+  }
+
+  async function handleExportDiagnostics() {
+    const path = await pickDiagnosticsPath()
+    if (!path) return
+    setExportingDiagnostics(true)
+    try {
+      await exportDiagnostics(path)
+      showToast(t('settings.exportDiagnosticsDone'))
+    } catch (e) {
+      showToast(t('settings.exportDiagnosticsFailed') + ': ' + friendlyError(e))
+    }
+    setExportingDiagnostics(false)
   }
 
   function normalizePaperReviewPromptValue(value) {
@@ -239,6 +373,9 @@ export function SettingsPage() {
           </div>
         </div>
 
+        {saveError && <div class="settings-save-error">{saveError}</div>}
+        {!saveError && saveWarning && <div class="settings-save-warning">{saveWarning}</div>}
+
         {activeSettingsTab === 'general' && (
         <>
         <div class="settings-section">
@@ -274,6 +411,30 @@ export function SettingsPage() {
               <span class="switch-slider" />
             </label>
           </div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.teamConfig')}
+              <small>
+                {sources?.teamError
+                  || (sources?.teamPath ? t('settings.teamConfigActive', { path: sources.teamPath }) : t('settings.teamConfigHint'))}
+              </small>
+            </span>
+            <input
+              class="settings-input"
+              type="text"
+              value={c.teamConfigPath}
+              readOnly
+              placeholder={t('settings.teamConfigPlaceholder')}
+            />
+            {c.teamConfigPath && (
+              <button class="btn btn-secondary" style="font-size:12px" onClick={() => handleSetTeamConfig('')}>
+                {t('settings.teamConfigClear')}
+              </button>
+            )}
+            <button class="btn btn-secondary" style="font-size:12px" onClick={handlePickTeamConfig}>
+              {t('settings.pick')}
+            </button>
+          </div>
           <div class="settings-row" style="justify-content:flex-end">
             <button class="btn btn-ghost" style="font-size:12px" onClick={() => { showWelcome.value = true }}>
               {t('settings.showGuide')}
@@ -286,115 +447,51 @@ export function SettingsPage() {
         {activeSettingsTab === 'ai' && (
         <>
         <div class="settings-section">
-          <div class="settings-section-title">{t('settings.aiProvider')}</div>
-          <div class="settings-row">
-            <span class="settings-label">{t('settings.selectMode')}</span>
-            <div class="toggle-group">
-              <button
-                class={`toggle-option ${c.provider === 'ollama' ? 'active' : ''}`}
-                onClick={() => update('provider', 'ollama')}
-              >
-                {t('settings.ollamaLocal')}
-              </button>
-              <button
-                class={`toggle-option ${c.provider === 'openai' ? 'active' : ''}`}
-                onClick={() => update('provider', 'openai')}
-              >
-                {t('settings.openaiCompat')}
-              </button>
-            </div>
-          </div>
-
-          {c.provider === 'ollama' ? (
-            <>
-              <div class="settings-row">
-                <span class="settings-label">
-                  {t('settings.serverUrl')}
-                  <small>{t('settings.serverUrlHint')}</small>
-                </span>
-                <input
-                  class="settings-input"
-                  type="text"
-                  value={c.ollamaUrl}
-                  onInput={e => update('ollamaUrl', e.target.value)}
-                  placeholder="http://localhost:11434"
-                />
-              </div>
-              <div class="settings-row">
-                <span class="settings-label">
-                  {t('settings.modelName')}
-                  <small>{t('settings.modelNameHint')}</small>
-                </span>
-                <input
-                  class="settings-input"
-                  type="text"
-                  value={c.ollamaModel}
-                  onInput={e => update('ollamaModel', e.target.value)}
-                  placeholder="llama3.2"
-                />
-              </div>
-            </>
-          ) : (
-            <>
-              <div class="settings-row">
-                <span class="settings-label">
-                  {t('settings.baseUrl')}
-                  <small>{t('settings.baseUrlHint')}</small>
-                </span>
-                <input
-                  class="settings-input"
-                  type="text"
-                  value={c.openaiBaseUrl}
-                  onInput={e => update('openaiBaseUrl', e.target.value)}
-                  placeholder="https://api.openai.com/v1"
-                />
-              </div>
-              <div class="settings-row">
-                <span class="settings-label">
-                  {t('settings.apiKey')}
-                  <small>Bearer Token</small>
-                </span>
-                <input
-                  class="settings-input"
-                  type="password"
-                  value={c.openaiKey}
-                  onInput={e => update('openaiKey', e.target.value)}
-                  placeholder="sk-..."
-                />
-              </div>
-              <div class="settings-row">
-                <span class="settings-label">
-                  {t('settings.model')}
-                  <small>{t('settings.modelHint')}</small>
-                </span>
-                <input
-                  class="settings-input"
-                  type="text"
-                  value={c.openaiModel}
-                  onInput={e => update('openaiModel', e.target.value)}
-                  placeholder="gpt-4o-mini"
-                />
-              </div>
-            </>
-          )}
-
-          <div class="settings-row" style="justify-content:flex-end">
-            <button class="btn btn-secondary" style="font-size:12px" disabled={testing} onClick={handleTestConnection}>
-              {testing ? t('settings.testing') : t('settings.testConnection')}
+          <div class="settings-section-title">{t('settings.modelProfiles')}</div>
+          {(c.profiles || []).map(profile => (
+            <ModelProfileEditor
+              key={profile.id}
+              profile={profile}
+              expanded={expandedProfileId === profile.id}
+              onToggle={() => setExpandedProfileId(expandedProfileId === profile.id ? null : profile.id)}
+              onChange={patch => save(withProfile(config.value, profile.id, patch))}
+              onRemove={() => handleRemoveProfile(profile.id)}
+            />
+          ))}
+          <div class="settings-row" style="justify-content:space-between">
+            <span class="settings-label">
+              <small>{t('settings.modelProfilesHint')}</small>
+            </span>
+            <button class="btn btn-secondary" style="font-size:12px" onClick={handleAddProfile}>
+              {t('settings.profileAdd')}
             </button>
-            {testResult && (
-              <span style={`font-size:12px;margin-left:8px;color:${testResult.ok ? 'var(--success)' : 'var(--danger)'}`}>
-                {testResult.msg}
-              </span>
-            )}
           </div>
         </div>
 
+        <div class="settings-section">
+          <div class="settings-section-title">{t('settings.taskProfiles')}</div>
+          <TaskProfileSelect
+            c={c}
+            task={TASKS.renameProfile}
+            label={t('settings.taskRename')}
+            hint={t('settings.taskRenameHint')}
+            onChange={update}
+          />
+          <TaskProfileSelect
+            c={c}
+            task={TASKS.vlmProfile}
+            label={t('settings.taskVlm')}
+            hint={t('settings.taskVlmHint')}
+            emptyLabel={t('settings.profileSameAsRename')}
+            onChange={update}
+          />
+        </div>
+
         <div class="settings-section">
           <div class="settings-section-title">{t('settings.namingRules')}</div>
           <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
             <span class="settings-label">
-              {t('settings.namingStyle')}
+              {t('settings.namingStyle')} <SourceTag source={sources?.fields?.namingStyle} />
               <small>{t('settings.namingStyleHint')}</small>
             </span>
             <div class="toggle-group">
@@ -409,6 +506,7 @@ export function SettingsPage() {
                 <button
                   key={val}
                   class={`toggle-option ${c.namingStyle === val ? 'active' : ''}`}
+                  disabled={isLocked('namingStyle')}
                   onClick={() => update('namingStyle', val)}
                 >
                   {label}
@@ -418,13 +516,14 @@ export function SettingsPage() {
           </div>
           <div class="settings-row">
             <span class="settings-label">
-              {t('settings.includeDate')}
+              {t('settings.includeDate')} <SourceTag source={sources?.fields?.includeDate} />
               <small>{t('settings.includeDateHint')}</small>
             </span>
             <label class="switch">
               <input
                 type="checkbox"
                 checked={c.includeDate}
+                disabled={isLocked('includeDate')}
                 onChange={e => update('includeDate', e.target.checked)}
               />
               <span class="switch-slider" />
@@ -432,25 +531,27 @@ export function SettingsPage() {
           </div>
           <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
             <span class="settings-label">
-              {t('settings.customRules')}
+              {t('settings.customRules')} <SourceTag source={sources?.fields?.customRules} />
               <small>{t('settings.customRulesHint')}</small>
             </span>
             <textarea
               class="settings-textarea"
               value={c.customRules}
+              readOnly={isLocked('customRules')}
               onInput={e => update('customRules', e.target.value)}
               placeholder={t('settings.customRulesPlaceholder')}
             />
           </div>
           <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
             <span class="settings-label">
-              {t('settings.nameTemplate')}
+              {t('settings.nameTemplate')} <SourceTag source={sources?.fields?.nameTemplate} />
               <small>{t('settings.nameTemplateHint')}</small>
             </span>
             <input
               class="settings-input"
               style="max-width:100%"
               value={c.nameTemplate}
+              readOnly={isLocked('nameTemplate')}
               onInput={e => update('nameTemplate', e.target.value)}
               placeholder={t('settings.nameTemplatePlaceholder')}
             />
@@ -478,63 +579,378 @@ export function SettingsPage() {
             <>
               <div class="settings-row">
                 <span class="settings-label">
-                  {t('settings.reuseLlm')}
-                  <small>{t('settings.reuseLlmHint')}</small>
+                  {t('settings.vlmMaxImageEdge')}
+                  <small>{t('settings.vlmMaxImageEdgeHint')}</small>
                 </span>
-                <label class="switch">
+                <input
+                  class="settings-input"
+                  type="number"
+                  min="256"
+                  step="256"
+                  value={c.vlmMaxImageEdge}
+                  onInput={e => update('vlmMaxImageEdge', Math.max(256, Number(e.target.value) || 2048))}
+                  placeholder="2048"
+                />
+              </div>
+            </>
+          )}
+        </div>
+
+        {/* OCR */}
+        <div class="settings-section">
+          <div class="settings-section-title">{t('settings.ocr')}</div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.ocrEngine')}
+              <small>{t('settings.ocrEngineHint')}</small>
+            </span>
+            <select class="settings-select" value={c.ocrEngine} onChange={e => update('ocrEngine', e.target.value)}>
+              <option value="auto">{t('settings.ocrEngineAuto')}</option>
+              <option value="vision">Apple Vision</option>
+              <option value="tesseract">Tesseract</option>
+            </select>
+          </div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.ocrLanguages')}
+              <small>{t('settings.ocrLanguagesHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              type="text"
+              value={c.ocrLanguages}
+              onInput={e => update('ocrLanguages', e.target.value)}
+              placeholder="chi_sim+chi_tra+eng"
+            />
+          </div>
+          {c.ocrEngine !== 'vision' && (
+            <div class="settings-row">
+              <span class="settings-label">
+                {t('settings.ocrTessdataDir')}
+                <small>{t('settings.ocrTessdataDirHint')}</small>
+              </span>
+              <input
+                class="settings-input"
+                type="text"
+                value={c.ocrTessdataDir}
+                onInput={e => update('ocrTessdataDir', e.target.value)}
+                placeholder="/opt/homebrew/share/tessdata"
+              />
+            </div>
+          )}
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.ocrPageRange')}
+              <small>{t('settings.ocrPageRangeHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              type="text"
+              value={c.ocrPageRange}
+              onInput={e => update('ocrPageRange', e.target.value)}
+              placeholder="1-30"
+            />
+          </div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.ocrParallelism')}
+              <small>{t('settings.ocrParallelismHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              type="number"
+              min="1"
+              max="8"
+              value={c.ocrParallelism}
+              onInput={e => update('ocrParallelism', Math.min(8, Math.max(1, Number(e.target.value) || 1)))}
+            />
+          </div>
+          <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
+            <span class="settings-label">
+              {t('settings.pdfPasswords')}
+              <small>{t('settings.pdfPasswordsHint')}</small>
+            </span>
+            <textarea
+              class="settings-textarea"
+              spellcheck={false}
+              value={c.pdfPasswords}
+              onInput={e => update('pdfPasswords', e.target.value)}
+              placeholder={t('settings.pdfPasswordsPlaceholder')}
+            />
+          </div>
+        </div>
+
+        {/* Redaction */}
+        <div class="settings-section">
+          <div class="settings-section-title">{t('settings.redaction')}</div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.redactionMode')}
+              <small>{t('settings.redactionModeHint')}</small>
+            </span>
+            <select class="settings-select" value={c.redactionMode} onChange={e => update('redactionMode', e.target.value)}>
+              <option value="off">{t('settings.redactionModeOff')}</option>
+              <option value="remote">{t('settings.redactionModeRemote')}</option>
+              <option value="all">{t('settings.redactionModeAll')}</option>
+            </select>
+          </div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.redactionKinds')}
+              <small>{t('settings.redactionKindsHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              type="text"
+              value={c.redactionKinds}
+              onInput={e => update('redactionKinds', e.target.value)}
+              placeholder="email,phone,iban,card,nationalId"
+            />
+          </div>
+          <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
+            <span class="settings-label">
+              {t('settings.redactionPatterns')}
+              <small>{t('settings.redactionPatternsHint')}</small>
+            </span>
+            <textarea
+              class="settings-textarea"
+              spellcheck={false}
+              value={c.redactionPatterns}
+              onInput={e => update('redactionPatterns', e.target.value)}
+              placeholder={'ACME-\\d{6}'}
+            />
+          </div>
+          <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
+            <span class="settings-label">
+              {t('settings.redactionFolders')}
+              <small>{t('settings.redactionFoldersHint')}</small>
+            </span>
+            <textarea
+              class="settings-textarea"
+              spellcheck={false}
+              value={c.redactionFolders}
+              onInput={e => update('redactionFolders', e.target.value)}
+              placeholder={'~/Documents/Bank = all\n~/Documents/Papers = off'}
+            />
+          </div>
+        </div>
+
+        <div class="settings-section">
+          <div class="settings-section-title">{t('settings.network')}</div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.httpProxy')}
+              <small>{t('settings.httpProxyHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              type="text"
+              value={c.httpProxy}
+              onInput={e => update('httpProxy', e.target.value)}
+              placeholder="http://proxy.example.com:8080"
+            />
+          </div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.httpNoProxy')}
+              <small>{t('settings.httpNoProxyHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              type="text"
+              value={c.httpNoProxy}
+              onInput={e => update('httpNoProxy', e.target.value)}
+              placeholder="*.corp.example.com,10.0.0.0/8"
+            />
+          </div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.httpCaBundle')}
+              <small>{t('settings.httpCaBundleHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              type="text"
+              value={c.httpCaBundle}
+              onInput={e => update('httpCaBundle', e.target.value)}
+              placeholder="/etc/ssl/corp-ca.pem"
+            />
+            <button class="btn btn-secondary" style="font-size:12px" onClick={handlePickCaBundle}>
+              {t('settings.pick')}
+            </button>
+          </div>
+          <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
+            <span class="settings-label">
+              {t('settings.httpHeaders')}
+              <small>{t('settings.httpHeadersHint')}</small>
+            </span>
+            <textarea
+              class="settings-textarea"
+              spellcheck={false}
+              value={c.httpHeaders}
+              onInput={e => update('httpHeaders', e.target.value)}
+              placeholder="X-Team: research"
+            />
+          </div>
+          <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
+            <span class="settings-label">
+              {t('settings.httpTimeouts')}
+              <small>{t('settings.httpTimeoutsHint')}</small>
+            </span>
+            <div class="settings-timeouts">
+              {HTTP_TIMEOUTS.map(([key, fallback]) => (
+                <label key={key} class="settings-timeout">
+                  <span>{t(`settings.httpTimeout${key.charAt(0).toUpperCase()}${key.slice(1)}`)}</span>
                   <input
-                    type="checkbox"
-                    checked={c.vlmSameAsLlm}
-                    onChange={e => update('vlmSameAsLlm', e.target.checked)}
+                    class="settings-input"
+                    type="number"
+                    min="1"
+                    max="3600"
+                    value={c.httpTimeouts?.[key] ?? ''}
+                    onInput={e => updateHttpTimeout(key, e.target.value)}
+                    placeholder={String(fallback)}
                   />
-                  <span class="switch-slider" />
                 </label>
-              </div>
-              {!c.vlmSameAsLlm && (
-                <>
-                  <div class="settings-row">
-                    <span class="settings-label">
-                      {t('settings.vlmBaseUrl')}
-                      <small>{t('settings.vlmBaseUrlHint')}</small>
-                    </span>
-                    <input
-                      class="settings-input"
-                      type="text"
-                      value={c.vlmBaseUrl}
-                      onInput={e => update('vlmBaseUrl', e.target.value)}
-                      placeholder="https://api.openai.com/v1"
-                    />
-                  </div>
-                  <div class="settings-row">
-                    <span class="settings-label">
-                      {t('settings.vlmApiKey')}
-                      <small>{t('settings.vlmApiKeyHint')}</small>
-                    </span>
-                    <input
-                      class="settings-input"
-                      type="password"
-                      value={c.vlmKey}
-                      onInput={e => update('vlmKey', e.target.value)}
-                      placeholder="sk-..."
-                    />
-                  </div>
-                  <div class="settings-row">
-                    <span class="settings-label">
-                      {t('settings.vlmModel')}
-                      <small>{t('settings.vlmModelHint')}</small>
-                    </span>
-                    <input
-                      class="settings-input"
-                      type="text"
-                      value={c.vlmModel}
-                      onInput={e => update('vlmModel', e.target.value)}
-                      placeholder="gpt-4o"
-                    />
-                  </div>
-                </>
+              ))}
+            </div>
+          </div>
+        </div>
+
+        <div class="settings-section">
+          <div class="settings-section-title">{t('settings.usage')}</div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.usageThisMonth')}
+              <small>{t('settings.usageThisMonthHint')}</small>
+            </span>
+            <span style="font-size:12px">
+              {c.monthlyBudget
+                ? t('settings.usageMonthOfBudget', {
+                  cost: formatCost(usageSummary?.monthCost),
+                  budget: formatCost(c.monthlyBudget),
+                })
+                : formatCost(usageSummary?.monthCost)}
+              {usageSummary?.unpricedModels?.length > 0 && (
+                <small style="display:block">
+                  {t('settings.usageUnpricedModels', { models: usageSummary.unpricedModels.join(', ') })}
+                </small>
               )}
-            </>
+            </span>
+          </div>
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.monthlyBudget')}
+              <small>{t('settings.monthlyBudgetHint')}</small>
+            </span>
+            <input
+              class="settings-input"
+              style="max-width:120px"
+              type="number"
+              min="0"
+              step="1"
+              value={c.monthlyBudget ?? ''}
+              onInput={e => {
+                const budget = Number(e.target.value)
+                update('monthlyBudget', e.target.value === '' || !Number.isFinite(budget) ? null : budget)
+              }}
+              placeholder={t('settings.monthlyBudgetPlaceholder')}
+            />
+          </div>
+          {usageSummary?.total?.calls > 0 && (
+            <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
+              <span class="settings-label">{t('settings.usageBreakdown')}</span>
+              <table class="settings-usage-table">
+                <thead>
+                  <tr>
+                    <th>{t('settings.usageTaskOrModel')}</th>
+                    <th>{t('settings.usageCalls')}</th>
+                    <th>{t('settings.usageInputTokens')}</th>
+                    <th>{t('settings.usageOutputTokens')}</th>
+                    <th>{t('settings.usageCost')}</th>
+                  </tr>
+                </thead>
+                <tbody>
+                  {[
+                    ...usageSummary.byTask.map(group => ({ ...group, label: t(`settings.usageTask${group.key.charAt(0).toUpperCase()}${group.key.slice(1)}`) })),
+                    ...usageSummary.byModel.map(group => ({ ...group, label: group.key })),
+                  ].map((group, index) => (
+                    <tr key={`${index}-${group.key}`}>
+                      <td>{group.label}</td>
+                      <td>{group.calls}</td>
+                      <td>{group.promptTokens}</td>
+                      <td>{group.completionTokens}</td>
+                      <td>
+                        {group.unpricedCalls === group.calls
+                          ? t('settings.usageUnpriced')
+                          : formatCost(group.cost)}
+                      </td>
+                    </tr>
+                  ))}
+                </tbody>
+              </table>
+            </div>
           )}
+          <div class="settings-row" style="flex-direction: column; align-items: flex-start; gap: 8px;">
+            <span class="settings-label">
+              {t('settings.modelPrices')}
+              <small>{t('settings.modelPricesHint')}</small>
+            </span>
+            {(c.modelPrices || []).length > 0 && (
+              <table class="settings-usage-table">
+                <thead>
+                  <tr>
+                    <th>{t('settings.model')}</th>
+                    <th>{t('settings.modelPriceInput')}</th>
+                    <th>{t('settings.modelPriceOutput')}</th>
+                    <th />
+                  </tr>
+                </thead>
+                <tbody>
+                  {c.modelPrices.map((price, index) => (
+                    <tr key={index}>
+                      <td>
+                        <input
+                          class="settings-input"
+                          type="text"
+                          value={price.model}
+                          onInput={e => updateModelPrice(index, { model: e.target.value })}
+                          placeholder="gpt-4o-mini"
+                        />
+                      </td>
+                      <td>
+                        <input
+                          class="settings-input"
+                          type="number"
+                          min="0"
+                          step="0.01"
+                          value={price.inputPerMillion}
+                          onInput={e => updateModelPrice(index, { inputPerMillion: Number(e.target.value) || 0 })}
+                        />
+                      </td>
+                      <td>
+                        <input
+                          class="settings-input"
+                          type="number"
+                          min="0"
+                          step="0.01"
+                          value={price.outputPerMillion}
+                          onInput={e => updateModelPrice(index, { outputPerMillion: Number(e.target.value) || 0 })}
+                        />
+                      </td>
+                      <td>
+                        <button class="btn btn-secondary" style="font-size:12px" onClick={() => handleRemoveModelPrice(index)}>
+                          {t('settings.profileDelete')}
+                        </button>
+                      </td>
+                    </tr>
+                  ))}
+                </tbody>
+              </table>
+            )}
+            <button class="btn btn-secondary" style="font-size:12px" onClick={handleAddModelPrice}>
+              {t('settings.modelPriceAdd')}
+            </button>
+          </div>
         </div>
         </>
         )}
@@ -556,94 +972,21 @@ export function SettingsPage() {
               <p class="settings-paper-card-subtitle">{t('settings.paperModelSectionIntro')}</p>
 
               <div class="settings-paper-fields">
-                <div class="settings-paper-field">
-                  <span class="settings-paper-field-label">{t('settings.selectPaperMode')}</span>
-                  <div class="toggle-group settings-paper-toggle">
-                    <button
-                      type="button"
-                      class={`toggle-option ${c.paperProvider === 'ollama' ? 'active' : ''}`}
-                      onClick={() => update('paperProvider', 'ollama')}
-                    >
-                      {t('settings.ollamaLocal')}
-                    </button>
-                    <button
-                      type="button"
-                      class={`toggle-option ${c.paperProvider === 'openai' ? 'active' : ''}`}
-                      onClick={() => update('paperProvider', 'openai')}
-                    >
-                      {t('settings.openaiCompat')}
-                    </button>
-                  </div>
-                </div>
-
-                {c.paperProvider === 'ollama' ? (
-                  <div class="settings-paper-subgroup">
-                    <div class="settings-paper-subgroup-title">{t('settings.ollamaLocal')}</div>
-                    <div class="settings-paper-fields">
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperServerUrl')}</span>
-                        <small>{t('settings.paperServerUrlHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperOllamaUrl}
-                          onInput={e => update('paperOllamaUrl', e.target.value)}
-                          placeholder="http://localhost:11434"
-                        />
-                      </div>
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperModel')}</span>
-                        <small>{t('settings.paperModelHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperOllamaModel}
-                          onInput={e => update('paperOllamaModel', e.target.value)}
-                          placeholder="llama3.2"
-                        />
-                      </div>
-                    </div>
-                  </div>
-                ) : (
-                  <div class="settings-paper-subgroup">
-                    <div class="settings-paper-subgroup-title">{t('settings.openaiCompat')}</div>
-                    <div class="settings-paper-fields">
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperBaseUrl')}</span>
-                        <small>{t('settings.paperBaseUrlHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperOpenaiBaseUrl}
-                          onInput={e => update('paperOpenaiBaseUrl', e.target.value)}
-                          placeholder="https://api.openai.com/v1"
-                        />
-                      </div>
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperApiKey')}</span>
-                        <small>Bearer Token</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="password"
-                          value={c.paperOpenaiKey}
-                          onInput={e => update('paperOpenaiKey', e.target.value)}
-                          placeholder="sk-..."
-                        />
-                      </div>
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperModel')}</span>
-                        <small>{t('settings.paperModelHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperOpenaiModel}
-                          onInput={e => update('paperOpenaiModel', e.target.value)}
-                          placeholder="gpt-4.1"
-                        />
-                      </div>
-                    </div>
-                  </div>
-                )}
+                <TaskProfileSelect
+                  c={c}
+                  task={TASKS.paperReviewProfile}
+                  label={t('settings.taskPaperReview')}
+                  hint={t('settings.taskPaperReviewHint')}
+                  onChange={update}
+                />
+                <TaskProfileSelect
+                  c={c}
+                  task={TASKS.paperChatProfile}
+                  label={t('settings.taskPaperChat')}
+                  hint={t('settings.taskPaperChatHint')}
+                  emptyLabel={t('settings.profileSameAsPaperReview')}
+                  onChange={update}
+                />
               </div>
 
               <div class="settings-paper-footer">
@@ -694,8 +1037,8 @@ export function SettingsPage() {
                     class="settings-input settings-paper-input settings-paper-input-path"
                     type="text"
                     value={c.paperArchiveRoot}
-                    onInput={e => update('paperArchiveRoot', e.target.value)}
-                    placeholder="/Users/chenghaoyang/Local/papers"
+                    readOnly
+                    placeholder={t('settings.paperArchiveRootDefault')}
                   />
                 </div>
               </div>
@@ -707,9 +1050,10 @@ export function SettingsPage() {
                 <button
                   type="button"
                   class="btn btn-secondary settings-paper-test-btn"
-                  onClick={handlePickPaperArchiveRoot}
+                  disabled={movingArchive}
+                  onClick={handleMovePaperArchive}
                 >
-                  {t('settings.pick')}
+                  {movingArchive ? t('settings.paperArchiveMoving') : t('settings.paperArchiveMove')}
                 </button>
               </div>
             </div>
@@ -722,32 +1066,21 @@ export function SettingsPage() {
               </div>
 
               <div class="settings-paper-fields">
-                <div class="settings-paper-field">
-                  <span class="settings-paper-field-label">{t('settings.paperEmbeddingMode')}</span>
-                  <div class="toggle-group settings-paper-toggle">
-                    <button
-                      type="button"
-                      class={`toggle-option ${c.paperEmbeddingProvider === 'auto' ? 'active' : ''}`}
-                      onClick={() => update('paperEmbeddingProvider', 'auto')}
-                    >
-                      {t('settings.paperEmbeddingAuto')}
-                    </button>
-                    <button
-                      type="button"
-                      class={`toggle-option ${c.paperEmbeddingProvider === 'ollama' ? 'active' : ''}`}
-                      onClick={() => update('paperEmbeddingProvider', 'ollama')}
-                    >
-                      {t('settings.ollamaLocal')}
-                    </button>
-                    <button
-                      type="button"
-                      class={`toggle-option ${c.paperEmbeddingProvider === 'openai' ? 'active' : ''}`}
-                      onClick={() => update('paperEmbeddingProvider', 'openai')}
-                    >
-                      {t('settings.openaiCompat')}
-                    </button>
-                  </div>
-                </div>
+                <TaskProfileSelect
+                  c={c}
+                  task={TASKS.embeddingProfile}
+                  label={t('settings.taskEmbedding')}
+                  hint={t('settings.taskEmbeddingHint')}
+                  onChange={update}
+                />
+                <TaskProfileSelect
+                  c={c}
+                  task={TASKS.embeddingFallbackProfile}
+                  label={t('settings.taskEmbeddingFallback')}
+                  hint={t('settings.taskEmbeddingFallbackHint')}
+                  emptyLabel={t('settings.profileNone')}
+                  onChange={update}
+                />
 
                 <div class="settings-paper-field">
                   <span class="settings-paper-field-label">{t('settings.paperFulltextTokenLimit')}</span>
@@ -762,118 +1095,6 @@ export function SettingsPage() {
                     placeholder="60000"
                   />
                 </div>
-
-                {c.paperEmbeddingProvider !== 'openai' && (
-                  <div class="settings-paper-subgroup">
-                    <div class="settings-paper-subgroup-title">{t('settings.paperEmbeddingLocalSection')}</div>
-                    <div class="settings-paper-fields">
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperEmbeddingOllamaUrl')}</span>
-                        <small>{t('settings.paperEmbeddingOllamaUrlHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperEmbeddingOllamaUrl}
-                          onInput={e => update('paperEmbeddingOllamaUrl', e.target.value)}
-                          placeholder="http://localhost:11434"
-                        />
-                      </div>
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperEmbeddingOllamaModel')}</span>
-                        <small>{t('settings.paperEmbeddingOllamaModelHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperEmbeddingOllamaModel}
-                          onInput={e => update('paperEmbeddingOllamaModel', e.target.value)}
-                          placeholder="nomic-embed-text"
-                        />
-                      </div>
-                    </div>
-                  </div>
-                )}
-
-                {c.paperEmbeddingProvider === 'auto' && (
-                  <div class="settings-paper-subgroup">
-                    <div class="settings-paper-subgroup-title">{t('settings.paperEmbeddingFallbackSection')}</div>
-                    <div class="settings-paper-fields">
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperEmbeddingBaseUrl')}</span>
-                        <small>{t('settings.paperEmbeddingBaseUrlHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperEmbeddingOpenaiBaseUrl}
-                          onInput={e => update('paperEmbeddingOpenaiBaseUrl', e.target.value)}
-                          placeholder="https://api.openai.com/v1"
-                        />
-                      </div>
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperEmbeddingApiKey')}</span>
-                        <small>Bearer Token</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="password"
-                          value={c.paperEmbeddingOpenaiKey}
-                          onInput={e => update('paperEmbeddingOpenaiKey', e.target.value)}
-                          placeholder="sk-..."
-                        />
-                      </div>
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperEmbeddingModel')}</span>
-                        <small>{t('settings.paperEmbeddingModelHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperEmbeddingOpenaiModel}
-                          onInput={e => update('paperEmbeddingOpenaiModel', e.target.value)}
-                          placeholder="text-embedding-3-small"
-                        />
-                      </div>
-                    </div>
-                  </div>
-                )}
-
-                {c.paperEmbeddingProvider === 'openai' && (
-                  <div class="settings-paper-subgroup">
-                    <div class="settings-paper-subgroup-title">{t('settings.paperEmbeddingCloudSection')}</div>
-                    <div class="settings-paper-fields">
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperEmbeddingBaseUrl')}</span>
-                        <small>{t('settings.paperEmbeddingBaseUrlHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperEmbeddingOpenaiBaseUrl}
-                          onInput={e => update('paperEmbeddingOpenaiBaseUrl', e.target.value)}
-                          placeholder="https://api.openai.com/v1"
-                        />
-                      </div>
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperEmbeddingApiKey')}</span>
-                        <small>Bearer Token</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="password"
-                          value={c.paperEmbeddingOpenaiKey}
-                          onInput={e => update('paperEmbeddingOpenaiKey', e.target.value)}
-                          placeholder="sk-..."
-                        />
-                      </div>
-                      <div class="settings-paper-field">
-                        <span class="settings-paper-field-label">{t('settings.paperEmbeddingModel')}</span>
-                        <small>{t('settings.paperEmbeddingModelHint')}</small>
-                        <input
-                          class="settings-input settings-paper-input"
-                          type="text"
-                          value={c.paperEmbeddingOpenaiModel}
-                          onInput={e => update('paperEmbeddingOpenaiModel', e.target.value)}
-                          placeholder="text-embedding-3-small"
-                        />
-                      </div>
-                    </div>
-                  </div>
-                )}
               </div>
 
               <div class="settings-paper-footer">
@@ -921,11 +1142,11 @@ export function SettingsPage() {
           </div>
           <div class="settings-row">
             <span class="settings-label">
-              {t('settings.autoCategorize')}
+              {t('settings.autoCategorize')} <SourceTag source={sources?.fields?.autoCategorize} />
               <small>{t('settings.autoCategorizeHint')}</small>
             </span>
             <label class="switch">
-              <input type="checkbox" checked={c.autoCategorize} onChange={e => update('autoCategorize', e.target.checked)} />
+              <input type="checkbox" checked={c.autoCategorize} disabled={isLocked('autoCategorize')} onChange={e => update('autoCategorize', e.target.checked)} />
               <span class="switch-slider" />
             </label>
           </div>
@@ -1010,6 +1231,15 @@ export function SettingsPage() {
               ))}
             </div>
           )}
+          <div class="settings-row">
+            <span class="settings-label">
+              {t('settings.diagnostics')}
+              <small>{t('settings.diagnosticsHint')}</small>
+            </span>
+            <button class="btn btn-secondary" style="font-size:12px" disabled={exportingDiagnostics} onClick={handleExportDiagnostics}>
+              {exportingDiagnostics ? t('settings.exportingDiagnostics') : t('settings.exportDiagnostics')}
+            </button>
+          </div>
         </div>
         )}
 